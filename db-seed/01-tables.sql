@@ -2,21 +2,50 @@
 -- 01‐tables.sql  (compatible with MariaDB/MySQL & PostgreSQL)
 -- ===============================================
 
+-- ------------------------------------------------
+-- 0) organizations table
+-- ------------------------------------------------
+-- Tenants; users, devices and uploaded files belong to exactly one
+CREATE TABLE organizations (
+    id           VARCHAR(36)    PRIMARY KEY,
+    name         VARCHAR(128)   NOT NULL UNIQUE,
+    created_by   VARCHAR(36),
+    created_at   TIMESTAMPTZ    NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    modified_by  VARCHAR(36),
+    modified_at  TIMESTAMPTZ    NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+-- The default organization holds the records created outside of any tenant
+INSERT INTO organizations (id, name) VALUES ('00000000-0000-0000-0000-000000000001', 'Default');
+
+
 -- ------------------------------------------------
 -- 1) users table
 -- ------------------------------------------------
 CREATE TABLE users (
     id           VARCHAR(36)    PRIMARY KEY,
+    org_id       VARCHAR(36)    NOT NULL DEFAULT '00000000-0000-0000-0000-000000000001'
+                                REFERENCES organizations(id),
     username     VARCHAR(64)    NOT NULL UNIQUE,
-    email        VARCHAR(128)   NOT NULL,
+    -- Stored trimmed and lowercased, so that the constraint is case-insensitive
+    email        VARCHAR(128)   NOT NULL UNIQUE,
     created_by   VARCHAR(36),
     created_at   TIMESTAMPTZ    NOT NULL DEFAULT CURRENT_TIMESTAMP,
     modified_by  VARCHAR(36),
-    modified_at  TIMESTAMPTZ    NOT NULL DEFAULT CURRENT_TIMESTAMP
+    modified_at  TIMESTAMPTZ    NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    version      BIGINT         NOT NULL DEFAULT 1, -- bumped on every change; returned as the ETag
+    deleted_by   VARCHAR(36),
+    deleted_at   TIMESTAMPTZ                      -- soft-deleted; purged after the retention period
 );
 
--- Separate index for email lookup
-CREATE INDEX idx_users_email ON users(email);
+-- Usernames are matched case-insensitively at login, so they must differ in more than case
+CREATE UNIQUE INDEX users_username_lower_key ON users(LOWER(username));
+
+-- Index for the stable ordering of paginated lists
+CREATE INDEX idx_users_created_at_id ON users(created_at, id);
+
+-- Index to speed up the tenant condition of every query
+CREATE INDEX idx_users_org_id ON users(org_id);
 
 
 -- ------------------------------------------------
@@ -24,6 +53,8 @@ CREATE INDEX idx_users_email ON users(email);
 -- ------------------------------------------------
 CREATE TABLE devices (
     id           VARCHAR(36)    PRIMARY KEY,
+    org_id       VARCHAR(36)    NOT NULL DEFAULT '00000000-0000-0000-0000-000000000001'
+                                REFERENCES organizations(id), -- always the owner's organization
     user_id      VARCHAR(36)    NOT NULL,
     name         VARCHAR(128)   NOT NULL,
     status       VARCHAR(32)    NOT NULL,
@@ -33,23 +64,34 @@ CREATE TABLE devices (
     created_at   TIMESTAMPTZ    NOT NULL DEFAULT CURRENT_TIMESTAMP,
     modified_by  VARCHAR(36),
     modified_at  TIMESTAMPTZ    NOT NULL DEFAULT CURRENT_TIMESTAMP,
-    
-    -- enforce unique (user_id, name)
-    UNIQUE (user_id, name),
+    version      BIGINT         NOT NULL DEFAULT 1, -- bumped on every change; returned as the ETag
+    deleted_by   VARCHAR(36),
+    deleted_at   TIMESTAMPTZ,                     -- soft-deleted; purged after the retention period
 
     -- foreign key → users.id
     FOREIGN KEY (user_id) REFERENCES users(id)
 );
 
+-- enforce unique (user_id, name) among the devices that are not deleted
+CREATE UNIQUE INDEX devices_user_id_name_key ON devices(user_id, name) WHERE deleted_at IS NULL;
+
 -- Index to speed up lookups by user_id
 CREATE INDEX idx_devices_user_id ON devices(user_id);
 
+-- Index for the stable ordering of paginated lists
+CREATE INDEX idx_devices_created_at_id ON devices(created_at, id);
+
+-- Index to speed up the tenant condition of every query
+CREATE INDEX idx_devices_org_id ON devices(org_id);
+
 
 -- ------------------------------------------------
 -- 3) uploaded_files table
 -- ------------------------------------------------
 CREATE TABLE uploaded_files (
     id                VARCHAR(36)  PRIMARY KEY,
+    org_id            VARCHAR(36)  NOT NULL DEFAULT '00000000-0000-0000-0000-000000000001'
+                                   REFERENCES organizations(id), -- always the owner's organization
     user_id           VARCHAR(36)  NOT NULL,
     file_name         VARCHAR(128) NOT NULL,  -- stored/generated file name
     origin_file_name  VARCHAR(128) NOT NULL,  -- original filename from upload
@@ -63,6 +105,8 @@ CREATE TABLE uploaded_files (
     created_at        TIMESTAMPTZ  NOT NULL DEFAULT CURRENT_TIMESTAMP,
     modified_by       VARCHAR(36),
     modified_at       TIMESTAMPTZ  NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    deleted_by        VARCHAR(36),
+    deleted_at        TIMESTAMPTZ,               -- soft-deleted; the stored file is kept until purged
 
     -- foreign key → users.id
     FOREIGN KEY (user_id) REFERENCES users(id)
@@ -71,6 +115,13 @@ CREATE TABLE uploaded_files (
 -- If you want a composite key (e.g. one file_name per user), uncomment and adjust:
 --   UNIQUE (user_id, file_name);
 
+-- Index to speed up the tenant condition of every query
+CREATE INDEX idx_uploaded_files_org_id ON uploaded_files(org_id);
+
+-- A user has at most one profile picture that is not deleted
+CREATE UNIQUE INDEX uploaded_files_profile_picture_key ON uploaded_files(user_id)
+    WHERE file_type = 'profile_picture' AND deleted_at IS NULL;
+
 
 -- ------------------------------------------------
 -- 4) user_auth table
@@ -82,6 +133,299 @@ CREATE TABLE user_auth (
     modified_at   TIMESTAMPTZ  NOT NULL DEFAULT CURRENT_TIMESTAMP,
 
     -- FK to users.id
-    FOREIGN KEY (user_id) REFERENCES users(id)
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+
+
+-- ------------------------------------------------
+-- 5) refresh_tokens table
+-- ------------------------------------------------
+CREATE TABLE refresh_tokens (
+    id            VARCHAR(36)  PRIMARY KEY,
+    user_id       VARCHAR(36)  NOT NULL,
+    family_id     VARCHAR(36)  NOT NULL,         -- session id shared by every rotation of one login
+    token_hash    VARCHAR(64)  NOT NULL UNIQUE,  -- sha-256 (hex) of the opaque token
+    expires_at    TIMESTAMPTZ  NOT NULL,
+    used_at       TIMESTAMPTZ,                   -- set on rotation; presenting it again is reuse
+    revoked_at    TIMESTAMPTZ,
+    client_id     VARCHAR(64),                   -- OAuth client the session was issued to (FK added with oauth_clients)
+    scopes        TEXT[],                        -- permissions granted to the OAuth client; NULL for first-party logins
+    created_at    TIMESTAMPTZ  NOT NULL DEFAULT CURRENT_TIMESTAMP,
+
+    -- FK to users.id
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+-- Index to speed up session checks and family revocation
+CREATE INDEX idx_refresh_tokens_family_id ON refresh_tokens(family_id);
+
+
+-- ------------------------------------------------
+-- 6) roles, role_permissions and user_roles tables
+-- ------------------------------------------------
+CREATE TABLE roles (
+    id           VARCHAR(36)    PRIMARY KEY,
+    name         VARCHAR(64)    NOT NULL UNIQUE,
+    description  VARCHAR(255),
+    created_at   TIMESTAMPTZ    NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE role_permissions (
+    role_id      VARCHAR(36)    NOT NULL,
+    permission   VARCHAR(64)    NOT NULL,  -- e.g. 'device:write'
+
+    PRIMARY KEY (role_id, permission),
+
+    -- FK to roles.id
+    FOREIGN KEY (role_id) REFERENCES roles(id) ON DELETE CASCADE
+);
+
+CREATE TABLE user_roles (
+    user_id      VARCHAR(36)    NOT NULL,
+    role_id      VARCHAR(36)    NOT NULL,
+    created_at   TIMESTAMPTZ    NOT NULL DEFAULT CURRENT_TIMESTAMP,
+
+    PRIMARY KEY (user_id, role_id),
+
+    -- FKs to users.id and roles.id
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE,
+    FOREIGN KEY (role_id) REFERENCES roles(id) ON DELETE CASCADE
+);
+
+
+-- ------------------------------------------------
+-- 7) registration_invites table
+-- ------------------------------------------------
+CREATE TABLE registration_invites (
+    id           VARCHAR(36)    PRIMARY KEY,
+    token_hash   VARCHAR(64)    NOT NULL UNIQUE,  -- SHA-256 hex of the invite token
+    email        VARCHAR(128),                    -- optional: invite is bound to this email
+    created_by   VARCHAR(36),
+    created_at   TIMESTAMPTZ    NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    expires_at   TIMESTAMPTZ    NOT NULL,
+    used_at      TIMESTAMPTZ,
+    used_by      VARCHAR(36),
+
+    -- FK to users.id
+    FOREIGN KEY (used_by) REFERENCES users(id) ON DELETE SET NULL
+);
+
+
+-- ------------------------------------------------
+-- 8) password_reset_tokens table
+-- ------------------------------------------------
+CREATE TABLE password_reset_tokens (
+    id           VARCHAR(36)    PRIMARY KEY,
+    user_id      VARCHAR(36)    NOT NULL,
+    token_hash   VARCHAR(64)    NOT NULL UNIQUE,  -- SHA-256 hex of the reset token
+    created_at   TIMESTAMPTZ    NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    expires_at   TIMESTAMPTZ    NOT NULL,
+    used_at      TIMESTAMPTZ,                     -- set when redeemed or superseded
+
+    -- FK to users.id
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+-- Index to speed up invalidating the outstanding tokens of a user
+CREATE INDEX idx_password_reset_tokens_user_id ON password_reset_tokens(user_id);
+
+
+-- ------------------------------------------------
+-- 9) login_throttles table
+-- ------------------------------------------------
+CREATE TABLE login_throttles (
+    throttle_key    VARCHAR(160)   PRIMARY KEY,     -- 'account:<username>' or 'ip:<address>'
+    failed_count    INTEGER        NOT NULL DEFAULT 0,
+    last_failed_at  TIMESTAMPTZ    NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    locked_until    TIMESTAMPTZ                     -- no login is attempted before this time
+);
+
+
+-- ------------------------------------------------
+-- 10) user_mfa, mfa_recovery_codes and mfa_challenges tables
+-- ------------------------------------------------
+CREATE TABLE user_mfa (
+    user_id         VARCHAR(36)    PRIMARY KEY,
+    totp_secret     VARCHAR(64)    NOT NULL,        -- base32; needed in clear to compute codes
+    enabled_at      TIMESTAMPTZ,                    -- NULL while enrollment is pending
+    last_used_step  BIGINT,                         -- time step of the last accepted code (replay guard)
+    created_at      TIMESTAMPTZ    NOT NULL DEFAULT CURRENT_TIMESTAMP,
+
+    -- FK to users.id
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+CREATE TABLE mfa_recovery_codes (
+    id          VARCHAR(36)    PRIMARY KEY,
+    user_id     VARCHAR(36)    NOT NULL,
+    code_hash   VARCHAR(64)    NOT NULL,            -- SHA-256 hex of the recovery code
+    used_at     TIMESTAMPTZ,
+
+    UNIQUE (user_id, code_hash),
+    -- removed together with the enrollment
+    FOREIGN KEY (user_id) REFERENCES user_mfa(user_id) ON DELETE CASCADE
+);
+
+CREATE TABLE mfa_challenges (
+    id               VARCHAR(36)    PRIMARY KEY,
+    user_id          VARCHAR(36)    NOT NULL,
+    token_hash       VARCHAR(64)    NOT NULL UNIQUE, -- SHA-256 hex of the challenge token
+    expires_at       TIMESTAMPTZ    NOT NULL,
+    failed_attempts  INTEGER        NOT NULL DEFAULT 0,
+    used_at          TIMESTAMPTZ,
+    created_at       TIMESTAMPTZ    NOT NULL DEFAULT CURRENT_TIMESTAMP,
+
+    -- FK to users.id
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+
+-- ------------------------------------------------
+-- 11) api_keys table
+-- ------------------------------------------------
+CREATE TABLE api_keys (
+    id            VARCHAR(36)    PRIMARY KEY,
+    user_id       VARCHAR(36)    NOT NULL,        -- the key acts on behalf of this user
+    name          VARCHAR(64)    NOT NULL,
+    key_prefix    VARCHAR(16)    NOT NULL,        -- leading characters of the key, shown to identify it
+    key_hash      VARCHAR(64)    NOT NULL UNIQUE, -- SHA-256 hex of the key
+    scopes        TEXT[]         NOT NULL,        -- permissions the key may use
+    expires_at    TIMESTAMPTZ,                    -- NULL for keys that do not expire
+    last_used_at  TIMESTAMPTZ,
+    revoked_at    TIMESTAMPTZ,
+    created_at    TIMESTAMPTZ    NOT NULL DEFAULT CURRENT_TIMESTAMP,
+
+    -- FK to users.id
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+-- Index to speed up listing the keys of a user
+CREATE INDEX idx_api_keys_user_id ON api_keys(user_id);
+
+
+-- ------------------------------------------------
+-- 12) oauth_clients and oauth_authorization_codes tables
+-- ------------------------------------------------
+CREATE TABLE oauth_clients (
+    client_id           VARCHAR(64)    PRIMARY KEY,
+    client_secret_hash  VARCHAR(64),                    -- SHA-256 hex of the secret; NULL for public clients
+    name                VARCHAR(64)    NOT NULL,
+    redirect_uris       TEXT[]         NOT NULL,        -- exact matches only
+    grant_types         TEXT[]         NOT NULL,        -- grants the client may use at /oauth/token
+    scopes              TEXT[]         NOT NULL,        -- permissions the client may request
+    user_id             VARCHAR(36),                    -- the user that client_credentials tokens act as
+    created_by          VARCHAR(36),
+    created_at          TIMESTAMPTZ    NOT NULL DEFAULT CURRENT_TIMESTAMP,
+
+    -- FK to users.id
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+-- Sessions of a client end when the client is deleted
+ALTER TABLE refresh_tokens
+    ADD FOREIGN KEY (client_id) REFERENCES oauth_clients(client_id) ON DELETE CASCADE;
+
+CREATE TABLE oauth_authorization_codes (
+    id              VARCHAR(36)    PRIMARY KEY,
+    code_hash       VARCHAR(64)    NOT NULL UNIQUE,     -- SHA-256 hex of the code
+    client_id       VARCHAR(64)    NOT NULL,
+    user_id         VARCHAR(36)    NOT NULL,
+    redirect_uri    TEXT           NOT NULL,
+    scopes          TEXT[]         NOT NULL,
+    code_challenge  VARCHAR(128)   NOT NULL,            -- PKCE S256 challenge
+    expires_at      TIMESTAMPTZ    NOT NULL,
+    used_at         TIMESTAMPTZ,
+    session_id      VARCHAR(36),                        -- session started with the code; revoked on replay
+    created_at      TIMESTAMPTZ    NOT NULL DEFAULT CURRENT_TIMESTAMP,
+
+    FOREIGN KEY (client_id) REFERENCES oauth_clients(client_id) ON DELETE CASCADE,
+    -- FK to users.id
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+
+-- ------------------------------------------------
+-- 13) auth_events table
+-- ------------------------------------------------
+CREATE TABLE auth_events (
+    id           VARCHAR(36)    PRIMARY KEY,
+    org_id       VARCHAR(36)    NOT NULL
+                                REFERENCES organizations(id), -- the user's organization, or the default one
+    event_type   VARCHAR(32)    NOT NULL,        -- login, mfa_verify, token_refresh, logout, ...
+    success      BOOLEAN        NOT NULL,
+    user_id      VARCHAR(36),                    -- NULL if the user is unknown; kept after the user is deleted
+    username     VARCHAR(64),                    -- username given at login, for attempts on unknown accounts
+    ip_address   VARCHAR(45),
+    user_agent   TEXT,
+    request_id   VARCHAR(64),                    -- X-Request-Id of the request that caused the event
+    detail       TEXT,                           -- failure reason or other context
+    created_at   TIMESTAMPTZ    NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+-- Indexes to speed up the audit queries by user and by time
+CREATE INDEX idx_auth_events_user_id_created_at ON auth_events(user_id, created_at);
+CREATE INDEX idx_auth_events_created_at ON auth_events(created_at);
+
+-- Index for the audit queries of an organization
+CREATE INDEX idx_auth_events_org_id_created_at ON auth_events(org_id, created_at);
+
+
+-- ------------------------------------------------
+-- 14) magic_links table
+-- ------------------------------------------------
+CREATE TABLE magic_links (
+    id               VARCHAR(36)    PRIMARY KEY,
+    user_id          VARCHAR(36)    NOT NULL,
+    token_hash       VARCHAR(64)    NOT NULL UNIQUE, -- SHA-256 hex of the link token
+    code_hash        VARCHAR(64)    NOT NULL,        -- SHA-256 hex of the one-time code
+    expires_at       TIMESTAMPTZ    NOT NULL,
+    failed_attempts  INTEGER        NOT NULL DEFAULT 0,
+    used_at          TIMESTAMPTZ,                    -- set when redeemed or superseded
+    created_at       TIMESTAMPTZ    NOT NULL DEFAULT CURRENT_TIMESTAMP,
+
+    -- FK to users.id
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+-- Index to speed up finding the outstanding link of a user
+CREATE INDEX idx_magic_links_user_id ON magic_links(user_id);
+
+
+-- ------------------------------------------------
+-- 15) groups and group_members tables
+-- ------------------------------------------------
+-- Teams of users within an organization
+CREATE TABLE groups (
+    id           VARCHAR(36)    PRIMARY KEY,
+    org_id       VARCHAR(36)    NOT NULL REFERENCES organizations(id),
+    name         VARCHAR(128)   NOT NULL,
+    description  VARCHAR(255),
+    created_by   VARCHAR(36),
+    created_at   TIMESTAMPTZ    NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    modified_by  VARCHAR(36),
+    modified_at  TIMESTAMPTZ    NOT NULL DEFAULT CURRENT_TIMESTAMP,
+
+    -- group names are unique within an organization
+    UNIQUE (org_id, name)
+);
+
+-- Index for the stable ordering of paginated lists
+CREATE INDEX idx_groups_created_at_id ON groups(created_at, id);
+
+CREATE TABLE group_members (
+    group_id     VARCHAR(36)    NOT NULL,
+    user_id      VARCHAR(36)    NOT NULL,
+    role         VARCHAR(16)    NOT NULL DEFAULT 'member', -- 'member' or 'manager'
+    created_by   VARCHAR(36),
+    created_at   TIMESTAMPTZ    NOT NULL DEFAULT CURRENT_TIMESTAMP,
+
+    PRIMARY KEY (group_id, user_id),
+
+    -- FKs to groups.id and users.id
+    FOREIGN KEY (group_id) REFERENCES groups(id) ON DELETE CASCADE,
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
 );
 
+-- Index to speed up listing the groups of a user
+CREATE INDEX idx_group_members_user_id ON group_members(user_id);
@@ -22,6 +22,15 @@ INSERT INTO users (id, username, email, created_by, created_at, modified_by, mod
   ('00000000-0000-0000-0000-000000000020', 'user20', 'user20@example.com', NULL, NOW(), NULL, NOW()),
   ('00000000-0000-0000-0000-000000000021', 'apitest01', 'apitest01@example.com', NULL, NOW(), NULL, NOW());
 
+-- Seed data for a second organization, to test the tenant isolation;
+-- every other seeded record belongs to the default organization
+INSERT INTO organizations (id, name) VALUES
+  ('00000000-0000-0000-0000-000000000002', 'Acme');
+
+INSERT INTO users (id, org_id, username, email, created_by, created_at, modified_by, modified_at) VALUES
+  ('00000000-0000-0000-0000-000000000022', '00000000-0000-0000-0000-000000000002', 'acmeadmin01', 'admin01@acme.example.com', NULL, NOW(), NULL, NOW()),
+  ('00000000-0000-0000-0000-000000000023', '00000000-0000-0000-0000-000000000002', 'acmeuser01', 'user01@acme.example.com', NULL, NOW(), NULL, NOW());
+
 -- Seed data for devices
 INSERT INTO devices (id, user_id, name, status, device_os, registered_at, created_by, created_at, modified_by, modified_at) VALUES
 -- 4 devices per user
@@ -126,8 +135,48 @@ INSERT INTO devices (id, user_id, name, status, device_os, registered_at, create
   ('00000000-0000-0000-0000-000000000079', '00000000-0000-0000-0000-000000000020', 'device20-3', 'active', 'Android', NOW(), NULL, NOW(), NULL, NOW()),
   ('00000000-0000-0000-0000-000000000080', '00000000-0000-0000-0000-000000000020', 'device20-4', 'decommissioned', 'iOS', NOW(), NULL, NOW(), NULL, NOW());
 
+INSERT INTO devices (id, org_id, user_id, name, status, device_os, registered_at, created_by, created_at, modified_by, modified_at) VALUES
+  -- acmeuser01
+  ('00000000-0000-0000-0000-000000000081', '00000000-0000-0000-0000-000000000002', '00000000-0000-0000-0000-000000000023', 'acme-device01-1', 'active', 'iOS', NOW(), NULL, NOW(), NULL, NOW());
+
+
+-- Seed data for roles
+INSERT INTO roles (id, name, description) VALUES
+  ('00000000-0000-0000-0000-000000000001', 'admin', 'Full access to every user, device and file'),
+  ('00000000-0000-0000-0000-000000000002', 'user', 'Access to own profile, devices and files');
+
+-- Seed data for role_permissions
+INSERT INTO role_permissions (role_id, permission) VALUES
+  ('00000000-0000-0000-0000-000000000001', 'user:read'),
+  ('00000000-0000-0000-0000-000000000001', 'user:write'),
+  ('00000000-0000-0000-0000-000000000001', 'user:delete'),
+  ('00000000-0000-0000-0000-000000000001', 'device:read'),
+  ('00000000-0000-0000-0000-000000000001', 'device:write'),
+  ('00000000-0000-0000-0000-000000000001', 'device:delete'),
+  ('00000000-0000-0000-0000-000000000001', 'file:read'),
+  ('00000000-0000-0000-0000-000000000001', 'file:delete'),
+  ('00000000-0000-0000-0000-000000000001', 'auth:manage'),
+  ('00000000-0000-0000-0000-000000000001', 'group:read'),
+  ('00000000-0000-0000-0000-000000000001', 'group:write'),
+  ('00000000-0000-0000-0000-000000000002', 'user:read'),
+  ('00000000-0000-0000-0000-000000000002', 'user:write'),
+  ('00000000-0000-0000-0000-000000000002', 'device:read'),
+  ('00000000-0000-0000-0000-000000000002', 'device:write'),
+  ('00000000-0000-0000-0000-000000000002', 'device:delete'),
+  ('00000000-0000-0000-0000-000000000002', 'file:read'),
+  ('00000000-0000-0000-0000-000000000002', 'file:delete'),
+  ('00000000-0000-0000-0000-000000000002', 'group:read');
 
 -- Seed data for user_roles
+-- apitest01 and acmeadmin01 are the admins of their organizations;
+-- every other seeded user gets the regular user role
+INSERT INTO user_roles (user_id, role_id)
+SELECT id,
+       CASE WHEN username IN ('apitest01', 'acmeadmin01')
+            THEN '00000000-0000-0000-0000-000000000001'
+            ELSE '00000000-0000-0000-0000-000000000002'
+       END
+  FROM users;
 
 -- for auth login
 -- client_id: apitest01
@@ -137,3 +186,17 @@ INSERT INTO devices (id, user_id, name, status, device_os, registered_at, create
 INSERT INTO user_auth
 (user_id, password_hash, created_at, modified_at)
 VALUES('00000000-0000-0000-0000-000000000021', '$argon2id$v=19$m=19456,t=2,p=1$XBFwBY52C9SpzkxON1OTLg$djDqZQvzxFKc9HOCWyZfKy+RlFTs0BJFSkcw/Tos14c', NOW(), NOW());
+
+-- non-admin login for authorization tests
+-- client_id: user01
+-- client_secret: test_password
+INSERT INTO user_auth
+(user_id, password_hash, created_at, modified_at)
+VALUES('00000000-0000-0000-0000-000000000001', '$argon2id$v=19$m=19456,t=2,p=1$XBFwBY52C9SpzkxON1OTLg$djDqZQvzxFKc9HOCWyZfKy+RlFTs0BJFSkcw/Tos14c', NOW(), NOW());
+
+-- admin of the second organization
+-- client_id: acmeadmin01
+-- client_secret: test_password
+INSERT INTO user_auth
+(user_id, password_hash, created_at, modified_at)
+VALUES('00000000-0000-0000-0000-000000000022', '$argon2id$v=19$m=19456,t=2,p=1$XBFwBY52C9SpzkxON1OTLg$djDqZQvzxFKc9HOCWyZfKy+RlFTs0BJFSkcw/Tos14c', NOW(), NOW());
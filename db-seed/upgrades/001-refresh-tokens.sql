@@ -0,0 +1,23 @@
+-- ------------------------------------------------
+-- Upgrade for databases created before refresh tokens.
+-- Adds the refresh_tokens table holding the sessions started by a login.
+-- Run once with: psql -v ON_ERROR_STOP=1 -f 001-refresh-tokens.sql
+-- ------------------------------------------------
+BEGIN;
+
+CREATE TABLE refresh_tokens (
+    id            VARCHAR(36)  PRIMARY KEY,
+    user_id       VARCHAR(36)  NOT NULL,
+    family_id     VARCHAR(36)  NOT NULL,
+    token_hash    VARCHAR(64)  NOT NULL UNIQUE,
+    expires_at    TIMESTAMPTZ  NOT NULL,
+    used_at       TIMESTAMPTZ,
+    revoked_at    TIMESTAMPTZ,
+    created_at    TIMESTAMPTZ  NOT NULL DEFAULT CURRENT_TIMESTAMP,
+
+    FOREIGN KEY (user_id) REFERENCES users(id)
+);
+
+CREATE INDEX idx_refresh_tokens_family_id ON refresh_tokens(family_id);
+
+COMMIT;
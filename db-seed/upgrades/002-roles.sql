@@ -0,0 +1,61 @@
+-- ------------------------------------------------
+-- Upgrade for databases created before role-based access control.
+-- Adds the roles, role_permissions and user_roles tables with the admin and
+-- user roles, and gives every existing user the user role.
+-- Admins are not known to the old schema; grant the role afterwards with
+--   INSERT INTO user_roles (user_id, role_id)
+--   VALUES ('<user-id>', '00000000-0000-0000-0000-000000000001');
+-- Run once with: psql -v ON_ERROR_STOP=1 -f 002-roles.sql
+-- ------------------------------------------------
+BEGIN;
+
+CREATE TABLE roles (
+    id           VARCHAR(36)    PRIMARY KEY,
+    name         VARCHAR(64)    NOT NULL UNIQUE,
+    description  VARCHAR(255),
+    created_at   TIMESTAMPTZ    NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE role_permissions (
+    role_id      VARCHAR(36)    NOT NULL,
+    permission   VARCHAR(64)    NOT NULL,
+
+    PRIMARY KEY (role_id, permission),
+    FOREIGN KEY (role_id) REFERENCES roles(id) ON DELETE CASCADE
+);
+
+CREATE TABLE user_roles (
+    user_id      VARCHAR(36)    NOT NULL,
+    role_id      VARCHAR(36)    NOT NULL,
+    created_at   TIMESTAMPTZ    NOT NULL DEFAULT CURRENT_TIMESTAMP,
+
+    PRIMARY KEY (user_id, role_id),
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE,
+    FOREIGN KEY (role_id) REFERENCES roles(id) ON DELETE CASCADE
+);
+
+INSERT INTO roles (id, name, description) VALUES
+  ('00000000-0000-0000-0000-000000000001', 'admin', 'Full access to every user, device and file'),
+  ('00000000-0000-0000-0000-000000000002', 'user', 'Access to own profile, devices and files');
+
+INSERT INTO role_permissions (role_id, permission) VALUES
+  ('00000000-0000-0000-0000-000000000001', 'user:read'),
+  ('00000000-0000-0000-0000-000000000001', 'user:write'),
+  ('00000000-0000-0000-0000-000000000001', 'user:delete'),
+  ('00000000-0000-0000-0000-000000000001', 'device:read'),
+  ('00000000-0000-0000-0000-000000000001', 'device:write'),
+  ('00000000-0000-0000-0000-000000000001', 'device:delete'),
+  ('00000000-0000-0000-0000-000000000001', 'file:read'),
+  ('00000000-0000-0000-0000-000000000001', 'file:delete'),
+  ('00000000-0000-0000-0000-000000000002', 'user:read'),
+  ('00000000-0000-0000-0000-000000000002', 'user:write'),
+  ('00000000-0000-0000-0000-000000000002', 'device:read'),
+  ('00000000-0000-0000-0000-000000000002', 'device:write'),
+  ('00000000-0000-0000-0000-000000000002', 'device:delete'),
+  ('00000000-0000-0000-0000-000000000002', 'file:read'),
+  ('00000000-0000-0000-0000-000000000002', 'file:delete');
+
+INSERT INTO user_roles (user_id, role_id)
+SELECT id, '00000000-0000-0000-0000-000000000002' FROM users;
+
+COMMIT;
@@ -0,0 +1,25 @@
+-- ------------------------------------------------
+-- Upgrade for databases created before registration modes.
+-- Adds the registration_invites table and the auth:manage permission of admins.
+-- Run once with: psql -v ON_ERROR_STOP=1 -f 003-registration-invites.sql
+-- ------------------------------------------------
+BEGIN;
+
+CREATE TABLE registration_invites (
+    id           VARCHAR(36)    PRIMARY KEY,
+    token_hash   VARCHAR(64)    NOT NULL UNIQUE,
+    email        VARCHAR(128),
+    created_by   VARCHAR(36),
+    created_at   TIMESTAMPTZ    NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    expires_at   TIMESTAMPTZ    NOT NULL,
+    used_at      TIMESTAMPTZ,
+    used_by      VARCHAR(36),
+
+    FOREIGN KEY (used_by) REFERENCES users(id) ON DELETE SET NULL
+);
+
+INSERT INTO role_permissions (role_id, permission)
+SELECT id, 'auth:manage' FROM roles WHERE name = 'admin'
+ON CONFLICT DO NOTHING;
+
+COMMIT;
@@ -0,0 +1,21 @@
+-- ------------------------------------------------
+-- Upgrade for databases created before password resets.
+-- Adds the password_reset_tokens table.
+-- Run once with: psql -v ON_ERROR_STOP=1 -f 004-password-reset.sql
+-- ------------------------------------------------
+BEGIN;
+
+CREATE TABLE password_reset_tokens (
+    id           VARCHAR(36)    PRIMARY KEY,
+    user_id      VARCHAR(36)    NOT NULL,
+    token_hash   VARCHAR(64)    NOT NULL UNIQUE,
+    created_at   TIMESTAMPTZ    NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    expires_at   TIMESTAMPTZ    NOT NULL,
+    used_at      TIMESTAMPTZ,
+
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_password_reset_tokens_user_id ON password_reset_tokens(user_id);
+
+COMMIT;
@@ -0,0 +1,15 @@
+-- ------------------------------------------------
+-- Upgrade for databases created before login throttling.
+-- Adds the login_throttles table.
+-- Run once with: psql -v ON_ERROR_STOP=1 -f 005-login-throttles.sql
+-- ------------------------------------------------
+BEGIN;
+
+CREATE TABLE login_throttles (
+    throttle_key    VARCHAR(160)   PRIMARY KEY,
+    failed_count    INTEGER        NOT NULL DEFAULT 0,
+    last_failed_at  TIMESTAMPTZ    NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    locked_until    TIMESTAMPTZ
+);
+
+COMMIT;
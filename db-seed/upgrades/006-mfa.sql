@@ -0,0 +1,40 @@
+-- ------------------------------------------------
+-- Upgrade for databases created before multi-factor authentication.
+-- Adds the user_mfa, mfa_recovery_codes and mfa_challenges tables.
+-- Run once with: psql -v ON_ERROR_STOP=1 -f 006-mfa.sql
+-- ------------------------------------------------
+BEGIN;
+
+CREATE TABLE user_mfa (
+    user_id         VARCHAR(36)    PRIMARY KEY,
+    totp_secret     VARCHAR(64)    NOT NULL,
+    enabled_at      TIMESTAMPTZ,
+    last_used_step  BIGINT,
+    created_at      TIMESTAMPTZ    NOT NULL DEFAULT CURRENT_TIMESTAMP,
+
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+CREATE TABLE mfa_recovery_codes (
+    id          VARCHAR(36)    PRIMARY KEY,
+    user_id     VARCHAR(36)    NOT NULL,
+    code_hash   VARCHAR(64)    NOT NULL,
+    used_at     TIMESTAMPTZ,
+
+    UNIQUE (user_id, code_hash),
+    FOREIGN KEY (user_id) REFERENCES user_mfa(user_id) ON DELETE CASCADE
+);
+
+CREATE TABLE mfa_challenges (
+    id               VARCHAR(36)    PRIMARY KEY,
+    user_id          VARCHAR(36)    NOT NULL,
+    token_hash       VARCHAR(64)    NOT NULL UNIQUE,
+    expires_at       TIMESTAMPTZ    NOT NULL,
+    failed_attempts  INTEGER        NOT NULL DEFAULT 0,
+    used_at          TIMESTAMPTZ,
+    created_at       TIMESTAMPTZ    NOT NULL DEFAULT CURRENT_TIMESTAMP,
+
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+COMMIT;
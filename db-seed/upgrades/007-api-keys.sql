@@ -0,0 +1,25 @@
+-- ------------------------------------------------
+-- Upgrade for databases created before API keys.
+-- Adds the api_keys table.
+-- Run once with: psql -v ON_ERROR_STOP=1 -f 007-api-keys.sql
+-- ------------------------------------------------
+BEGIN;
+
+CREATE TABLE api_keys (
+    id            VARCHAR(36)    PRIMARY KEY,
+    user_id       VARCHAR(36)    NOT NULL,
+    name          VARCHAR(64)    NOT NULL,
+    key_prefix    VARCHAR(16)    NOT NULL,
+    key_hash      VARCHAR(64)    NOT NULL UNIQUE,
+    scopes        TEXT[]         NOT NULL,
+    expires_at    TIMESTAMPTZ,
+    last_used_at  TIMESTAMPTZ,
+    revoked_at    TIMESTAMPTZ,
+    created_at    TIMESTAMPTZ    NOT NULL DEFAULT CURRENT_TIMESTAMP,
+
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_api_keys_user_id ON api_keys(user_id);
+
+COMMIT;
@@ -0,0 +1,46 @@
+-- ------------------------------------------------
+-- Upgrade for databases created before OAuth 2.0 clients.
+-- Adds the oauth_clients and oauth_authorization_codes tables and binds
+-- refresh tokens to the client and scopes they were issued for; existing
+-- sessions are first-party logins and keep both columns NULL.
+-- Run once with: psql -v ON_ERROR_STOP=1 -f 008-oauth.sql
+-- ------------------------------------------------
+BEGIN;
+
+CREATE TABLE oauth_clients (
+    client_id           VARCHAR(64)    PRIMARY KEY,
+    client_secret_hash  VARCHAR(64),
+    name                VARCHAR(64)    NOT NULL,
+    redirect_uris       TEXT[]         NOT NULL,
+    grant_types         TEXT[]         NOT NULL,
+    scopes              TEXT[]         NOT NULL,
+    user_id             VARCHAR(36),
+    created_by          VARCHAR(36),
+    created_at          TIMESTAMPTZ    NOT NULL DEFAULT CURRENT_TIMESTAMP,
+
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+ALTER TABLE refresh_tokens
+    ADD COLUMN client_id VARCHAR(64),
+    ADD COLUMN scopes TEXT[],
+    ADD FOREIGN KEY (client_id) REFERENCES oauth_clients(client_id) ON DELETE CASCADE;
+
+CREATE TABLE oauth_authorization_codes (
+    id              VARCHAR(36)    PRIMARY KEY,
+    code_hash       VARCHAR(64)    NOT NULL UNIQUE,
+    client_id       VARCHAR(64)    NOT NULL,
+    user_id         VARCHAR(36)    NOT NULL,
+    redirect_uri    TEXT           NOT NULL,
+    scopes          TEXT[]         NOT NULL,
+    code_challenge  VARCHAR(128)   NOT NULL,
+    expires_at      TIMESTAMPTZ    NOT NULL,
+    used_at         TIMESTAMPTZ,
+    session_id      VARCHAR(36),
+    created_at      TIMESTAMPTZ    NOT NULL DEFAULT CURRENT_TIMESTAMP,
+
+    FOREIGN KEY (client_id) REFERENCES oauth_clients(client_id) ON DELETE CASCADE,
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+COMMIT;
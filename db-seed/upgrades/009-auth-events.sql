@@ -0,0 +1,24 @@
+-- ------------------------------------------------
+-- Upgrade for databases created before the authentication audit trail.
+-- Adds the auth_events table.
+-- Run once with: psql -v ON_ERROR_STOP=1 -f 009-auth-events.sql
+-- ------------------------------------------------
+BEGIN;
+
+CREATE TABLE auth_events (
+    id           VARCHAR(36)    PRIMARY KEY,
+    event_type   VARCHAR(32)    NOT NULL,
+    success      BOOLEAN        NOT NULL,
+    user_id      VARCHAR(36),
+    username     VARCHAR(64),
+    ip_address   VARCHAR(45),
+    user_agent   TEXT,
+    request_id   VARCHAR(64),
+    detail       TEXT,
+    created_at   TIMESTAMPTZ    NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX idx_auth_events_user_id_created_at ON auth_events(user_id, created_at);
+CREATE INDEX idx_auth_events_created_at ON auth_events(created_at);
+
+COMMIT;
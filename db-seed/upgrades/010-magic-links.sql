@@ -0,0 +1,23 @@
+-- ------------------------------------------------
+-- Upgrade for databases created before passwordless login.
+-- Adds the magic_links table.
+-- Run once with: psql -v ON_ERROR_STOP=1 -f 010-magic-links.sql
+-- ------------------------------------------------
+BEGIN;
+
+CREATE TABLE magic_links (
+    id               VARCHAR(36)    PRIMARY KEY,
+    user_id          VARCHAR(36)    NOT NULL,
+    token_hash       VARCHAR(64)    NOT NULL UNIQUE,
+    code_hash        VARCHAR(64)    NOT NULL,
+    expires_at       TIMESTAMPTZ    NOT NULL,
+    failed_attempts  INTEGER        NOT NULL DEFAULT 0,
+    used_at          TIMESTAMPTZ,
+    created_at       TIMESTAMPTZ    NOT NULL DEFAULT CURRENT_TIMESTAMP,
+
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_magic_links_user_id ON magic_links(user_id);
+
+COMMIT;
@@ -0,0 +1,22 @@
+-- ------------------------------------------------
+-- Upgrade for databases created before login by email.
+-- Normalizes user emails and makes them unique.
+-- Run once with: psql -v ON_ERROR_STOP=1 -f 011-unique-user-email.sql
+-- ------------------------------------------------
+BEGIN;
+
+UPDATE users
+   SET email = LOWER(TRIM(email))
+ WHERE email <> LOWER(TRIM(email));
+
+DO $$
+BEGIN
+    IF EXISTS (SELECT 1 FROM users GROUP BY email HAVING COUNT(*) > 1) THEN
+        RAISE EXCEPTION 'Users share an email address, resolve the duplicates before upgrading';
+    END IF;
+END $$;
+
+DROP INDEX IF EXISTS idx_users_email;
+ALTER TABLE users ADD CONSTRAINT users_email_key UNIQUE (email);
+
+COMMIT;
@@ -0,0 +1,11 @@
+-- ------------------------------------------------
+-- Upgrade for databases created before paginated lists.
+-- Adds the indexes behind the stable (created_at, id) ordering of users and devices.
+-- Run once with: psql -v ON_ERROR_STOP=1 -f 012-pagination-indexes.sql
+-- ------------------------------------------------
+BEGIN;
+
+CREATE INDEX idx_users_created_at_id ON users(created_at, id);
+CREATE INDEX idx_devices_created_at_id ON devices(created_at, id);
+
+COMMIT;
@@ -0,0 +1,49 @@
+-- ------------------------------------------------
+-- Upgrade for databases created before soft delete.
+-- Adds the deleted_at/deleted_by columns, limits the unique device names and
+-- profile pictures to the records that are not deleted, and lets purged users
+-- take their credentials and sessions with them.
+-- Run once with: psql -v ON_ERROR_STOP=1 -f 013-soft-delete.sql
+-- ------------------------------------------------
+BEGIN;
+
+ALTER TABLE users
+    ADD COLUMN deleted_by VARCHAR(36),
+    ADD COLUMN deleted_at TIMESTAMPTZ;
+
+ALTER TABLE devices
+    ADD COLUMN deleted_by VARCHAR(36),
+    ADD COLUMN deleted_at TIMESTAMPTZ;
+
+ALTER TABLE uploaded_files
+    ADD COLUMN deleted_by VARCHAR(36),
+    ADD COLUMN deleted_at TIMESTAMPTZ;
+
+ALTER TABLE devices DROP CONSTRAINT devices_user_id_name_key;
+CREATE UNIQUE INDEX devices_user_id_name_key ON devices(user_id, name) WHERE deleted_at IS NULL;
+
+DO $$
+BEGIN
+    IF EXISTS (
+        SELECT 1 FROM uploaded_files
+         WHERE file_type = 'profile_picture'
+         GROUP BY user_id HAVING COUNT(*) > 1
+    ) THEN
+        RAISE EXCEPTION 'Users have several profile pictures, remove the extra ones before upgrading';
+    END IF;
+END $$;
+
+CREATE UNIQUE INDEX uploaded_files_profile_picture_key ON uploaded_files(user_id)
+    WHERE file_type = 'profile_picture' AND deleted_at IS NULL;
+
+ALTER TABLE user_auth
+    DROP CONSTRAINT user_auth_user_id_fkey,
+    ADD CONSTRAINT user_auth_user_id_fkey
+        FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE;
+
+ALTER TABLE refresh_tokens
+    DROP CONSTRAINT refresh_tokens_user_id_fkey,
+    ADD CONSTRAINT refresh_tokens_user_id_fkey
+        FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE;
+
+COMMIT;
@@ -0,0 +1,15 @@
+-- ------------------------------------------------
+-- Upgrade for databases created before optimistic concurrency.
+-- Adds the version column of users and devices, which every change bumps
+-- and the API returns as the ETag.
+-- Run once with: psql -v ON_ERROR_STOP=1 -f 014-versioning.sql
+-- ------------------------------------------------
+BEGIN;
+
+ALTER TABLE users
+    ADD COLUMN version BIGINT NOT NULL DEFAULT 1;
+
+ALTER TABLE devices
+    ADD COLUMN version BIGINT NOT NULL DEFAULT 1;
+
+COMMIT;
@@ -0,0 +1,43 @@
+-- ------------------------------------------------
+-- Upgrade for databases created before multi-tenancy.
+-- Adds the organizations table and moves every existing user, device and
+-- uploaded file into the default organization.
+-- Private files are served from the directory of their organization, so move
+-- the stored files as well, e.g. from ASSETS_PRIVATE_PATH:
+--   mkdir 00000000-0000-0000-0000-000000000001
+--   mv profile_picture 00000000-0000-0000-0000-000000000001/
+-- Run once with: psql -v ON_ERROR_STOP=1 -f 015-organizations.sql
+-- ------------------------------------------------
+BEGIN;
+
+CREATE TABLE organizations (
+    id           VARCHAR(36)    PRIMARY KEY,
+    name         VARCHAR(128)   NOT NULL UNIQUE,
+    created_by   VARCHAR(36),
+    created_at   TIMESTAMPTZ    NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    modified_by  VARCHAR(36),
+    modified_at  TIMESTAMPTZ    NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+INSERT INTO organizations (id, name) VALUES ('00000000-0000-0000-0000-000000000001', 'Default');
+
+ALTER TABLE users
+    ADD COLUMN org_id VARCHAR(36) NOT NULL DEFAULT '00000000-0000-0000-0000-000000000001'
+        REFERENCES organizations(id);
+CREATE INDEX idx_users_org_id ON users(org_id);
+
+ALTER TABLE devices
+    ADD COLUMN org_id VARCHAR(36) NOT NULL DEFAULT '00000000-0000-0000-0000-000000000001'
+        REFERENCES organizations(id);
+CREATE INDEX idx_devices_org_id ON devices(org_id);
+
+ALTER TABLE uploaded_files
+    ADD COLUMN org_id VARCHAR(36) NOT NULL DEFAULT '00000000-0000-0000-0000-000000000001'
+        REFERENCES organizations(id);
+CREATE INDEX idx_uploaded_files_org_id ON uploaded_files(org_id);
+
+UPDATE uploaded_files
+   SET file_relative_path = org_id || '/' || file_relative_path,
+       file_url = regexp_replace(file_url, '/profile/[^/]*$', '') || '/' || org_id || '/' || file_relative_path;
+
+COMMIT;
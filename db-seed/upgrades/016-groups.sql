@@ -0,0 +1,44 @@
+-- ------------------------------------------------
+-- Upgrade for databases created before user groups.
+-- Adds the groups and group_members tables and the group permissions:
+-- everyone may read the groups they belong to, admins manage them.
+-- Run once with: psql -v ON_ERROR_STOP=1 -f 016-groups.sql
+-- ------------------------------------------------
+BEGIN;
+
+CREATE TABLE groups (
+    id           VARCHAR(36)    PRIMARY KEY,
+    org_id       VARCHAR(36)    NOT NULL REFERENCES organizations(id),
+    name         VARCHAR(128)   NOT NULL,
+    description  VARCHAR(255),
+    created_by   VARCHAR(36),
+    created_at   TIMESTAMPTZ    NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    modified_by  VARCHAR(36),
+    modified_at  TIMESTAMPTZ    NOT NULL DEFAULT CURRENT_TIMESTAMP,
+
+    UNIQUE (org_id, name)
+);
+
+CREATE INDEX idx_groups_created_at_id ON groups(created_at, id);
+
+CREATE TABLE group_members (
+    group_id     VARCHAR(36)    NOT NULL,
+    user_id      VARCHAR(36)    NOT NULL,
+    role         VARCHAR(16)    NOT NULL DEFAULT 'member',
+    created_by   VARCHAR(36),
+    created_at   TIMESTAMPTZ    NOT NULL DEFAULT CURRENT_TIMESTAMP,
+
+    PRIMARY KEY (group_id, user_id),
+    FOREIGN KEY (group_id) REFERENCES groups(id) ON DELETE CASCADE,
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_group_members_user_id ON group_members(user_id);
+
+INSERT INTO role_permissions (role_id, permission)
+SELECT id, 'group:read' FROM roles WHERE name IN ('admin', 'user')
+UNION ALL
+SELECT id, 'group:write' FROM roles WHERE name = 'admin'
+ON CONFLICT DO NOTHING;
+
+COMMIT;
@@ -0,0 +1,17 @@
+-- ------------------------------------------------
+-- Upgrade for databases created before case-insensitive usernames.
+-- Makes usernames unique regardless of case, as they are matched at login.
+-- Run once with: psql -v ON_ERROR_STOP=1 -f 017-case-insensitive-usernames.sql
+-- ------------------------------------------------
+BEGIN;
+
+DO $$
+BEGIN
+    IF EXISTS (SELECT 1 FROM users GROUP BY LOWER(username) HAVING COUNT(*) > 1) THEN
+        RAISE EXCEPTION 'Usernames differ only in case, rename the duplicates before upgrading';
+    END IF;
+END $$;
+
+CREATE UNIQUE INDEX users_username_lower_key ON users(LOWER(username));
+
+COMMIT;
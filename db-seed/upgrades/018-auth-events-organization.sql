@@ -0,0 +1,19 @@
+-- ------------------------------------------------
+-- Upgrade for databases created before the audit trail was scoped to tenants.
+-- Moves every auth event into the organization of its user; events without a
+-- known user go to the default organization.
+-- Run once with: psql -v ON_ERROR_STOP=1 -f 018-auth-events-organization.sql
+-- ------------------------------------------------
+BEGIN;
+
+ALTER TABLE auth_events ADD COLUMN org_id VARCHAR(36) REFERENCES organizations(id);
+
+UPDATE auth_events e
+   SET org_id = COALESCE((SELECT u.org_id FROM users u WHERE u.id = e.user_id),
+                         '00000000-0000-0000-0000-000000000001');
+
+ALTER TABLE auth_events ALTER COLUMN org_id SET NOT NULL;
+
+CREATE INDEX idx_auth_events_org_id_created_at ON auth_events(org_id, created_at);
+
+COMMIT;
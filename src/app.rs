@@ -3,8 +3,8 @@ use axum::{
     error_handling::HandleErrorLayer,
     extract::{DefaultBodyLimit, Request},
     http::{
-        header::{AUTHORIZATION, CONTENT_TYPE},
-        Method, StatusCode,
+        header::{AUTHORIZATION, CONTENT_TYPE, ETAG, IF_MATCH},
+        HeaderName, HeaderValue, Method, StatusCode,
     },
     middleware::{self, Next},
     response::{IntoResponse, Response},
@@ -15,7 +15,8 @@ use http_body_util::BodyExt;
 use std::time::Duration;
 use tower::ServiceBuilder;
 use tower_http::{
-    cors::{Any, CorsLayer},
+    cors::{AllowOrigin, Any, CorsLayer},
+    request_id::{MakeRequestUuid, PropagateRequestIdLayer, SetRequestIdLayer},
     services::ServeDir,
     trace::TraceLayer,
 };
@@ -25,13 +26,19 @@ use utoipa::OpenApi;
 use crate::{
     common::{
         app_state::AppState,
+        client_info::REQUEST_ID_HEADER,
+        config::{Config, SessionMode},
         error::{handle_error, AppError},
         jwt,
+        session_cookie::CSRF_HEADER,
+        tenant,
     },
     domains::{
-        auth::{user_auth_routes, UserAuthApiDoc},
+        auth::{protected_auth_routes, user_auth_routes, well_known_routes, UserAuthApiDoc},
         device::{device_routes, DeviceApiDoc},
         file::{file_routes, FileApiDoc},
+        group::{group_routes, GroupApiDoc},
+        oauth::{oauth_routes, protected_oauth_routes, OAuthApiDoc},
         user::{user_routes, UserApiDoc},
     },
 };
@@ -57,17 +64,66 @@ fn create_swagger_ui() -> SwaggerUi {
         .url("/api-docs/user/openapi.json", UserApiDoc::openapi())
         .url("/api-docs/device/openapi.json", DeviceApiDoc::openapi())
         .url("/api-docs/file/openapi.json", FileApiDoc::openapi())
+        .url("/api-docs/group/openapi.json", GroupApiDoc::openapi())
+        .url("/api-docs/oauth/openapi.json", OAuthApiDoc::openapi())
 }
 
-pub fn create_router(state: AppState) -> Router {
-    // Build a CORS layer that applies to everyone
+/// Build the CORS layer.
+/// Bearer tokens are not sent by browsers on their own, so in bearer mode any origin is allowed.
+/// Session cookies are, so in cookie mode only the configured origins may make credentialed requests.
+/// The request ID can be sent by and is exposed to browsers in both modes.
+fn create_cors_layer(config: &Config) -> CorsLayer {
+    let request_id = HeaderName::from_static(REQUEST_ID_HEADER);
     let cors = CorsLayer::new()
-        .allow_methods([Method::GET, Method::POST, Method::PUT, Method::DELETE])
-        .allow_origin(Any)
-        .allow_headers([AUTHORIZATION, CONTENT_TYPE]);
+        .allow_methods([
+            Method::GET,
+            Method::POST,
+            Method::PUT,
+            Method::PATCH,
+            Method::DELETE,
+        ])
+        .expose_headers([request_id.clone(), ETAG]);
+
+    match config.session_mode {
+        SessionMode::Bearer => cors.allow_origin(Any).allow_headers([
+            AUTHORIZATION,
+            CONTENT_TYPE,
+            IF_MATCH,
+            request_id,
+        ]),
+        SessionMode::Cookie => {
+            let origins = config
+                .cors_allowed_origins
+                .iter()
+                .filter_map(|origin| {
+                    HeaderValue::from_str(origin)
+                        .inspect_err(|_| tracing::error!("Invalid CORS origin: {origin}"))
+                        .ok()
+                })
+                .collect::<Vec<_>>();
+
+            cors.allow_origin(AllowOrigin::list(origins))
+                .allow_credentials(true)
+                .allow_headers([
+                    AUTHORIZATION,
+                    CONTENT_TYPE,
+                    IF_MATCH,
+                    HeaderName::from_static(CSRF_HEADER),
+                    request_id,
+                ])
+        }
+    }
+}
+
+pub fn create_router(state: AppState) -> Router {
+    let cors = create_cors_layer(&state.config);
 
-    // Create a common middleware stack for error handling, timeouts, and CORS.
+    // Create a common middleware stack for request IDs, error handling, timeouts, and CORS.
+    // Requests without an `X-Request-Id` get a new UUID, and every response echoes it.
+    let request_id = HeaderName::from_static(REQUEST_ID_HEADER);
     let middleware_stack = ServiceBuilder::new()
+        .layer(SetRequestIdLayer::new(request_id.clone(), MakeRequestUuid))
+        .layer(PropagateRequestIdLayer::new(request_id))
         .layer(HandleErrorLayer::new(handle_error))
         .timeout(Duration::from_secs(1800))
         .layer(cors);
@@ -75,18 +131,23 @@ pub fn create_router(state: AppState) -> Router {
     // /auth routes (login, register, refresh, etc.) — no logging here
     let auth_router = Router::new()
         .nest("/auth", user_auth_routes())
+        .nest("/oauth", oauth_routes())
+        .merge(well_known_routes())
         .layer(middleware::from_fn(make_request_response_inspecter(false)));
 
     // Protected API routes
     let protected_routes = Router::new()
+        .nest("/auth", protected_auth_routes())
+        .nest("/oauth", protected_oauth_routes())
         .nest("/user", user_routes())
         .nest("/device", device_routes())
         .nest("/file", file_routes())
+        .nest("/group", group_routes())
         // by default, Multipart limits to 2MB; override with `asset_max_size`
         // See https://docs.rs/axum/latest/axum/extract/struct.Multipart.html
         .layer(DefaultBodyLimit::max(state.config.asset_max_size))
         // enforce JWT authentication
-        .route_layer(middleware::from_fn(jwt::jwt_auth))
+        .route_layer(middleware::from_fn_with_state(state.clone(), jwt::jwt_auth))
         // attach inspecter
         .layer(middleware::from_fn(make_request_response_inspecter(true)));
 
@@ -101,8 +162,13 @@ pub fn create_router(state: AppState) -> Router {
             state.config.assets_private_url.as_str(),
             ServeDir::new(state.config.assets_private_path.clone()),
         )
+        // only serve the files of the caller's organization
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            tenant::restrict_private_assets,
+        ))
         // enforce JWT authentication
-        .route_layer(middleware::from_fn(jwt::jwt_auth))
+        .route_layer(middleware::from_fn_with_state(state.clone(), jwt::jwt_auth))
         // attach inspecter
         .layer(middleware::from_fn(make_request_response_inspecter(true)));
 
@@ -124,6 +190,7 @@ pub fn create_router(state: AppState) -> Router {
                         "request",
                         method = %req.method(),
                         uri = %req.uri(),
+                        request_id = ?req.headers().get(REQUEST_ID_HEADER),
                     )
                 })
                 .on_response(
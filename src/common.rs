@@ -1,11 +1,23 @@
 pub mod app_state;
 pub mod bootstrap;
+pub mod client_info;
+pub mod concurrency;
 pub mod config;
 pub mod dto;
 pub mod error;
 pub mod hash_util;
 pub mod jwt;
+pub mod keyring;
+pub mod merge_patch;
 pub mod multipart_helper;
+pub mod notifier;
 #[cfg(feature = "opentelemetry")]
 pub mod opentelemetry;
+pub mod pagination;
+pub mod password_policy;
+pub mod permission;
+pub mod record_format;
+pub mod session_cookie;
+pub mod tenant;
+pub mod totp;
 pub mod ts_format;
@@ -2,7 +2,7 @@ use std::sync::Arc;
 
 use crate::domains::{
     auth::AuthServiceTrait, device::DeviceServiceTrait, file::FileServiceTrait,
-    user::UserServiceTrait,
+    group::GroupServiceTrait, oauth::OAuthServiceTrait, user::UserServiceTrait,
 };
 
 use super::config::Config;
@@ -21,6 +21,10 @@ pub struct AppState {
     pub device_service: Arc<dyn DeviceServiceTrait>,
     /// Service handling file-related logic.
     pub file_service: Arc<dyn FileServiceTrait>,
+    /// Service handling groups of users and their members.
+    pub group_service: Arc<dyn GroupServiceTrait>,
+    /// Service handling OAuth 2.0 clients and grants.
+    pub oauth_service: Arc<dyn OAuthServiceTrait>,
 }
 
 impl AppState {
@@ -31,6 +35,8 @@ impl AppState {
         user_service: Arc<dyn UserServiceTrait>,
         device_service: Arc<dyn DeviceServiceTrait>,
         file_service: Arc<dyn FileServiceTrait>,
+        group_service: Arc<dyn GroupServiceTrait>,
+        oauth_service: Arc<dyn OAuthServiceTrait>,
     ) -> Self {
         Self {
             config,
@@ -38,6 +44,8 @@ impl AppState {
             user_service,
             device_service,
             file_service,
+            group_service,
+            oauth_service,
         }
     }
 }
@@ -1,24 +1,47 @@
-use std::sync::Arc;
+use std::sync::{Arc, LazyLock};
+use std::time::Duration;
 
+use chrono::Utc;
 use sqlx::PgPool;
 
 use crate::common::config::Config;
+use crate::common::error::AppError;
+use crate::common::keyring::KEYRING;
+use crate::common::notifier::create_notifier;
 use crate::domains::auth::{AuthService, AuthServiceTrait};
 use crate::domains::device::{DeviceService, DeviceServiceTrait};
 use crate::domains::file::{FileService, FileServiceTrait};
+use crate::domains::group::{GroupService, GroupServiceTrait};
+use crate::domains::oauth::{OAuthService, OAuthServiceTrait};
 use crate::domains::user::UserServiceTrait;
 use crate::{common::app_state::AppState, domains::user::UserService};
 
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
 /// Constructs and wires all application services and returns a configured AppState.
+/// Panics if the JWT keyring cannot be loaded, so a misconfigured key fails at startup
+/// rather than on the first sign-in.
 pub fn build_app_state(pool: PgPool, config: Config) -> AppState {
-    let auth_service: Arc<dyn AuthServiceTrait> = AuthService::create_service(pool.clone());
+    LazyLock::force(&KEYRING);
+
     let file_service: Arc<dyn FileServiceTrait> =
         FileService::create_service(config.clone(), pool.clone());
-    let user_service: Arc<dyn UserServiceTrait> =
-        UserService::create_service(pool.clone(), Arc::clone(&file_service));
-    let device_service: Arc<dyn DeviceServiceTrait> = DeviceService::create_service(pool.clone());
+    let group_service: Arc<dyn GroupServiceTrait> = GroupService::create_service(pool.clone());
+    let device_service: Arc<dyn DeviceServiceTrait> =
+        DeviceService::create_service(pool.clone(), Arc::clone(&group_service));
+    let user_service: Arc<dyn UserServiceTrait> = UserService::create_service(
+        pool.clone(),
+        Arc::clone(&file_service),
+        Arc::clone(&device_service),
+    );
+    let auth_service: Arc<dyn AuthServiceTrait> = AuthService::create_service(
+        config.clone(),
+        pool.clone(),
+        Arc::clone(&user_service),
+        create_notifier(&config),
+    );
+    let oauth_service: Arc<dyn OAuthServiceTrait> =
+        OAuthService::create_service(config.clone(), pool.clone(), Arc::clone(&auth_service));
 
     AppState::new(
         config,
@@ -26,9 +49,45 @@ pub fn build_app_state(pool: PgPool, config: Config) -> AppState {
         user_service,
         device_service,
         file_service,
+        group_service,
+        oauth_service,
     )
 }
 
+/// Hard-deletes the users, devices and files soft-deleted more than `PURGE_RETENTION_DAYS` ago.
+/// Purging a user also purges all of their devices and files.
+pub async fn purge_deleted_records(state: &AppState) -> Result<(), AppError> {
+    let deleted_before = Utc::now() - chrono::Duration::days(state.config.purge_retention_days);
+
+    let files = state.file_service.purge_deleted(deleted_before).await?;
+    let devices = state.device_service.purge_deleted(deleted_before).await?;
+    let users = state.user_service.purge_deleted(deleted_before).await?;
+
+    tracing::info!(
+        "Purged {users} users, {devices} devices and {files} files deleted before {deleted_before}"
+    );
+    Ok(())
+}
+
+/// Runs `purge_deleted_records` every `PURGE_INTERVAL_SECS` in the background.
+/// The job is disabled when the interval is 0.
+pub fn spawn_purge_job(state: AppState) {
+    if state.config.purge_interval_secs == 0 {
+        return;
+    }
+
+    let period = Duration::from_secs(state.config.purge_interval_secs);
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(period);
+        loop {
+            interval.tick().await;
+            if let Err(err) = purge_deleted_records(&state).await {
+                tracing::error!("Error purging deleted records: {err}");
+            }
+        }
+    });
+}
+
 /// Setup tracing for the application.
 /// This function initializes the tracing subscriber with a default filter and formatting.
 pub fn setup_tracing() {
@@ -0,0 +1,78 @@
+use std::{convert::Infallible, net::IpAddr, net::SocketAddr};
+
+use axum::{
+    extract::{ConnectInfo, FromRequestParts},
+    http::{header::USER_AGENT, request::Parts, Extensions, HeaderMap, HeaderName},
+};
+
+use crate::common::{app_state::AppState, config::Config};
+
+/// Header carrying the ID of a request. Set by the router if the client did not send one.
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// ClientInfo describes the client that sent a request.
+/// Used for throttling and auditing; every field is best effort.
+#[derive(Debug, Clone, Default)]
+pub struct ClientInfo {
+    /// IP address of the client, if known.
+    pub ip: Option<IpAddr>,
+    /// Value of the `User-Agent` header, if sent.
+    pub user_agent: Option<String>,
+    /// Value of the `X-Request-Id` header, which the router sets on every request.
+    pub request_id: Option<String>,
+}
+
+impl ClientInfo {
+    /// Reads the client information from the parts of a request.
+    ///
+    /// The IP is read from the connection (requires serving the router with
+    /// `into_make_service_with_connect_info::<SocketAddr>()`). When `TRUST_FORWARDED_FOR`
+    /// is enabled, the last `X-Forwarded-For` entry, i.e. the address seen by the proxy, takes precedence.
+    pub fn from_parts(config: &Config, headers: &HeaderMap, extensions: &Extensions) -> Self {
+        let forwarded_ip = config
+            .trust_forwarded_for
+            .then(|| {
+                headers
+                    .get("x-forwarded-for")
+                    .and_then(|value| value.to_str().ok())
+                    .and_then(|value| value.rsplit(',').next())
+                    .and_then(|ip| ip.trim().parse::<IpAddr>().ok())
+            })
+            .flatten();
+
+        let ip = forwarded_ip.or_else(|| {
+            extensions
+                .get::<ConnectInfo<SocketAddr>>()
+                .map(|ConnectInfo(addr)| addr.ip())
+        });
+
+        let header = |name| {
+            headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_string)
+        };
+
+        Self {
+            ip,
+            user_agent: header(USER_AGENT),
+            request_id: header(HeaderName::from_static(REQUEST_ID_HEADER)),
+        }
+    }
+}
+
+/// Extracts the client information from the request.
+impl FromRequestParts<AppState> for ClientInfo {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        Ok(Self::from_parts(
+            &state.config,
+            &parts.headers,
+            &parts.extensions,
+        ))
+    }
+}
@@ -0,0 +1,83 @@
+use std::convert::Infallible;
+
+use axum::{
+    extract::FromRequestParts,
+    http::{header::ETAG, header::IF_MATCH, request::Parts, HeaderName},
+};
+
+use crate::common::error::AppError;
+
+/// Formats the version of a resource as a strong ETag, e.g. `"3"`.
+pub fn etag(version: i64) -> String {
+    format!("\"{version}\"")
+}
+
+/// The `ETag` header of a resource with the given version.
+/// Returned next to the body, e.g. `Ok((etag_header(dto.version), RestApiResponse::success(dto)))`.
+pub fn etag_header(version: i64) -> [(HeaderName, String); 1] {
+    [(ETAG, etag(version))]
+}
+
+/// The `If-Match` precondition of a request.
+///
+/// Without the header, or with `If-Match: *`, every version of an existing resource matches.
+/// Otherwise one of the listed ETags must be the current one; weak ETags never match.
+#[derive(Debug, Clone, Default)]
+pub struct IfMatch(Option<Vec<String>>);
+
+impl IfMatch {
+    /// Parses the value of an `If-Match` header.
+    pub fn parse(value: &str) -> Self {
+        let tags: Vec<String> = value
+            .split(',')
+            .map(str::trim)
+            .filter(|tag| !tag.is_empty())
+            .map(str::to_string)
+            .collect();
+
+        match tags.iter().any(|tag| tag == "*") {
+            true => Self(None),
+            false => Self(Some(tags)),
+        }
+    }
+
+    /// Checks the precondition against the current version of a resource.
+    /// Fails with `PreconditionFailed` if the resource was changed since the client read it.
+    pub fn check(&self, version: i64) -> Result<(), AppError> {
+        match &self.0 {
+            Some(tags) if !tags.contains(&etag(version)) => Err(AppError::PreconditionFailed),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Extracts the `If-Match` precondition from the request headers.
+/// A header that is not valid text matches no version.
+impl<S: Send + Sync> FromRequestParts<S> for IfMatch {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Ok(match parts.headers.get(IF_MATCH) {
+            Some(value) => Self::parse(value.to_str().unwrap_or_default()),
+            None => Self::default(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_if_match() {
+        assert!(IfMatch::default().check(3).is_ok());
+        assert!(IfMatch::parse("*").check(3).is_ok());
+        assert!(IfMatch::parse("\"3\"").check(3).is_ok());
+        assert!(IfMatch::parse("\"2\", \"3\"").check(3).is_ok());
+
+        assert!(IfMatch::parse("\"2\"").check(3).is_err());
+        assert!(IfMatch::parse("W/\"3\"").check(3).is_err());
+        assert!(IfMatch::parse("3").check(3).is_err());
+        assert!(IfMatch::parse("").check(3).is_err());
+    }
+}
@@ -1,9 +1,62 @@
+use argon2::Params;
 use regex::Regex;
 use sqlx::{postgres::PgPoolOptions, PgPool};
 use std::env;
+use std::str::FromStr;
+use std::sync::Arc;
 use std::time::Duration;
 use tokio::time::sleep;
 
+use crate::common::password_policy::{load_denylist, PasswordPolicy};
+
+/// RegistrationMode controls how new accounts and credentials may be created.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RegistrationMode {
+    /// No new credentials can be created.
+    Disabled,
+    /// Self-signup requires a single-use invite issued by an administrator.
+    InviteOnly,
+    /// Only administrators can attach credentials to existing users.
+    AdminOnly,
+    /// Anyone can sign up.
+    Open,
+}
+
+impl FromStr for RegistrationMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "disabled" => Ok(Self::Disabled),
+            "invite-only" => Ok(Self::InviteOnly),
+            "admin-only" => Ok(Self::AdminOnly),
+            "open" => Ok(Self::Open),
+            other => Err(format!("Invalid REGISTRATION_MODE: {other}")),
+        }
+    }
+}
+
+/// SessionMode controls how browser sessions carry their tokens.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SessionMode {
+    /// Tokens are returned in the response body and sent back as `Authorization: Bearer`.
+    Bearer,
+    /// Tokens are kept in HttpOnly cookies; state-changing requests need a CSRF token.
+    Cookie,
+}
+
+impl FromStr for SessionMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "bearer" => Ok(Self::Bearer),
+            "cookie" => Ok(Self::Cookie),
+            other => Err(format!("Invalid SESSION_MODE: {other}")),
+        }
+    }
+}
+
 /// Config is a struct that holds the configuration for the application.
 #[derive(Clone, Debug)]
 pub struct Config {
@@ -22,6 +75,70 @@ pub struct Config {
 
     pub asset_allowed_extensions_pattern: Regex,
     pub asset_max_size: usize,
+
+    /// Lifetime of issued access tokens, in seconds.
+    pub jwt_access_token_ttl_secs: i64,
+    /// Lifetime of issued refresh tokens, in seconds.
+    pub jwt_refresh_token_ttl_secs: i64,
+    /// Issuer (`iss`) set on issued tokens and required on incoming ones.
+    pub jwt_issuer: String,
+    /// Accepted audiences (`aud`). Issued tokens carry the first one.
+    pub jwt_audience: Vec<String>,
+    /// Allowed clock skew when validating `exp` and `nbf`, in seconds.
+    pub jwt_leeway_secs: u64,
+
+    /// How new accounts and credentials may be created.
+    pub registration_mode: RegistrationMode,
+    /// Lifetime of registration invites, in seconds.
+    pub invite_ttl_secs: i64,
+
+    /// Lifetime of password reset tokens, in seconds.
+    pub password_reset_ttl_secs: i64,
+    /// Lifetime of magic links and their one-time codes, in seconds.
+    pub magic_link_ttl_secs: i64,
+    /// Page of the frontend that magic links point to; the token is appended as `?token=`.
+    pub magic_link_url: String,
+    /// File that notifications are appended to. Notifications are logged when unset.
+    pub notifier_file: Option<String>,
+
+    /// Consecutive failed logins of an account before it is temporarily locked.
+    pub login_max_attempts: i32,
+    /// Failed logins from one client IP before the IP is temporarily locked.
+    pub login_max_attempts_per_ip: i32,
+    /// Delay after the first failed login, in seconds. It doubles with every further failure.
+    pub login_backoff_base_secs: i64,
+    /// Duration of a lockout, in seconds. Failures older than this are forgotten.
+    pub login_lockout_secs: i64,
+    /// Whether to take the client IP from the `X-Forwarded-For` header.
+    /// Only enable this behind a reverse proxy that sets the header.
+    pub trust_forwarded_for: bool,
+
+    /// Lifetime of the MFA challenge issued by a login of an MFA-enabled account, in seconds.
+    pub mfa_challenge_ttl_secs: i64,
+
+    /// Lifetime of the tokens issued to admins impersonating a user, in seconds.
+    pub impersonation_ttl_secs: i64,
+
+    /// Days that soft-deleted users, devices and files are kept before they are purged.
+    pub purge_retention_days: i64,
+    /// Interval of the purge job, in seconds. `0` disables the job.
+    pub purge_interval_secs: u64,
+
+    /// Argon2id parameters of new password hashes.
+    /// Weaker stored hashes are upgraded on the next successful login.
+    pub password_hash_params: Params,
+    /// Rules that new passwords must satisfy.
+    pub password_policy: PasswordPolicy,
+
+    /// Lifetime of OAuth authorization codes, in seconds.
+    pub oauth_code_ttl_secs: i64,
+
+    /// How `/auth/login` hands out tokens to browsers.
+    pub session_mode: SessionMode,
+    /// Whether session cookies carry the `Secure` attribute. Only disable this for local HTTP.
+    pub cookie_secure: bool,
+    /// Origins allowed to make credentialed cross-origin requests in cookie mode.
+    pub cors_allowed_origins: Vec<String>,
 }
 
 /// from_env reads the environment variables and returns a Config struct.
@@ -63,6 +180,154 @@ impl Config {
 
             asset_max_size: env::var("ASSET_MAX_SIZE")
                 .map(|s| s.parse::<usize>().unwrap_or(50 * 1024 * 1024))?, // Default to 50MB
+
+            jwt_access_token_ttl_secs: env::var("JWT_ACCESS_TOKEN_TTL_SECS")
+                .map(|s| s.parse::<i64>().unwrap_or(15 * 60))
+                .unwrap_or(15 * 60), // Default to 15 minutes
+            jwt_refresh_token_ttl_secs: env::var("JWT_REFRESH_TOKEN_TTL_SECS")
+                .map(|s| s.parse::<i64>().unwrap_or(14 * 24 * 60 * 60))
+                .unwrap_or(14 * 24 * 60 * 60), // Default to 14 days
+
+            jwt_issuer: env::var("JWT_ISSUER").unwrap_or_else(|_| "clean_axum_demo".to_string()),
+            jwt_audience: env::var("JWT_AUDIENCE")
+                .map(|s| {
+                    s.split(',')
+                        .map(|aud| aud.trim().to_string())
+                        .filter(|aud| !aud.is_empty())
+                        .collect::<Vec<_>>()
+                })
+                .ok()
+                .filter(|audience| !audience.is_empty())
+                .unwrap_or_else(|| vec!["clean_axum_demo".to_string()]),
+            jwt_leeway_secs: env::var("JWT_LEEWAY_SECS")
+                .map(|s| s.parse::<u64>().unwrap_or(60))
+                .unwrap_or(60), // Default to 1 minute
+
+            registration_mode: env::var("REGISTRATION_MODE")
+                .map(|s| {
+                    s.parse::<RegistrationMode>().unwrap_or_else(|err| {
+                        eprintln!("{err}");
+                        RegistrationMode::AdminOnly
+                    })
+                })
+                .unwrap_or(RegistrationMode::AdminOnly),
+            invite_ttl_secs: env::var("INVITE_TTL_SECS")
+                .map(|s| s.parse::<i64>().unwrap_or(7 * 24 * 60 * 60))
+                .unwrap_or(7 * 24 * 60 * 60), // Default to 7 days
+
+            password_reset_ttl_secs: env::var("PASSWORD_RESET_TTL_SECS")
+                .map(|s| s.parse::<i64>().unwrap_or(60 * 60))
+                .unwrap_or(60 * 60), // Default to 1 hour
+            magic_link_ttl_secs: env::var("MAGIC_LINK_TTL_SECS")
+                .map(|s| s.parse::<i64>().unwrap_or(15 * 60))
+                .unwrap_or(15 * 60), // Default to 15 minutes
+            magic_link_url: env::var("MAGIC_LINK_URL")
+                .unwrap_or_else(|_| "http://localhost:3000/magic-link".to_string()),
+            notifier_file: env::var("NOTIFIER_FILE").ok().filter(|s| !s.is_empty()),
+
+            login_max_attempts: env::var("LOGIN_MAX_ATTEMPTS")
+                .map(|s| s.parse::<i32>().unwrap_or(5))
+                .unwrap_or(5),
+            login_max_attempts_per_ip: env::var("LOGIN_MAX_ATTEMPTS_PER_IP")
+                .map(|s| s.parse::<i32>().unwrap_or(20))
+                .unwrap_or(20),
+            login_backoff_base_secs: env::var("LOGIN_BACKOFF_BASE_SECS")
+                .map(|s| s.parse::<i64>().unwrap_or(1))
+                .unwrap_or(1),
+            login_lockout_secs: env::var("LOGIN_LOCKOUT_SECS")
+                .map(|s| s.parse::<i64>().unwrap_or(15 * 60))
+                .unwrap_or(15 * 60), // Default to 15 minutes
+            trust_forwarded_for: env::var("TRUST_FORWARDED_FOR")
+                .map(|s| s.eq_ignore_ascii_case("true"))
+                .unwrap_or(false),
+
+            mfa_challenge_ttl_secs: env::var("MFA_CHALLENGE_TTL_SECS")
+                .map(|s| s.parse::<i64>().unwrap_or(5 * 60))
+                .unwrap_or(5 * 60), // Default to 5 minutes
+
+            impersonation_ttl_secs: env::var("IMPERSONATION_TTL_SECS")
+                .map(|s| s.parse::<i64>().unwrap_or(10 * 60))
+                .unwrap_or(10 * 60), // Default to 10 minutes
+
+            purge_retention_days: env::var("PURGE_RETENTION_DAYS")
+                .map(|s| s.parse::<i64>().unwrap_or(30))
+                .unwrap_or(30), // Default to 30 days
+            purge_interval_secs: env::var("PURGE_INTERVAL_SECS")
+                .map(|s| s.parse::<u64>().unwrap_or(60 * 60))
+                .unwrap_or(60 * 60), // Default to 1 hour
+
+            password_hash_params: Params::new(
+                env::var("PASSWORD_HASH_MEMORY_KIB")
+                    .map(|s| s.parse::<u32>().unwrap_or(Params::DEFAULT_M_COST))
+                    .unwrap_or(Params::DEFAULT_M_COST),
+                env::var("PASSWORD_HASH_ITERATIONS")
+                    .map(|s| s.parse::<u32>().unwrap_or(Params::DEFAULT_T_COST))
+                    .unwrap_or(Params::DEFAULT_T_COST),
+                env::var("PASSWORD_HASH_PARALLELISM")
+                    .map(|s| s.parse::<u32>().unwrap_or(Params::DEFAULT_P_COST))
+                    .unwrap_or(Params::DEFAULT_P_COST),
+                None,
+            )
+            .unwrap_or_else(|err| {
+                eprintln!("Invalid PASSWORD_HASH_* parameters: {err}");
+                Params::default()
+            }),
+            password_policy: PasswordPolicy {
+                min_length: env::var("PASSWORD_MIN_LENGTH")
+                    .map(|s| s.parse::<usize>().unwrap_or(8))
+                    .unwrap_or(8),
+                max_length: env::var("PASSWORD_MAX_LENGTH")
+                    .map(|s| s.parse::<usize>().unwrap_or(128))
+                    .unwrap_or(128),
+                require_lowercase: env::var("PASSWORD_REQUIRE_LOWERCASE")
+                    .map(|s| s.eq_ignore_ascii_case("true"))
+                    .unwrap_or(false),
+                require_uppercase: env::var("PASSWORD_REQUIRE_UPPERCASE")
+                    .map(|s| s.eq_ignore_ascii_case("true"))
+                    .unwrap_or(false),
+                require_digit: env::var("PASSWORD_REQUIRE_DIGIT")
+                    .map(|s| s.eq_ignore_ascii_case("true"))
+                    .unwrap_or(false),
+                require_symbol: env::var("PASSWORD_REQUIRE_SYMBOL")
+                    .map(|s| s.eq_ignore_ascii_case("true"))
+                    .unwrap_or(false),
+                denylist: Arc::new(
+                    env::var("PASSWORD_DENYLIST_FILE")
+                        .ok()
+                        .filter(|s| !s.is_empty())
+                        .map(|path| {
+                            load_denylist(&path).unwrap_or_else(|err| {
+                                eprintln!("Cannot read PASSWORD_DENYLIST_FILE {path}: {err}");
+                                Default::default()
+                            })
+                        })
+                        .unwrap_or_default(),
+                ),
+            },
+
+            oauth_code_ttl_secs: env::var("OAUTH_CODE_TTL_SECS")
+                .map(|s| s.parse::<i64>().unwrap_or(60))
+                .unwrap_or(60), // Default to 1 minute
+
+            session_mode: env::var("SESSION_MODE")
+                .map(|s| {
+                    s.parse::<SessionMode>().unwrap_or_else(|err| {
+                        eprintln!("{err}");
+                        SessionMode::Bearer
+                    })
+                })
+                .unwrap_or(SessionMode::Bearer),
+            cookie_secure: env::var("COOKIE_SECURE")
+                .map(|s| !s.eq_ignore_ascii_case("false"))
+                .unwrap_or(true),
+            cors_allowed_origins: env::var("CORS_ALLOWED_ORIGINS")
+                .map(|s| {
+                    s.split(',')
+                        .map(|origin| origin.trim().to_string())
+                        .filter(|origin| !origin.is_empty())
+                        .collect()
+                })
+                .unwrap_or_default(),
         })
     }
 }
@@ -1,5 +1,6 @@
 use axum::response::{IntoResponse, Response};
 use serde::{Deserialize, Serialize};
+use utoipa::IntoParams;
 
 /// A standardized API response format.
 #[derive(Serialize, Deserialize, Debug)]
@@ -82,3 +83,11 @@ impl<T: Serialize> IntoResponse for RestApiResponse<T> {
         axum::Json(self.0).into_response()
     }
 }
+
+/// Query parameter of the reads that can return soft-deleted records.
+#[derive(Debug, Default, Clone, Copy, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct IncludeDeletedQuery {
+    /// Also return soft-deleted records. Only admins may set this.
+    pub include_deleted: Option<bool>,
+}
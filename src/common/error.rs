@@ -1,5 +1,5 @@
 use axum::{
-    http::StatusCode,
+    http::{header::RETRY_AFTER, HeaderValue, StatusCode},
     response::{IntoResponse, Response},
     BoxError,
 };
@@ -7,6 +7,7 @@ use axum::{
 use sqlx::Error as SqlxError;
 use thiserror::Error;
 use tracing::error;
+use validator::ValidationErrors;
 
 use crate::common::dto::RestApiResponse;
 
@@ -28,9 +29,20 @@ pub enum AppError {
     #[error("Validation error: {0}")]
     ValidationError(String),
 
+    /// Field-level validation errors, returned as the response data.
+    #[error("Invalid input: {0}")]
+    InvalidInput(#[from] ValidationErrors),
+
     #[error("Forbidden Request")]
     Forbidden,
 
+    #[error("Conflict: {0}")]
+    Conflict(String), // Used when a unique resource already exists
+
+    /// Used when the `If-Match` header names an outdated version of the resource
+    #[error("The resource was modified by another request")]
+    PreconditionFailed,
+
     /// Used for file-related errors
     #[error("File data is empty")]
     InvalidFileData,
@@ -51,10 +63,26 @@ pub enum AppError {
     MissingCredentials,
     #[error("Invalid token")]
     InvalidToken,
+    #[error("Malformed token")]
+    MalformedToken,
+    #[error("Token expired")]
+    TokenExpired,
+    #[error("Token not yet valid")]
+    TokenNotYetValid,
+    #[error("Invalid token audience")]
+    InvalidAudience,
+    #[error("Invalid token issuer")]
+    InvalidIssuer,
     #[error("Token creation error")]
     TokenCreation,
     #[error("User not found")]
     UserNotFound,
+    #[error("MFA required")]
+    MfaRequired,
+    #[error("Invalid CSRF token")]
+    InvalidCsrfToken,
+    #[error("Too many failed login attempts, retry in {retry_after_secs} seconds")]
+    TooManyAttempts { retry_after_secs: u64 },
 }
 
 /// Converts the AppError enum into an HTTP response.
@@ -62,28 +90,90 @@ pub enum AppError {
 impl IntoResponse for AppError {
     fn into_response(self) -> Response {
         let status = match self {
-            AppError::ValidationError(_) => StatusCode::BAD_REQUEST,
+            AppError::ValidationError(_) | AppError::InvalidInput(_) => StatusCode::BAD_REQUEST,
             AppError::DatabaseError(_) => StatusCode::INTERNAL_SERVER_ERROR,
             AppError::NotFound(_) => StatusCode::NOT_FOUND,
             AppError::InternalError => StatusCode::INTERNAL_SERVER_ERROR,
             AppError::Forbidden => StatusCode::FORBIDDEN,
+            AppError::Conflict(_) => StatusCode::CONFLICT,
+            AppError::PreconditionFailed => StatusCode::PRECONDITION_FAILED,
             AppError::InvalidFileData
             | AppError::FileSizeExceeded
             | AppError::InvalidFileName
             | AppError::UnsupportedFileExtension => StatusCode::BAD_REQUEST,
             AppError::WrongCredentials => StatusCode::UNAUTHORIZED,
             AppError::MissingCredentials => StatusCode::BAD_REQUEST,
-            AppError::InvalidToken => StatusCode::UNAUTHORIZED,
+            AppError::InvalidToken
+            | AppError::MalformedToken
+            | AppError::TokenExpired
+            | AppError::TokenNotYetValid
+            | AppError::InvalidAudience
+            | AppError::InvalidIssuer => StatusCode::UNAUTHORIZED,
             AppError::TokenCreation => StatusCode::INTERNAL_SERVER_ERROR,
             AppError::UserNotFound => StatusCode::NOT_FOUND,
+            AppError::MfaRequired | AppError::InvalidCsrfToken => StatusCode::FORBIDDEN,
+            AppError::TooManyAttempts { .. } => StatusCode::TOO_MANY_REQUESTS,
+        };
+        let retry_after = match self {
+            AppError::TooManyAttempts { retry_after_secs } => Some(retry_after_secs),
+            _ => None,
         };
-        let body = axum::Json(ApiResponse::<()> {
+        let data = match &self {
+            AppError::InvalidInput(errors) => serde_json::to_value(errors).ok(),
+            _ => None,
+        };
+        let body = axum::Json(ApiResponse::<serde_json::Value> {
             status: status.as_u16(),
             message: self.to_string(),
-            data: None,
+            data,
         });
 
-        (status, body).into_response()
+        let mut response = (status, body).into_response();
+        if let Some(secs) = retry_after {
+            response
+                .headers_mut()
+                .insert(RETRY_AFTER, HeaderValue::from(secs));
+        }
+        response
+    }
+}
+
+/// Returns `true` if the database rejected a statement because of a unique constraint.
+pub fn is_unique_violation(err: &SqlxError) -> bool {
+    matches!(err, SqlxError::Database(db_err) if db_err.is_unique_violation())
+}
+
+/// Returns the name of the unique constraint that rejected a statement, if any.
+pub fn unique_violation_constraint(err: &SqlxError) -> Option<&str> {
+    match err {
+        SqlxError::Database(db_err) if db_err.is_unique_violation() => db_err.constraint(),
+        _ => None,
+    }
+}
+
+/// Returns `true` if the database rejected a statement because of a foreign key constraint.
+pub fn is_foreign_key_violation(err: &SqlxError) -> bool {
+    matches!(err, SqlxError::Database(db_err) if db_err.is_foreign_key_violation())
+}
+
+/// Maps token validation failures to the matching authentication error.
+/// Signature and algorithm failures stay `InvalidToken` so they reveal nothing about the keys.
+impl From<jsonwebtoken::errors::Error> for AppError {
+    fn from(err: jsonwebtoken::errors::Error) -> Self {
+        use jsonwebtoken::errors::ErrorKind;
+
+        match err.kind() {
+            ErrorKind::ExpiredSignature => AppError::TokenExpired,
+            ErrorKind::ImmatureSignature => AppError::TokenNotYetValid,
+            ErrorKind::InvalidAudience => AppError::InvalidAudience,
+            ErrorKind::InvalidIssuer => AppError::InvalidIssuer,
+            ErrorKind::InvalidToken
+            | ErrorKind::MissingRequiredClaim(_)
+            | ErrorKind::Base64(_)
+            | ErrorKind::Json(_)
+            | ErrorKind::Utf8(_) => AppError::MalformedToken,
+            _ => AppError::InvalidToken,
+        }
     }
 }
 
@@ -1,14 +1,23 @@
 use argon2::{
-    password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
-    Argon2,
+    password_hash::{
+        rand_core::{OsRng, RngCore},
+        PasswordHash, PasswordHasher, PasswordVerifier, SaltString,
+    },
+    Algorithm, Argon2, Params, Version,
 };
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use sha2::{Digest, Sha256};
+use std::sync::OnceLock;
 
-/// Hash the provided password using Argon2.
-pub fn hash_password(password: &str) -> Result<String, argon2::Error> {
+/// A valid hash of a throwaway password, verified against when a user does not exist.
+/// Created with the parameters of the first call, so that it costs as much as a real hash.
+static DUMMY_PASSWORD_HASH: OnceLock<String> = OnceLock::new();
+
+/// Hash the provided password using Argon2id v19 with the given parameters.
+pub fn hash_password(password: &str, params: &Params) -> Result<String, argon2::Error> {
     let salt = SaltString::generate(&mut OsRng);
 
-    // Argon2 with default params (Argon2id v19)
-    let argon2 = Argon2::default();
+    let argon2 = Argon2::new(Algorithm::Argon2id, Version::V0x13, params.clone());
 
     // Hash password to PHC string ($argon2id$v=19$...)
     let hash_password = argon2
@@ -21,7 +30,8 @@ pub fn hash_password(password: &str) -> Result<String, argon2::Error> {
     Ok(hash_password.to_string())
 }
 
-/// Verify that a password matches the provided çå.
+/// Verify that a password matches the provided hash.
+/// The algorithm and parameters are read from the hash, so legacy hashes keep verifying.
 pub fn verify_password(password_hash: &str, password: &str) -> bool {
     let parsed_hash = match PasswordHash::new(password_hash) {
         Ok(hash) => hash,
@@ -35,6 +45,61 @@ pub fn verify_password(password_hash: &str, password: &str) -> bool {
         .is_ok()
 }
 
+/// Verify a password against a dummy hash and discard the result.
+/// Called for unknown users so that a login takes as long as one with a wrong password.
+pub fn dummy_verify_password(password: &str, params: &Params) {
+    let hash = DUMMY_PASSWORD_HASH
+        .get_or_init(|| hash_password("dummy-password", params).unwrap_or_default());
+    let _ = verify_password(hash, password);
+}
+
+/// Returns `true` if the hash should be replaced by one created with the given parameters:
+/// it is not Argon2id v19, or any of its costs is lower than configured.
+/// Hashes that cannot be parsed never need a rehash, since they cannot be verified either.
+pub fn needs_rehash(password_hash: &str, params: &Params) -> bool {
+    let Ok(parsed_hash) = PasswordHash::new(password_hash) else {
+        return false;
+    };
+    if parsed_hash.algorithm != Algorithm::Argon2id.ident()
+        || parsed_hash.version != Some(Version::V0x13.into())
+    {
+        return true;
+    }
+    match Params::try_from(&parsed_hash) {
+        Ok(current) => {
+            current.m_cost() < params.m_cost()
+                || current.t_cost() < params.t_cost()
+                || current.p_cost() < params.p_cost()
+        }
+        Err(_) => true,
+    }
+}
+
+/// Generate an opaque, URL-safe random token (256 bits of entropy).
+/// Used for refresh tokens and other bearer secrets that are only stored hashed.
+pub fn generate_token() -> String {
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+/// Generate a random numeric code of the given number of digits, e.g. for codes typed by hand.
+/// Such codes are guessable and must only be accepted a limited number of times.
+pub fn generate_numeric_code(digits: u32) -> String {
+    let modulus = 10u64.pow(digits);
+    format!(
+        "{:0width$}",
+        OsRng.next_u64() % modulus,
+        width = digits as usize
+    )
+}
+
+/// Hash an opaque token with SHA-256 and return it hex encoded.
+/// Tokens are high-entropy, so a fast digest is sufficient and keeps lookups indexable.
+pub fn hash_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -42,7 +107,7 @@ mod tests {
     #[test]
     fn test_password_hash_and_verify() {
         let password = "super_secret_password";
-        let hash = hash_password(password).expect("Failed to hash password");
+        let hash = hash_password(password, &Params::default()).expect("Failed to hash password");
 
         // Verifying that the hashed password matches the original one
         assert!(verify_password(&hash, password));
@@ -55,5 +120,43 @@ mod tests {
         let password = "mySecretPassword";
         let hash = "$argon2i$v=19$m=65536,t=2,p=1$vNVL5PZ1hRwgLUlGmCQVTA$fg1d0/f8pdtMnzQTeh2YE6R0E8vfqMOQOs5k6Y22Qi0";
         assert!(verify_password(hash, password));
+        // Legacy argon2i hashes verify but are upgraded on the next login.
+        assert!(needs_rehash(hash, &Params::default()));
+    }
+
+    #[test]
+    fn test_needs_rehash_on_weaker_params() {
+        let params = Params::default();
+        let hash = hash_password("password", &params).unwrap();
+        assert!(!needs_rehash(&hash, &params));
+
+        let stronger =
+            Params::new(params.m_cost(), params.t_cost() + 1, params.p_cost(), None).unwrap();
+        assert!(needs_rehash(&hash, &stronger));
+
+        // A hash stronger than configured is kept.
+        let weaker =
+            Params::new(params.m_cost() / 2, params.t_cost(), params.p_cost(), None).unwrap();
+        assert!(!needs_rehash(&hash, &weaker));
+    }
+
+    #[test]
+    fn test_token_generate_and_hash() {
+        let token = generate_token();
+        assert_eq!(token.len(), 43);
+        assert_ne!(token, generate_token());
+
+        let hash = hash_token(&token);
+        assert_eq!(hash.len(), 64);
+        assert_eq!(hash, hash_token(&token));
+    }
+
+    #[test]
+    fn test_generate_numeric_code() {
+        for _ in 0..100 {
+            let code = generate_numeric_code(6);
+            assert_eq!(code.len(), 6);
+            assert!(code.bytes().all(|b| b.is_ascii_digit()));
+        }
     }
 }
@@ -1,93 +1,220 @@
 use axum::{
-    extract::Request,
+    extract::{Request, State},
+    http::{header::AUTHORIZATION, HeaderMap, Method},
     middleware::Next,
     response::{IntoResponse, Response},
 };
 
 use chrono::{Duration, Utc};
-use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation};
-use serde::{Deserialize, Serialize};
-use std::sync::LazyLock;
-use std::{env, fmt::Display};
+use jsonwebtoken::Validation;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt::Display;
 use utoipa::ToSchema;
+use uuid::Uuid;
 
-use super::error::AppError;
+use axum_extra::extract::cookie::CookieJar;
 
-/// JWT_SECRET_KEY is the environment variable that holds the secret key for JWT encoding and decoding.
-/// It is loaded from the environment variables using the dotenv crate.
-/// The secret key is used to sign the JWT tokens and should be kept secret.
-pub static KEYS: LazyLock<Keys> = LazyLock::new(|| {
-    dotenvy::dotenv().ok();
-
-    let secret = env::var("JWT_SECRET_KEY").expect("JWT_SECRET_KEY must be set");
-    Keys::new(secret.as_bytes())
-});
-
-/// Keys is a struct that holds the encoding and decoding keys for JWT.
-pub struct Keys {
-    pub encoding: EncodingKey,
-    pub decoding: DecodingKey,
-}
-
-/// The Keys struct is used to create the encoding and decoding keys for JWT.
-impl Keys {
-    fn new(secret: &[u8]) -> Self {
-        Self {
-            encoding: EncodingKey::from_secret(secret),
-            decoding: DecodingKey::from_secret(secret),
-        }
-    }
-}
+use super::{
+    app_state::AppState,
+    client_info::ClientInfo,
+    config::Config,
+    error::AppError,
+    keyring::KEYRING,
+    permission::ADMIN_ROLE,
+    session_cookie::{self, ACCESS_TOKEN_COOKIE},
+    tenant,
+};
 
 /// Claims is a struct that represents the claims in the JWT token.
-/// It contains the subject (user ID), expiration time, and issued at time.
+/// It contains the subject (user ID), expiration time, issued at time and session ID.
 /// The `sub` field is the user ID, `exp` is the expiration time, and `iat` is the issued at time.
+/// `iss`, `aud`, `nbf` and `jti` are the registered claims of RFC 7519; the issuer and audience
+/// come from `Config` and `jti` uniquely identifies each issued token.
+/// The `sid` field links the token to the refresh token family it was issued for,
+/// so that revoking the session also invalidates outstanding access tokens.
+/// The `roles` and `permissions` fields are loaded from the database when the token is issued.
 /// The `Claims` struct is used to encode and decode the JWT tokens.
+/// Requests authenticated with an API key carry the same claims, with `api_key_id` set.
+/// Tokens issued to an OAuth client carry its `client_id`, and only the permissions it was granted.
+/// Tokens issued to an admin impersonating a user carry the user as `sub` and the admin as `act`.
+/// `org_id` is the organization of the subject; requests only see the records of that tenant.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Claims {
     pub sub: String,
     pub exp: usize,
     pub iat: usize,
+    pub nbf: usize,
+    pub iss: String,
+    #[serde(
+        serialize_with = "serialize_audience",
+        deserialize_with = "deserialize_audience"
+    )]
+    pub aud: Vec<String>,
+    pub jti: String,
+    #[serde(default)]
+    pub org_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sid: Option<String>,
+    #[serde(default)]
+    pub roles: Vec<String>,
+    #[serde(default)]
+    pub permissions: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub act: Option<Actor>,
+    /// ID of the API key the request was authenticated with. Never part of a token.
+    #[serde(skip)]
+    pub api_key_id: Option<String>,
+}
+
+/// Actor claim of RFC 8693: the party actually acting on behalf of the subject.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Actor {
+    pub sub: String,
 }
 
 /// The Claims struct implements the `Display` trait for easy printing.
-/// It formats the claims as a string, showing the user ID.
+/// It formats the claims as a string, showing the user ID and, if impersonated, the actor.
 impl Display for Claims {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "user_id: {}", self.sub)
+        write!(f, "user_id: {}", self.sub)?;
+        if let Some(act) = &self.act {
+            write!(f, " (impersonated by {})", act.sub)?;
+        }
+        Ok(())
     }
 }
 
-/// The Default trait is implemented for the Claims struct.
-/// It sets the default values for the claims.
-impl Default for Claims {
-    fn default() -> Self {
+impl Claims {
+    /// Creates claims for the given subject that expire after `ttl_secs` seconds.
+    /// The issuer and the primary audience are taken from the configuration.
+    pub fn new(config: &Config, sub: &str, ttl_secs: i64) -> Self {
         let now = Utc::now();
-        let expire: Duration = Duration::hours(24);
+        let expire: Duration = Duration::seconds(ttl_secs);
         let exp: usize = (now + expire).timestamp() as usize;
         let iat: usize = now.timestamp() as usize;
         Claims {
-            sub: String::new(),
+            sub: sub.to_string(),
             exp,
             iat,
+            nbf: iat,
+            iss: config.jwt_issuer.clone(),
+            aud: config.jwt_audience.iter().take(1).cloned().collect(),
+            jti: Uuid::new_v4().to_string(),
+            org_id: String::new(),
+            sid: None,
+            roles: Vec::new(),
+            permissions: Vec::new(),
+            client_id: None,
+            act: None,
+            api_key_id: None,
+        }
+    }
+
+    /// Returns `true` if the token grants the given permission.
+    pub fn has_permission(&self, permission: &str) -> bool {
+        self.permissions.iter().any(|p| p == permission)
+    }
+
+    /// Limits the permissions to the given scopes, as done for API keys and OAuth clients.
+    /// The admin role bypasses ownership checks, so it is only kept while the scopes still
+    /// cover every permission of the subject.
+    pub fn restrict_to_scopes(&mut self, scopes: &[String]) {
+        let granted = self.permissions.len();
+        self.permissions
+            .retain(|permission| scopes.contains(permission));
+
+        if self.permissions.len() < granted {
+            self.roles.retain(|role| role != ADMIN_ROLE);
+        }
+    }
+
+    /// Returns `true` if the subject holds the admin role.
+    pub fn is_admin(&self) -> bool {
+        self.roles.iter().any(|r| r == ADMIN_ROLE)
+    }
+
+    /// Returns the ID of the user actually making the request:
+    /// the impersonating admin if there is one, the subject otherwise.
+    /// Used for audit fields such as `modified_by`.
+    pub fn actor_id(&self) -> &str {
+        self.act.as_ref().map_or(&self.sub, |act| &act.sub)
+    }
+
+    /// Returns `true` if the subject may act on a resource owned by `owner_id`.
+    /// Admins may act on any resource; everyone else only on their own.
+    pub fn can_access(&self, owner_id: &str) -> bool {
+        self.is_admin() || self.sub == owner_id
+    }
+
+    /// Resolves an `include_deleted` request. Only admins may see soft-deleted records.
+    pub fn include_deleted(&self, requested: Option<bool>) -> Result<bool, AppError> {
+        match requested {
+            Some(true) if !self.is_admin() => Err(AppError::Forbidden),
+            requested => Ok(requested.unwrap_or(false)),
         }
     }
 }
 
+/// Serializes a single audience as a string and several as an array, as allowed by RFC 7519.
+fn serialize_audience<S: Serializer>(aud: &[String], serializer: S) -> Result<S::Ok, S::Error> {
+    match aud {
+        [single] => serializer.serialize_str(single),
+        _ => aud.serialize(serializer),
+    }
+}
+
+/// Accepts `aud` either as a string or as an array of strings.
+fn deserialize_audience<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Vec<String>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(String),
+        Many(Vec<String>),
+    }
+
+    Ok(match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::One(aud) => vec![aud],
+        OneOrMany::Many(aud) => aud,
+    })
+}
+
+/// Builds the validation rules for incoming tokens from the configuration.
+/// `exp`, `nbf`, `iss` and `aud` are required and checked with the configured leeway;
+/// the accepted algorithm is set by the keyring from the key that verifies the token.
+pub fn validation(config: &Config) -> Validation {
+    let mut validation = Validation::default();
+    validation.set_required_spec_claims(&["exp", "nbf", "iss", "aud"]);
+    validation.validate_nbf = true;
+    validation.leeway = config.jwt_leeway_secs;
+    validation.set_issuer(&[&config.jwt_issuer]);
+    validation.set_audience(&config.jwt_audience);
+    validation
+}
+
 /// AuthBody is a struct that represents the authentication body.
+/// `expires_in` is the access token lifetime in seconds; the `refresh_token`
+/// is exchanged at `/auth/refresh` for a new pair once the access token expires.
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct AuthBody {
     pub access_token: String,
     pub token_type: String,
+    pub expires_in: i64,
+    pub refresh_token: String,
 }
 
 /// The AuthBody struct is used to create a new instance of the authentication body.
-/// It takes an access token as a parameter and sets the token type to "Bearer".
+/// It takes the issued tokens as parameters and sets the token type to "Bearer".
 impl AuthBody {
-    pub fn new(access_token: String) -> Self {
+    pub fn new(access_token: String, expires_in: i64, refresh_token: String) -> Self {
         Self {
             access_token,
             token_type: "Bearer".to_string(),
+            expires_in,
+            refresh_token,
         }
     }
 }
@@ -96,44 +223,119 @@ impl AuthBody {
 /// It contains the client ID and client secret.
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct AuthPayload {
+    /// The username or the email address of the user.
     pub client_id: String,
     pub client_secret: String,
 }
 
 /// make_jwt_token is a function that creates a JWT token.
-/// It takes a user ID as a parameter and returns a Result with the JWT token or an error.
-pub fn make_jwt_token(user_id: &str) -> Result<String, AppError> {
-    let claims = Claims {
-        sub: user_id.to_string(),
-        ..Default::default()
-    };
-    encode(&Header::default(), &claims, &KEYS.encoding).map_err(|_| AppError::TokenCreation)
+/// It takes the claims to sign and returns a Result with the JWT token or an error.
+/// The token is signed with the current signing key of the keyring.
+pub fn make_jwt_token(claims: &Claims) -> Result<String, AppError> {
+    KEYRING.sign(claims).map_err(|_| AppError::TokenCreation)
 }
 
 /// Middleware to validate JWT tokens.
-/// If the token is valid, the request proceeds; otherwise, a 401 Unauthorized is returned.
-pub async fn jwt_auth<B>(mut req: Request<B>, next: Next) -> Result<Response, Response>
-where
-    B: Send + Into<axum::body::Body>,
-{
-    // Try to extract and trim the token in one go.
-    let token = req
-        .headers()
-        .get("Authorization")
+/// If the token is valid and its session has not been revoked, the request proceeds;
+/// otherwise, a 401 Unauthorized is returned.
+/// `Authorization: ApiKey <key>` is accepted as well and yields the same `Claims`,
+/// so handlers do not need to know how the caller authenticated.
+/// In cookie session mode, requests without an `Authorization` header are authenticated
+/// by the access token cookie, and state-changing ones must pass the CSRF check.
+/// Rejected tokens and API keys are recorded in the authentication audit trail;
+/// requests that present no credentials at all are not.
+pub async fn jwt_auth(
+    State(state): State<AppState>,
+    mut req: Request,
+    next: Next,
+) -> Result<Response, Response> {
+    match authenticate(&state, req.method(), req.headers()).await {
+        Ok(claims) => {
+            if let Some(act) = &claims.act {
+                tracing::info!(
+                    "Impersonated request {} {} by {} as {}",
+                    req.method(),
+                    req.uri(),
+                    act.sub,
+                    claims.sub
+                );
+            }
+
+            // Insert the decoded claims into the request extensions
+            // and handle the request within the caller's tenant.
+            let org_id = claims.org_id.clone();
+            req.extensions_mut().insert(claims);
+            Ok(tenant::scope(Some(org_id), next.run(req)).await)
+        }
+        Err(err) => {
+            let jar = CookieJar::from_headers(req.headers());
+            let presented = req.headers().contains_key(AUTHORIZATION)
+                || session_cookie::cookie_token(&state.config, &jar, ACCESS_TOKEN_COOKIE).is_some();
+            if presented {
+                let client = ClientInfo::from_parts(&state.config, req.headers(), req.extensions());
+                state
+                    .auth_service
+                    .record_rejected_token(&client, &err)
+                    .await;
+            }
+            Err(err.into_response())
+        }
+    }
+}
+
+/// Resolves the credentials of a request to its claims. See `jwt_auth`.
+async fn authenticate(
+    state: &AppState,
+    method: &Method,
+    headers: &HeaderMap,
+) -> Result<Claims, AppError> {
+    let header = headers
+        .get(AUTHORIZATION)
         .and_then(|v| v.to_str().ok())
-        .and_then(|header| header.strip_prefix("Bearer "))
-        .map(|t| t.trim())
-        .filter(|t| !t.is_empty())
-        .ok_or_else(|| AppError::InvalidToken.into_response())?;
-
-    // Validate and decode the token.
-    let token_data =
-        decode::<Claims>(token, &KEYS.decoding, &Validation::default()).map_err(|err| {
+        .unwrap_or_default();
+
+    if let Some(api_key) = header.strip_prefix("ApiKey ") {
+        return state
+            .auth_service
+            .authenticate_api_key(api_key.trim())
+            .await;
+    }
+
+    // Try to extract and trim the token in one go.
+    let bearer = header
+        .strip_prefix("Bearer ")
+        .map(|t| t.trim().to_string())
+        .filter(|t| !t.is_empty());
+
+    // Fall back to the session cookie; browsers send it on cross-site requests too,
+    // so those requests must prove that they can read the CSRF cookie.
+    let token = match bearer {
+        Some(token) => token,
+        None if header.is_empty() => {
+            let jar = CookieJar::from_headers(headers);
+            let token = session_cookie::cookie_token(&state.config, &jar, ACCESS_TOKEN_COOKIE)
+                .ok_or(AppError::InvalidToken)?;
+            session_cookie::verify_csrf(method, headers, &jar)?;
+            token
+        }
+        None => return Err(AppError::InvalidToken),
+    };
+
+    // Validate and decode the token with the key named by its `kid`.
+    let token_data = KEYRING
+        .verify::<Claims>(&token, &validation(&state.config))
+        .map_err(|err| {
             tracing::error!("Error decoding token: {:?}", err);
-            AppError::InvalidToken.into_response()
+            AppError::from(err)
         })?;
 
-    // Insert the decoded claims into the request extensions.
-    req.extensions_mut().insert(token_data.claims);
-    Ok(next.run(req.map(Into::into)).await)
+    // Reject tokens whose session has been revoked (logout or refresh token reuse).
+    if let Some(sid) = token_data.claims.sid.as_deref() {
+        if !state.auth_service.is_session_active(sid).await? {
+            tracing::error!("Rejected token for revoked session: {}", sid);
+            return Err(AppError::InvalidToken);
+        }
+    }
+
+    Ok(token_data.claims)
 }
@@ -0,0 +1,448 @@
+//! Keyring used to sign and verify JWT tokens.
+//!
+//! Keys are loaded from a directory given by `JWT_KEYS_DIR`. Each key is identified by its `kid`
+//! and stored as `{kid}.pub.pem` (SubjectPublicKeyInfo) with an optional `{kid}.key.pem` (PKCS#8).
+//! The algorithm is derived from the public key: RSA keys use RS256, P-256 keys ES256,
+//! P-384 keys ES384 and Ed25519 keys EdDSA.
+//!
+//! `JWT_SIGNING_KID` selects the key that signs new tokens. Every other key in the directory
+//! only verifies, so a key can be rotated by adding the new pair, switching `JWT_SIGNING_KID`
+//! and removing the old files once the tokens it signed have expired.
+//!
+//! Without `JWT_KEYS_DIR` the keyring falls back to the HS256 secret in `JWT_SECRET_KEY`.
+//! When both are set the secret is kept as a verification-only key for tokens without a `kid`.
+
+use std::{env, fs, path::Path, sync::LazyLock};
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use ed25519_dalek::pkcs8::DecodePublicKey;
+use jsonwebtoken::{
+    decode, decode_header, encode,
+    errors::{ErrorKind, Result as JwtResult},
+    jwk::{
+        AlgorithmParameters, CommonParameters, EllipticCurve, EllipticCurveKeyParameters,
+        EllipticCurveKeyType, Jwk, JwkSet, KeyAlgorithm, OctetKeyPairParameters, OctetKeyPairType,
+        PublicKeyUse, RSAKeyParameters, RSAKeyType,
+    },
+    Algorithm, DecodingKey, EncodingKey, Header, TokenData, Validation,
+};
+use p256::elliptic_curve::sec1::ToEncodedPoint;
+use rsa::{traits::PublicKeyParts, RsaPublicKey};
+use serde::{de::DeserializeOwned, Serialize};
+use thiserror::Error;
+
+/// KEYRING holds the keys used by `jwt::make_jwt_token` and `jwt::jwt_auth`.
+/// It is loaded once from the environment variables described in the module documentation;
+/// `bootstrap::build_app_state` forces it so that invalid keys stop the server at startup.
+pub static KEYRING: LazyLock<Keyring> = LazyLock::new(|| {
+    dotenvy::dotenv().ok();
+
+    Keyring::from_env().unwrap_or_else(|err| panic!("Failed to load JWT keyring: {err}"))
+});
+
+/// Errors raised while loading the keyring.
+#[derive(Error, Debug)]
+pub enum KeyringError {
+    #[error("JWT_KEYS_DIR or JWT_SECRET_KEY must be set")]
+    NoKeys,
+
+    #[error("JWT_SIGNING_KID must be set when JWT_KEYS_DIR is used")]
+    MissingSigningKid,
+
+    #[error("Signing key '{0}' not found or has no private key")]
+    SigningKeyUnavailable(String),
+
+    #[error("Failed to read {0}: {1}")]
+    Io(String, std::io::Error),
+
+    #[error("Invalid key '{0}': {1}")]
+    InvalidKey(String, String),
+}
+
+/// A single key of the keyring.
+/// `kid` is `None` only for the HS256 fallback secret.
+struct JwtKey {
+    kid: Option<String>,
+    algorithm: Algorithm,
+    decoding: DecodingKey,
+    encoding: Option<EncodingKey>,
+    jwk: Option<Jwk>,
+}
+
+/// Keyring is a set of verification keys, one of which signs new tokens.
+pub struct Keyring {
+    keys: Vec<JwtKey>,
+    signing: usize,
+}
+
+impl Keyring {
+    /// Loads the keyring from `JWT_KEYS_DIR`, `JWT_SIGNING_KID` and `JWT_SECRET_KEY`.
+    pub fn from_env() -> Result<Self, KeyringError> {
+        let non_empty = |name: &str| env::var(name).ok().filter(|v| !v.trim().is_empty());
+
+        Self::load(
+            non_empty("JWT_KEYS_DIR").as_deref(),
+            non_empty("JWT_SIGNING_KID").as_deref(),
+            non_empty("JWT_SECRET_KEY").as_deref(),
+        )
+    }
+
+    /// Builds a keyring from a key directory and/or an HS256 secret.
+    /// When `keys_dir` is given, `signing_kid` must name a key with a private key in it.
+    pub fn load(
+        keys_dir: Option<&str>,
+        signing_kid: Option<&str>,
+        secret: Option<&str>,
+    ) -> Result<Self, KeyringError> {
+        let mut keys = Vec::new();
+
+        if let Some(secret) = secret {
+            keys.push(JwtKey {
+                kid: None,
+                algorithm: Algorithm::HS256,
+                decoding: DecodingKey::from_secret(secret.as_bytes()),
+                encoding: Some(EncodingKey::from_secret(secret.as_bytes())),
+                jwk: None,
+            });
+        }
+
+        let Some(keys_dir) = keys_dir else {
+            return match keys.is_empty() {
+                true => Err(KeyringError::NoKeys),
+                false => Ok(Self { keys, signing: 0 }),
+            };
+        };
+
+        let signing_kid = signing_kid.ok_or(KeyringError::MissingSigningKid)?;
+
+        let entries =
+            fs::read_dir(keys_dir).map_err(|err| KeyringError::Io(keys_dir.to_string(), err))?;
+        let mut kids: Vec<String> = entries
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| {
+                let name = entry.file_name().into_string().ok()?;
+                name.strip_suffix(".pub.pem").map(str::to_string)
+            })
+            .collect();
+        kids.sort();
+
+        for kid in kids {
+            keys.push(load_key(Path::new(keys_dir), &kid)?);
+        }
+
+        let signing = keys
+            .iter()
+            .position(|key| key.kid.as_deref() == Some(signing_kid) && key.encoding.is_some())
+            .ok_or_else(|| KeyringError::SigningKeyUnavailable(signing_kid.to_string()))?;
+
+        Ok(Self { keys, signing })
+    }
+
+    /// Signs the claims with the current signing key, setting `kid` in the header.
+    pub fn sign<T: Serialize>(&self, claims: &T) -> JwtResult<String> {
+        let key = &self.keys[self.signing];
+        let encoding = key
+            .encoding
+            .as_ref()
+            .ok_or_else(|| jsonwebtoken::errors::Error::from(ErrorKind::InvalidKeyFormat))?;
+
+        let mut header = Header::new(key.algorithm);
+        header.kid = key.kid.clone();
+
+        encode(&header, claims, encoding)
+    }
+
+    /// Verifies a token with the key named by its `kid` header and the given claim rules.
+    /// Tokens without a `kid` are only accepted by the HS256 fallback secret,
+    /// and the header algorithm must match the algorithm of the selected key.
+    pub fn verify<T: DeserializeOwned>(
+        &self,
+        token: &str,
+        validation: &Validation,
+    ) -> JwtResult<TokenData<T>> {
+        let header = decode_header(token)?;
+
+        // A token naming a key we do not hold cannot have a valid signature.
+        let key = self
+            .keys
+            .iter()
+            .find(|key| key.kid == header.kid)
+            .ok_or_else(|| jsonwebtoken::errors::Error::from(ErrorKind::InvalidSignature))?;
+
+        let mut validation = validation.clone();
+        validation.algorithms = vec![key.algorithm];
+
+        decode::<T>(token, &key.decoding, &validation)
+    }
+
+    /// Returns the public keys as a JSON Web Key Set.
+    /// The HS256 fallback secret is never published.
+    pub fn jwks(&self) -> JwkSet {
+        JwkSet {
+            keys: self.keys.iter().filter_map(|key| key.jwk.clone()).collect(),
+        }
+    }
+}
+
+/// Loads `{kid}.pub.pem` and, if present, `{kid}.key.pem` from `dir`.
+fn load_key(dir: &Path, kid: &str) -> Result<JwtKey, KeyringError> {
+    let invalid = |msg: String| KeyringError::InvalidKey(kid.to_string(), msg);
+
+    let public_path = dir.join(format!("{kid}.pub.pem"));
+    let public_pem = fs::read(&public_path)
+        .map_err(|err| KeyringError::Io(public_path.display().to_string(), err))?;
+
+    let spki = pem::parse(&public_pem).map_err(|err| invalid(err.to_string()))?;
+    if spki.tag() != "PUBLIC KEY" {
+        return Err(invalid(format!(
+            "expected PUBLIC KEY, found {}",
+            spki.tag()
+        )));
+    }
+    let public_key = parse_spki(spki.contents()).map_err(invalid)?;
+    let algorithm = public_key.algorithm();
+
+    let decoding = match public_key {
+        PublicKey::Rsa { .. } => DecodingKey::from_rsa_pem(&public_pem),
+        PublicKey::Ec { .. } => DecodingKey::from_ec_pem(&public_pem),
+        PublicKey::Ed25519 { .. } => DecodingKey::from_ed_pem(&public_pem),
+    }
+    .map_err(|err| invalid(err.to_string()))?;
+
+    let private_path = dir.join(format!("{kid}.key.pem"));
+    let encoding = match fs::read(&private_path) {
+        Ok(private_pem) => {
+            let encoding = match public_key {
+                PublicKey::Rsa { .. } => EncodingKey::from_rsa_pem(&private_pem),
+                PublicKey::Ec { .. } => EncodingKey::from_ec_pem(&private_pem),
+                PublicKey::Ed25519 { .. } => EncodingKey::from_ed_pem(&private_pem),
+            }
+            .map_err(|err| invalid(err.to_string()))?;
+
+            // Make sure the private key belongs to the public key next to it.
+            let probe = encode(&Header::new(algorithm), &serde_json::json!({}), &encoding)
+                .map_err(|err| invalid(err.to_string()))?;
+            let mut validation = Validation::new(algorithm);
+            validation.required_spec_claims.clear();
+            validation.validate_exp = false;
+            decode::<serde_json::Value>(&probe, &decoding, &validation)
+                .map_err(|_| invalid("private key does not match public key".into()))?;
+
+            Some(encoding)
+        }
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => None,
+        Err(err) => return Err(KeyringError::Io(private_path.display().to_string(), err)),
+    };
+
+    Ok(JwtKey {
+        kid: Some(kid.to_string()),
+        algorithm,
+        decoding,
+        encoding,
+        jwk: Some(public_key.to_jwk(kid)),
+    })
+}
+
+/// Public key material extracted from a SubjectPublicKeyInfo structure.
+enum PublicKey {
+    Rsa {
+        n: Vec<u8>,
+        e: Vec<u8>,
+    },
+    Ec {
+        curve: EllipticCurve,
+        x: Vec<u8>,
+        y: Vec<u8>,
+    },
+    Ed25519 {
+        x: Vec<u8>,
+    },
+}
+
+impl PublicKey {
+    fn algorithm(&self) -> Algorithm {
+        match self {
+            PublicKey::Rsa { .. } => Algorithm::RS256,
+            PublicKey::Ec {
+                curve: EllipticCurve::P384,
+                ..
+            } => Algorithm::ES384,
+            PublicKey::Ec { .. } => Algorithm::ES256,
+            PublicKey::Ed25519 { .. } => Algorithm::EdDSA,
+        }
+    }
+
+    fn to_jwk(&self, kid: &str) -> Jwk {
+        let key_algorithm = match self.algorithm() {
+            Algorithm::RS256 => KeyAlgorithm::RS256,
+            Algorithm::ES384 => KeyAlgorithm::ES384,
+            Algorithm::EdDSA => KeyAlgorithm::EdDSA,
+            _ => KeyAlgorithm::ES256,
+        };
+
+        let algorithm = match self {
+            PublicKey::Rsa { n, e } => AlgorithmParameters::RSA(RSAKeyParameters {
+                key_type: RSAKeyType::RSA,
+                n: URL_SAFE_NO_PAD.encode(n),
+                e: URL_SAFE_NO_PAD.encode(e),
+            }),
+            PublicKey::Ec { curve, x, y } => {
+                AlgorithmParameters::EllipticCurve(EllipticCurveKeyParameters {
+                    key_type: EllipticCurveKeyType::EC,
+                    curve: curve.clone(),
+                    x: URL_SAFE_NO_PAD.encode(x),
+                    y: URL_SAFE_NO_PAD.encode(y),
+                })
+            }
+            PublicKey::Ed25519 { x } => AlgorithmParameters::OctetKeyPair(OctetKeyPairParameters {
+                key_type: OctetKeyPairType::OctetKeyPair,
+                curve: EllipticCurve::Ed25519,
+                x: URL_SAFE_NO_PAD.encode(x),
+            }),
+        };
+
+        Jwk {
+            common: CommonParameters {
+                public_key_use: Some(PublicKeyUse::Signature),
+                key_algorithm: Some(key_algorithm),
+                key_id: Some(kid.to_string()),
+                ..Default::default()
+            },
+            algorithm,
+        }
+    }
+}
+
+/// Extracts the key material from a DER encoded SubjectPublicKeyInfo.
+/// Each supported key type is tried in turn; its crate checks the algorithm identifier.
+fn parse_spki(der: &[u8]) -> Result<PublicKey, String> {
+    if let Ok(key) = RsaPublicKey::from_public_key_der(der) {
+        return Ok(PublicKey::Rsa {
+            n: key.n().to_bytes_be(),
+            e: key.e().to_bytes_be(),
+        });
+    }
+
+    if let Ok(key) = p256::PublicKey::from_public_key_der(der) {
+        let point = key.to_encoded_point(false);
+        return ec_public_key(EllipticCurve::P256, point.x(), point.y());
+    }
+
+    if let Ok(key) = p384::PublicKey::from_public_key_der(der) {
+        let point = key.to_encoded_point(false);
+        return ec_public_key(EllipticCurve::P384, point.x(), point.y());
+    }
+
+    if let Ok(key) = ed25519_dalek::VerifyingKey::from_public_key_der(der) {
+        return Ok(PublicKey::Ed25519 {
+            x: key.to_bytes().to_vec(),
+        });
+    }
+
+    Err("unsupported key type".into())
+}
+
+/// Builds an elliptic curve key from the coordinates of an uncompressed point.
+fn ec_public_key<T: AsRef<[u8]>>(
+    curve: EllipticCurve,
+    x: Option<&T>,
+    y: Option<&T>,
+) -> Result<PublicKey, String> {
+    match (x, y) {
+        (Some(x), Some(y)) => Ok(PublicKey::Ec {
+            curve,
+            x: x.as_ref().to_vec(),
+            y: y.as_ref().to_vec(),
+        }),
+        _ => Err("expected an uncompressed elliptic curve point".into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    const TEST_KEYS_DIR: &str = "tests/keys";
+
+    #[derive(Debug, Serialize, Deserialize)]
+    struct TestClaims {
+        sub: String,
+        exp: usize,
+    }
+
+    fn claims() -> TestClaims {
+        TestClaims {
+            sub: "user".into(),
+            exp: (chrono::Utc::now().timestamp() + 60) as usize,
+        }
+    }
+
+    #[test]
+    fn test_sign_verify_and_rotate() {
+        let old = Keyring::load(Some(TEST_KEYS_DIR), Some("rs256-2025"), None).unwrap();
+        let old_token = old.sign(&claims()).unwrap();
+        assert_eq!(decode_header(&old_token).unwrap().alg, Algorithm::RS256);
+
+        // After rotation the new key signs and the old key still verifies.
+        let new = Keyring::load(Some(TEST_KEYS_DIR), Some("es256-2026"), None).unwrap();
+        let new_token = new.sign(&claims()).unwrap();
+        let header = decode_header(&new_token).unwrap();
+        assert_eq!(header.alg, Algorithm::ES256);
+        assert_eq!(header.kid.as_deref(), Some("es256-2026"));
+
+        assert!(new
+            .verify::<TestClaims>(&old_token, &Validation::default())
+            .is_ok());
+        assert!(new
+            .verify::<TestClaims>(&new_token, &Validation::default())
+            .is_ok());
+
+        // Verification-only keys cannot sign.
+        assert!(Keyring::load(Some(TEST_KEYS_DIR), Some("ed25519-2024"), None).is_err());
+    }
+
+    #[test]
+    fn test_hmac_fallback_is_not_published() {
+        let keyring = Keyring::load(None, None, Some("secret")).unwrap();
+        let token = keyring.sign(&claims()).unwrap();
+        assert!(decode_header(&token).unwrap().kid.is_none());
+        assert!(keyring
+            .verify::<TestClaims>(&token, &Validation::default())
+            .is_ok());
+        assert!(keyring.jwks().keys.is_empty());
+
+        // A token without kid is rejected once the secret is gone.
+        let keys_only = Keyring::load(Some(TEST_KEYS_DIR), Some("es256-2026"), None).unwrap();
+        assert!(keys_only
+            .verify::<TestClaims>(&token, &Validation::default())
+            .is_err());
+    }
+
+    #[test]
+    fn test_jwks_contains_all_public_keys() {
+        let keyring = Keyring::load(Some(TEST_KEYS_DIR), Some("es256-2026"), Some("s")).unwrap();
+        let jwks = keyring.jwks();
+        assert_eq!(jwks.keys.len(), 3);
+
+        // Every published key must verify the tokens signed with its private half.
+        let token = keyring.sign(&claims()).unwrap();
+        let jwk = jwks.find("es256-2026").unwrap();
+        let decoding = DecodingKey::from_jwk(jwk).unwrap();
+        assert!(
+            decode::<TestClaims>(&token, &decoding, &Validation::new(Algorithm::ES256)).is_ok()
+        );
+
+        let rsa = Keyring::load(Some(TEST_KEYS_DIR), Some("rs256-2025"), None).unwrap();
+        let token = rsa.sign(&claims()).unwrap();
+        let decoding = DecodingKey::from_jwk(jwks.find("rs256-2025").unwrap()).unwrap();
+        assert!(
+            decode::<TestClaims>(&token, &decoding, &Validation::new(Algorithm::RS256)).is_ok()
+        );
+
+        assert!(matches!(
+            jwks.find("ed25519-2024").unwrap().algorithm,
+            AlgorithmParameters::OctetKeyPair(_)
+        ));
+    }
+}
@@ -0,0 +1,96 @@
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+
+use crate::common::error::AppError;
+
+/// Applies a JSON Merge Patch (RFC 7396) to `current` and returns the patched value.
+///
+/// Members of the patch replace those of `current`, `null` removes a member, and
+/// nested objects are merged recursively. The result must still deserialize into `T`,
+/// so removing a required field, or giving a field the wrong type, is a validation error.
+pub fn apply_merge_patch<T>(current: &T, patch: Value) -> Result<T, AppError>
+where
+    T: Serialize + DeserializeOwned,
+{
+    if !patch.is_object() {
+        return Err(AppError::ValidationError(
+            "A merge patch must be a JSON object".into(),
+        ));
+    }
+
+    let mut target = serde_json::to_value(current).map_err(|err| {
+        tracing::error!("Error serializing patch target: {err}");
+        AppError::InternalError
+    })?;
+    merge(&mut target, patch);
+
+    serde_json::from_value(target)
+        .map_err(|err| AppError::ValidationError(format!("Invalid merge patch: {err}")))
+}
+
+/// Merges `patch` into `target` as described in RFC 7396.
+fn merge(target: &mut Value, patch: Value) {
+    let Value::Object(patch) = patch else {
+        *target = patch;
+        return;
+    };
+
+    if !target.is_object() {
+        *target = Value::Object(Default::default());
+    }
+    let Value::Object(target) = target else {
+        unreachable!()
+    };
+
+    for (key, value) in patch {
+        if value.is_null() {
+            target.remove(&key);
+        } else {
+            merge(target.entry(key).or_insert(Value::Null), value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_merge() {
+        let mut target = json!({ "a": "b", "c": { "d": "e", "f": "g" }, "h": [1] });
+        merge(
+            &mut target,
+            json!({ "a": "z", "c": { "f": null, "x": 1 }, "h": [2, 3], "n": null }),
+        );
+
+        assert_eq!(
+            target,
+            json!({ "a": "z", "c": { "d": "e", "x": 1 }, "h": [2, 3] })
+        );
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Item {
+        name: String,
+        note: Option<String>,
+    }
+
+    #[test]
+    fn test_apply_merge_patch() {
+        let item = Item {
+            name: "a".into(),
+            note: Some("b".into()),
+        };
+
+        let patched = apply_merge_patch(&item, json!({ "note": null })).unwrap();
+        assert_eq!(patched.name, "a");
+        assert_eq!(patched.note, None);
+
+        assert!(apply_merge_patch(&item, json!({ "name": null })).is_err());
+        assert!(apply_merge_patch(&item, json!({ "name": 1 })).is_err());
+        assert!(apply_merge_patch(&item, json!(["name"])).is_err());
+    }
+}
@@ -0,0 +1,94 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use tokio::io::AsyncWriteExt;
+
+use crate::common::{config::Config, error::AppError};
+
+/// A message delivered to a user out of band, e.g. a password reset link.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Notification {
+    /// Email address of the recipient.
+    pub recipient: String,
+    pub subject: String,
+    pub body: String,
+}
+
+/// Notifier delivers notifications to users.
+/// Implement it to plug in a real delivery channel such as SMTP or a mail API.
+#[async_trait]
+pub trait Notifier: Send + Sync {
+    async fn send(&self, notification: Notification) -> Result<(), AppError>;
+}
+
+/// Writes every notification to the application log.
+/// Intended for local development only, since the log then contains secrets.
+pub struct LogNotifier;
+
+#[async_trait]
+impl Notifier for LogNotifier {
+    async fn send(&self, notification: Notification) -> Result<(), AppError> {
+        tracing::info!(
+            recipient = %notification.recipient,
+            subject = %notification.subject,
+            "Notification:\n{}",
+            notification.body
+        );
+        Ok(())
+    }
+}
+
+/// Appends every notification as one JSON line to a file.
+/// Useful for local testing, where the file acts as an outbox.
+pub struct FileNotifier {
+    path: PathBuf,
+}
+
+impl FileNotifier {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+}
+
+#[async_trait]
+impl Notifier for FileNotifier {
+    async fn send(&self, notification: Notification) -> Result<(), AppError> {
+        let mut line = serde_json::to_string(&notification).map_err(|err| {
+            tracing::error!("Error serializing notification: {err}");
+            AppError::InternalError
+        })?;
+        line.push('\n');
+
+        let mut file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .await
+            .map_err(|err| {
+                tracing::error!("Error opening notification file {:?}: {err}", self.path);
+                AppError::InternalError
+            })?;
+
+        // A single write keeps lines from concurrent senders intact.
+        // Tokio completes file writes in the background, so flush before returning.
+        file.write_all(line.as_bytes()).await.map_err(|err| {
+            tracing::error!("Error writing notification: {err}");
+            AppError::InternalError
+        })?;
+        file.flush().await.map_err(|err| {
+            tracing::error!("Error writing notification: {err}");
+            AppError::InternalError
+        })
+    }
+}
+
+/// Creates the notifier selected by the configuration:
+/// a `FileNotifier` when `NOTIFIER_FILE` is set, otherwise a `LogNotifier`.
+pub fn create_notifier(config: &Config) -> Arc<dyn Notifier> {
+    match &config.notifier_file {
+        Some(path) => Arc::new(FileNotifier::new(path)),
+        None => Arc::new(LogNotifier),
+    }
+}
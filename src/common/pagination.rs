@@ -0,0 +1,466 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{Postgres, QueryBuilder};
+use utoipa::{IntoParams, ToSchema};
+use validator::Validate;
+
+use crate::common::error::AppError;
+
+/// Number of items per page when the query sets no size.
+pub const DEFAULT_PAGE_SIZE: i64 = 20;
+
+/// Largest page size a client may request.
+pub const MAX_PAGE_SIZE: i64 = 100;
+
+/// Pagination parameters of the list endpoints.
+/// `page` selects offset pagination; without it the list is walked with the
+/// `next_cursor` of the previous page, starting from the first page.
+#[derive(Debug, Default, Clone, Serialize, Deserialize, ToSchema, IntoParams, Validate)]
+#[into_params(parameter_in = Query)]
+pub struct PageQuery {
+    /// 1-based page number.
+    #[validate(range(min = 1, message = "Page must be at least 1"))]
+    pub page: Option<i64>,
+    /// Number of items per page, 20 by default.
+    #[validate(range(
+        min = 1,
+        max = "MAX_PAGE_SIZE",
+        message = "Size must be between 1 and 100"
+    ))]
+    pub size: Option<i64>,
+    /// Opaque cursor returned as `next_cursor` by the previous page.
+    pub cursor: Option<String>,
+}
+
+/// Type of the values of a sort field.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SortKind {
+    Text,
+    Time,
+}
+
+/// A field a list may be sorted by. `name` is both the public name and the column.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SortField {
+    pub name: &'static str,
+    pub kind: SortKind,
+}
+
+/// The default ordering of every list, by creation time.
+pub const CREATED_AT: SortField = SortField {
+    name: "created_at",
+    kind: SortKind::Time,
+};
+
+/// A sort field with its direction.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SortKey {
+    pub field: SortField,
+    pub descending: bool,
+}
+
+impl SortKey {
+    /// Parses a comma-separated sort specification such as `-created_at,username`,
+    /// where a leading `-` sorts descending. Only the given fields are accepted.
+    pub fn parse_list(spec: &str, fields: &[SortField]) -> Result<Vec<SortKey>, AppError> {
+        let mut keys: Vec<SortKey> = Vec::new();
+        for part in spec
+            .split(',')
+            .map(str::trim)
+            .filter(|part| !part.is_empty())
+        {
+            let (name, descending) = match part.strip_prefix('-') {
+                Some(name) => (name, true),
+                None => (part.strip_prefix('+').unwrap_or(part), false),
+            };
+            let field = fields
+                .iter()
+                .find(|field| field.name == name)
+                .copied()
+                .ok_or_else(|| {
+                    let allowed: Vec<&str> = fields.iter().map(|field| field.name).collect();
+                    AppError::ValidationError(format!(
+                        "Cannot sort by '{name}', allowed fields are {}",
+                        allowed.join(", ")
+                    ))
+                })?;
+            if keys.iter().any(|key| key.field == field) {
+                return Err(AppError::ValidationError(format!(
+                    "Sort field '{name}' is given twice"
+                )));
+            }
+            keys.push(SortKey { field, descending });
+        }
+        Ok(keys)
+    }
+
+    fn spec(keys: &[SortKey]) -> String {
+        let parts: Vec<String> = keys
+            .iter()
+            .map(|key| match key.descending {
+                true => format!("-{}", key.field.name),
+                false => key.field.name.to_string(),
+            })
+            .collect();
+        parts.join(",")
+    }
+}
+
+/// Value of a sort field, as stored in a cursor.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum SortValue {
+    /// Time in microseconds, the precision of Postgres timestamps.
+    Time(i64),
+    Text(String),
+}
+
+impl SortValue {
+    /// Creates the value of a time field. Missing times sort first.
+    pub fn time(value: Option<DateTime<Utc>>) -> Self {
+        Self::Time(value.unwrap_or_default().timestamp_micros())
+    }
+
+    fn kind(&self) -> SortKind {
+        match self {
+            Self::Time(_) => SortKind::Time,
+            Self::Text(_) => SortKind::Text,
+        }
+    }
+
+    fn push_bind(&self, builder: &mut QueryBuilder<'_, Postgres>) {
+        match self {
+            Self::Time(micros) => {
+                builder.push_bind(DateTime::from_timestamp_micros(*micros).unwrap_or_default())
+            }
+            Self::Text(text) => builder.push_bind(text.clone()),
+        };
+    }
+}
+
+/// Items of a list that can be paginated with keyset cursors.
+pub trait Keyset {
+    /// The unique ID, which breaks ties of the sort fields.
+    fn id(&self) -> &str;
+
+    /// The value of a sort field of the list.
+    fn sort_value(&self, field: &str) -> SortValue;
+}
+
+/// Position of the last item of a page in the ordering of the list.
+/// Clients receive it base64url-encoded and must treat it as opaque.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Cursor {
+    /// The sort specification the cursor was created for.
+    #[serde(rename = "s")]
+    sort: String,
+    #[serde(rename = "v")]
+    values: Vec<SortValue>,
+    id: String,
+}
+
+impl Cursor {
+    /// Creates the cursor of an item in the given ordering.
+    pub fn new(item: &impl Keyset, order: &[SortKey]) -> Self {
+        Self {
+            sort: SortKey::spec(order),
+            values: order
+                .iter()
+                .map(|key| item.sort_value(key.field.name))
+                .collect(),
+            id: item.id().to_string(),
+        }
+    }
+
+    /// Encodes the cursor for clients.
+    pub fn encode(&self) -> String {
+        let json = serde_json::to_vec(self).unwrap_or_default();
+        URL_SAFE_NO_PAD.encode(json)
+    }
+
+    /// Decodes a cursor received from a client.
+    pub fn decode(value: &str) -> Result<Self, AppError> {
+        URL_SAFE_NO_PAD
+            .decode(value)
+            .ok()
+            .and_then(|json| serde_json::from_slice(&json).ok())
+            .ok_or_else(invalid_cursor)
+    }
+
+    /// Checks that the cursor was created for the ordering.
+    fn check(&self, order: &[SortKey]) -> Result<(), AppError> {
+        let kinds_match = self.values.len() == order.len()
+            && self
+                .values
+                .iter()
+                .zip(order)
+                .all(|(value, key)| value.kind() == key.field.kind);
+        if self.sort != SortKey::spec(order) || !kinds_match {
+            return Err(AppError::ValidationError(
+                "Cursor does not match the sort order".into(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+fn invalid_cursor() -> AppError {
+    AppError::ValidationError("Invalid cursor".into())
+}
+
+/// A validated page request, passed down to the repositories.
+/// Lists are ordered by `order`, then by ID.
+#[derive(Debug, Clone)]
+pub struct PageRequest {
+    pub page: Option<i64>,
+    pub size: i64,
+    pub after: Option<Cursor>,
+    pub order: Vec<SortKey>,
+}
+
+impl Default for PageRequest {
+    fn default() -> Self {
+        Self {
+            page: None,
+            size: DEFAULT_PAGE_SIZE,
+            after: None,
+            order: vec![SortKey {
+                field: CREATED_AT,
+                descending: false,
+            }],
+        }
+    }
+}
+
+impl TryFrom<PageQuery> for PageRequest {
+    type Error = AppError;
+
+    /// Creates a page request in the default ordering.
+    fn try_from(query: PageQuery) -> Result<Self, Self::Error> {
+        Self::sorted(query, Vec::new())
+    }
+}
+
+impl PageRequest {
+    /// Creates a page request in the given ordering, or the default one without sort keys.
+    pub fn sorted(query: PageQuery, order: Vec<SortKey>) -> Result<Self, AppError> {
+        query.validate()?;
+
+        let after = query
+            .cursor
+            .as_deref()
+            .filter(|cursor| !cursor.is_empty())
+            .map(Cursor::decode)
+            .transpose()?;
+        if after.is_some() && query.page.is_some() {
+            return Err(AppError::ValidationError(
+                "Use either page or cursor, not both".into(),
+            ));
+        }
+
+        let mut request = Self {
+            page: query.page,
+            size: query.size.unwrap_or(DEFAULT_PAGE_SIZE),
+            after,
+            ..Default::default()
+        };
+        if !order.is_empty() {
+            request.order = order;
+        }
+        request.check_cursor()?;
+        Ok(request)
+    }
+
+    fn check_cursor(&self) -> Result<(), AppError> {
+        match &self.after {
+            Some(after) => after.check(&self.order),
+            None => Ok(()),
+        }
+    }
+
+    /// Appends the cursor condition, the ordering and the limit to a query that ends
+    /// in its `WHERE` clause. `alias` qualifies the sort field and `id` columns.
+    /// One extra row is fetched to tell whether a next page exists.
+    pub fn push_to(&self, builder: &mut QueryBuilder<'_, Postgres>, alias: &str) {
+        let column = |name: &str| format!("{alias}.{name}");
+
+        // Rows after the cursor: greater in the first differing key, in its direction.
+        if let Some(after) = &self.after {
+            builder.push(" AND (");
+            for (i, key) in self.order.iter().enumerate() {
+                for (previous, value) in self.order[..i].iter().zip(&after.values) {
+                    builder.push(format!("{} = ", column(previous.field.name)));
+                    value.push_bind(builder);
+                    builder.push(" AND ");
+                }
+                let op = if key.descending { "<" } else { ">" };
+                builder.push(format!("{} {op} ", column(key.field.name)));
+                after.values[i].push_bind(builder);
+                builder.push(" OR ");
+            }
+            for (key, value) in self.order.iter().zip(&after.values) {
+                builder.push(format!("{} = ", column(key.field.name)));
+                value.push_bind(builder);
+                builder.push(" AND ");
+            }
+            builder.push(format!("{} > ", column("id")));
+            builder.push_bind(after.id.clone());
+            builder.push(")");
+        }
+
+        builder.push(" ORDER BY ");
+        for key in &self.order {
+            let direction = if key.descending { "DESC" } else { "ASC" };
+            builder.push(format!("{} {direction}, ", column(key.field.name)));
+        }
+        builder.push(format!("{} ASC LIMIT ", column("id")));
+        builder.push_bind(self.size + 1);
+
+        if let Some(page) = self.page {
+            builder.push(" OFFSET ");
+            builder.push_bind((page - 1) * self.size);
+        }
+    }
+
+    /// Builds the page from the rows fetched with `push_to`.
+    pub fn paginate<T: Keyset>(&self, mut rows: Vec<T>, total: i64) -> Paginated<T> {
+        let has_next = rows.len() as i64 > self.size;
+        rows.truncate(self.size as usize);
+        let next_cursor = match has_next {
+            true => rows
+                .last()
+                .map(|row| Cursor::new(row, &self.order).encode()),
+            false => None,
+        };
+
+        Paginated {
+            items: rows,
+            total,
+            page: self.page,
+            size: self.size,
+            next_cursor,
+        }
+    }
+}
+
+/// A page of a list, returned as the data of the API response.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct Paginated<T> {
+    pub items: Vec<T>,
+    /// Number of items in the whole list.
+    pub total: i64,
+    /// Page number, set for offset pagination.
+    pub page: Option<i64>,
+    pub size: i64,
+    /// Cursor of the next page, absent on the last page.
+    pub next_cursor: Option<String>,
+}
+
+impl<T> Paginated<T> {
+    /// Converts the items, e.g. from domain models to DTOs.
+    pub fn map<U>(self, f: impl FnMut(T) -> U) -> Paginated<U> {
+        Paginated {
+            items: self.items.into_iter().map(f).collect(),
+            total: self.total,
+            page: self.page,
+            size: self.size,
+            next_cursor: self.next_cursor,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NAME: SortField = SortField {
+        name: "name",
+        kind: SortKind::Text,
+    };
+
+    struct Item(&'static str);
+
+    impl Keyset for Item {
+        fn id(&self) -> &str {
+            self.0
+        }
+
+        fn sort_value(&self, field: &str) -> SortValue {
+            match field {
+                "name" => SortValue::Text(self.0.to_uppercase()),
+                _ => SortValue::time(None),
+            }
+        }
+    }
+
+    #[test]
+    fn test_cursor_round_trip() {
+        let order = SortKey::parse_list("-name,created_at", &[NAME, CREATED_AT]).unwrap();
+        let cursor = Cursor::new(&Item("a"), &order);
+        let decoded = Cursor::decode(&cursor.encode()).unwrap();
+
+        assert_eq!(decoded, cursor);
+        assert!(decoded.check(&order).is_ok());
+        assert!(decoded.check(&order[..1]).is_err());
+        assert!(Cursor::decode("not-a-cursor").is_err());
+    }
+
+    #[test]
+    fn test_parse_sort_keys() {
+        let order = SortKey::parse_list(" -name, +created_at ", &[NAME, CREATED_AT]).unwrap();
+        assert_eq!(order.len(), 2);
+        assert!(order[0].descending && order[0].field == NAME);
+        assert!(!order[1].descending && order[1].field == CREATED_AT);
+
+        assert!(SortKey::parse_list("", &[NAME]).unwrap().is_empty());
+        assert!(SortKey::parse_list("password", &[NAME]).is_err());
+        assert!(SortKey::parse_list("name,-name", &[NAME]).is_err());
+    }
+
+    #[test]
+    fn test_page_request_from_query() {
+        let request = PageRequest::try_from(PageQuery::default()).unwrap();
+        assert_eq!(request.size, DEFAULT_PAGE_SIZE);
+        assert!(request.page.is_none() && request.after.is_none());
+
+        let too_large = PageQuery {
+            size: Some(MAX_PAGE_SIZE + 1),
+            ..Default::default()
+        };
+        assert!(PageRequest::try_from(too_large).is_err());
+
+        let cursor = Cursor::new(&Item("a"), &PageRequest::default().order).encode();
+        let both = PageQuery {
+            page: Some(2),
+            cursor: Some(cursor.clone()),
+            ..Default::default()
+        };
+        assert!(PageRequest::try_from(both).is_err());
+
+        // A cursor is only valid for the ordering it was created for.
+        let query = PageQuery {
+            cursor: Some(cursor),
+            ..Default::default()
+        };
+        assert!(PageRequest::try_from(query.clone()).is_ok());
+        let order = SortKey::parse_list("name", &[NAME]).unwrap();
+        assert!(PageRequest::sorted(query, order).is_err());
+    }
+
+    #[test]
+    fn test_paginate() {
+        let request = PageRequest {
+            size: 2,
+            ..Default::default()
+        };
+
+        let page = request.paginate(vec![Item("a"), Item("b"), Item("c")], 3);
+        assert_eq!(page.items.len(), 2);
+        let expected = Cursor::new(&Item("b"), &request.order).encode();
+        assert_eq!(page.next_cursor, Some(expected));
+
+        let last = request.paginate(vec![Item("c")], 3);
+        assert!(last.next_cursor.is_none());
+    }
+}
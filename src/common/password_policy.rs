@@ -0,0 +1,150 @@
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use validator::{ValidationError, ValidationErrors};
+
+/// PasswordPolicy describes the passwords users may choose.
+/// Violations are reported as validation errors of the password field, one per broken rule.
+#[derive(Clone, Debug)]
+pub struct PasswordPolicy {
+    /// Minimum length, in characters.
+    pub min_length: usize,
+    /// Maximum length, in characters. Bounds the cost of hashing.
+    pub max_length: usize,
+    pub require_lowercase: bool,
+    pub require_uppercase: bool,
+    pub require_digit: bool,
+    /// Require a character that is neither a letter nor a digit.
+    pub require_symbol: bool,
+    /// Common passwords that are rejected, lowercased.
+    pub denylist: Arc<HashSet<String>>,
+}
+
+impl Default for PasswordPolicy {
+    fn default() -> Self {
+        Self {
+            min_length: 8,
+            max_length: 128,
+            require_lowercase: false,
+            require_uppercase: false,
+            require_digit: false,
+            require_symbol: false,
+            denylist: Arc::new(HashSet::new()),
+        }
+    }
+}
+
+impl PasswordPolicy {
+    /// Checks the password and adds an error under `field` for every rule it breaks.
+    pub fn check(&self, field: &'static str, password: &str, errors: &mut ValidationErrors) {
+        let length = password.chars().count();
+        if length < self.min_length || length > self.max_length {
+            let mut error = ValidationError::new("length").with_message(Cow::Owned(format!(
+                "Password must be between {} and {} characters long",
+                self.min_length, self.max_length
+            )));
+            error.add_param(Cow::Borrowed("min"), &self.min_length);
+            error.add_param(Cow::Borrowed("max"), &self.max_length);
+            errors.add(field, error);
+        }
+
+        let classes = [
+            (
+                self.require_lowercase,
+                "lowercase",
+                "Password must contain a lowercase letter",
+                password.chars().any(char::is_lowercase),
+            ),
+            (
+                self.require_uppercase,
+                "uppercase",
+                "Password must contain an uppercase letter",
+                password.chars().any(char::is_uppercase),
+            ),
+            (
+                self.require_digit,
+                "digit",
+                "Password must contain a digit",
+                password.chars().any(|c| c.is_ascii_digit()),
+            ),
+            (
+                self.require_symbol,
+                "symbol",
+                "Password must contain a symbol",
+                password.chars().any(|c| !c.is_alphanumeric()),
+            ),
+        ];
+        for (required, code, message, present) in classes {
+            if required && !present {
+                errors.add(
+                    field,
+                    ValidationError::new(code).with_message(Cow::Borrowed(message)),
+                );
+            }
+        }
+
+        if self.denylist.contains(&password.to_lowercase()) {
+            errors.add(
+                field,
+                ValidationError::new("common_password")
+                    .with_message(Cow::Borrowed("Password is too common")),
+            );
+        }
+    }
+}
+
+/// Loads a denylist file with one password per line. Blank lines and lines starting with `#` are skipped.
+pub fn load_denylist(path: &str) -> std::io::Result<HashSet<String>> {
+    let content = std::fs::read_to_string(path)?;
+    Ok(content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_lowercase)
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn codes(policy: &PasswordPolicy, password: &str) -> Vec<String> {
+        let mut errors = ValidationErrors::new();
+        policy.check("password", password, &mut errors);
+        errors
+            .field_errors()
+            .get("password")
+            .map(|errors| errors.iter().map(|e| e.code.to_string()).collect())
+            .unwrap_or_default()
+    }
+
+    #[test]
+    fn test_length_and_character_classes() {
+        let policy = PasswordPolicy {
+            require_lowercase: true,
+            require_uppercase: true,
+            require_digit: true,
+            require_symbol: true,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            codes(&policy, "short"),
+            ["length", "uppercase", "digit", "symbol"]
+        );
+        assert!(codes(&policy, "Valid-Passw0rd").is_empty());
+        assert_eq!(codes(&policy, &"Aa1!".repeat(40)), ["length"]);
+    }
+
+    #[test]
+    fn test_denylist_is_case_insensitive() {
+        let policy = PasswordPolicy {
+            denylist: Arc::new(HashSet::from(["password123".to_string()])),
+            ..Default::default()
+        };
+
+        assert_eq!(codes(&policy, "PassWord123"), ["common_password"]);
+        assert!(codes(&policy, "password1234").is_empty());
+    }
+}
@@ -0,0 +1,59 @@
+use axum::{
+    body::Body,
+    extract::Request,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+
+use super::{error::AppError, jwt::Claims};
+
+/// Name of the role whose holders may act on resources owned by other users.
+pub const ADMIN_ROLE: &str = "admin";
+
+/// Name of the role assigned to users who sign up themselves.
+pub const USER_ROLE: &str = "user";
+
+/// Permission names stored in `role_permissions` and embedded in the JWT claims.
+pub const USER_READ: &str = "user:read";
+pub const USER_WRITE: &str = "user:write";
+pub const USER_DELETE: &str = "user:delete";
+pub const DEVICE_READ: &str = "device:read";
+pub const DEVICE_WRITE: &str = "device:write";
+pub const DEVICE_DELETE: &str = "device:delete";
+pub const FILE_READ: &str = "file:read";
+pub const FILE_DELETE: &str = "file:delete";
+pub const GROUP_READ: &str = "group:read";
+pub const GROUP_WRITE: &str = "group:write";
+pub const AUTH_MANAGE: &str = "auth:manage";
+
+// Type alias for the boxed future returned by the permission middleware
+type PermissionFuture =
+    std::pin::Pin<Box<dyn std::future::Future<Output = Result<Response, Response>> + Send>>;
+
+/// Middleware factory that requires the authenticated caller to hold `permission`.
+/// Must run after `jwt::jwt_auth`, which places the decoded `Claims` in the request extensions.
+/// Returns 401 Unauthorized if no claims are present and 403 Forbidden if the permission is missing.
+/// Attach it per route with `.route_layer(middleware::from_fn(require_permission(USER_DELETE)))`.
+pub fn require_permission(
+    permission: &'static str,
+) -> impl Fn(Request<Body>, Next) -> PermissionFuture + Clone + Send + Sync + 'static {
+    move |req, next| Box::pin(check_permission(req, next, permission))
+}
+
+async fn check_permission(
+    req: Request<Body>,
+    next: Next,
+    permission: &'static str,
+) -> Result<Response, Response> {
+    let claims = req
+        .extensions()
+        .get::<Claims>()
+        .ok_or_else(|| AppError::InvalidToken.into_response())?;
+
+    if !claims.has_permission(permission) {
+        tracing::error!("{} lacks permission {}", claims, permission);
+        return Err(AppError::Forbidden.into_response());
+    }
+
+    Ok(next.run(req).await)
+}
@@ -0,0 +1,179 @@
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::common::error::AppError;
+
+/// Format of bulk imports and exports: CSV with a header row,
+/// or newline-delimited JSON with one object per line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum RecordFormat {
+    #[default]
+    Csv,
+    Ndjson,
+}
+
+impl RecordFormat {
+    /// The format of a request body with the given `Content-Type`, if it is a known one.
+    pub fn from_content_type(content_type: &str) -> Option<Self> {
+        let mime = content_type.split(';').next().unwrap_or_default().trim();
+        match mime.to_ascii_lowercase().as_str() {
+            "text/csv" => Some(Self::Csv),
+            "application/x-ndjson" | "application/jsonl" => Some(Self::Ndjson),
+            _ => None,
+        }
+    }
+
+    /// The `Content-Type` of a response in this format.
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Self::Csv => "text/csv; charset=utf-8",
+            Self::Ndjson => "application/x-ndjson",
+        }
+    }
+
+    /// The file extension of exports in this format.
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Csv => "csv",
+            Self::Ndjson => "ndjson",
+        }
+    }
+
+    /// Parses the records of `data`, one result per record, so that a broken record
+    /// does not hide the others. CSV headers name the fields, extra columns are ignored,
+    /// and blank NDJSON lines are skipped.
+    pub fn parse<T: DeserializeOwned>(self, data: &[u8]) -> Vec<Result<T, String>> {
+        match self {
+            Self::Csv => csv::ReaderBuilder::new()
+                .trim(csv::Trim::All)
+                .from_reader(data)
+                .deserialize()
+                .map(|record| record.map_err(|err| csv_error_message(&err)))
+                .collect(),
+            Self::Ndjson => data
+                .split(|byte| *byte == b'\n')
+                .filter(|line| !line.trim_ascii().is_empty())
+                .map(|line| serde_json::from_slice(line).map_err(|err| err.to_string()))
+                .collect(),
+        }
+    }
+
+    /// Returns an encoder writing records in this format.
+    pub fn encoder(self) -> RecordEncoder {
+        RecordEncoder {
+            format: self,
+            header_written: false,
+        }
+    }
+}
+
+fn csv_error_message(err: &csv::Error) -> String {
+    match err.kind() {
+        csv::ErrorKind::Deserialize { err, .. } => err.to_string(),
+        _ => err.to_string(),
+    }
+}
+
+/// Encodes records one at a time, so that exports can be streamed.
+/// The first CSV record is preceded by the header row.
+pub struct RecordEncoder {
+    format: RecordFormat,
+    header_written: bool,
+}
+
+impl RecordEncoder {
+    /// Encodes a record, terminated by a newline.
+    pub fn encode<T: Serialize>(&mut self, record: &T) -> Result<Vec<u8>, AppError> {
+        match self.format {
+            RecordFormat::Csv => {
+                let mut writer = csv::WriterBuilder::new()
+                    .has_headers(!self.header_written)
+                    .from_writer(Vec::new());
+                writer.serialize(record).map_err(encode_error)?;
+                self.header_written = true;
+                writer
+                    .into_inner()
+                    .map_err(|err| encode_error(err.into_error()))
+            }
+            RecordFormat::Ndjson => {
+                let mut line = serde_json::to_vec(record).map_err(encode_error)?;
+                line.push(b'\n');
+                Ok(line)
+            }
+        }
+    }
+}
+
+fn encode_error(err: impl std::fmt::Display) -> AppError {
+    tracing::error!("Error encoding record: {err}");
+    AppError::InternalError
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Item {
+        name: String,
+        note: Option<String>,
+    }
+
+    #[test]
+    fn test_from_content_type() {
+        assert_eq!(
+            RecordFormat::from_content_type("text/csv; charset=utf-8"),
+            Some(RecordFormat::Csv)
+        );
+        assert_eq!(
+            RecordFormat::from_content_type("application/x-ndjson"),
+            Some(RecordFormat::Ndjson)
+        );
+        assert_eq!(RecordFormat::from_content_type("application/json"), None);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let items = vec![
+            Item {
+                name: "a, \"quoted\"".into(),
+                note: None,
+            },
+            Item {
+                name: "b".into(),
+                note: Some("note".into()),
+            },
+        ];
+
+        for format in [RecordFormat::Csv, RecordFormat::Ndjson] {
+            let mut encoder = format.encoder();
+            let data: Vec<u8> = items
+                .iter()
+                .flat_map(|item| encoder.encode(item).unwrap())
+                .collect();
+            let parsed: Vec<Item> = format
+                .parse::<Item>(&data)
+                .into_iter()
+                .map(Result::unwrap)
+                .collect();
+            assert_eq!(parsed, items);
+        }
+    }
+
+    #[test]
+    fn test_parse_reports_each_broken_record() {
+        let csv = b"note,name,extra\nx,a,1\ny\n,c,3\n";
+        let parsed = RecordFormat::Csv.parse::<Item>(csv);
+        assert_eq!(parsed.len(), 3);
+        assert_eq!(parsed[0].as_ref().unwrap().name, "a");
+        assert!(parsed[1].is_err());
+        assert_eq!(parsed[2].as_ref().unwrap().note, None);
+
+        let ndjson = b"{\"name\":\"a\"}\n\n{\"note\":\"x\"}\n{\"name\":\"c\"}";
+        let parsed = RecordFormat::Ndjson.parse::<Item>(ndjson);
+        assert_eq!(parsed.len(), 3);
+        assert!(parsed[1].as_ref().unwrap_err().contains("name"));
+        assert_eq!(parsed[2].as_ref().unwrap().name, "c");
+    }
+}
@@ -0,0 +1,154 @@
+use axum::{
+    http::{HeaderMap, Method},
+    response::{IntoResponse, Response},
+};
+use axum_extra::extract::cookie::{Cookie, CookieJar, SameSite};
+use serde::{Deserialize, Serialize};
+use time::Duration;
+use utoipa::ToSchema;
+
+use super::{
+    config::{Config, SessionMode},
+    dto::RestApiResponse,
+    error::AppError,
+    hash_util,
+    jwt::AuthBody,
+};
+
+/// HttpOnly cookie holding the access token.
+pub const ACCESS_TOKEN_COOKIE: &str = "access_token";
+/// HttpOnly cookie holding the refresh token. It is only sent to `/auth`.
+pub const REFRESH_TOKEN_COOKIE: &str = "refresh_token";
+/// Cookie holding the CSRF token. It is readable by scripts, which echo it in `CSRF_HEADER`.
+pub const CSRF_COOKIE: &str = "csrf_token";
+/// Header that state-changing requests authenticated by cookie must carry.
+pub const CSRF_HEADER: &str = "x-csrf-token";
+
+/// Response body of a login in cookie mode. The tokens themselves are only set as cookies.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct CookieSessionBody {
+    /// Lifetime of the access token cookie, in seconds.
+    pub expires_in: i64,
+    /// Double-submit token, also set as the `csrf_token` cookie.
+    pub csrf_token: String,
+}
+
+/// Answers a successful login, refresh or password change.
+/// In bearer mode the token pair is the response body; in cookie mode it is set as
+/// HttpOnly cookies together with a new CSRF token.
+pub fn session_response(config: &Config, jar: CookieJar, auth_body: AuthBody) -> Response {
+    if config.session_mode == SessionMode::Bearer {
+        return RestApiResponse::success(auth_body).into_response();
+    }
+
+    let csrf_token = hash_util::generate_token();
+    let jar = jar
+        .add(session_cookie(
+            config,
+            ACCESS_TOKEN_COOKIE,
+            auth_body.access_token,
+            "/",
+            config.jwt_access_token_ttl_secs,
+            true,
+        ))
+        .add(session_cookie(
+            config,
+            REFRESH_TOKEN_COOKIE,
+            auth_body.refresh_token,
+            "/auth",
+            config.jwt_refresh_token_ttl_secs,
+            true,
+        ))
+        .add(session_cookie(
+            config,
+            CSRF_COOKIE,
+            csrf_token.clone(),
+            "/",
+            config.jwt_refresh_token_ttl_secs,
+            false,
+        ));
+
+    let body = CookieSessionBody {
+        expires_in: auth_body.expires_in,
+        csrf_token,
+    };
+    (jar, RestApiResponse::success(body)).into_response()
+}
+
+/// Removes the session cookies, e.g. on logout.
+pub fn clear_session_cookies(config: &Config, jar: CookieJar) -> CookieJar {
+    jar.remove(session_cookie(
+        config,
+        ACCESS_TOKEN_COOKIE,
+        String::new(),
+        "/",
+        0,
+        true,
+    ))
+    .remove(session_cookie(
+        config,
+        REFRESH_TOKEN_COOKIE,
+        String::new(),
+        "/auth",
+        0,
+        true,
+    ))
+    .remove(session_cookie(
+        config,
+        CSRF_COOKIE,
+        String::new(),
+        "/",
+        0,
+        false,
+    ))
+}
+
+/// Returns the token of the named cookie in cookie mode.
+/// In bearer mode cookies are ignored, so a stray cookie never authenticates a request.
+pub fn cookie_token(config: &Config, jar: &CookieJar, name: &str) -> Option<String> {
+    if config.session_mode != SessionMode::Cookie {
+        return None;
+    }
+    jar.get(name)
+        .map(|cookie| cookie.value().to_string())
+        .filter(|token| !token.is_empty())
+}
+
+/// Checks the double-submit CSRF token of a request authenticated by cookie.
+/// Safe methods are exempt; every other request must echo the `csrf_token` cookie
+/// in the `X-CSRF-Token` header, which a cross-site page cannot read.
+pub fn verify_csrf(method: &Method, headers: &HeaderMap, jar: &CookieJar) -> Result<(), AppError> {
+    if matches!(*method, Method::GET | Method::HEAD | Method::OPTIONS) {
+        return Ok(());
+    }
+
+    let cookie = jar.get(CSRF_COOKIE).map(Cookie::value).unwrap_or_default();
+    let header = headers
+        .get(CSRF_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default();
+
+    // Compare digests so that the comparison time does not depend on the token.
+    if cookie.is_empty() || hash_util::hash_token(cookie) != hash_util::hash_token(header) {
+        tracing::error!("Rejected request with a missing or invalid CSRF token");
+        return Err(AppError::InvalidCsrfToken);
+    }
+    Ok(())
+}
+
+fn session_cookie(
+    config: &Config,
+    name: &'static str,
+    value: String,
+    path: &'static str,
+    max_age_secs: i64,
+    http_only: bool,
+) -> Cookie<'static> {
+    Cookie::build((name, value))
+        .path(path)
+        .http_only(http_only)
+        .secure(config.cookie_secure)
+        .same_site(SameSite::Strict)
+        .max_age(Duration::seconds(max_age_secs))
+        .build()
+}
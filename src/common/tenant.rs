@@ -0,0 +1,78 @@
+//! The tenant (organization) of the current request.
+//!
+//! `jwt::jwt_auth` runs every authenticated request within the scope of the caller's
+//! organization, and the user, device, file and group repositories, API key revocation
+//! and the audit trail restrict their queries to it,
+//! so services and handlers never pass the tenant around. Code running outside of a
+//! request, such as the purge job or the sign-in flows, has no tenant and is not restricted.
+
+use std::future::Future;
+
+use axum::{
+    extract::{Request, State},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use sqlx::{Postgres, QueryBuilder};
+
+use super::{app_state::AppState, error::AppError, jwt::Claims};
+
+/// Organization of the records created outside of any tenant, e.g. by open registration.
+pub const DEFAULT_ORGANIZATION_ID: &str = "00000000-0000-0000-0000-000000000001";
+
+tokio::task_local! {
+    static CURRENT_ORG_ID: Option<String>;
+}
+
+/// Runs `f` with `org_id` as the current tenant; `None` runs it without a tenant.
+/// Futures that outlive the request, like streamed responses, must be scoped again.
+pub async fn scope<F: Future>(org_id: Option<String>, f: F) -> F::Output {
+    CURRENT_ORG_ID.scope(org_id, f).await
+}
+
+/// Returns the current tenant, if there is one.
+/// Queries bind it and compare with `($n::varchar IS NULL OR org_id = $n)`.
+pub fn current() -> Option<String> {
+    CURRENT_ORG_ID.try_with(Clone::clone).ok().flatten()
+}
+
+/// Returns the organization new records are created in:
+/// the current tenant, or the default organization outside of requests.
+pub fn current_or_default() -> String {
+    current().unwrap_or_else(|| DEFAULT_ORGANIZATION_ID.to_string())
+}
+
+/// Appends the tenant condition on `alias.org_id` to a query that ends in its `WHERE` clause.
+pub fn push_condition(builder: &mut QueryBuilder<'_, Postgres>, alias: &str) {
+    if let Some(org_id) = current() {
+        builder.push(format!(" AND {alias}.org_id = "));
+        builder.push_bind(org_id);
+    }
+}
+
+/// Middleware restricting the private assets to the directory of the caller's organization.
+/// Must run after `jwt::jwt_auth`. Other organizations' files are reported as not found.
+pub async fn restrict_private_assets(
+    State(state): State<AppState>,
+    req: Request,
+    next: Next,
+) -> Result<Response, Response> {
+    let org_id = req
+        .extensions()
+        .get::<Claims>()
+        .map(|claims| claims.org_id.clone())
+        .ok_or_else(|| AppError::InvalidToken.into_response())?;
+
+    let own_directory = req
+        .uri()
+        .path()
+        .strip_prefix(state.config.assets_private_url.as_str())
+        .and_then(|path| path.strip_prefix('/'))
+        .and_then(|path| path.strip_prefix(org_id.as_str()))
+        .is_some_and(|path| path.starts_with('/'));
+    if !own_directory {
+        return Err(AppError::NotFound("File not found".into()).into_response());
+    }
+
+    Ok(next.run(req).await)
+}
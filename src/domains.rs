@@ -1,4 +1,6 @@
 pub mod auth;
 pub mod device;
 pub mod file;
+pub mod group;
+pub mod oauth;
 pub mod user;
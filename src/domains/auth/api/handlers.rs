@@ -1,45 +1,544 @@
 use crate::{
     common::{
         app_state::AppState,
+        client_info::ClientInfo,
         dto::RestApiResponse,
         error::AppError,
-        jwt::{AuthBody, AuthPayload},
+        jwt::{AuthBody, AuthPayload, Claims},
+        keyring::KEYRING,
+        session_cookie::{self, session_response, REFRESH_TOKEN_COOKIE},
     },
-    domains::auth::dto::auth_dto::AuthUserDto,
+    domains::{
+        auth::dto::auth_dto::{
+            ApiKeyDto, AuthEventDto, AuthEventQuery, AuthUserDto, ChangePasswordDto,
+            CreateApiKeyDto, CreateInviteDto, CreatedApiKeyDto, ForgotPasswordDto,
+            ImpersonationTokenDto, InviteDto, LoginResult, MagicLinkRequestDto, MagicLinkVerifyDto,
+            MfaCodeDto, MfaEnrollmentDto, MfaRecoveryCodesDto, MfaVerifyDto, RefreshTokenDto,
+            RegisterUserDto, ResetPasswordDto, UnlockAccountDto,
+        },
+        user::dto::user_dto::UserDto,
+    },
+};
+use axum::extract::{Path, Query, State};
+use axum::{
+    http::{HeaderMap, Method},
+    response::{IntoResponse, Response},
+    Extension, Json,
 };
-use axum::extract::State;
-use axum::{response::IntoResponse, Json};
+use axum_extra::extract::cookie::CookieJar;
 
-/// this function creates a router for creating user authentication registration
-/// it will create a new user in the database
+/// this function creates a router for self-signup
+/// it creates the user and its credentials, depending on the registration mode
 #[utoipa::path(
     post,
     path = "/auth/register",
-    request_body = AuthUserDto,
-    responses((status = 200, description = "Create user authentication", body = AuthUserDto)),
+    request_body = RegisterUserDto,
+    responses(
+        (status = 200, description = "Register a new user", body = UserDto),
+        (status = 403, description = "Registration disabled or invalid invite"),
+        (status = 409, description = "Username already exists")
+    ),
     tag = "UserAuth"
 )]
+pub async fn register_user(
+    State(state): State<AppState>,
+    Json(payload): Json<RegisterUserDto>,
+) -> Result<impl IntoResponse, AppError> {
+    let user = state.auth_service.register_user(payload).await?;
+    Ok(RestApiResponse::success(user))
+}
+
+/// this function creates a router for attaching credentials to an existing user
+/// it is restricted to administrators
+#[utoipa::path(
+    post,
+    path = "/auth/credentials",
+    request_body = AuthUserDto,
+    responses(
+        (status = 200, description = "Create user authentication"),
+        (status = 404, description = "User not found"),
+        (status = 409, description = "Credentials already exist")
+    ),
+    tag = "UserAuth",
+    security(("bearer_auth" = []))
+)]
 pub async fn create_user_auth(
     State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
     Json(payload): Json<AuthUserDto>,
 ) -> Result<impl IntoResponse, AppError> {
-    state.auth_service.create_user_auth(payload).await?;
+    state
+        .auth_service
+        .create_user_auth(&claims, payload)
+        .await?;
     Ok(RestApiResponse::success(()))
 }
 
+/// this function creates a router for issuing registration invites
+/// the returned token is redeemed at `/auth/register`
+#[utoipa::path(
+    post,
+    path = "/auth/invites",
+    request_body = CreateInviteDto,
+    responses((status = 200, description = "Create a registration invite", body = InviteDto)),
+    tag = "UserAuth",
+    security(("bearer_auth" = []))
+)]
+pub async fn create_invite(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Json(payload): Json<CreateInviteDto>,
+) -> Result<impl IntoResponse, AppError> {
+    let invite = state.auth_service.create_invite(&claims, payload).await?;
+    Ok(RestApiResponse::success(invite))
+}
+
+/// this function creates a router for impersonating a user
+/// it returns a short-lived token that acts as the user and records the admin as the actor
+#[utoipa::path(
+    post,
+    path = "/auth/impersonate/{user_id}",
+    responses(
+        (status = 200, description = "Impersonate a user", body = ImpersonationTokenDto),
+        (status = 403, description = "Caller may not impersonate"),
+        (status = 404, description = "User not found")
+    ),
+    tag = "UserAuth",
+    security(("bearer_auth" = []))
+)]
+pub async fn impersonate(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    client: ClientInfo,
+    Path(user_id): Path<String>,
+) -> Result<impl IntoResponse, AppError> {
+    let token = state
+        .auth_service
+        .impersonate(&claims, user_id, &client)
+        .await?;
+    Ok(RestApiResponse::success(token))
+}
+
+/// this function creates a router for creating an API key
+/// the key is returned once and is sent as `Authorization: ApiKey <key>`
+#[utoipa::path(
+    post,
+    path = "/auth/api-keys",
+    request_body = CreateApiKeyDto,
+    responses(
+        (status = 200, description = "Create an API key", body = CreatedApiKeyDto),
+        (status = 400, description = "Invalid name, lifetime or scopes")
+    ),
+    tag = "UserAuth",
+    security(("bearer_auth" = []))
+)]
+pub async fn create_api_key(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Json(payload): Json<CreateApiKeyDto>,
+) -> Result<impl IntoResponse, AppError> {
+    let api_key = state.auth_service.create_api_key(&claims, payload).await?;
+    Ok(RestApiResponse::success(api_key))
+}
+
+/// this function creates a router for listing the caller's API keys
+#[utoipa::path(
+    get,
+    path = "/auth/api-keys",
+    responses((status = 200, description = "List API keys", body = [ApiKeyDto])),
+    tag = "UserAuth",
+    security(("bearer_auth" = []))
+)]
+pub async fn list_api_keys(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+) -> Result<impl IntoResponse, AppError> {
+    let api_keys = state.auth_service.list_api_keys(&claims).await?;
+    Ok(RestApiResponse::success(api_keys))
+}
+
+/// this function creates a router for revoking an API key
+#[utoipa::path(
+    delete,
+    path = "/auth/api-keys/{id}",
+    responses(
+        (status = 200, description = "API key revoked"),
+        (status = 404, description = "API key not found")
+    ),
+    tag = "UserAuth",
+    security(("bearer_auth" = []))
+)]
+pub async fn revoke_api_key(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    axum::extract::Path(id): axum::extract::Path<String>,
+) -> Result<impl IntoResponse, AppError> {
+    state.auth_service.revoke_api_key(&claims, id).await?;
+    Ok(RestApiResponse::success_with_message("API key revoked", ()))
+}
+
 /// this function creates a router for login user
-/// it will return a JWT token if the user is authenticated
+/// it will return a JWT token if the user is authenticated,
+/// or an MFA challenge to be completed at `/auth/mfa/verify`
 #[utoipa::path(
     post,
     path = "/auth/login",
     request_body = AuthPayload,
-    responses((status = 200, description = "Login user", body = AuthBody)),
+    responses(
+        (status = 200, description = "Login user; MfaChallengeDto when MFA is enabled, CookieSessionBody in cookie session mode", body = AuthBody),
+        (status = 401, description = "Wrong credentials"),
+        (status = 429, description = "Too many failed attempts, see the Retry-After header")
+    ),
     tag = "UserAuth"
 )]
 pub async fn login_user(
     State(state): State<AppState>,
+    client: ClientInfo,
+    jar: CookieJar,
     Json(payload): Json<AuthPayload>,
 ) -> Result<impl IntoResponse, AppError> {
-    let auth_body = state.auth_service.login_user(payload).await?;
-    Ok(RestApiResponse::success(auth_body))
+    let result = state.auth_service.login_user(payload, &client).await?;
+    Ok(login_response(&state, jar, result))
+}
+
+/// this function creates a router for the second step of an MFA login
+/// it exchanges the challenge token and a TOTP or recovery code for a token pair
+#[utoipa::path(
+    post,
+    path = "/auth/mfa/verify",
+    request_body = MfaVerifyDto,
+    responses(
+        (status = 200, description = "Complete an MFA login", body = AuthBody),
+        (status = 401, description = "Wrong code or invalid challenge")
+    ),
+    tag = "UserAuth"
+)]
+pub async fn verify_mfa(
+    State(state): State<AppState>,
+    client: ClientInfo,
+    jar: CookieJar,
+    Json(payload): Json<MfaVerifyDto>,
+) -> Result<impl IntoResponse, AppError> {
+    let auth_body = state.auth_service.verify_mfa(payload, &client).await?;
+    Ok(session_response(&state.config, jar, auth_body))
+}
+
+/// this function creates a router for starting a TOTP enrollment
+/// it returns the secret and the otpauth URI for authenticator apps
+#[utoipa::path(
+    post,
+    path = "/auth/mfa/enroll",
+    responses(
+        (status = 200, description = "Start MFA enrollment", body = MfaEnrollmentDto),
+        (status = 409, description = "MFA already enabled")
+    ),
+    tag = "UserAuth",
+    security(("bearer_auth" = []))
+)]
+pub async fn enroll_mfa(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+) -> Result<impl IntoResponse, AppError> {
+    let enrollment = state.auth_service.enroll_mfa(&claims).await?;
+    Ok(RestApiResponse::success(enrollment))
+}
+
+/// this function creates a router for confirming a TOTP enrollment
+/// it enables MFA and returns the recovery codes
+#[utoipa::path(
+    post,
+    path = "/auth/mfa/confirm",
+    request_body = MfaCodeDto,
+    responses(
+        (status = 200, description = "Enable MFA", body = MfaRecoveryCodesDto),
+        (status = 400, description = "Invalid code")
+    ),
+    tag = "UserAuth",
+    security(("bearer_auth" = []))
+)]
+pub async fn confirm_mfa(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Json(payload): Json<MfaCodeDto>,
+) -> Result<impl IntoResponse, AppError> {
+    let recovery_codes = state.auth_service.confirm_mfa(&claims, payload).await?;
+    Ok(RestApiResponse::success(recovery_codes))
+}
+
+/// this function creates a router for disabling MFA
+/// it requires a TOTP or recovery code
+#[utoipa::path(
+    post,
+    path = "/auth/mfa/disable",
+    request_body = MfaCodeDto,
+    responses(
+        (status = 200, description = "Disable MFA"),
+        (status = 401, description = "Wrong code")
+    ),
+    tag = "UserAuth",
+    security(("bearer_auth" = []))
+)]
+pub async fn disable_mfa(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Json(payload): Json<MfaCodeDto>,
+) -> Result<impl IntoResponse, AppError> {
+    state.auth_service.disable_mfa(&claims, payload).await?;
+    Ok(RestApiResponse::success_with_message("MFA disabled", ()))
+}
+
+/// this function creates a router for unlocking accounts
+/// it clears the failed-login counters of an account and/or a client IP
+#[utoipa::path(
+    post,
+    path = "/auth/unlock",
+    request_body = UnlockAccountDto,
+    responses((status = 200, description = "Unlock an account or client IP")),
+    tag = "UserAuth",
+    security(("bearer_auth" = []))
+)]
+pub async fn unlock_account(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    client: ClientInfo,
+    Json(payload): Json<UnlockAccountDto>,
+) -> Result<impl IntoResponse, AppError> {
+    state
+        .auth_service
+        .unlock_account(&claims, payload, &client)
+        .await?;
+    Ok(RestApiResponse::success_with_message("Unlocked", ()))
+}
+
+/// this function creates a router for refreshing tokens
+/// it rotates the refresh token and returns a new token pair
+/// in cookie session mode the refresh token cookie is used and the body may be omitted
+#[utoipa::path(
+    post,
+    path = "/auth/refresh",
+    request_body = RefreshTokenDto,
+    responses(
+        (status = 200, description = "Refresh tokens", body = AuthBody),
+        (status = 403, description = "Invalid CSRF token")
+    ),
+    tag = "UserAuth"
+)]
+pub async fn refresh_token(
+    State(state): State<AppState>,
+    client: ClientInfo,
+    method: Method,
+    headers: HeaderMap,
+    jar: CookieJar,
+    payload: Option<Json<RefreshTokenDto>>,
+) -> Result<impl IntoResponse, AppError> {
+    let payload = refresh_token_payload(&state, &method, &headers, &jar, payload)?;
+    let auth_body = state.auth_service.refresh_token(payload, &client).await?;
+    Ok(session_response(&state.config, jar, auth_body))
+}
+
+/// this function creates a router for logout
+/// it revokes the session the refresh token belongs to
+/// in cookie session mode the session cookies are cleared as well
+#[utoipa::path(
+    post,
+    path = "/auth/logout",
+    request_body = RefreshTokenDto,
+    responses(
+        (status = 200, description = "Logout user"),
+        (status = 403, description = "Invalid CSRF token")
+    ),
+    tag = "UserAuth"
+)]
+pub async fn logout(
+    State(state): State<AppState>,
+    client: ClientInfo,
+    method: Method,
+    headers: HeaderMap,
+    jar: CookieJar,
+    payload: Option<Json<RefreshTokenDto>>,
+) -> Result<impl IntoResponse, AppError> {
+    let payload = refresh_token_payload(&state, &method, &headers, &jar, payload)?;
+    state.auth_service.logout(payload, &client).await?;
+    Ok((
+        session_cookie::clear_session_cookies(&state.config, jar),
+        RestApiResponse::success_with_message("Logged out", ()),
+    ))
+}
+
+/// Takes the refresh token from the cookie in cookie session mode, after checking the CSRF token,
+/// and from the request body otherwise.
+fn refresh_token_payload(
+    state: &AppState,
+    method: &Method,
+    headers: &HeaderMap,
+    jar: &CookieJar,
+    payload: Option<Json<RefreshTokenDto>>,
+) -> Result<RefreshTokenDto, AppError> {
+    if let Some(Json(payload)) = payload {
+        return Ok(payload);
+    }
+
+    let refresh_token = session_cookie::cookie_token(&state.config, jar, REFRESH_TOKEN_COOKIE)
+        .ok_or(AppError::MissingCredentials)?;
+    session_cookie::verify_csrf(method, headers, jar)?;
+    Ok(RefreshTokenDto { refresh_token })
+}
+
+/// this function creates a router for changing the caller's password
+/// it revokes every existing session and returns a new token pair
+#[utoipa::path(
+    post,
+    path = "/auth/password/change",
+    request_body = ChangePasswordDto,
+    responses(
+        (status = 200, description = "Change password", body = AuthBody),
+        (status = 401, description = "Wrong current password")
+    ),
+    tag = "UserAuth",
+    security(("bearer_auth" = []))
+)]
+pub async fn change_password(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    client: ClientInfo,
+    jar: CookieJar,
+    Json(payload): Json<ChangePasswordDto>,
+) -> Result<impl IntoResponse, AppError> {
+    let auth_body = state
+        .auth_service
+        .change_password(&claims, payload, &client)
+        .await?;
+    Ok(session_response(&state.config, jar, auth_body))
+}
+
+/// this function creates a router for requesting a password reset
+/// it always succeeds, the reset token is delivered to the user's email address
+#[utoipa::path(
+    post,
+    path = "/auth/password/forgot",
+    request_body = ForgotPasswordDto,
+    responses((status = 200, description = "Request a password reset")),
+    tag = "UserAuth"
+)]
+pub async fn forgot_password(
+    State(state): State<AppState>,
+    Json(payload): Json<ForgotPasswordDto>,
+) -> Result<impl IntoResponse, AppError> {
+    state.auth_service.forgot_password(payload).await?;
+    Ok(RestApiResponse::success_with_message(
+        "If the account exists, a reset token has been sent",
+        (),
+    ))
+}
+
+/// this function creates a router for requesting a passwordless login
+/// it always succeeds, the link and one-time code are delivered to the user's email address
+#[utoipa::path(
+    post,
+    path = "/auth/magic-link",
+    request_body = MagicLinkRequestDto,
+    responses((status = 200, description = "Request a magic link")),
+    tag = "UserAuth"
+)]
+pub async fn request_magic_link(
+    State(state): State<AppState>,
+    Json(payload): Json<MagicLinkRequestDto>,
+) -> Result<impl IntoResponse, AppError> {
+    state.auth_service.request_magic_link(payload).await?;
+    Ok(RestApiResponse::success_with_message(
+        "If the account exists, a sign-in link has been sent",
+        (),
+    ))
+}
+
+/// this function creates a router for completing a passwordless login
+/// it accepts the link token, or the username and the one-time code
+#[utoipa::path(
+    post,
+    path = "/auth/magic-link/verify",
+    request_body = MagicLinkVerifyDto,
+    responses(
+        (status = 200, description = "Login user; MfaChallengeDto when MFA is enabled, CookieSessionBody in cookie session mode", body = AuthBody),
+        (status = 401, description = "Wrong code or invalid link"),
+        (status = 429, description = "Too many failed attempts, see the Retry-After header")
+    ),
+    tag = "UserAuth"
+)]
+pub async fn verify_magic_link(
+    State(state): State<AppState>,
+    client: ClientInfo,
+    jar: CookieJar,
+    Json(payload): Json<MagicLinkVerifyDto>,
+) -> Result<impl IntoResponse, AppError> {
+    let result = state
+        .auth_service
+        .verify_magic_link(payload, &client)
+        .await?;
+    Ok(login_response(&state, jar, result))
+}
+
+/// Answers a successful first login step with the session, or with the MFA challenge.
+fn login_response(state: &AppState, jar: CookieJar, result: LoginResult) -> Response {
+    match result {
+        LoginResult::Authenticated(auth_body) => session_response(&state.config, jar, auth_body),
+        LoginResult::MfaRequired(challenge) => {
+            RestApiResponse::success_with_message("MFA required", challenge).into_response()
+        }
+    }
+}
+
+/// this function creates a router for resetting a password with a reset token
+/// it revokes every existing session of the user
+#[utoipa::path(
+    post,
+    path = "/auth/password/reset",
+    request_body = ResetPasswordDto,
+    responses(
+        (status = 200, description = "Reset password"),
+        (status = 401, description = "Invalid or expired reset token")
+    ),
+    tag = "UserAuth"
+)]
+pub async fn reset_password(
+    State(state): State<AppState>,
+    client: ClientInfo,
+    Json(payload): Json<ResetPasswordDto>,
+) -> Result<impl IntoResponse, AppError> {
+    state.auth_service.reset_password(payload, &client).await?;
+    Ok(RestApiResponse::success_with_message("Password reset", ()))
+}
+
+/// this function creates a router for querying the authentication audit trail
+/// events can be filtered by user, event type and time range, newest first
+#[utoipa::path(
+    get,
+    path = "/auth/events",
+    params(AuthEventQuery),
+    responses(
+        (status = 200, description = "List authentication events", body = [AuthEventDto]),
+        (status = 400, description = "Invalid filter"),
+        (status = 404, description = "The user filtered by belongs to another organization")
+    ),
+    tag = "UserAuth",
+    security(("bearer_auth" = []))
+)]
+pub async fn list_auth_events(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Query(query): Query<AuthEventQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    let events = state.auth_service.list_auth_events(&claims, query).await?;
+    Ok(RestApiResponse::success(events))
+}
+
+/// this function serves the public keys of the JWT keyring
+/// so that other services can verify the issued tokens
+#[utoipa::path(
+    get,
+    path = "/.well-known/jwks.json",
+    responses((status = 200, description = "JSON Web Key Set of the token signing keys")),
+    tag = "UserAuth"
+)]
+pub async fn jwks() -> impl IntoResponse {
+    Json(KEYRING.jwks())
 }
@@ -1,33 +1,162 @@
-use crate::common::app_state::AppState;
-use axum::{routing::post, Router};
+use crate::common::{
+    app_state::AppState,
+    permission::{require_permission, AUTH_MANAGE},
+};
+use axum::{
+    middleware,
+    routing::{delete, get, post},
+    Router,
+};
 
 use super::handlers;
 
-use utoipa::OpenApi;
+use utoipa::{
+    openapi::security::{ApiKey, ApiKeyValue, HttpAuthScheme, HttpBuilder, SecurityScheme},
+    OpenApi,
+};
 
 /// Import the necessary modules for OpenAPI documentation generation
 #[derive(OpenApi)]
 #[openapi(
     paths(
         super::handlers::login_user,
+        super::handlers::unlock_account,
+        super::handlers::verify_mfa,
+        super::handlers::enroll_mfa,
+        super::handlers::confirm_mfa,
+        super::handlers::disable_mfa,
+        super::handlers::register_user,
         super::handlers::create_user_auth,
+        super::handlers::create_invite,
+        super::handlers::impersonate,
+        super::handlers::refresh_token,
+        super::handlers::logout,
+        super::handlers::change_password,
+        super::handlers::forgot_password,
+        super::handlers::reset_password,
+        super::handlers::request_magic_link,
+        super::handlers::verify_magic_link,
+        super::handlers::jwks,
+        super::handlers::create_api_key,
+        super::handlers::list_api_keys,
+        super::handlers::revoke_api_key,
+        super::handlers::list_auth_events,
     ),
     components(schemas(
         crate::domains::auth::dto::auth_dto::AuthUserDto,
+        crate::domains::auth::dto::auth_dto::RefreshTokenDto,
+        crate::domains::auth::dto::auth_dto::RegisterUserDto,
+        crate::domains::auth::dto::auth_dto::CreateInviteDto,
+        crate::domains::auth::dto::auth_dto::InviteDto,
+        crate::domains::auth::dto::auth_dto::ImpersonationTokenDto,
+        crate::domains::auth::dto::auth_dto::ChangePasswordDto,
+        crate::domains::auth::dto::auth_dto::ForgotPasswordDto,
+        crate::domains::auth::dto::auth_dto::ResetPasswordDto,
+        crate::domains::auth::dto::auth_dto::MagicLinkRequestDto,
+        crate::domains::auth::dto::auth_dto::MagicLinkVerifyDto,
+        crate::domains::auth::dto::auth_dto::UnlockAccountDto,
+        crate::domains::auth::dto::auth_dto::MfaChallengeDto,
+        crate::domains::auth::dto::auth_dto::MfaVerifyDto,
+        crate::domains::auth::dto::auth_dto::MfaEnrollmentDto,
+        crate::domains::auth::dto::auth_dto::MfaCodeDto,
+        crate::domains::auth::dto::auth_dto::MfaRecoveryCodesDto,
+        crate::domains::auth::dto::auth_dto::CreateApiKeyDto,
+        crate::domains::auth::dto::auth_dto::CreatedApiKeyDto,
+        crate::domains::auth::dto::auth_dto::ApiKeyDto,
+        crate::domains::auth::dto::auth_dto::AuthEventDto,
+        crate::domains::auth::domain::model::AuthEventKind,
         crate::common::jwt::AuthPayload,
         crate::common::jwt::AuthBody,
+        crate::common::session_cookie::CookieSessionBody,
     )),
     tags(
         (name = "UserAuth", description = "User authentication endpoints")
-    )
+    ),
+    modifiers(&UserAuthApiDoc)
 )]
 /// This struct is used to generate OpenAPI documentation for the user authentication routes.
 pub struct UserAuthApiDoc;
 
+impl utoipa::Modify for UserAuthApiDoc {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.as_mut().unwrap();
+        components.add_security_scheme(
+            "bearer_auth",
+            SecurityScheme::Http(
+                HttpBuilder::new()
+                    .scheme(HttpAuthScheme::Bearer)
+                    .bearer_format("JWT")
+                    .description(Some("Input your `<your‑jwt>`"))
+                    .build(),
+            ),
+        );
+        components.add_security_scheme(
+            "api_key",
+            SecurityScheme::ApiKey(ApiKey::Header(ApiKeyValue::with_description(
+                "Authorization",
+                "Input `ApiKey <your-api-key>`",
+            ))),
+        );
+    }
+}
+
 /// This function creates a router for the user authentication routes.
 /// It defines the routes and their corresponding handlers.
 pub fn user_auth_routes() -> Router<AppState> {
     Router::new()
         .route("/login", post(handlers::login_user))
-        .route("/register", post(handlers::create_user_auth))
+        .route("/register", post(handlers::register_user))
+        .route("/refresh", post(handlers::refresh_token))
+        .route("/logout", post(handlers::logout))
+        .route("/password/forgot", post(handlers::forgot_password))
+        .route("/password/reset", post(handlers::reset_password))
+        .route("/magic-link", post(handlers::request_magic_link))
+        .route("/magic-link/verify", post(handlers::verify_magic_link))
+        .route("/mfa/verify", post(handlers::verify_mfa))
+}
+
+/// This function creates a router for the authentication routes that require a JWT.
+/// It is nested under `/auth` inside the protected router.
+pub fn protected_auth_routes() -> Router<AppState> {
+    Router::new()
+        .route("/password/change", post(handlers::change_password))
+        .route("/mfa/enroll", post(handlers::enroll_mfa))
+        .route("/mfa/confirm", post(handlers::confirm_mfa))
+        .route("/mfa/disable", post(handlers::disable_mfa))
+        .route(
+            "/api-keys",
+            post(handlers::create_api_key).get(handlers::list_api_keys),
+        )
+        .route("/api-keys/{id}", delete(handlers::revoke_api_key))
+        .route(
+            "/credentials",
+            post(handlers::create_user_auth)
+                .route_layer(middleware::from_fn(require_permission(AUTH_MANAGE))),
+        )
+        .route(
+            "/invites",
+            post(handlers::create_invite)
+                .route_layer(middleware::from_fn(require_permission(AUTH_MANAGE))),
+        )
+        .route(
+            "/impersonate/{user_id}",
+            post(handlers::impersonate)
+                .route_layer(middleware::from_fn(require_permission(AUTH_MANAGE))),
+        )
+        .route(
+            "/unlock",
+            post(handlers::unlock_account)
+                .route_layer(middleware::from_fn(require_permission(AUTH_MANAGE))),
+        )
+        .route(
+            "/events",
+            get(handlers::list_auth_events)
+                .route_layer(middleware::from_fn(require_permission(AUTH_MANAGE))),
+        )
+}
+
+/// This function creates a router for the public well-known endpoints.
+/// They are served at the root path rather than under `/auth`.
+pub fn well_known_routes() -> Router<AppState> {
+    Router::new().route("/.well-known/jwks.json", get(handlers::jwks))
 }
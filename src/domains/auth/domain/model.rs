@@ -1,8 +1,16 @@
 //! This module defines the `UserAuth` model used for representing
-//! authentication data tied to a user.
+//! authentication data tied to a user, the `RefreshToken` model
+//! used for rotating refresh tokens, the `RegistrationInvite` model,
+//! the `PasswordResetToken` model, the TOTP MFA models, the `ApiKey` model,
+//! the `MagicLink` model of passwordless logins and the `AuthEvent` model of the audit trail.
 
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use sqlx::prelude::FromRow;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::common::client_info::ClientInfo;
 
 /// Represents a user's authentication information, including hashed password.
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
@@ -10,3 +18,175 @@ pub struct UserAuth {
     pub user_id: String,
     pub password_hash: String,
 }
+
+/// Represents a persisted refresh token.
+/// Only the SHA-256 hash of the token is stored. Every rotation of the same login
+/// shares a `family_id`, which doubles as the session ID carried in access tokens.
+/// Sessions issued to an OAuth client carry its ID and the granted scopes.
+#[derive(Debug, Clone, FromRow)]
+pub struct RefreshToken {
+    pub id: String,
+    pub user_id: String,
+    pub family_id: String,
+    pub token_hash: String,
+    pub expires_at: DateTime<Utc>,
+    pub used_at: Option<DateTime<Utc>>,
+    pub revoked_at: Option<DateTime<Utc>>,
+    pub client_id: Option<String>,
+    pub scopes: Option<Vec<String>>,
+}
+
+/// Represents a single-use registration invite.
+/// Only the SHA-256 hash of the invite token is stored. When `email` is set,
+/// the invite can only be redeemed by signing up with that email address.
+#[derive(Debug, Clone, FromRow)]
+pub struct RegistrationInvite {
+    pub id: String,
+    pub token_hash: String,
+    pub email: Option<String>,
+    pub created_by: Option<String>,
+    pub expires_at: DateTime<Utc>,
+    pub used_at: Option<DateTime<Utc>>,
+}
+
+/// Represents a single-use password reset token.
+/// Only the SHA-256 hash of the token is stored.
+#[derive(Debug, Clone, FromRow)]
+pub struct PasswordResetToken {
+    pub id: String,
+    pub user_id: String,
+    pub token_hash: String,
+    pub expires_at: DateTime<Utc>,
+    pub used_at: Option<DateTime<Utc>>,
+}
+
+/// Represents a passwordless login sent to a user's email address.
+/// It is redeemed either with the link token or with the one-time code;
+/// only the SHA-256 hashes of both are stored.
+#[derive(Debug, Clone, FromRow)]
+pub struct MagicLink {
+    pub id: String,
+    pub user_id: String,
+    pub token_hash: String,
+    pub code_hash: String,
+    pub expires_at: DateTime<Utc>,
+    pub failed_attempts: i32,
+    pub used_at: Option<DateTime<Utc>>,
+}
+
+/// Represents the TOTP enrollment of a user.
+/// The enrollment is pending until the first code is confirmed.
+#[derive(Debug, Clone, FromRow)]
+pub struct UserMfa {
+    pub user_id: String,
+    pub totp_secret: String,
+    pub enabled_at: Option<DateTime<Utc>>,
+    pub last_used_step: Option<i64>,
+}
+
+/// Represents the pending second step of a login of an MFA-enabled user.
+/// Only the SHA-256 hash of the challenge token is stored.
+#[derive(Debug, Clone, FromRow)]
+pub struct MfaChallenge {
+    pub id: String,
+    pub user_id: String,
+    pub token_hash: String,
+    pub expires_at: DateTime<Utc>,
+    pub failed_attempts: i32,
+    pub used_at: Option<DateTime<Utc>>,
+}
+
+/// Represents an API key that authenticates machine clients on behalf of a user.
+/// Only the SHA-256 hash of the key is stored. The key can use the permissions
+/// listed in `scopes` that its owner still holds.
+#[derive(Debug, Clone, FromRow)]
+pub struct ApiKey {
+    pub id: String,
+    pub user_id: String,
+    pub name: String,
+    pub key_prefix: String,
+    pub key_hash: String,
+    pub scopes: Vec<String>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub revoked_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Kinds of events recorded in the authentication audit trail.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum AuthEventKind {
+    /// Password login, including logins through the OAuth password grant.
+    Login,
+    /// Second step of an MFA login.
+    MfaVerify,
+    TokenRefresh,
+    Logout,
+    PasswordChange,
+    PasswordReset,
+    /// An account or IP was locked after too many failed logins.
+    Lockout,
+    /// An administrator lifted a login lock.
+    Unlock,
+    /// A protected request presented a token or API key that was rejected.
+    TokenRejected,
+    /// An administrator obtained a token to act as another user.
+    Impersonate,
+    /// Passwordless login with a magic link or one-time code.
+    MagicLink,
+}
+
+impl AuthEventKind {
+    /// The value stored in `auth_events.event_type`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuthEventKind::Login => "login",
+            AuthEventKind::MfaVerify => "mfa_verify",
+            AuthEventKind::TokenRefresh => "token_refresh",
+            AuthEventKind::Logout => "logout",
+            AuthEventKind::PasswordChange => "password_change",
+            AuthEventKind::PasswordReset => "password_reset",
+            AuthEventKind::Lockout => "lockout",
+            AuthEventKind::Unlock => "unlock",
+            AuthEventKind::TokenRejected => "token_rejected",
+            AuthEventKind::Impersonate => "impersonate",
+            AuthEventKind::MagicLink => "magic_link",
+        }
+    }
+}
+
+/// Represents an entry of the authentication audit trail.
+/// Events are never updated; the user ID is kept after the user is deleted.
+#[derive(Debug, Clone, FromRow)]
+pub struct AuthEvent {
+    pub id: String,
+    pub event_type: String,
+    pub success: bool,
+    pub user_id: Option<String>,
+    pub username: Option<String>,
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+    pub request_id: Option<String>,
+    pub detail: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl AuthEvent {
+    /// Starts a successful event of the given kind for the request described by `client`.
+    /// The user and the outcome are filled in as the operation proceeds.
+    pub fn new(kind: AuthEventKind, client: &ClientInfo) -> Self {
+        Self {
+            id: Uuid::new_v4().to_string(),
+            event_type: kind.as_str().to_string(),
+            success: true,
+            user_id: None,
+            username: None,
+            ip_address: client.ip.map(|ip| ip.to_string()),
+            user_agent: client.user_agent.clone(),
+            request_id: client.request_id.clone(),
+            detail: None,
+            created_at: Utc::now(),
+        }
+    }
+}
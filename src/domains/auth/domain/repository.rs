@@ -1,16 +1,22 @@
 //! This module defines the `UserAuthRepository` trait, which provides an abstraction
 //! over database operations related to user authentication records.
 
-use super::model::UserAuth;
+use super::model::{
+    ApiKey, AuthEvent, MagicLink, MfaChallenge, PasswordResetToken, RefreshToken,
+    RegistrationInvite, UserAuth, UserMfa,
+};
+use crate::domains::auth::dto::auth_dto::AuthEventQuery;
 
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
 use sqlx::{PgPool, Postgres, Transaction};
 
 #[async_trait]
 /// Trait representing the repository contract for user authentication data.
 /// Enables decoupling of business logic from direct database interaction.
 pub trait UserAuthRepository: Send + Sync {
-    /// Finds a user authentication record by the user's username.
+    /// Finds a user authentication record by the user's username or, case-insensitively, email.
+    /// A username match wins over an email match; soft-deleted users are not found.
     /// Returns `Ok(Some(UserAuth))` if found, or `Ok(None)` if not found.
     async fn find_by_user_name(
         &self,
@@ -18,10 +24,378 @@ pub trait UserAuthRepository: Send + Sync {
         user_name: String,
     ) -> Result<Option<UserAuth>, sqlx::Error>;
 
+    /// Finds a user authentication record by the user's ID.
+    async fn find_by_user_id(
+        &self,
+        pool: PgPool,
+        user_id: String,
+    ) -> Result<Option<UserAuth>, sqlx::Error>;
+
     /// Inserts a new user authentication record into the database using a transaction.
+    /// Returns `false` if the user does not exist, is deleted or belongs to another tenant.
     async fn create(
         &self,
         tx: &mut Transaction<'_, Postgres>,
         user_auth: UserAuth,
+    ) -> Result<bool, sqlx::Error>;
+
+    /// Replaces the password hash of a user.
+    /// Returns the number of updated records.
+    async fn update_password(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        user_id: String,
+        password_hash: String,
+    ) -> Result<u64, sqlx::Error>;
+
+    /// Assigns the role with the given name to a user.
+    async fn assign_role(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        user_id: String,
+        role_name: String,
+    ) -> Result<(), sqlx::Error>;
+
+    /// Returns the names of the roles assigned to a user.
+    async fn find_roles_by_user_id(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        user_id: String,
+    ) -> Result<Vec<String>, sqlx::Error>;
+
+    /// Returns the distinct permissions granted to a user through their roles.
+    async fn find_permissions_by_user_id(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        user_id: String,
+    ) -> Result<Vec<String>, sqlx::Error>;
+
+    /// Returns the organization of a user, if the user exists and is not soft-deleted.
+    async fn find_org_id_by_user_id(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        user_id: String,
+    ) -> Result<Option<String>, sqlx::Error>;
+
+    /// Inserts a new refresh token record using a transaction.
+    async fn create_refresh_token(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        refresh_token: RefreshToken,
+    ) -> Result<(), sqlx::Error>;
+
+    /// Finds a refresh token by its hash and locks the row for the rest of the transaction,
+    /// so that concurrent refreshes of the same token are serialized.
+    /// Tokens of soft-deleted users are not found.
+    async fn find_refresh_token_for_update(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        token_hash: String,
+    ) -> Result<Option<RefreshToken>, sqlx::Error>;
+
+    /// Marks a refresh token as used after it has been rotated.
+    async fn mark_refresh_token_used(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        id: String,
+    ) -> Result<(), sqlx::Error>;
+
+    /// Revokes every refresh token of a token family (i.e. the whole session).
+    /// Returns the number of tokens that were revoked.
+    async fn revoke_token_family(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        family_id: String,
+    ) -> Result<u64, sqlx::Error>;
+
+    /// Revokes every refresh token of a user, ending all of their sessions.
+    /// Returns the number of tokens that were revoked.
+    async fn revoke_user_tokens(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        user_id: String,
+    ) -> Result<u64, sqlx::Error>;
+
+    /// Returns `true` if the token family still has at least one non-revoked token
+    /// and its user is not soft-deleted.
+    async fn is_token_family_active(
+        &self,
+        pool: PgPool,
+        family_id: String,
+    ) -> Result<bool, sqlx::Error>;
+
+    /// Inserts a new registration invite.
+    async fn create_invite(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        invite: RegistrationInvite,
+    ) -> Result<(), sqlx::Error>;
+
+    /// Finds an invite by its token hash and locks the row,
+    /// so that the same invite cannot be redeemed twice concurrently.
+    async fn find_invite_for_update(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        token_hash: String,
+    ) -> Result<Option<RegistrationInvite>, sqlx::Error>;
+
+    /// Marks an invite as redeemed by the given user.
+    async fn mark_invite_used(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        id: String,
+        user_id: String,
+    ) -> Result<(), sqlx::Error>;
+
+    /// Inserts a new password reset token.
+    async fn create_password_reset(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        reset: PasswordResetToken,
+    ) -> Result<(), sqlx::Error>;
+
+    /// Finds a password reset token by its hash and locks the row,
+    /// so that the same token cannot be redeemed twice concurrently.
+    async fn find_password_reset_for_update(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        token_hash: String,
+    ) -> Result<Option<PasswordResetToken>, sqlx::Error>;
+
+    /// Marks every outstanding password reset token of a user as used.
+    async fn invalidate_password_resets(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        user_id: String,
+    ) -> Result<(), sqlx::Error>;
+
+    /// Finds the ID of a user by username or email, whether or not the user has a password.
+    /// Soft-deleted users are not found.
+    async fn find_user_id_by_username(
+        &self,
+        pool: PgPool,
+        username: String,
+    ) -> Result<Option<String>, sqlx::Error>;
+
+    /// Inserts a new magic link.
+    async fn create_magic_link(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        magic_link: MagicLink,
+    ) -> Result<(), sqlx::Error>;
+
+    /// Finds a magic link by the hash of its token and locks the row.
+    async fn find_magic_link_for_update(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        token_hash: String,
+    ) -> Result<Option<MagicLink>, sqlx::Error>;
+
+    /// Finds the newest outstanding magic link of a user and locks the row.
+    async fn find_latest_magic_link_for_update(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        user_id: String,
+    ) -> Result<Option<MagicLink>, sqlx::Error>;
+
+    /// Marks every outstanding magic link of a user as used.
+    async fn invalidate_magic_links(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        user_id: String,
+    ) -> Result<(), sqlx::Error>;
+
+    /// Counts a wrong code entered for a magic link.
+    async fn record_magic_link_failure(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        id: String,
     ) -> Result<(), sqlx::Error>;
+
+    /// Marks a magic link as redeemed.
+    async fn mark_magic_link_used(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        id: String,
+    ) -> Result<(), sqlx::Error>;
+
+    /// Returns the latest lock among the given keys that has not expired yet.
+    async fn find_login_locked_until(
+        &self,
+        pool: PgPool,
+        throttle_keys: Vec<String>,
+    ) -> Result<Option<DateTime<Utc>>, sqlx::Error>;
+
+    /// Counts a failed login for the key and returns the number of recent failures.
+    /// Failures older than `window_secs` are forgotten.
+    async fn record_login_failure(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        throttle_key: String,
+        window_secs: i64,
+    ) -> Result<i32, sqlx::Error>;
+
+    /// Rejects logins for the key until the given time.
+    async fn lock_login(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        throttle_key: String,
+        locked_until: DateTime<Utc>,
+    ) -> Result<(), sqlx::Error>;
+
+    /// Removes the failed-login counter and any lock of the key.
+    /// Returns the number of removed records.
+    async fn clear_login_throttle(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        throttle_key: String,
+    ) -> Result<u64, sqlx::Error>;
+
+    /// Finds the TOTP enrollment of a user, pending or enabled.
+    async fn find_mfa(&self, pool: PgPool, user_id: String)
+        -> Result<Option<UserMfa>, sqlx::Error>;
+
+    /// Finds the TOTP enrollment of a user and locks the row,
+    /// so that concurrent verifications cannot accept the same code twice.
+    async fn find_mfa_for_update(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        user_id: String,
+    ) -> Result<Option<UserMfa>, sqlx::Error>;
+
+    /// Starts or restarts a pending TOTP enrollment with a new secret.
+    /// Returns the number of affected records, which is 0 if MFA is already enabled.
+    async fn upsert_pending_mfa(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        user_id: String,
+        totp_secret: String,
+    ) -> Result<u64, sqlx::Error>;
+
+    /// Enables a pending TOTP enrollment.
+    async fn enable_mfa(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        user_id: String,
+        last_used_step: i64,
+    ) -> Result<(), sqlx::Error>;
+
+    /// Records the time step of the last accepted code.
+    async fn update_mfa_last_used_step(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        user_id: String,
+        last_used_step: i64,
+    ) -> Result<(), sqlx::Error>;
+
+    /// Removes the TOTP enrollment of a user together with its recovery codes.
+    async fn delete_mfa(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        user_id: String,
+    ) -> Result<(), sqlx::Error>;
+
+    /// Replaces the recovery codes of a user with the given hashes.
+    async fn replace_recovery_codes(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        user_id: String,
+        code_hashes: Vec<String>,
+    ) -> Result<(), sqlx::Error>;
+
+    /// Marks an unused recovery code as used.
+    /// Returns `true` if the code existed and was unused.
+    async fn use_recovery_code(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        user_id: String,
+        code_hash: String,
+    ) -> Result<bool, sqlx::Error>;
+
+    /// Inserts a new MFA challenge.
+    async fn create_mfa_challenge(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        challenge: MfaChallenge,
+    ) -> Result<(), sqlx::Error>;
+
+    /// Finds an MFA challenge by its token hash and locks the row.
+    async fn find_mfa_challenge_for_update(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        token_hash: String,
+    ) -> Result<Option<MfaChallenge>, sqlx::Error>;
+
+    /// Counts a wrong code against the challenge.
+    async fn record_mfa_challenge_failure(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        id: String,
+    ) -> Result<(), sqlx::Error>;
+
+    /// Marks an MFA challenge as completed.
+    async fn mark_mfa_challenge_used(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        id: String,
+    ) -> Result<(), sqlx::Error>;
+
+    /// Inserts a new API key.
+    async fn create_api_key(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        api_key: ApiKey,
+    ) -> Result<(), sqlx::Error>;
+
+    /// Returns the API keys of a user, newest first.
+    async fn find_api_keys_by_user_id(
+        &self,
+        pool: PgPool,
+        user_id: String,
+    ) -> Result<Vec<ApiKey>, sqlx::Error>;
+
+    /// Finds an API key by its ID among the keys of the current tenant's users.
+    async fn find_api_key_by_id(
+        &self,
+        pool: PgPool,
+        id: String,
+    ) -> Result<Option<ApiKey>, sqlx::Error>;
+
+    /// Finds an API key by its hash. Keys of soft-deleted users are not found.
+    async fn find_api_key_by_hash(
+        &self,
+        pool: PgPool,
+        key_hash: String,
+    ) -> Result<Option<ApiKey>, sqlx::Error>;
+
+    /// Records a use of an API key. The timestamp is refreshed at most once a minute
+    /// to avoid a write on every request.
+    async fn touch_api_key(&self, pool: PgPool, id: String) -> Result<(), sqlx::Error>;
+
+    /// Revokes an API key. Returns the number of revoked keys.
+    async fn revoke_api_key(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        id: String,
+    ) -> Result<u64, sqlx::Error>;
+
+    /// Appends an event to the audit trail, in the organization of its user, or in the
+    /// current tenant or the default organization if the user is unknown.
+    /// Uses the pool rather than a transaction, so that failed operations are recorded too.
+    async fn create_auth_event(&self, pool: PgPool, event: AuthEvent) -> Result<(), sqlx::Error>;
+
+    /// Whether the user exists, soft-deleted or not, in another organization
+    /// than the current tenant. Always `false` outside of a tenant.
+    async fn is_user_of_other_tenant(
+        &self,
+        pool: PgPool,
+        user_id: String,
+    ) -> Result<bool, sqlx::Error>;
+
+    /// Finds the events of the current tenant matching the query, newest first.
+    async fn find_auth_events(
+        &self,
+        pool: PgPool,
+        query: AuthEventQuery,
+    ) -> Result<Vec<AuthEvent>, sqlx::Error>;
 }
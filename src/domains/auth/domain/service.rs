@@ -1,5 +1,5 @@
 //! This module defines the authentication service trait used to abstract
-//! user login and registration logic.
+//! user login, registration and session logic.
 
 use std::sync::Arc;
 
@@ -7,10 +7,22 @@ use sqlx::PgPool;
 
 use crate::{
     common::{
+        client_info::ClientInfo,
+        config::Config,
         error::AppError,
-        jwt::{AuthBody, AuthPayload},
+        jwt::{AuthBody, AuthPayload, Claims},
+        notifier::Notifier,
+    },
+    domains::{
+        auth::dto::auth_dto::{
+            ApiKeyDto, AuthEventDto, AuthEventQuery, AuthUserDto, ChangePasswordDto, ClientScope,
+            CreateApiKeyDto, CreateInviteDto, CreatedApiKeyDto, ForgotPasswordDto,
+            ImpersonationTokenDto, InviteDto, LoginResult, MagicLinkRequestDto, MagicLinkVerifyDto,
+            MfaCodeDto, MfaEnrollmentDto, MfaRecoveryCodesDto, MfaVerifyDto, RefreshTokenDto,
+            RegisterUserDto, ResetPasswordDto, UnlockAccountDto,
+        },
+        user::{dto::user_dto::UserDto, UserServiceTrait},
     },
-    domains::auth::dto::auth_dto::AuthUserDto,
 };
 
 #[async_trait::async_trait]
@@ -18,13 +30,187 @@ use crate::{
 /// Implementors are responsible for handling user creation and login logic.
 pub trait AuthServiceTrait: Send + Sync {
     /// constructor for the service.
-    fn create_service(pool: PgPool) -> Arc<dyn AuthServiceTrait>
+    fn create_service(
+        config: Config,
+        pool: PgPool,
+        user_service: Arc<dyn UserServiceTrait>,
+        notifier: Arc<dyn Notifier>,
+    ) -> Arc<dyn AuthServiceTrait>
     where
         Self: Sized;
 
-    /// Registers a new user authentication entry.
-    async fn create_user_auth(&self, auth_user: AuthUserDto) -> Result<(), AppError>;
+    /// Attaches credentials to an existing user. Administrators only;
+    /// rejected when registration is disabled.
+    async fn create_user_auth(
+        &self,
+        claims: &Claims,
+        auth_user: AuthUserDto,
+    ) -> Result<(), AppError>;
+
+    /// Self-signup: creates the user, its credentials and its default role in one transaction.
+    /// Allowed in the `open` mode, and in the `invite-only` mode with a valid invite.
+    async fn register_user(&self, payload: RegisterUserDto) -> Result<UserDto, AppError>;
+
+    /// Issues a single-use registration invite.
+    async fn create_invite(
+        &self,
+        claims: &Claims,
+        payload: CreateInviteDto,
+    ) -> Result<InviteDto, AppError>;
+
+    /// Authenticates a user and returns a JWT token payload on success,
+    /// or an MFA challenge if the user has MFA enabled.
+    /// Failed attempts are throttled per account and per client IP.
+    async fn login_user(
+        &self,
+        auth_payload: AuthPayload,
+        client: &ClientInfo,
+    ) -> Result<LoginResult, AppError>;
+
+    /// Authenticates a user for an OAuth client and starts a session limited to its scopes.
+    /// Fails with `MfaRequired` for users with MFA enabled.
+    async fn login_client(
+        &self,
+        auth_payload: AuthPayload,
+        client: &ClientInfo,
+        scope: ClientScope,
+    ) -> Result<AuthBody, AppError>;
+
+    /// Starts a session bound to an OAuth client for an already authenticated user.
+    async fn create_client_session(
+        &self,
+        user_id: &str,
+        session_id: &str,
+        scope: ClientScope,
+    ) -> Result<AuthBody, AppError>;
+
+    /// Rotates a refresh token issued to the given OAuth client.
+    async fn refresh_client_session(
+        &self,
+        payload: RefreshTokenDto,
+        client_id: &str,
+        client: &ClientInfo,
+    ) -> Result<AuthBody, AppError>;
+
+    /// Signs an access token that is not tied to a session, for the client credentials grant.
+    async fn issue_client_access_token(
+        &self,
+        user_id: &str,
+        scope: ClientScope,
+    ) -> Result<String, AppError>;
+
+    /// Revokes a session and with it every token issued in it.
+    async fn revoke_session(&self, session_id: &str) -> Result<(), AppError>;
+
+    /// Completes an MFA login: exchanges the challenge token and a code for a token pair.
+    async fn verify_mfa(
+        &self,
+        payload: MfaVerifyDto,
+        client: &ClientInfo,
+    ) -> Result<AuthBody, AppError>;
+
+    /// Starts (or restarts) the TOTP enrollment of the caller.
+    async fn enroll_mfa(&self, claims: &Claims) -> Result<MfaEnrollmentDto, AppError>;
+
+    /// Enables MFA after verifying a first code and returns fresh recovery codes.
+    async fn confirm_mfa(
+        &self,
+        claims: &Claims,
+        payload: MfaCodeDto,
+    ) -> Result<MfaRecoveryCodesDto, AppError>;
+
+    /// Disables MFA after verifying a TOTP or recovery code.
+    async fn disable_mfa(&self, claims: &Claims, payload: MfaCodeDto) -> Result<(), AppError>;
+
+    /// Clears the failed-login counters and locks of an account and/or a client IP.
+    /// Administrators only.
+    async fn unlock_account(
+        &self,
+        claims: &Claims,
+        payload: UnlockAccountDto,
+        client: &ClientInfo,
+    ) -> Result<(), AppError>;
+
+    /// Rotates a refresh token and returns a new token pair.
+    /// Presenting an already rotated token revokes the whole token family.
+    async fn refresh_token(
+        &self,
+        payload: RefreshTokenDto,
+        client: &ClientInfo,
+    ) -> Result<AuthBody, AppError>;
+
+    /// Revokes the session the given refresh token belongs to.
+    async fn logout(&self, payload: RefreshTokenDto, client: &ClientInfo) -> Result<(), AppError>;
+
+    /// Changes the caller's password after verifying the current one.
+    /// Every existing session is revoked and a fresh token pair is returned.
+    async fn change_password(
+        &self,
+        claims: &Claims,
+        payload: ChangePasswordDto,
+        client: &ClientInfo,
+    ) -> Result<AuthBody, AppError>;
+
+    /// Sends a single-use password reset token to the user's email address.
+    /// Succeeds for unknown users too, so that it does not reveal which accounts exist.
+    async fn forgot_password(&self, payload: ForgotPasswordDto) -> Result<(), AppError>;
+
+    /// Sends a single-use magic link and one-time code to the user's email address.
+    /// Succeeds for unknown users as well, so that accounts cannot be enumerated.
+    async fn request_magic_link(&self, payload: MagicLinkRequestDto) -> Result<(), AppError>;
+
+    /// Redeems a magic link or its one-time code and starts a session.
+    /// Users with MFA enabled get an MFA challenge instead, as with a password login.
+    async fn verify_magic_link(
+        &self,
+        payload: MagicLinkVerifyDto,
+        client: &ClientInfo,
+    ) -> Result<LoginResult, AppError>;
+
+    /// Sets a new password using a reset token and revokes every existing session.
+    async fn reset_password(
+        &self,
+        payload: ResetPasswordDto,
+        client: &ClientInfo,
+    ) -> Result<(), AppError>;
+
+    /// Returns `true` if the session (refresh token family) has not been revoked.
+    async fn is_session_active(&self, session_id: &str) -> Result<bool, AppError>;
+
+    /// Creates an API key for the caller, limited to scopes the caller holds.
+    /// The key is only returned once.
+    async fn create_api_key(
+        &self,
+        claims: &Claims,
+        payload: CreateApiKeyDto,
+    ) -> Result<CreatedApiKeyDto, AppError>;
+
+    /// Lists the API keys of the caller.
+    async fn list_api_keys(&self, claims: &Claims) -> Result<Vec<ApiKeyDto>, AppError>;
+
+    /// Revokes an API key of the caller. Admins may revoke any key of their organization.
+    async fn revoke_api_key(&self, claims: &Claims, id: String) -> Result<(), AppError>;
+
+    /// Resolves an API key to the claims of its owner, limited to the key's scopes.
+    /// A key that does not carry all of its owner's permissions never acts as an admin.
+    async fn authenticate_api_key(&self, api_key: &str) -> Result<Claims, AppError>;
+
+    /// Issues a short-lived token that acts as `user_id` on behalf of the calling admin.
+    async fn impersonate(
+        &self,
+        claims: &Claims,
+        user_id: String,
+        client: &ClientInfo,
+    ) -> Result<ImpersonationTokenDto, AppError>;
+
+    /// Records that a protected request presented a token or API key that was rejected.
+    async fn record_rejected_token(&self, client: &ClientInfo, err: &AppError);
 
-    /// Authenticates a user and returns a JWT token payload on success.
-    async fn login_user(&self, auth_payload: AuthPayload) -> Result<AuthBody, AppError>;
+    /// Lists the audit trail of authentication events of the caller's organization.
+    /// Administrators only; filtering by a user of another organization is `NotFound`.
+    async fn list_auth_events(
+        &self,
+        claims: &Claims,
+        query: AuthEventQuery,
+    ) -> Result<Vec<AuthEventDto>, AppError>;
 }
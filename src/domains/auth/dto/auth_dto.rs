@@ -1,9 +1,226 @@
-use serde::Deserialize;
-use utoipa::ToSchema;
+use chrono::{DateTime, Utc};
+
+use crate::{
+    common::jwt::AuthBody,
+    domains::auth::domain::model::{ApiKey, AuthEvent, AuthEventKind},
+};
+use serde::{Deserialize, Serialize};
+use simple_dto_mapper_derive::DtoFrom;
+use utoipa::{IntoParams, ToSchema};
 use validator::Validate;
 
-#[derive(Debug, Deserialize, ToSchema, Validate)]
+#[derive(Debug, Serialize, Deserialize, ToSchema, Validate)]
 pub struct AuthUserDto {
     pub user_id: String,
     pub password: String,
 }
+
+/// The OAuth client a session is issued to, and the permissions it was granted.
+#[derive(Debug, Clone)]
+pub struct ClientScope {
+    pub client_id: String,
+    pub scopes: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct RefreshTokenDto {
+    pub refresh_token: String,
+}
+
+/// Self-signup payload used in the `open` and `invite-only` registration modes.
+#[derive(Debug, Serialize, Deserialize, ToSchema, Validate)]
+pub struct RegisterUserDto {
+    #[validate(length(min = 1, max = 64, message = "Username must be 1 to 64 characters"))]
+    pub username: String,
+    #[validate(email(message = "Invalid email format"))]
+    pub email: String,
+    /// Checked against the configured password policy.
+    pub password: String,
+    /// Required in the `invite-only` registration mode.
+    pub invite_token: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, Validate)]
+pub struct CreateInviteDto {
+    /// Binds the invite to this email address when set.
+    #[validate(email(message = "Invalid email format"))]
+    pub email: Option<String>,
+}
+
+/// A newly created invite. The token is only returned once.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct InviteDto {
+    pub invite_token: String,
+    pub email: Option<String>,
+    #[serde(with = "crate::common::ts_format")]
+    pub expires_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, Validate)]
+pub struct ChangePasswordDto {
+    pub current_password: String,
+    pub new_password: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ForgotPasswordDto {
+    pub username: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct MagicLinkRequestDto {
+    pub username: String,
+}
+
+/// Redeems a magic link with either the token of the link,
+/// or the username together with the one-time code.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct MagicLinkVerifyDto {
+    pub token: Option<String>,
+    pub username: Option<String>,
+    /// The 6-digit code sent with the link.
+    pub code: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, Validate)]
+pub struct ResetPasswordDto {
+    pub reset_token: String,
+    pub new_password: String,
+}
+
+/// Clears the failed-login counters of an account and/or a client IP.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct UnlockAccountDto {
+    pub username: Option<String>,
+    pub ip: Option<String>,
+}
+
+/// Outcome of a successful password check.
+/// Users with MFA enabled receive a challenge instead of tokens.
+#[derive(Debug)]
+pub enum LoginResult {
+    Authenticated(AuthBody),
+    MfaRequired(MfaChallengeDto),
+}
+
+/// Returned by `/auth/login` for users with MFA enabled.
+/// The token is exchanged at `/auth/mfa/verify` together with a code.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct MfaChallengeDto {
+    pub mfa_token: String,
+    pub expires_in: i64,
+}
+
+/// Returned by `/auth/impersonate/{user_id}`.
+/// The token acts as `user_id` and cannot be refreshed.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ImpersonationTokenDto {
+    pub access_token: String,
+    pub token_type: String,
+    pub expires_in: i64,
+    pub user_id: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct MfaVerifyDto {
+    pub mfa_token: String,
+    /// A 6-digit TOTP code or an unused recovery code.
+    pub code: String,
+}
+
+/// A pending TOTP enrollment. Import the URI into an authenticator app,
+/// then confirm it with a first code.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct MfaEnrollmentDto {
+    pub secret: String,
+    pub otpauth_uri: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct MfaCodeDto {
+    /// A 6-digit TOTP code, or a recovery code where accepted.
+    pub code: String,
+}
+
+/// One-time recovery codes. They are only returned once.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct MfaRecoveryCodesDto {
+    pub recovery_codes: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, Validate)]
+pub struct CreateApiKeyDto {
+    #[validate(length(min = 1, max = 64, message = "Name must be 1 to 64 characters"))]
+    pub name: String,
+    /// Permissions the key may use, e.g. `device:read`. Each must be held by the caller.
+    #[validate(length(min = 1, message = "At least one scope is required"))]
+    pub scopes: Vec<String>,
+    /// Lifetime of the key in seconds. Keys without a lifetime do not expire.
+    #[validate(range(min = 1, message = "Lifetime must be positive"))]
+    pub expires_in: Option<i64>,
+}
+
+/// A newly created API key. The key itself is only returned once;
+/// send it as `Authorization: ApiKey <api_key>`.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct CreatedApiKeyDto {
+    pub id: String,
+    pub name: String,
+    pub api_key: String,
+    pub scopes: Vec<String>,
+    #[serde(with = "crate::common::ts_format::option")]
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+/// An API key without its secret. `key_prefix` helps to tell keys apart.
+#[derive(Debug, Serialize, Deserialize, ToSchema, DtoFrom)]
+#[dto(from = ApiKey)]
+pub struct ApiKeyDto {
+    pub id: String,
+    pub name: String,
+    pub key_prefix: String,
+    pub scopes: Vec<String>,
+    #[serde(with = "crate::common::ts_format::option")]
+    pub expires_at: Option<DateTime<Utc>>,
+    #[serde(with = "crate::common::ts_format::option")]
+    pub last_used_at: Option<DateTime<Utc>>,
+    #[serde(with = "crate::common::ts_format::option")]
+    pub revoked_at: Option<DateTime<Utc>>,
+    #[serde(with = "crate::common::ts_format")]
+    pub created_at: DateTime<Utc>,
+}
+
+/// Filters of the audit trail. Events are returned newest first.
+#[derive(Debug, Default, Serialize, Deserialize, ToSchema, IntoParams, Validate)]
+pub struct AuthEventQuery {
+    pub user_id: Option<String>,
+    /// Username given at login; also matches attempts on unknown accounts.
+    pub username: Option<String>,
+    pub event_type: Option<AuthEventKind>,
+    /// Only events at or after this time (RFC 3339).
+    #[serde(default, with = "crate::common::ts_format::option")]
+    pub from: Option<DateTime<Utc>>,
+    /// Only events before this time (RFC 3339).
+    #[serde(default, with = "crate::common::ts_format::option")]
+    pub to: Option<DateTime<Utc>>,
+    /// Maximum number of events, 100 by default.
+    #[validate(range(min = 1, max = 1000, message = "Limit must be between 1 and 1000"))]
+    pub limit: Option<i64>,
+}
+
+/// An entry of the authentication audit trail.
+#[derive(Debug, Serialize, Deserialize, ToSchema, DtoFrom)]
+#[dto(from = AuthEvent)]
+pub struct AuthEventDto {
+    pub id: String,
+    pub event_type: String,
+    pub success: bool,
+    pub user_id: Option<String>,
+    pub username: Option<String>,
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+    pub request_id: Option<String>,
+    pub detail: Option<String>,
+    #[serde(with = "crate::common::ts_format")]
+    pub created_at: DateTime<Utc>,
+}
@@ -1,8 +1,14 @@
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
 use sqlx::{PgPool, Postgres, Transaction};
 
-use crate::domains::auth::domain::model::UserAuth;
+use crate::common::tenant;
+use crate::domains::auth::domain::model::{
+    ApiKey, AuthEvent, MagicLink, MfaChallenge, PasswordResetToken, RefreshToken,
+    RegistrationInvite, UserAuth, UserMfa,
+};
 use crate::domains::auth::domain::repository::UserAuthRepository;
+use crate::domains::auth::dto::auth_dto::AuthEventQuery;
 pub struct UserAuthRepo;
 
 #[async_trait]
@@ -18,7 +24,10 @@ impl UserAuthRepository for UserAuthRepo {
             SELECT ua.user_id, ua.password_hash
               FROM user_auth ua
               JOIN users u ON ua.user_id = u.id
-              WHERE u.username = $1
+              WHERE (LOWER(u.username) = LOWER($1) OR u.email = LOWER(TRIM($1)))
+                AND u.deleted_at IS NULL
+              ORDER BY u.username = $1 DESC
+              LIMIT 1
             "#,
             user_name
         )
@@ -28,24 +37,1118 @@ impl UserAuthRepository for UserAuthRepo {
         Ok(result)
     }
 
+    async fn find_by_user_id(
+        &self,
+        pool: PgPool,
+        user_id: String,
+    ) -> Result<Option<UserAuth>, sqlx::Error> {
+        let result = sqlx::query_as!(
+            UserAuth,
+            r#"
+            SELECT user_id, password_hash
+              FROM user_auth
+             WHERE user_id = $1
+            "#,
+            user_id
+        )
+        .fetch_optional(&pool)
+        .await?;
+
+        Ok(result)
+    }
+
     async fn create(
         &self,
         tx: &mut Transaction<'_, Postgres>,
         user_auth: UserAuth,
-    ) -> Result<(), sqlx::Error> {
-        sqlx::query!(
+    ) -> Result<bool, sqlx::Error> {
+        let res = sqlx::query!(
             r#"
             INSERT INTO user_auth 
             (user_id, password_hash)
-            VALUES 
-            ($1, $2)
+            SELECT $1::varchar, $2::varchar
+             WHERE EXISTS (SELECT 1 FROM users
+                            WHERE id = $1
+                              AND deleted_at IS NULL
+                              AND ($3::varchar IS NULL OR org_id = $3))
             "#,
             user_auth.user_id,
-            user_auth.password_hash
+            user_auth.password_hash,
+            tenant::current()
+        )
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(res.rows_affected() > 0)
+    }
+
+    async fn update_password(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        user_id: String,
+        password_hash: String,
+    ) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE user_auth
+               SET password_hash = $2, modified_at = NOW()
+             WHERE user_id = $1
+            "#,
+            user_id,
+            password_hash
+        )
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    async fn assign_role(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        user_id: String,
+        role_name: String,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            r#"
+            INSERT INTO user_roles (user_id, role_id)
+            SELECT $1, id FROM roles WHERE name = $2
+            "#,
+            user_id,
+            role_name
+        )
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn find_roles_by_user_id(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        user_id: String,
+    ) -> Result<Vec<String>, sqlx::Error> {
+        let roles = sqlx::query_scalar!(
+            r#"
+            SELECT r.name
+              FROM user_roles ur
+              JOIN roles r ON r.id = ur.role_id
+             WHERE ur.user_id = $1
+             ORDER BY r.name
+            "#,
+            user_id
+        )
+        .fetch_all(&mut **tx)
+        .await?;
+
+        Ok(roles)
+    }
+
+    async fn find_org_id_by_user_id(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        user_id: String,
+    ) -> Result<Option<String>, sqlx::Error> {
+        let org_id = sqlx::query_scalar!(
+            r#"SELECT org_id FROM users WHERE id = $1 AND deleted_at IS NULL"#,
+            user_id
+        )
+        .fetch_optional(&mut **tx)
+        .await?;
+
+        Ok(org_id)
+    }
+
+    async fn find_permissions_by_user_id(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        user_id: String,
+    ) -> Result<Vec<String>, sqlx::Error> {
+        let permissions = sqlx::query_scalar!(
+            r#"
+            SELECT DISTINCT rp.permission
+              FROM user_roles ur
+              JOIN role_permissions rp ON rp.role_id = ur.role_id
+             WHERE ur.user_id = $1
+             ORDER BY rp.permission
+            "#,
+            user_id
+        )
+        .fetch_all(&mut **tx)
+        .await?;
+
+        Ok(permissions)
+    }
+
+    async fn create_refresh_token(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        refresh_token: RefreshToken,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            r#"
+            INSERT INTO refresh_tokens
+            (id, user_id, family_id, token_hash, expires_at, client_id, scopes)
+            VALUES
+            ($1, $2, $3, $4, $5, $6, $7)
+            "#,
+            refresh_token.id,
+            refresh_token.user_id,
+            refresh_token.family_id,
+            refresh_token.token_hash,
+            refresh_token.expires_at,
+            refresh_token.client_id,
+            refresh_token.scopes.as_deref()
+        )
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn find_refresh_token_for_update(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        token_hash: String,
+    ) -> Result<Option<RefreshToken>, sqlx::Error> {
+        let result = sqlx::query_as!(
+            RefreshToken,
+            r#"
+            SELECT id, user_id, family_id, token_hash, expires_at, used_at, revoked_at,
+                   client_id, scopes
+              FROM refresh_tokens rt
+              WHERE token_hash = $1
+                AND EXISTS (SELECT 1 FROM users u WHERE u.id = rt.user_id AND u.deleted_at IS NULL)
+              FOR UPDATE
+            "#,
+            token_hash
+        )
+        .fetch_optional(&mut **tx)
+        .await?;
+
+        Ok(result)
+    }
+
+    async fn mark_refresh_token_used(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        id: String,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            r#"UPDATE refresh_tokens SET used_at = NOW() WHERE id = $1"#,
+            id
+        )
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn revoke_token_family(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        family_id: String,
+    ) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE refresh_tokens
+               SET revoked_at = NOW()
+             WHERE family_id = $1
+               AND revoked_at IS NULL
+            "#,
+            family_id
+        )
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    async fn revoke_user_tokens(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        user_id: String,
+    ) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE refresh_tokens
+               SET revoked_at = NOW()
+             WHERE user_id = $1
+               AND revoked_at IS NULL
+            "#,
+            user_id
+        )
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    async fn is_token_family_active(
+        &self,
+        pool: PgPool,
+        family_id: String,
+    ) -> Result<bool, sqlx::Error> {
+        let active = sqlx::query_scalar!(
+            r#"
+            SELECT EXISTS (
+                SELECT 1
+                  FROM refresh_tokens rt
+                  JOIN users u ON u.id = rt.user_id
+                 WHERE rt.family_id = $1
+                   AND rt.revoked_at IS NULL
+                   AND u.deleted_at IS NULL
+            ) AS "active!"
+            "#,
+            family_id
+        )
+        .fetch_one(&pool)
+        .await?;
+
+        Ok(active)
+    }
+
+    async fn create_invite(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        invite: RegistrationInvite,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            r#"
+            INSERT INTO registration_invites
+            (id, token_hash, email, created_by, expires_at)
+            VALUES
+            ($1, $2, $3, $4, $5)
+            "#,
+            invite.id,
+            invite.token_hash,
+            invite.email,
+            invite.created_by,
+            invite.expires_at
+        )
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn find_invite_for_update(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        token_hash: String,
+    ) -> Result<Option<RegistrationInvite>, sqlx::Error> {
+        let invite = sqlx::query_as!(
+            RegistrationInvite,
+            r#"
+            SELECT id, token_hash, email, created_by, expires_at, used_at
+              FROM registration_invites
+              WHERE token_hash = $1
+              FOR UPDATE
+            "#,
+            token_hash
+        )
+        .fetch_optional(&mut **tx)
+        .await?;
+
+        Ok(invite)
+    }
+
+    async fn mark_invite_used(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        id: String,
+        user_id: String,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            r#"
+            UPDATE registration_invites
+               SET used_at = NOW(), used_by = $2
+             WHERE id = $1
+            "#,
+            id,
+            user_id
+        )
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn create_password_reset(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        reset: PasswordResetToken,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            r#"
+            INSERT INTO password_reset_tokens
+            (id, user_id, token_hash, expires_at)
+            VALUES
+            ($1, $2, $3, $4)
+            "#,
+            reset.id,
+            reset.user_id,
+            reset.token_hash,
+            reset.expires_at
+        )
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn find_password_reset_for_update(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        token_hash: String,
+    ) -> Result<Option<PasswordResetToken>, sqlx::Error> {
+        let reset = sqlx::query_as!(
+            PasswordResetToken,
+            r#"
+            SELECT id, user_id, token_hash, expires_at, used_at
+              FROM password_reset_tokens
+             WHERE token_hash = $1
+               FOR UPDATE
+            "#,
+            token_hash
+        )
+        .fetch_optional(&mut **tx)
+        .await?;
+
+        Ok(reset)
+    }
+
+    async fn invalidate_password_resets(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        user_id: String,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            r#"
+            UPDATE password_reset_tokens
+               SET used_at = NOW()
+             WHERE user_id = $1
+               AND used_at IS NULL
+            "#,
+            user_id
+        )
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn find_user_id_by_username(
+        &self,
+        pool: PgPool,
+        username: String,
+    ) -> Result<Option<String>, sqlx::Error> {
+        let user_id = sqlx::query_scalar!(
+            r#"
+            SELECT id
+              FROM users
+             WHERE (LOWER(username) = LOWER($1) OR email = LOWER(TRIM($1)))
+               AND deleted_at IS NULL
+             ORDER BY username = $1 DESC
+             LIMIT 1
+            "#,
+            username
+        )
+        .fetch_optional(&pool)
+        .await?;
+
+        Ok(user_id)
+    }
+
+    async fn create_magic_link(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        magic_link: MagicLink,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            r#"
+            INSERT INTO magic_links
+            (id, user_id, token_hash, code_hash, expires_at)
+            VALUES
+            ($1, $2, $3, $4, $5)
+            "#,
+            magic_link.id,
+            magic_link.user_id,
+            magic_link.token_hash,
+            magic_link.code_hash,
+            magic_link.expires_at
+        )
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn find_magic_link_for_update(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        token_hash: String,
+    ) -> Result<Option<MagicLink>, sqlx::Error> {
+        let magic_link = sqlx::query_as!(
+            MagicLink,
+            r#"
+            SELECT id, user_id, token_hash, code_hash, expires_at, failed_attempts, used_at
+              FROM magic_links
+             WHERE token_hash = $1
+               FOR UPDATE
+            "#,
+            token_hash
+        )
+        .fetch_optional(&mut **tx)
+        .await?;
+
+        Ok(magic_link)
+    }
+
+    async fn find_latest_magic_link_for_update(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        user_id: String,
+    ) -> Result<Option<MagicLink>, sqlx::Error> {
+        let magic_link = sqlx::query_as!(
+            MagicLink,
+            r#"
+            SELECT id, user_id, token_hash, code_hash, expires_at, failed_attempts, used_at
+              FROM magic_links
+             WHERE user_id = $1
+               AND used_at IS NULL
+             ORDER BY created_at DESC
+             LIMIT 1
+               FOR UPDATE
+            "#,
+            user_id
+        )
+        .fetch_optional(&mut **tx)
+        .await?;
+
+        Ok(magic_link)
+    }
+
+    async fn invalidate_magic_links(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        user_id: String,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            r#"
+            UPDATE magic_links
+               SET used_at = NOW()
+             WHERE user_id = $1
+               AND used_at IS NULL
+            "#,
+            user_id
         )
         .execute(&mut **tx)
         .await?;
 
         Ok(())
     }
+
+    async fn record_magic_link_failure(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        id: String,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            r#"
+            UPDATE magic_links
+               SET failed_attempts = failed_attempts + 1
+             WHERE id = $1
+            "#,
+            id
+        )
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn mark_magic_link_used(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        id: String,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            r#"
+            UPDATE magic_links
+               SET used_at = NOW()
+             WHERE id = $1
+            "#,
+            id
+        )
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn find_login_locked_until(
+        &self,
+        pool: PgPool,
+        throttle_keys: Vec<String>,
+    ) -> Result<Option<DateTime<Utc>>, sqlx::Error> {
+        let locked_until = sqlx::query_scalar!(
+            r#"
+            SELECT MAX(locked_until)
+              FROM login_throttles
+             WHERE throttle_key = ANY($1)
+               AND locked_until > NOW()
+            "#,
+            &throttle_keys
+        )
+        .fetch_one(&pool)
+        .await?;
+
+        Ok(locked_until)
+    }
+
+    async fn record_login_failure(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        throttle_key: String,
+        window_secs: i64,
+    ) -> Result<i32, sqlx::Error> {
+        let failed_count = sqlx::query_scalar!(
+            r#"
+            INSERT INTO login_throttles (throttle_key, failed_count, last_failed_at)
+            VALUES ($1, 1, NOW())
+            ON CONFLICT (throttle_key) DO UPDATE
+               SET failed_count = CASE
+                       WHEN login_throttles.last_failed_at < NOW() - make_interval(secs => $2)
+                       THEN 1
+                       ELSE login_throttles.failed_count + 1
+                   END,
+                   last_failed_at = NOW()
+            RETURNING failed_count
+            "#,
+            throttle_key,
+            window_secs as f64
+        )
+        .fetch_one(&mut **tx)
+        .await?;
+
+        Ok(failed_count)
+    }
+
+    async fn lock_login(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        throttle_key: String,
+        locked_until: DateTime<Utc>,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            r#"
+            UPDATE login_throttles
+               SET locked_until = $2
+             WHERE throttle_key = $1
+            "#,
+            throttle_key,
+            locked_until
+        )
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn clear_login_throttle(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        throttle_key: String,
+    ) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query!(
+            r#"
+            DELETE FROM login_throttles
+             WHERE throttle_key = $1
+            "#,
+            throttle_key
+        )
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    async fn find_mfa(
+        &self,
+        pool: PgPool,
+        user_id: String,
+    ) -> Result<Option<UserMfa>, sqlx::Error> {
+        let mfa = sqlx::query_as!(
+            UserMfa,
+            r#"
+            SELECT user_id, totp_secret, enabled_at, last_used_step
+              FROM user_mfa
+             WHERE user_id = $1
+            "#,
+            user_id
+        )
+        .fetch_optional(&pool)
+        .await?;
+
+        Ok(mfa)
+    }
+
+    async fn find_mfa_for_update(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        user_id: String,
+    ) -> Result<Option<UserMfa>, sqlx::Error> {
+        let mfa = sqlx::query_as!(
+            UserMfa,
+            r#"
+            SELECT user_id, totp_secret, enabled_at, last_used_step
+              FROM user_mfa
+             WHERE user_id = $1
+               FOR UPDATE
+            "#,
+            user_id
+        )
+        .fetch_optional(&mut **tx)
+        .await?;
+
+        Ok(mfa)
+    }
+
+    async fn upsert_pending_mfa(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        user_id: String,
+        totp_secret: String,
+    ) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query!(
+            r#"
+            INSERT INTO user_mfa (user_id, totp_secret)
+            VALUES ($1, $2)
+            ON CONFLICT (user_id) DO UPDATE
+               SET totp_secret = EXCLUDED.totp_secret,
+                   last_used_step = NULL,
+                   created_at = NOW()
+             WHERE user_mfa.enabled_at IS NULL
+            "#,
+            user_id,
+            totp_secret
+        )
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    async fn enable_mfa(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        user_id: String,
+        last_used_step: i64,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            r#"
+            UPDATE user_mfa
+               SET enabled_at = NOW(), last_used_step = $2
+             WHERE user_id = $1
+            "#,
+            user_id,
+            last_used_step
+        )
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn update_mfa_last_used_step(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        user_id: String,
+        last_used_step: i64,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            r#"
+            UPDATE user_mfa
+               SET last_used_step = $2
+             WHERE user_id = $1
+            "#,
+            user_id,
+            last_used_step
+        )
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn delete_mfa(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        user_id: String,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            r#"
+            DELETE FROM user_mfa
+             WHERE user_id = $1
+            "#,
+            user_id
+        )
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn replace_recovery_codes(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        user_id: String,
+        code_hashes: Vec<String>,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            r#"
+            DELETE FROM mfa_recovery_codes
+             WHERE user_id = $1
+            "#,
+            user_id
+        )
+        .execute(&mut **tx)
+        .await?;
+
+        sqlx::query!(
+            r#"
+            INSERT INTO mfa_recovery_codes (id, user_id, code_hash)
+            SELECT gen_random_uuid()::text, $1, code_hash
+              FROM UNNEST($2::text[]) AS code_hash
+            "#,
+            user_id,
+            &code_hashes
+        )
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn use_recovery_code(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        user_id: String,
+        code_hash: String,
+    ) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE mfa_recovery_codes
+               SET used_at = NOW()
+             WHERE user_id = $1
+               AND code_hash = $2
+               AND used_at IS NULL
+            "#,
+            user_id,
+            code_hash
+        )
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn create_mfa_challenge(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        challenge: MfaChallenge,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            r#"
+            INSERT INTO mfa_challenges
+            (id, user_id, token_hash, expires_at)
+            VALUES
+            ($1, $2, $3, $4)
+            "#,
+            challenge.id,
+            challenge.user_id,
+            challenge.token_hash,
+            challenge.expires_at
+        )
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn find_mfa_challenge_for_update(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        token_hash: String,
+    ) -> Result<Option<MfaChallenge>, sqlx::Error> {
+        let challenge = sqlx::query_as!(
+            MfaChallenge,
+            r#"
+            SELECT id, user_id, token_hash, expires_at, failed_attempts, used_at
+              FROM mfa_challenges
+             WHERE token_hash = $1
+               FOR UPDATE
+            "#,
+            token_hash
+        )
+        .fetch_optional(&mut **tx)
+        .await?;
+
+        Ok(challenge)
+    }
+
+    async fn record_mfa_challenge_failure(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        id: String,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            r#"
+            UPDATE mfa_challenges
+               SET failed_attempts = failed_attempts + 1
+             WHERE id = $1
+            "#,
+            id
+        )
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn mark_mfa_challenge_used(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        id: String,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            r#"
+            UPDATE mfa_challenges
+               SET used_at = NOW()
+             WHERE id = $1
+            "#,
+            id
+        )
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn create_api_key(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        api_key: ApiKey,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            r#"
+            INSERT INTO api_keys
+            (id, user_id, name, key_prefix, key_hash, scopes, expires_at, created_at)
+            VALUES
+            ($1, $2, $3, $4, $5, $6, $7, $8)
+            "#,
+            api_key.id,
+            api_key.user_id,
+            api_key.name,
+            api_key.key_prefix,
+            api_key.key_hash,
+            &api_key.scopes,
+            api_key.expires_at,
+            api_key.created_at
+        )
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn find_api_keys_by_user_id(
+        &self,
+        pool: PgPool,
+        user_id: String,
+    ) -> Result<Vec<ApiKey>, sqlx::Error> {
+        let api_keys = sqlx::query_as!(
+            ApiKey,
+            r#"
+            SELECT id, user_id, name, key_prefix, key_hash, scopes,
+                   expires_at, last_used_at, revoked_at, created_at
+              FROM api_keys
+             WHERE user_id = $1
+             ORDER BY created_at DESC
+            "#,
+            user_id
+        )
+        .fetch_all(&pool)
+        .await?;
+
+        Ok(api_keys)
+    }
+
+    async fn find_api_key_by_id(
+        &self,
+        pool: PgPool,
+        id: String,
+    ) -> Result<Option<ApiKey>, sqlx::Error> {
+        let api_key = sqlx::query_as!(
+            ApiKey,
+            r#"
+            SELECT k.id, k.user_id, k.name, k.key_prefix, k.key_hash, k.scopes,
+                   k.expires_at, k.last_used_at, k.revoked_at, k.created_at
+              FROM api_keys k
+              JOIN users u ON u.id = k.user_id
+             WHERE k.id = $1
+               AND ($2::varchar IS NULL OR u.org_id = $2)
+            "#,
+            id,
+            tenant::current()
+        )
+        .fetch_optional(&pool)
+        .await?;
+
+        Ok(api_key)
+    }
+
+    async fn find_api_key_by_hash(
+        &self,
+        pool: PgPool,
+        key_hash: String,
+    ) -> Result<Option<ApiKey>, sqlx::Error> {
+        let api_key = sqlx::query_as!(
+            ApiKey,
+            r#"
+            SELECT k.id, k.user_id, k.name, k.key_prefix, k.key_hash, k.scopes,
+                   k.expires_at, k.last_used_at, k.revoked_at, k.created_at
+              FROM api_keys k
+              JOIN users u ON u.id = k.user_id
+             WHERE k.key_hash = $1
+               AND u.deleted_at IS NULL
+            "#,
+            key_hash
+        )
+        .fetch_optional(&pool)
+        .await?;
+
+        Ok(api_key)
+    }
+
+    async fn touch_api_key(&self, pool: PgPool, id: String) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            r#"
+            UPDATE api_keys
+               SET last_used_at = NOW()
+             WHERE id = $1
+               AND (last_used_at IS NULL OR last_used_at < NOW() - INTERVAL '1 minute')
+            "#,
+            id
+        )
+        .execute(&pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn revoke_api_key(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        id: String,
+    ) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE api_keys
+               SET revoked_at = NOW()
+             WHERE id = $1
+               AND revoked_at IS NULL
+            "#,
+            id
+        )
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    async fn create_auth_event(&self, pool: PgPool, event: AuthEvent) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            r#"
+            INSERT INTO auth_events (id, org_id, event_type, success, user_id, username,
+                                     ip_address, user_agent, request_id, detail, created_at)
+            VALUES ($1, COALESCE((SELECT org_id FROM users WHERE id = $4), $11),
+                    $2, $3, $4, $5, $6, $7, $8, $9, $10)
+            "#,
+            event.id,
+            event.event_type,
+            event.success,
+            event.user_id,
+            event.username,
+            event.ip_address,
+            event.user_agent,
+            event.request_id,
+            event.detail,
+            event.created_at,
+            tenant::current_or_default()
+        )
+        .execute(&pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn is_user_of_other_tenant(
+        &self,
+        pool: PgPool,
+        user_id: String,
+    ) -> Result<bool, sqlx::Error> {
+        let other = sqlx::query_scalar!(
+            r#"
+            SELECT EXISTS (
+                SELECT 1
+                  FROM users
+                 WHERE id = $1
+                   AND $2::varchar IS NOT NULL
+                   AND org_id <> $2
+            ) AS "other!"
+            "#,
+            user_id,
+            tenant::current()
+        )
+        .fetch_one(&pool)
+        .await?;
+
+        Ok(other)
+    }
+
+    async fn find_auth_events(
+        &self,
+        pool: PgPool,
+        query: AuthEventQuery,
+    ) -> Result<Vec<AuthEvent>, sqlx::Error> {
+        let events = sqlx::query_as!(
+            AuthEvent,
+            r#"
+            SELECT id, event_type, success, user_id, username, ip_address,
+                   user_agent, request_id, detail, created_at
+              FROM auth_events
+             WHERE ($1::VARCHAR IS NULL OR user_id = $1)
+               AND ($2::VARCHAR IS NULL OR username = $2)
+               AND ($3::VARCHAR IS NULL OR event_type = $3)
+               AND ($4::TIMESTAMPTZ IS NULL OR created_at >= $4)
+               AND ($5::TIMESTAMPTZ IS NULL OR created_at < $5)
+               AND ($7::VARCHAR IS NULL OR org_id = $7)
+             ORDER BY created_at DESC
+             LIMIT $6
+            "#,
+            query.user_id,
+            query.username,
+            query.event_type.map(|kind| kind.as_str()),
+            query.from,
+            query.to,
+            query.limit,
+            tenant::current()
+        )
+        .fetch_all(&pool)
+        .await?;
+
+        Ok(events)
+    }
 }
@@ -2,86 +2,1701 @@ use std::sync::Arc;
 
 use crate::{
     common::{
-        error::AppError,
+        client_info::ClientInfo,
+        config::{Config, RegistrationMode},
+        error::{is_unique_violation, AppError},
         hash_util,
-        jwt::{make_jwt_token, AuthBody, AuthPayload},
+        jwt::{make_jwt_token, Actor, AuthBody, AuthPayload, Claims},
+        notifier::{Notification, Notifier},
+        permission::USER_ROLE,
+        tenant, totp,
     },
-    domains::auth::{
-        domain::{model::UserAuth, repository::UserAuthRepository, service::AuthServiceTrait},
-        dto::auth_dto::AuthUserDto,
-        infra::impl_repository::UserAuthRepo,
+    domains::{
+        auth::{
+            domain::{
+                model::{
+                    ApiKey, AuthEvent, AuthEventKind, MagicLink, MfaChallenge, PasswordResetToken,
+                    RefreshToken, RegistrationInvite, UserAuth, UserMfa,
+                },
+                repository::UserAuthRepository,
+                service::AuthServiceTrait,
+            },
+            dto::auth_dto::{
+                ApiKeyDto, AuthEventDto, AuthEventQuery, AuthUserDto, ChangePasswordDto,
+                ClientScope, CreateApiKeyDto, CreateInviteDto, CreatedApiKeyDto, ForgotPasswordDto,
+                ImpersonationTokenDto, InviteDto, LoginResult, MagicLinkRequestDto,
+                MagicLinkVerifyDto, MfaChallengeDto, MfaCodeDto, MfaEnrollmentDto,
+                MfaRecoveryCodesDto, MfaVerifyDto, RefreshTokenDto, RegisterUserDto,
+                ResetPasswordDto, UnlockAccountDto,
+            },
+            infra::impl_repository::UserAuthRepo,
+        },
+        user::{
+            dto::user_dto::{CreateUserMultipartDto, UserDto},
+            UserServiceTrait,
+        },
     },
 };
 
-use sqlx::PgPool;
+use chrono::{Duration, Utc};
+use sqlx::{PgPool, Postgres, Transaction};
+use std::net::IpAddr;
+use uuid::Uuid;
+use validator::{Validate, ValidationError};
+
+/// Wrong codes accepted per MFA challenge before it is invalidated.
+const MFA_MAX_ATTEMPTS: i32 = 5;
+
+/// Number of recovery codes issued when MFA is enabled.
+const MFA_RECOVERY_CODE_COUNT: usize = 10;
+
+/// Prefix of every API key, which makes leaked keys easy to recognize.
+const API_KEY_PREFIX: &str = "ak_";
+
+/// Number of leading characters of an API key stored to identify it.
+const API_KEY_DISPLAY_LEN: usize = 11;
+
+/// Number of digits of the one-time code sent with a magic link.
+const MAGIC_LINK_CODE_DIGITS: u32 = 6;
+
+/// Wrong codes accepted per magic link before it is invalidated.
+const MAGIC_LINK_MAX_ATTEMPTS: i32 = 5;
+
+/// Number of audit events returned when the query sets no limit.
+const AUTH_EVENT_DEFAULT_LIMIT: i64 = 100;
 
 /// Service for handling user authentication
 /// and authorization logic.
 #[derive(Clone)]
 pub struct AuthService {
+    config: Config,
     pool: PgPool,
     repo: Arc<dyn UserAuthRepository + Send + Sync>,
+    user_service: Arc<dyn UserServiceTrait>,
+    notifier: Arc<dyn Notifier>,
 }
 
 /// Implementation of the AuthService
 #[async_trait::async_trait]
 impl AuthServiceTrait for AuthService {
     /// constructor for the service.
-    fn create_service(pool: PgPool) -> Arc<dyn AuthServiceTrait> {
+    fn create_service(
+        config: Config,
+        pool: PgPool,
+        user_service: Arc<dyn UserServiceTrait>,
+        notifier: Arc<dyn Notifier>,
+    ) -> Arc<dyn AuthServiceTrait> {
         Arc::new(Self {
+            config,
             pool,
             repo: Arc::new(UserAuthRepo {}),
+            user_service,
+            notifier,
         })
     }
 
     /// It hashes the password and stores it in the database.
-    async fn create_user_auth(&self, auth_user: AuthUserDto) -> Result<(), AppError> {
-        let mut tx = self.pool.begin().await?;
+    /// Fails with a conflict if the user already has credentials.
+    async fn create_user_auth(
+        &self,
+        claims: &Claims,
+        auth_user: AuthUserDto,
+    ) -> Result<(), AppError> {
+        if self.config.registration_mode == RegistrationMode::Disabled || !claims.is_admin() {
+            return Err(AppError::Forbidden);
+        }
 
-        let password_hash =
-            hash_util::hash_password(&auth_user.password).map_err(|_| AppError::InternalError)?;
+        self.validate_password(&auth_user, "password", &auth_user.password)?;
 
-        let user_auth = UserAuth {
-            user_id: auth_user.user_id,
-            password_hash,
-        };
+        let mut tx = self.pool.begin().await?;
 
-        match self.repo.create(&mut tx, user_auth).await {
+        match self
+            .store_credentials(&mut tx, auth_user.user_id, &auth_user.password)
+            .await
+        {
             Ok(()) => {
                 tx.commit().await?;
                 Ok(())
             }
             Err(err) => {
-                tracing::error!("Error creating user auth: {err}");
                 tx.rollback().await?;
-                Err(AppError::DatabaseError(err))
+                Err(err)
             }
         }
     }
 
+    /// Creates the user, its credentials and the default role in one transaction.
+    /// In the `invite-only` mode the invite is redeemed in the same transaction,
+    /// and the user joins the organization of the admin who issued the invite;
+    /// invites of an admin who has since been deleted are refused.
+    async fn register_user(&self, payload: RegisterUserDto) -> Result<UserDto, AppError> {
+        let invite_required = match self.config.registration_mode {
+            RegistrationMode::Open => false,
+            RegistrationMode::InviteOnly => true,
+            RegistrationMode::Disabled | RegistrationMode::AdminOnly => {
+                return Err(AppError::Forbidden)
+            }
+        };
+
+        self.validate_password(&payload, "password", &payload.password)?;
+
+        let mut tx = self.pool.begin().await?;
+
+        let invite = if invite_required {
+            let token = payload
+                .invite_token
+                .as_deref()
+                .filter(|token| !token.is_empty())
+                .ok_or(AppError::Forbidden)?;
+            Some(
+                self.find_redeemable_invite(&mut tx, token, &payload.email)
+                    .await?,
+            )
+        } else {
+            None
+        };
+
+        let org_id = match invite.as_ref().and_then(|invite| invite.created_by.clone()) {
+            Some(inviter) => Some(
+                self.repo
+                    .find_org_id_by_user_id(&mut tx, inviter)
+                    .await?
+                    .ok_or(AppError::Forbidden)?,
+            ),
+            None => None,
+        };
+
+        let create_user = CreateUserMultipartDto {
+            username: payload.username,
+            email: payload.email,
+            modified_by: String::new(),
+            profile_picture: None,
+        };
+
+        let result = tenant::scope(org_id, async {
+            let user_id = self
+                .user_service
+                .create_user_in_tx(&mut tx, create_user)
+                .await?;
+            self.store_credentials(&mut tx, user_id.clone(), &payload.password)
+                .await?;
+            self.repo
+                .assign_role(&mut tx, user_id.clone(), USER_ROLE.to_string())
+                .await?;
+            if let Some(invite) = invite {
+                self.repo
+                    .mark_invite_used(&mut tx, invite.id, user_id.clone())
+                    .await?;
+            }
+            Ok::<_, AppError>(user_id)
+        })
+        .await;
+
+        let user_id = match result {
+            Ok(user_id) => {
+                tx.commit().await?;
+                user_id
+            }
+            Err(err) => {
+                tx.rollback().await?;
+                return Err(err);
+            }
+        };
+
+        self.user_service.get_user_by_id(user_id).await
+    }
+
+    /// Issues an invite that expires after the configured invite lifetime.
+    /// Only the hash of the token is stored; the token itself is returned once.
+    async fn create_invite(
+        &self,
+        claims: &Claims,
+        payload: CreateInviteDto,
+    ) -> Result<InviteDto, AppError> {
+        if !claims.is_admin() {
+            return Err(AppError::Forbidden);
+        }
+
+        payload.validate()?;
+
+        let invite_token = hash_util::generate_token();
+        let invite = RegistrationInvite {
+            id: Uuid::new_v4().to_string(),
+            token_hash: hash_util::hash_token(&invite_token),
+            email: payload.email,
+            created_by: Some(claims.sub.clone()),
+            expires_at: Utc::now() + Duration::seconds(self.config.invite_ttl_secs),
+            used_at: None,
+        };
+
+        let mut tx = self.pool.begin().await?;
+        self.repo.create_invite(&mut tx, invite.clone()).await?;
+        tx.commit().await?;
+
+        Ok(InviteDto {
+            invite_token,
+            email: invite.email,
+            expires_at: invite.expires_at,
+        })
+    }
+
     /// Authenticates a user by checking the provided credentials
     /// against the stored credentials in the database.
-    /// If the credentials are valid, it generates a JWT token for the user.
+    /// If the credentials are valid, it starts a new session and issues
+    /// a short-lived access token together with a refresh token.
     /// If the credentials are invalid, it returns an error.
-    async fn login_user(&self, auth_payload: AuthPayload) -> Result<AuthBody, AppError> {
+    ///
+    /// Locked accounts and IPs are rejected before the password is checked.
+    /// Unknown users and wrong passwords fail identically, including the time spent hashing.
+    /// If the user has MFA enabled, a short-lived challenge is returned instead of tokens.
+    async fn login_user(
+        &self,
+        auth_payload: AuthPayload,
+        client: &ClientInfo,
+    ) -> Result<LoginResult, AppError> {
+        let mut event = AuthEvent::new(AuthEventKind::Login, client);
+        let result = self.login(auth_payload, client, None, &mut event).await;
+        self.record_event(event, &result).await;
+        result
+    }
+
+    /// Same as `login_user`, but the session is bound to an OAuth client and its scopes.
+    /// Users with MFA enabled cannot complete a second factor here and are rejected.
+    async fn login_client(
+        &self,
+        auth_payload: AuthPayload,
+        client: &ClientInfo,
+        scope: ClientScope,
+    ) -> Result<AuthBody, AppError> {
+        let mut event = AuthEvent::new(AuthEventKind::Login, client);
+        event.detail = Some(format!("OAuth client {}", scope.client_id));
+        let result = self
+            .login(auth_payload, client, Some(scope), &mut event)
+            .await;
+        self.record_event(event, &result).await;
+
+        match result? {
+            LoginResult::Authenticated(auth_body) => Ok(auth_body),
+            LoginResult::MfaRequired(_) => Err(AppError::MfaRequired),
+        }
+    }
+
+    /// Starts a session bound to an OAuth client for a user who has already authenticated.
+    async fn create_client_session(
+        &self,
+        user_id: &str,
+        session_id: &str,
+        scope: ClientScope,
+    ) -> Result<AuthBody, AppError> {
+        let mut tx = self.pool.begin().await?;
+        let auth_body = self
+            .issue_tokens(&mut tx, user_id, session_id, Some(&scope))
+            .await?;
+        tx.commit().await?;
+
+        Ok(auth_body)
+    }
+
+    /// Rotates a refresh token that was issued to the given OAuth client.
+    async fn refresh_client_session(
+        &self,
+        payload: RefreshTokenDto,
+        client_id: &str,
+        client: &ClientInfo,
+    ) -> Result<AuthBody, AppError> {
+        let mut event = AuthEvent::new(AuthEventKind::TokenRefresh, client);
+        let result = self
+            .rotate_refresh_token(payload, Some(client_id), &mut event)
+            .await;
+        self.record_event(event, &result).await;
+        result
+    }
+
+    /// Signs an access token without a session, as issued by the OAuth client credentials grant.
+    /// Such tokens cannot be refreshed or revoked and expire after the access token lifetime.
+    async fn issue_client_access_token(
+        &self,
+        user_id: &str,
+        scope: ClientScope,
+    ) -> Result<String, AppError> {
+        let mut tx = self.pool.begin().await?;
+        let claims = self
+            .client_claims(&mut tx, user_id, None, Some(&scope))
+            .await?;
+        tx.commit().await?;
+
+        make_jwt_token(&claims)
+    }
+
+    /// Revokes every refresh token of the session, which also invalidates its access tokens.
+    async fn revoke_session(&self, session_id: &str) -> Result<(), AppError> {
+        let mut tx = self.pool.begin().await?;
+        self.repo
+            .revoke_token_family(&mut tx, session_id.to_string())
+            .await?;
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    /// Verifies the code for a pending challenge and starts a new session.
+    /// Each challenge is single-use and is invalidated after too many wrong codes.
+    async fn verify_mfa(
+        &self,
+        payload: MfaVerifyDto,
+        client: &ClientInfo,
+    ) -> Result<AuthBody, AppError> {
+        let mut event = AuthEvent::new(AuthEventKind::MfaVerify, client);
+        let result = self.complete_mfa_login(payload, &mut event).await;
+        self.record_event(event, &result).await;
+        result
+    }
+
+    /// Generates a new secret for a pending enrollment.
+    /// Fails with a conflict if MFA is already enabled; disable it first to re-enroll.
+    async fn enroll_mfa(&self, claims: &Claims) -> Result<MfaEnrollmentDto, AppError> {
+        require_session(claims)?;
+
+        let user = self.user_service.get_user_by_id(claims.sub.clone()).await?;
+        let secret = totp::generate_secret();
+        let otpauth_uri = totp::otpauth_uri(&secret, &self.config.jwt_issuer, &user.username)?;
+
+        let mut tx = self.pool.begin().await?;
+        let affected = self
+            .repo
+            .upsert_pending_mfa(&mut tx, claims.sub.clone(), secret.clone())
+            .await?;
+        if affected == 0 {
+            tx.rollback().await?;
+            return Err(AppError::Conflict("MFA is already enabled".into()));
+        }
+        tx.commit().await?;
+
+        Ok(MfaEnrollmentDto {
+            secret,
+            otpauth_uri,
+        })
+    }
+
+    /// Enables a pending enrollment once the user proves the authenticator works.
+    /// Recovery codes are stored as SHA-256 hashes and returned in clear only here.
+    async fn confirm_mfa(
+        &self,
+        claims: &Claims,
+        payload: MfaCodeDto,
+    ) -> Result<MfaRecoveryCodesDto, AppError> {
+        require_session(claims)?;
+
+        let mut tx = self.pool.begin().await?;
+
+        let mfa = self
+            .repo
+            .find_mfa_for_update(&mut tx, claims.sub.clone())
+            .await?
+            .ok_or_else(|| AppError::NotFound("MFA enrollment not found".into()))?;
+
+        if mfa.enabled_at.is_some() {
+            return Err(AppError::Conflict("MFA is already enabled".into()));
+        }
+
+        let step = totp::verify_code(&mfa.totp_secret, payload.code.trim(), None)?
+            .ok_or_else(|| AppError::ValidationError("Invalid MFA code".into()))?;
+
+        let recovery_codes: Vec<String> = (0..MFA_RECOVERY_CODE_COUNT)
+            .map(|_| totp::generate_recovery_code())
+            .collect();
+        let code_hashes = recovery_codes
+            .iter()
+            .map(|code| hash_util::hash_token(code))
+            .collect();
+
+        self.repo
+            .enable_mfa(&mut tx, claims.sub.clone(), step)
+            .await?;
+        self.repo
+            .replace_recovery_codes(&mut tx, claims.sub.clone(), code_hashes)
+            .await?;
+        tx.commit().await?;
+
+        Ok(MfaRecoveryCodesDto { recovery_codes })
+    }
+
+    /// Removes the enrollment and its recovery codes after verifying a code.
+    async fn disable_mfa(&self, claims: &Claims, payload: MfaCodeDto) -> Result<(), AppError> {
+        require_session(claims)?;
+
+        let mut tx = self.pool.begin().await?;
+
+        let mfa = self
+            .repo
+            .find_mfa_for_update(&mut tx, claims.sub.clone())
+            .await?
+            .filter(|mfa| mfa.enabled_at.is_some())
+            .ok_or_else(|| AppError::NotFound("MFA is not enabled".into()))?;
+
+        if !self
+            .verify_second_factor(&mut tx, &mfa, &payload.code)
+            .await?
+        {
+            tx.rollback().await?;
+            return Err(AppError::WrongCredentials);
+        }
+
+        self.repo.delete_mfa(&mut tx, claims.sub.clone()).await?;
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    /// Rotates the presented refresh token.
+    /// The old token is marked as used and a new one is issued in the same family.
+    /// If a token that was already used is presented again, it is treated as stolen
+    /// and the whole family is revoked, which also invalidates its access tokens.
+    async fn refresh_token(
+        &self,
+        payload: RefreshTokenDto,
+        client: &ClientInfo,
+    ) -> Result<AuthBody, AppError> {
+        let mut event = AuthEvent::new(AuthEventKind::TokenRefresh, client);
+        let result = self.rotate_refresh_token(payload, None, &mut event).await;
+        self.record_event(event, &result).await;
+        result
+    }
+
+    /// Revokes the session of the presented refresh token.
+    /// Unknown or already revoked tokens are ignored so that logout is idempotent.
+    async fn logout(&self, payload: RefreshTokenDto, client: &ClientInfo) -> Result<(), AppError> {
+        if payload.refresh_token.is_empty() {
+            return Err(AppError::MissingCredentials);
+        }
+
+        let mut event = AuthEvent::new(AuthEventKind::Logout, client);
+        let result = async {
+            let mut tx = self.pool.begin().await?;
+
+            let token_hash = hash_util::hash_token(&payload.refresh_token);
+            if let Some(stored) = self
+                .repo
+                .find_refresh_token_for_update(&mut tx, token_hash)
+                .await?
+            {
+                event.user_id = Some(stored.user_id);
+                self.repo
+                    .revoke_token_family(&mut tx, stored.family_id)
+                    .await?;
+            }
+
+            tx.commit().await?;
+            Ok(())
+        }
+        .await;
+
+        self.record_event(event, &result).await;
+        result
+    }
+
+    /// Removes the counters, and with them any lock, of the given account and/or IP.
+    async fn unlock_account(
+        &self,
+        claims: &Claims,
+        payload: UnlockAccountDto,
+        client: &ClientInfo,
+    ) -> Result<(), AppError> {
+        if !claims.is_admin() {
+            return Err(AppError::Forbidden);
+        }
+
+        let mut keys = Vec::new();
+        let username = payload.username.filter(|username| !username.is_empty());
+        if let Some(username) = &username {
+            keys.push(account_throttle_key(username));
+        }
+        if let Some(ip) = payload.ip.filter(|ip| !ip.is_empty()) {
+            let ip = ip
+                .parse::<IpAddr>()
+                .map_err(|_| AppError::ValidationError("Invalid IP address".into()))?;
+            keys.push(ip_throttle_key(&ip));
+        }
+        if keys.is_empty() {
+            return Err(AppError::ValidationError(
+                "Either username or ip is required".into(),
+            ));
+        }
+
+        let mut tx = self.pool.begin().await?;
+        for key in &keys {
+            if self.repo.clear_login_throttle(&mut tx, key.clone()).await? > 0 {
+                tracing::info!("Login throttle {key} cleared by {}", claims.sub);
+            }
+        }
+        tx.commit().await?;
+
+        let mut event = AuthEvent::new(AuthEventKind::Unlock, client);
+        event.username = username;
+        event.detail = Some(format!("{} unlocked by {}", keys.join(", "), claims.sub));
+        self.record_event(event, &Ok::<_, AppError>(())).await;
+
+        Ok(())
+    }
+
+    /// Verifies the current password, stores the new one and revokes every session
+    /// of the user, including the caller's. The caller continues in a new session.
+    async fn change_password(
+        &self,
+        claims: &Claims,
+        payload: ChangePasswordDto,
+        client: &ClientInfo,
+    ) -> Result<AuthBody, AppError> {
+        require_session(claims)?;
+        self.validate_password(&payload, "new_password", &payload.new_password)?;
+
+        let mut event = AuthEvent::new(AuthEventKind::PasswordChange, client);
+        event.user_id = Some(claims.sub.clone());
+        let result = async {
+            let user_auth = self
+                .repo
+                .find_by_user_id(self.pool.clone(), claims.sub.clone())
+                .await?
+                .ok_or(AppError::WrongCredentials)?;
+
+            if !hash_util::verify_password(&user_auth.password_hash, &payload.current_password) {
+                return Err(AppError::WrongCredentials);
+            }
+
+            let mut tx = self.pool.begin().await?;
+            self.replace_password(&mut tx, &user_auth.user_id, &payload.new_password)
+                .await?;
+            let family_id = Uuid::new_v4().to_string();
+            let auth_body = self
+                .issue_tokens(&mut tx, &user_auth.user_id, &family_id, None)
+                .await?;
+            tx.commit().await?;
+
+            Ok(auth_body)
+        }
+        .await;
+
+        self.record_event(event, &result).await;
+        result
+    }
+
+    /// Replaces any outstanding reset token of the user with a new one
+    /// and delivers it to the user's email address through the notifier.
+    async fn forgot_password(&self, payload: ForgotPasswordDto) -> Result<(), AppError> {
+        if payload.username.is_empty() {
+            return Err(AppError::MissingCredentials);
+        }
+
+        let Some(user_auth) = self
+            .repo
+            .find_by_user_name(self.pool.clone(), payload.username)
+            .await?
+        else {
+            return Ok(());
+        };
+        let Some(email) = self
+            .user_service
+            .get_user_by_id(user_auth.user_id.clone())
+            .await?
+            .email
+            .filter(|email| !email.is_empty())
+        else {
+            tracing::warn!(
+                "Password reset requested for user {} without an email address",
+                user_auth.user_id
+            );
+            return Ok(());
+        };
+
+        let reset_token = hash_util::generate_token();
+        let reset = PasswordResetToken {
+            id: Uuid::new_v4().to_string(),
+            user_id: user_auth.user_id.clone(),
+            token_hash: hash_util::hash_token(&reset_token),
+            expires_at: Utc::now() + Duration::seconds(self.config.password_reset_ttl_secs),
+            used_at: None,
+        };
+
+        let mut tx = self.pool.begin().await?;
+        self.repo
+            .invalidate_password_resets(&mut tx, user_auth.user_id)
+            .await?;
+        self.repo.create_password_reset(&mut tx, reset).await?;
+        tx.commit().await?;
+
+        let notification = Notification {
+            recipient: email,
+            subject: "Reset your password".to_string(),
+            body: format!(
+                "Use the following token at /auth/password/reset within {} minutes:\n{}",
+                self.config.password_reset_ttl_secs / 60,
+                reset_token
+            ),
+        };
+
+        // The response must not depend on the delivery outcome, so failures are only logged.
+        if let Err(err) = self.notifier.send(notification).await {
+            tracing::error!("Error sending password reset notification: {err}");
+        }
+
+        Ok(())
+    }
+
+    /// Replaces any outstanding magic link of the user with a new one and delivers its link
+    /// and one-time code to the user's email address. Users without a password may use it too.
+    async fn request_magic_link(&self, payload: MagicLinkRequestDto) -> Result<(), AppError> {
+        if payload.username.is_empty() {
+            return Err(AppError::MissingCredentials);
+        }
+
+        let Some(user_id) = self
+            .repo
+            .find_user_id_by_username(self.pool.clone(), payload.username)
+            .await?
+        else {
+            return Ok(());
+        };
+        let Some(email) = self
+            .user_service
+            .get_user_by_id(user_id.clone())
+            .await?
+            .email
+            .filter(|email| !email.is_empty())
+        else {
+            tracing::warn!("Magic link requested for user {user_id} without an email address");
+            return Ok(());
+        };
+
+        let token = hash_util::generate_token();
+        let code = hash_util::generate_numeric_code(MAGIC_LINK_CODE_DIGITS);
+        let magic_link = MagicLink {
+            id: Uuid::new_v4().to_string(),
+            user_id: user_id.clone(),
+            token_hash: hash_util::hash_token(&token),
+            code_hash: hash_util::hash_token(&code),
+            expires_at: Utc::now() + Duration::seconds(self.config.magic_link_ttl_secs),
+            failed_attempts: 0,
+            used_at: None,
+        };
+
+        let mut tx = self.pool.begin().await?;
+        self.repo.invalidate_magic_links(&mut tx, user_id).await?;
+        self.repo.create_magic_link(&mut tx, magic_link).await?;
+        tx.commit().await?;
+
+        let notification = Notification {
+            recipient: email,
+            subject: "Your sign-in link".to_string(),
+            body: format!(
+                "Sign in within {} minutes with this link:\n{}?token={}\nor enter this code:\n{}",
+                self.config.magic_link_ttl_secs / 60,
+                self.config.magic_link_url,
+                token,
+                code
+            ),
+        };
+
+        // The response must not depend on the delivery outcome, so failures are only logged.
+        if let Err(err) = self.notifier.send(notification).await {
+            tracing::error!("Error sending magic link notification: {err}");
+        }
+
+        Ok(())
+    }
+
+    /// Wrong codes count towards the login throttles of the account and the client IP,
+    /// and invalidate the link after too many attempts. The link token itself is unguessable.
+    async fn verify_magic_link(
+        &self,
+        payload: MagicLinkVerifyDto,
+        client: &ClientInfo,
+    ) -> Result<LoginResult, AppError> {
+        let mut event = AuthEvent::new(AuthEventKind::MagicLink, client);
+        let result = self.redeem_magic_link(payload, client, &mut event).await;
+        self.record_event(event, &result).await;
+        result
+    }
+
+    /// Redeems the reset token, stores the new password and revokes every session of the user.
+    async fn reset_password(
+        &self,
+        payload: ResetPasswordDto,
+        client: &ClientInfo,
+    ) -> Result<(), AppError> {
+        if payload.reset_token.is_empty() {
+            return Err(AppError::MissingCredentials);
+        }
+
+        self.validate_password(&payload, "new_password", &payload.new_password)?;
+
+        let mut event = AuthEvent::new(AuthEventKind::PasswordReset, client);
+        let result = async {
+            let mut tx = self.pool.begin().await?;
+
+            let reset = self
+                .repo
+                .find_password_reset_for_update(
+                    &mut tx,
+                    hash_util::hash_token(&payload.reset_token),
+                )
+                .await?
+                .ok_or(AppError::InvalidToken)?;
+            event.user_id = Some(reset.user_id.clone());
+
+            if reset.used_at.is_some() {
+                return Err(AppError::InvalidToken);
+            }
+
+            if reset.expires_at <= Utc::now() {
+                return Err(AppError::TokenExpired);
+            }
+
+            self.replace_password(&mut tx, &reset.user_id, &payload.new_password)
+                .await?;
+            tx.commit().await?;
+
+            Ok(())
+        }
+        .await;
+
+        self.record_event(event, &result).await;
+        result
+    }
+
+    /// Checks whether the session has at least one refresh token that is not revoked.
+    async fn is_session_active(&self, session_id: &str) -> Result<bool, AppError> {
+        self.repo
+            .is_token_family_active(self.pool.clone(), session_id.to_string())
+            .await
+            .map_err(|err| {
+                tracing::error!("Error checking session: {err}");
+                AppError::DatabaseError(err)
+            })
+    }
+
+    /// Only the hash and a short prefix of the key are stored.
+    /// API keys cannot create further keys, so a leaked key cannot outlive its revocation.
+    async fn create_api_key(
+        &self,
+        claims: &Claims,
+        payload: CreateApiKeyDto,
+    ) -> Result<CreatedApiKeyDto, AppError> {
+        require_session(claims)?;
+
+        let mut errors = payload.validate().err().unwrap_or_default();
+        for scope in &payload.scopes {
+            if !claims.has_permission(scope) {
+                errors.add(
+                    "scopes",
+                    ValidationError::new("scope")
+                        .with_message(format!("Scope {scope} is not granted to the caller").into()),
+                );
+            }
+        }
+        if !errors.is_empty() {
+            return Err(AppError::InvalidInput(errors));
+        }
+
+        let mut scopes = payload.scopes;
+        scopes.sort();
+        scopes.dedup();
+
+        let secret = format!("{API_KEY_PREFIX}{}", hash_util::generate_token());
+        let now = Utc::now();
+        let api_key = ApiKey {
+            id: Uuid::new_v4().to_string(),
+            user_id: claims.sub.clone(),
+            name: payload.name,
+            key_prefix: secret[..API_KEY_DISPLAY_LEN].to_string(),
+            key_hash: hash_util::hash_token(&secret),
+            scopes,
+            expires_at: payload.expires_in.map(|secs| now + Duration::seconds(secs)),
+            last_used_at: None,
+            revoked_at: None,
+            created_at: now,
+        };
+
+        let mut tx = self.pool.begin().await?;
+        self.repo.create_api_key(&mut tx, api_key.clone()).await?;
+        tx.commit().await?;
+
+        tracing::info!("API key {} created by {}", api_key.id, claims.sub);
+
+        Ok(CreatedApiKeyDto {
+            id: api_key.id,
+            name: api_key.name,
+            api_key: secret,
+            scopes: api_key.scopes,
+            expires_at: api_key.expires_at,
+        })
+    }
+
+    async fn list_api_keys(&self, claims: &Claims) -> Result<Vec<ApiKeyDto>, AppError> {
+        let api_keys = self
+            .repo
+            .find_api_keys_by_user_id(self.pool.clone(), claims.sub.clone())
+            .await?;
+
+        Ok(api_keys.into_iter().map(ApiKeyDto::from).collect())
+    }
+
+    /// Keys of other users are reported as not found to non-admins.
+    async fn revoke_api_key(&self, claims: &Claims, id: String) -> Result<(), AppError> {
+        let api_key = self
+            .repo
+            .find_api_key_by_id(self.pool.clone(), id)
+            .await?
+            .filter(|api_key| claims.can_access(&api_key.user_id))
+            .ok_or_else(|| AppError::NotFound("API key not found".into()))?;
+
+        let mut tx = self.pool.begin().await?;
+        if self
+            .repo
+            .revoke_api_key(&mut tx, api_key.id.clone())
+            .await?
+            > 0
+        {
+            tracing::info!("API key {} revoked by {}", api_key.id, claims.sub);
+        }
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    /// The owner's roles and permissions are loaded on every request,
+    /// so that removing a role from the owner also takes effect for their keys.
+    async fn authenticate_api_key(&self, api_key: &str) -> Result<Claims, AppError> {
+        if !api_key.starts_with(API_KEY_PREFIX) {
+            return Err(AppError::InvalidToken);
+        }
+
+        let api_key = self
+            .repo
+            .find_api_key_by_hash(self.pool.clone(), hash_util::hash_token(api_key))
+            .await?
+            .ok_or(AppError::InvalidToken)?;
+
+        if api_key.revoked_at.is_some() {
+            tracing::error!("Rejected revoked API key: {}", api_key.id);
+            return Err(AppError::InvalidToken);
+        }
+
+        if api_key
+            .expires_at
+            .is_some_and(|expires_at| expires_at <= Utc::now())
+        {
+            return Err(AppError::TokenExpired);
+        }
+
+        let mut tx = self.pool.begin().await?;
+        let roles = self
+            .repo
+            .find_roles_by_user_id(&mut tx, api_key.user_id.clone())
+            .await?;
+        let permissions = self
+            .repo
+            .find_permissions_by_user_id(&mut tx, api_key.user_id.clone())
+            .await?;
+        let org_id = self
+            .repo
+            .find_org_id_by_user_id(&mut tx, api_key.user_id.clone())
+            .await?
+            .ok_or(AppError::InvalidToken)?;
+        tx.commit().await?;
+
+        if let Err(err) = self
+            .repo
+            .touch_api_key(self.pool.clone(), api_key.id.clone())
+            .await
+        {
+            tracing::warn!("Error recording use of API key {}: {err}", api_key.id);
+        }
+
+        let mut claims = Claims::new(
+            &self.config,
+            &api_key.user_id,
+            self.config.jwt_access_token_ttl_secs,
+        );
+        claims.jti = api_key.id.clone();
+        claims.org_id = org_id;
+        claims.roles = roles;
+        claims.permissions = permissions;
+        claims.restrict_to_scopes(&api_key.scopes);
+        claims.api_key_id = Some(api_key.id);
+
+        Ok(claims)
+    }
+
+    /// The token carries the user's current roles and permissions, with the admin as `act`.
+    /// It shares the admin's session, so logging out also ends the impersonation.
+    /// Only interactive admin sessions may impersonate, and never while impersonating.
+    async fn impersonate(
+        &self,
+        claims: &Claims,
+        user_id: String,
+        client: &ClientInfo,
+    ) -> Result<ImpersonationTokenDto, AppError> {
+        if !claims.is_admin() || claims.client_id.is_some() {
+            return Err(AppError::Forbidden);
+        }
+        require_session(claims)?;
+
+        if user_id == claims.sub {
+            return Err(AppError::ValidationError(
+                "Cannot impersonate yourself".into(),
+            ));
+        }
+
+        let mut event = AuthEvent::new(AuthEventKind::Impersonate, client);
+        event.user_id = Some(user_id.clone());
+        event.detail = Some(format!("Impersonated by {}", claims.sub));
+        let result = async {
+            let user = self.user_service.get_user_by_id(user_id).await?;
+            event.username = Some(user.username);
+
+            let ttl_secs = self.config.impersonation_ttl_secs;
+            let mut tx = self.pool.begin().await?;
+            let mut impersonated = self
+                .client_claims(&mut tx, &user.id, claims.sid.as_deref(), None)
+                .await?;
+            tx.commit().await?;
+
+            impersonated.exp = impersonated.iat + ttl_secs as usize;
+            impersonated.act = Some(Actor {
+                sub: claims.sub.clone(),
+            });
+            tracing::warn!("{impersonated} issued an impersonation token");
+
+            Ok(ImpersonationTokenDto {
+                access_token: make_jwt_token(&impersonated)?,
+                token_type: "Bearer".to_string(),
+                expires_in: ttl_secs,
+                user_id: impersonated.sub,
+            })
+        }
+        .await;
+
+        self.record_event(event, &result).await;
+        result
+    }
+
+    async fn record_rejected_token(&self, client: &ClientInfo, err: &AppError) {
+        let event = AuthEvent::new(AuthEventKind::TokenRejected, client);
+        self.record_event(event, &Err::<(), _>(err)).await;
+    }
+
+    /// Events are filtered in the database; at most 100 are returned unless a limit is given.
+    async fn list_auth_events(
+        &self,
+        claims: &Claims,
+        mut query: AuthEventQuery,
+    ) -> Result<Vec<AuthEventDto>, AppError> {
+        if !claims.is_admin() {
+            return Err(AppError::Forbidden);
+        }
+
+        query.validate()?;
+        query.limit.get_or_insert(AUTH_EVENT_DEFAULT_LIMIT);
+
+        if let Some(user_id) = &query.user_id {
+            if self
+                .repo
+                .is_user_of_other_tenant(self.pool.clone(), user_id.clone())
+                .await?
+            {
+                return Err(AppError::NotFound("User not found".into()));
+            }
+        }
+
+        let events = self.repo.find_auth_events(self.pool.clone(), query).await?;
+
+        Ok(events.into_iter().map(AuthEventDto::from).collect())
+    }
+}
+
+/// Rejects requests authenticated with an API key or an impersonation token.
+/// Used for account settings, which require an interactive login of the user themselves.
+fn require_session(claims: &Claims) -> Result<(), AppError> {
+    if claims.api_key_id.is_some() {
+        tracing::error!("{claims} attempted an account change with an API key");
+        return Err(AppError::Forbidden);
+    }
+    if claims.act.is_some() {
+        tracing::error!("{claims} attempted an account change while impersonating");
+        return Err(AppError::Forbidden);
+    }
+    Ok(())
+}
+
+/// A failed-login counter that a login attempt is checked against.
+struct LoginThrottleKey {
+    key: String,
+    max_attempts: i32,
+    /// Whether failures below the limit already delay the next attempt.
+    backoff: bool,
+}
+
+/// Throttle key of the failed-login counter of an account.
+fn account_throttle_key(username: &str) -> String {
+    format!("account:{}", username.to_lowercase())
+}
+
+/// Throttle key of the failed-login counter of a client IP.
+fn ip_throttle_key(ip: &IpAddr) -> String {
+    format!("ip:{ip}")
+}
+
+/// Internal helper methods defined on `AuthService`.
+impl AuthService {
+    /// Returns the throttle keys of a login attempt.
+    /// The account key always comes first; the IP key is only present if the IP is known.
+    /// IPs may be shared by many users, so they are only locked once their limit is reached.
+    fn login_throttle_keys(&self, username: &str, client: &ClientInfo) -> Vec<LoginThrottleKey> {
+        let mut keys = vec![LoginThrottleKey {
+            key: account_throttle_key(username),
+            max_attempts: self.config.login_max_attempts,
+            backoff: true,
+        }];
+        if let Some(ip) = &client.ip {
+            keys.push(LoginThrottleKey {
+                key: ip_throttle_key(ip),
+                max_attempts: self.config.login_max_attempts_per_ip,
+                backoff: false,
+            });
+        }
+        keys
+    }
+
+    /// Rejects the attempt if any of the keys is locked,
+    /// reporting how long the caller has to wait.
+    async fn check_login_throttles(&self, keys: &[LoginThrottleKey]) -> Result<(), AppError> {
+        let keys = keys.iter().map(|throttle| throttle.key.clone()).collect();
+
+        let locked_until = self
+            .repo
+            .find_login_locked_until(self.pool.clone(), keys)
+            .await?;
+
+        match locked_until {
+            Some(locked_until) => {
+                let remaining_ms = (locked_until - Utc::now()).num_milliseconds().max(0);
+                Err(AppError::TooManyAttempts {
+                    retry_after_secs: (remaining_ms as u64).div_ceil(1000),
+                })
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Counts a failed attempt for every key and locks the keys for an exponentially
+    /// growing delay, or for the full lockout once their failure limit is reached.
+    /// Every lock that reaches the full lockout is recorded in the audit trail.
+    async fn record_login_failure(
+        &self,
+        keys: &[LoginThrottleKey],
+        client: &ClientInfo,
+        username: &str,
+    ) -> Result<(), AppError> {
+        let lockout_secs = self.config.login_lockout_secs;
+        let mut lockouts = Vec::new();
+        let mut tx = self.pool.begin().await?;
+
+        for LoginThrottleKey {
+            key,
+            max_attempts,
+            backoff,
+        } in keys
+        {
+            let failed_count = self
+                .repo
+                .record_login_failure(&mut tx, key.clone(), lockout_secs)
+                .await?;
+
+            let delay_secs = if failed_count >= *max_attempts {
+                tracing::warn!("Login locked for {key} after {failed_count} failed attempts");
+                lockouts.push(format!("{key} after {failed_count} failed attempts"));
+                lockout_secs
+            } else if !backoff {
+                0
+            } else {
+                let exponent = (failed_count - 1).clamp(0, 30) as u32;
+                self.config
+                    .login_backoff_base_secs
+                    .saturating_mul(1 << exponent)
+                    .min(lockout_secs)
+            };
+
+            if delay_secs > 0 {
+                self.repo
+                    .lock_login(
+                        &mut tx,
+                        key.clone(),
+                        Utc::now() + Duration::seconds(delay_secs),
+                    )
+                    .await?;
+            }
+        }
+
+        tx.commit().await?;
+
+        for detail in lockouts {
+            let mut event = AuthEvent::new(AuthEventKind::Lockout, client);
+            event.username = Some(username.to_string());
+            event.detail = Some(detail);
+            self.record_event(event, &Ok::<_, AppError>(())).await;
+        }
+
+        Ok(())
+    }
+
+    /// Completes an audit event with the outcome of its operation and appends it to the trail.
+    /// Failures to record are only logged, so that auditing never fails the operation itself.
+    async fn record_event<T, E: std::fmt::Display>(
+        &self,
+        mut event: AuthEvent,
+        result: &Result<T, E>,
+    ) {
+        if let Err(err) = result {
+            event.success = false;
+            event.detail.get_or_insert_with(|| err.to_string());
+        }
+
+        if let Err(err) = self.repo.create_auth_event(self.pool.clone(), event).await {
+            tracing::error!("Error recording auth event: {err}");
+        }
+    }
+
+    /// Validates the payload and checks `password` against the password policy.
+    /// Policy violations are reported under `field` together with the payload's own errors.
+    fn validate_password<T: Validate>(
+        &self,
+        payload: &T,
+        field: &'static str,
+        password: &str,
+    ) -> Result<(), AppError> {
+        let mut errors = payload.validate().err().unwrap_or_default();
+        self.config
+            .password_policy
+            .check(field, password, &mut errors);
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(AppError::InvalidInput(errors))
+        }
+    }
+
+    /// Hashes the password and stores it as the credentials of `user_id`.
+    async fn store_credentials(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        user_id: String,
+        password: &str,
+    ) -> Result<(), AppError> {
+        let password_hash = hash_util::hash_password(password, &self.config.password_hash_params)
+            .map_err(|_| AppError::InternalError)?;
+
+        let user_auth = UserAuth {
+            user_id,
+            password_hash,
+        };
+
+        match self.repo.create(tx, user_auth).await {
+            Ok(true) => Ok(()),
+            Ok(false) => Err(AppError::NotFound("User not found".into())),
+            Err(err) if is_unique_violation(&err) => Err(AppError::Conflict(
+                "Credentials already exist for this user".into(),
+            )),
+            Err(err) => {
+                tracing::error!("Error creating user auth: {err}");
+                Err(AppError::DatabaseError(err))
+            }
+        }
+    }
+
+    /// Accepts either a TOTP code newer than the last accepted one, or an unused recovery code.
+    /// Accepted codes are consumed within the given transaction.
+    async fn verify_second_factor(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        mfa: &UserMfa,
+        code: &str,
+    ) -> Result<bool, AppError> {
+        let code = code.trim();
+
+        if code.bytes().all(|b| b.is_ascii_digit()) {
+            let Some(step) = totp::verify_code(&mfa.totp_secret, code, mfa.last_used_step)? else {
+                return Ok(false);
+            };
+            self.repo
+                .update_mfa_last_used_step(tx, mfa.user_id.clone(), step)
+                .await?;
+            return Ok(true);
+        }
+
+        let code_hash = hash_util::hash_token(&totp::normalize_recovery_code(code));
+        let used = self
+            .repo
+            .use_recovery_code(tx, mfa.user_id.clone(), code_hash)
+            .await?;
+        if used {
+            tracing::info!("Recovery code used by user {}", mfa.user_id);
+        }
+        Ok(used)
+    }
+
+    /// Stores a new password hash, revokes every session of the user
+    /// and invalidates any outstanding reset tokens.
+    async fn replace_password(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        user_id: &str,
+        password: &str,
+    ) -> Result<(), AppError> {
+        let password_hash = hash_util::hash_password(password, &self.config.password_hash_params)
+            .map_err(|_| AppError::InternalError)?;
+
+        let updated = self
+            .repo
+            .update_password(tx, user_id.to_string(), password_hash)
+            .await?;
+        if updated == 0 {
+            return Err(AppError::UserNotFound);
+        }
+
+        let revoked = self
+            .repo
+            .revoke_user_tokens(tx, user_id.to_string())
+            .await?;
+        tracing::info!("Password of user {user_id} changed; revoked {revoked} refresh tokens");
+
+        self.repo
+            .invalidate_password_resets(tx, user_id.to_string())
+            .await?;
+
+        Ok(())
+    }
+
+    /// Loads and locks an invite that is unused, unexpired and, if bound to an email, matches it.
+    async fn find_redeemable_invite(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        token: &str,
+        email: &str,
+    ) -> Result<RegistrationInvite, AppError> {
+        let invite = self
+            .repo
+            .find_invite_for_update(tx, hash_util::hash_token(token))
+            .await?
+            .ok_or(AppError::Forbidden)?;
+
+        let email_matches = invite
+            .email
+            .as_deref()
+            .is_none_or(|bound| bound.eq_ignore_ascii_case(email));
+
+        if invite.used_at.is_some() || invite.expires_at <= Utc::now() || !email_matches {
+            return Err(AppError::Forbidden);
+        }
+
+        Ok(invite)
+    }
+
+    /// Redeems an MFA challenge. See `verify_mfa`.
+    async fn complete_mfa_login(
+        &self,
+        payload: MfaVerifyDto,
+        event: &mut AuthEvent,
+    ) -> Result<AuthBody, AppError> {
+        if payload.mfa_token.is_empty() || payload.code.is_empty() {
+            return Err(AppError::MissingCredentials);
+        }
+
+        let mut tx = self.pool.begin().await?;
+
+        let challenge = self
+            .repo
+            .find_mfa_challenge_for_update(&mut tx, hash_util::hash_token(&payload.mfa_token))
+            .await?
+            .ok_or(AppError::InvalidToken)?;
+        event.user_id = Some(challenge.user_id.clone());
+
+        if challenge.used_at.is_some() || challenge.failed_attempts >= MFA_MAX_ATTEMPTS {
+            return Err(AppError::InvalidToken);
+        }
+
+        if challenge.expires_at <= Utc::now() {
+            return Err(AppError::TokenExpired);
+        }
+
+        let mfa = self
+            .repo
+            .find_mfa_for_update(&mut tx, challenge.user_id.clone())
+            .await?
+            .filter(|mfa| mfa.enabled_at.is_some())
+            .ok_or(AppError::InvalidToken)?;
+
+        if !self
+            .verify_second_factor(&mut tx, &mfa, &payload.code)
+            .await?
+        {
+            self.repo
+                .record_mfa_challenge_failure(&mut tx, challenge.id)
+                .await?;
+            tx.commit().await?;
+            return Err(AppError::WrongCredentials);
+        }
+
+        self.repo
+            .mark_mfa_challenge_used(&mut tx, challenge.id)
+            .await?;
+        let family_id = Uuid::new_v4().to_string();
+        let auth_body = self
+            .issue_tokens(&mut tx, &challenge.user_id, &family_id, None)
+            .await?;
+        tx.commit().await?;
+
+        Ok(auth_body)
+    }
+
+    /// Redeems a magic link. See `verify_magic_link`.
+    async fn redeem_magic_link(
+        &self,
+        payload: MagicLinkVerifyDto,
+        client: &ClientInfo,
+        event: &mut AuthEvent,
+    ) -> Result<LoginResult, AppError> {
+        let non_empty = |value: Option<String>| value.filter(|value| !value.is_empty());
+        let (token, username, code) = (
+            non_empty(payload.token),
+            non_empty(payload.username),
+            non_empty(payload.code),
+        );
+
+        let mut tx = self.pool.begin().await?;
+
+        let magic_link = match (token, username, code) {
+            (Some(token), _, _) => self
+                .repo
+                .find_magic_link_for_update(&mut tx, hash_util::hash_token(&token))
+                .await?
+                .ok_or(AppError::InvalidToken)?,
+            (None, Some(username), Some(code)) => {
+                event.username = Some(username.clone());
+                let throttles = self.login_throttle_keys(&username, client);
+                self.check_login_throttles(&throttles).await?;
+
+                let magic_link = match self
+                    .repo
+                    .find_user_id_by_username(self.pool.clone(), username.clone())
+                    .await?
+                {
+                    Some(user_id) => {
+                        self.repo
+                            .find_latest_magic_link_for_update(&mut tx, user_id)
+                            .await?
+                    }
+                    None => None,
+                };
+
+                match magic_link {
+                    Some(magic_link)
+                        if magic_link.code_hash == hash_util::hash_token(code.trim()) =>
+                    {
+                        self.repo
+                            .clear_login_throttle(&mut tx, throttles[0].key.clone())
+                            .await?;
+                        magic_link
+                    }
+                    magic_link => {
+                        if let Some(magic_link) = magic_link {
+                            event.user_id = Some(magic_link.user_id);
+                            self.repo
+                                .record_magic_link_failure(&mut tx, magic_link.id)
+                                .await?;
+                        }
+                        tx.commit().await?;
+                        self.record_login_failure(&throttles, client, &username)
+                            .await?;
+                        return Err(AppError::WrongCredentials);
+                    }
+                }
+            }
+            _ => return Err(AppError::MissingCredentials),
+        };
+        event.user_id = Some(magic_link.user_id.clone());
+
+        if magic_link.used_at.is_some() || magic_link.failed_attempts >= MAGIC_LINK_MAX_ATTEMPTS {
+            return Err(AppError::InvalidToken);
+        }
+
+        if magic_link.expires_at <= Utc::now() {
+            return Err(AppError::TokenExpired);
+        }
+
+        self.repo
+            .mark_magic_link_used(&mut tx, magic_link.id)
+            .await?;
+        self.start_session(tx, &magic_link.user_id, None, event)
+            .await
+    }
+
+    /// Checks the credentials and starts a session, optionally bound to an OAuth client.
+    /// See `login_user` for the throttling and MFA behavior.
+    async fn login(
+        &self,
+        auth_payload: AuthPayload,
+        client: &ClientInfo,
+        scope: Option<ClientScope>,
+        event: &mut AuthEvent,
+    ) -> Result<LoginResult, AppError> {
         if auth_payload.client_id.is_empty() || auth_payload.client_secret.is_empty() {
             return Err(AppError::MissingCredentials);
         }
+        event.username = Some(auth_payload.client_id.clone());
+
+        let throttles = self.login_throttle_keys(&auth_payload.client_id, client);
+        self.check_login_throttles(&throttles).await?;
 
         let user_auth = self
             .repo
             .find_by_user_name(self.pool.clone(), auth_payload.client_id.clone())
             .await
             .map_err(AppError::DatabaseError)?;
+        event.user_id = user_auth
+            .as_ref()
+            .map(|user_auth| user_auth.user_id.clone());
 
-        let user_auth = user_auth.ok_or(AppError::UserNotFound)?;
+        let verified = match &user_auth {
+            Some(user_auth) => {
+                hash_util::verify_password(&user_auth.password_hash, &auth_payload.client_secret)
+            }
+            None => {
+                hash_util::dummy_verify_password(
+                    &auth_payload.client_secret,
+                    &self.config.password_hash_params,
+                );
+                false
+            }
+        };
 
-        if !hash_util::verify_password(&user_auth.password_hash, &auth_payload.client_secret) {
-            return Err(AppError::WrongCredentials);
+        let user_auth = match user_auth {
+            Some(user_auth) if verified => user_auth,
+            _ => {
+                self.record_login_failure(&throttles, client, &auth_payload.client_id)
+                    .await?;
+                return Err(AppError::WrongCredentials);
+            }
+        };
+
+        let mut tx = self.pool.begin().await?;
+        // Only the account counter is reset; the IP counter expires on its own.
+        self.repo
+            .clear_login_throttle(&mut tx, throttles[0].key.clone())
+            .await?;
+
+        // The plain password is only available now, so weaker hashes are upgraded on login.
+        if hash_util::needs_rehash(&user_auth.password_hash, &self.config.password_hash_params) {
+            match hash_util::hash_password(
+                &auth_payload.client_secret,
+                &self.config.password_hash_params,
+            ) {
+                Ok(password_hash) => {
+                    self.repo
+                        .update_password(&mut tx, user_auth.user_id.clone(), password_hash)
+                        .await?;
+                    tracing::info!("Password hash of user {} upgraded", user_auth.user_id);
+                }
+                Err(err) => tracing::warn!("Error upgrading password hash: {err}"),
+            }
+        }
+
+        self.start_session(tx, &user_auth.user_id, scope, event)
+            .await
+    }
+
+    /// Starts a session for an authenticated user and commits the transaction.
+    /// Users with MFA enabled get a challenge instead; OAuth clients cannot complete one.
+    async fn start_session(
+        &self,
+        mut tx: Transaction<'_, Postgres>,
+        user_id: &str,
+        scope: Option<ClientScope>,
+        event: &mut AuthEvent,
+    ) -> Result<LoginResult, AppError> {
+        let mfa_enabled = self
+            .repo
+            .find_mfa(self.pool.clone(), user_id.to_string())
+            .await?
+            .is_some_and(|mfa| mfa.enabled_at.is_some());
+
+        if mfa_enabled && scope.is_some() {
+            return Err(AppError::MfaRequired);
+        }
+
+        if mfa_enabled {
+            let mfa_token = hash_util::generate_token();
+            let challenge = MfaChallenge {
+                id: Uuid::new_v4().to_string(),
+                user_id: user_id.to_string(),
+                token_hash: hash_util::hash_token(&mfa_token),
+                expires_at: Utc::now() + Duration::seconds(self.config.mfa_challenge_ttl_secs),
+                failed_attempts: 0,
+                used_at: None,
+            };
+            self.repo.create_mfa_challenge(&mut tx, challenge).await?;
+            tx.commit().await?;
+
+            event.detail = Some("MFA challenge issued".to_string());
+            return Ok(LoginResult::MfaRequired(MfaChallengeDto {
+                mfa_token,
+                expires_in: self.config.mfa_challenge_ttl_secs,
+            }));
+        }
+
+        let family_id = Uuid::new_v4().to_string();
+        let auth_body = self
+            .issue_tokens(&mut tx, user_id, &family_id, scope.as_ref())
+            .await?;
+        tx.commit().await?;
+
+        Ok(LoginResult::Authenticated(auth_body))
+    }
+
+    /// Exchanges a refresh token for a new pair in the same session.
+    /// The token must have been issued to `client_id`; first-party sessions have none.
+    /// Presenting an already rotated token revokes the whole session.
+    async fn rotate_refresh_token(
+        &self,
+        payload: RefreshTokenDto,
+        client_id: Option<&str>,
+        event: &mut AuthEvent,
+    ) -> Result<AuthBody, AppError> {
+        if payload.refresh_token.is_empty() {
+            return Err(AppError::MissingCredentials);
         }
 
-        let token = make_jwt_token(&user_auth.user_id).map_err(|_| AppError::InternalError)?;
+        let mut tx = self.pool.begin().await?;
+
+        let token_hash = hash_util::hash_token(&payload.refresh_token);
+        let stored = self
+            .repo
+            .find_refresh_token_for_update(&mut tx, token_hash)
+            .await?
+            .ok_or(AppError::InvalidToken)?;
+        event.user_id = Some(stored.user_id.clone());
+
+        // Checked first, so that another client cannot revoke the session by replaying a token.
+        if stored.client_id.as_deref() != client_id {
+            return Err(AppError::InvalidToken);
+        }
+
+        if stored.revoked_at.is_some() {
+            return Err(AppError::InvalidToken);
+        }
+
+        if stored.used_at.is_some() {
+            tracing::warn!(
+                "Refresh token reuse detected for user {}; revoking session {}",
+                stored.user_id,
+                stored.family_id
+            );
+            event.detail = Some(format!(
+                "Reuse detected; session {} revoked",
+                stored.family_id
+            ));
+            self.repo
+                .revoke_token_family(&mut tx, stored.family_id)
+                .await?;
+            tx.commit().await?;
+            return Err(AppError::InvalidToken);
+        }
+
+        if stored.expires_at <= Utc::now() {
+            return Err(AppError::TokenExpired);
+        }
+
+        self.repo
+            .mark_refresh_token_used(&mut tx, stored.id)
+            .await?;
+        let scope = stored.client_id.map(|client_id| ClientScope {
+            client_id,
+            scopes: stored.scopes.unwrap_or_default(),
+        });
+        let auth_body = self
+            .issue_tokens(&mut tx, &stored.user_id, &stored.family_id, scope.as_ref())
+            .await?;
+        tx.commit().await?;
+
+        Ok(auth_body)
+    }
+
+    /// Persists a new refresh token in the given family and signs a matching access token.
+    /// Roles and permissions are read at issue time, so changes apply on the next refresh.
+    async fn issue_tokens(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        user_id: &str,
+        family_id: &str,
+        scope: Option<&ClientScope>,
+    ) -> Result<AuthBody, AppError> {
+        let refresh_token = hash_util::generate_token();
+
+        let record = RefreshToken {
+            id: Uuid::new_v4().to_string(),
+            user_id: user_id.to_string(),
+            family_id: family_id.to_string(),
+            token_hash: hash_util::hash_token(&refresh_token),
+            expires_at: Utc::now() + Duration::seconds(self.config.jwt_refresh_token_ttl_secs),
+            used_at: None,
+            revoked_at: None,
+            client_id: scope.map(|scope| scope.client_id.clone()),
+            scopes: scope.map(|scope| scope.scopes.clone()),
+        };
+        self.repo.create_refresh_token(tx, record).await?;
+
+        let claims = self
+            .client_claims(tx, user_id, Some(family_id), scope)
+            .await?;
+        let access_token = make_jwt_token(&claims)?;
+
+        Ok(AuthBody::new(
+            access_token,
+            self.config.jwt_access_token_ttl_secs,
+            refresh_token,
+        ))
+    }
+
+    /// Builds the access token claims of a user with their current roles and permissions.
+    /// Tokens of an OAuth client only get the permissions within the client's scopes,
+    /// and lose the admin role unless the scopes cover all of the user's permissions.
+    async fn client_claims(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        user_id: &str,
+        session_id: Option<&str>,
+        scope: Option<&ClientScope>,
+    ) -> Result<Claims, AppError> {
+        let mut claims = Claims::new(&self.config, user_id, self.config.jwt_access_token_ttl_secs);
+        claims.sid = session_id.map(str::to_string);
+        claims.org_id = self
+            .repo
+            .find_org_id_by_user_id(tx, user_id.to_string())
+            .await?
+            .ok_or(AppError::UserNotFound)?;
+        claims.roles = self
+            .repo
+            .find_roles_by_user_id(tx, user_id.to_string())
+            .await?;
+        claims.permissions = self
+            .repo
+            .find_permissions_by_user_id(tx, user_id.to_string())
+            .await?;
+
+        if let Some(scope) = scope {
+            claims.client_id = Some(scope.client_id.clone());
+            claims.restrict_to_scopes(&scope.scopes);
+        }
 
-        Ok(AuthBody::new(token))
+        Ok(claims)
     }
 }
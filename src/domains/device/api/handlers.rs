@@ -1,29 +1,43 @@
-use crate::common::dto::RestApiResponse;
-use crate::common::{app_state::AppState, error::AppError, jwt::Claims};
+use crate::common::dto::{IncludeDeletedQuery, RestApiResponse};
+use crate::common::{
+    app_state::AppState,
+    concurrency::{etag_header, IfMatch},
+    error::AppError,
+    jwt::Claims,
+    pagination::{PageQuery, Paginated},
+};
 
 use crate::domains::device::dto::device_dto::{
-    CreateDeviceDto, DeviceDto, UpdateDeviceDto, UpdateManyDevicesDto,
+    CreateDeviceDto, DeviceDto, PatchDeviceDto, UpdateDeviceDto, UpdateManyDevicesDto,
 };
 use axum::{
-    extract::{Path, State},
+    extract::{Path, Query, State},
     response::IntoResponse,
     Extension, Json,
 };
+use serde_json::Value;
 
 /// This function creates a router for getting a device by ID
 /// It will return a device if found, otherwise it will return an error
 #[utoipa::path(
     get,
     path = "/device/{id}",
-    responses((status = 200, description = "Get device by ID", body = DeviceDto)),
+    responses(
+        (status = 200, description = "Get device by ID", body = DeviceDto,
+            headers(("ETag" = String, description = "Version of the device, for `If-Match`")))
+    ),
     tag = "Devices"
 )]
 pub async fn get_device_by_id(
     State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
     axum::extract::Path(id): axum::extract::Path<String>,
 ) -> Result<impl IntoResponse, AppError> {
-    let device = state.device_service.get_device_by_id(id).await?;
-    Ok(RestApiResponse::success(device))
+    let device = state.device_service.get_device_by_id(&claims, id).await?;
+    Ok((
+        etag_header(device.version),
+        RestApiResponse::success(device),
+    ))
 }
 
 /// This function creates a router for getting all devices
@@ -31,11 +45,49 @@ pub async fn get_device_by_id(
 #[utoipa::path(
     get,
     path = "/device",
-    responses((status = 200, description = "List all devices", body = [DeviceDto])),
+    params(PageQuery, IncludeDeletedQuery),
+    responses(
+        (status = 200, description = "List all devices", body = Paginated<DeviceDto>),
+        (status = 403, description = "Only admins may include deleted devices")
+    ),
     tag = "Devices"
 )]
-pub async fn get_devices(State(state): State<AppState>) -> Result<impl IntoResponse, AppError> {
-    let devices = state.device_service.get_devices().await?;
+pub async fn get_devices(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Query(page): Query<PageQuery>,
+    Query(deleted): Query<IncludeDeletedQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    let devices = state
+        .device_service
+        .get_devices(&claims, page, deleted.include_deleted)
+        .await?;
+    Ok(RestApiResponse::success(devices))
+}
+
+/// This function creates a router for getting the devices of all members of a group
+/// Only admins and managers of the group may list them
+#[utoipa::path(
+    get,
+    path = "/device/group/{group_id}",
+    params(PageQuery),
+    responses(
+        (status = 200, description = "List the devices of the members of a group", body = Paginated<DeviceDto>),
+        (status = 403, description = "The caller does not manage the group"),
+        (status = 404, description = "Group not found")
+    ),
+    tag = "Devices"
+)]
+pub async fn get_group_devices(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(group_id): Path<String>,
+    Query(page): Query<PageQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    let devices = state
+        .device_service
+        .get_group_devices(&claims, group_id, page)
+        .await?;
     Ok(RestApiResponse::success(devices))
 }
 
@@ -54,11 +106,11 @@ pub async fn create_device(
     Extension(claims): Extension<Claims>,
     Json(payload): Json<CreateDeviceDto>,
 ) -> Result<impl IntoResponse, AppError> {
-    // Set the modified_by field to the current user's ID.
+    // Set the modified_by field to the acting user's ID, i.e. the admin when impersonating.
     let mut payload = payload;
-    payload.modified_by = claims.sub.clone().to_string();
+    payload.modified_by = claims.actor_id().to_string();
 
-    let device = state.device_service.create_device(payload).await?;
+    let device = state.device_service.create_device(&claims, payload).await?;
     Ok(RestApiResponse::success(device))
 }
 
@@ -68,22 +120,71 @@ pub async fn create_device(
 #[utoipa::path(
     put,
     path = "/device/{id}",
+    params(("If-Match" = Option<String>, Header, description = "ETag of the version being updated")),
     request_body = UpdateDeviceDto,
-    responses((status = 200, description = "Update device", body = DeviceDto)),
+    responses(
+        (status = 200, description = "Update device", body = DeviceDto,
+            headers(("ETag" = String, description = "Version of the updated device"))),
+        (status = 409, description = "The owner has another device with the same name"),
+        (status = 412, description = "The device was modified since it was read")
+    ),
     tag = "Devices"
 )]
 pub async fn update_device(
     State(state): State<AppState>,
     Extension(claims): Extension<Claims>,
     axum::extract::Path(id): axum::extract::Path<String>,
+    if_match: IfMatch,
     Json(payload): Json<UpdateDeviceDto>,
 ) -> Result<impl IntoResponse, AppError> {
-    // Set the modified_by field to the current user's ID.
+    // Set the modified_by field to the acting user's ID, i.e. the admin when impersonating.
     let mut payload = payload;
-    payload.modified_by = claims.sub.clone().to_string();
+    payload.modified_by = claims.actor_id().to_string();
 
-    let device = state.device_service.update_device(id, payload).await?;
-    Ok(RestApiResponse::success(device))
+    let device = state
+        .device_service
+        .update_device(&claims, id, payload, &if_match)
+        .await?;
+    Ok((
+        etag_header(device.version),
+        RestApiResponse::success(device),
+    ))
+}
+
+/// This function creates a router for patching a device
+/// It will apply a JSON Merge Patch to the device:
+/// members that are left out keep their value
+/// It will return the patched device
+#[utoipa::path(
+    patch,
+    path = "/device/{id}",
+    params(("If-Match" = Option<String>, Header, description = "ETag of the version being patched")),
+    request_body(content = PatchDeviceDto, content_type = "application/merge-patch+json",
+        description = "The members of the device to change"),
+    responses(
+        (status = 200, description = "Patch device", body = DeviceDto,
+            headers(("ETag" = String, description = "Version of the patched device"))),
+        (status = 400, description = "Invalid merge patch"),
+        (status = 409, description = "The owner has another device with the same name"),
+        (status = 412, description = "The device was modified since it was read")
+    ),
+    tag = "Devices"
+)]
+pub async fn patch_device(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    axum::extract::Path(id): axum::extract::Path<String>,
+    if_match: IfMatch,
+    Json(patch): Json<Value>,
+) -> Result<impl IntoResponse, AppError> {
+    let device = state
+        .device_service
+        .patch_device(&claims, id, patch, &if_match)
+        .await?;
+    Ok((
+        etag_header(device.version),
+        RestApiResponse::success(device),
+    ))
 }
 
 /// This function creates a router for deleting a device
@@ -97,13 +198,39 @@ pub async fn update_device(
 )]
 pub async fn delete_device(
     State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
     axum::extract::Path(id): axum::extract::Path<String>,
 ) -> Result<impl IntoResponse, AppError> {
-    let message = state.device_service.delete_device(id).await?;
+    let message = state.device_service.delete_device(&claims, id).await?;
 
     Ok(RestApiResponse::success_with_message(message, ()))
 }
 
+/// This function creates a router for restoring a deleted device
+/// It will return the restored device
+#[utoipa::path(
+    post,
+    path = "/device/{id}/restore",
+    responses(
+        (status = 200, description = "Restore a deleted device", body = DeviceDto),
+        (status = 404, description = "No deleted device with this ID"),
+        (status = 409, description = "The owner is deleted or has another device with the same name")
+    ),
+    tag = "Devices"
+)]
+pub async fn restore_device(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, AppError> {
+    let device = state.device_service.restore_device(&claims, id).await?;
+
+    Ok(RestApiResponse::success_with_message(
+        "Device restored",
+        device,
+    ))
+}
+
 /// This function creates a router for batch updating devices
 /// It will update multiple devices in the database
 /// It will return a message indicating the result of the operation
@@ -120,11 +247,11 @@ pub async fn update_many_devices(
     Path(user_id): Path<String>,
     Json(payload): Json<UpdateManyDevicesDto>,
 ) -> Result<impl IntoResponse, AppError> {
-    let modified_by = claims.sub.clone().to_string();
+    let modified_by = claims.actor_id().to_string();
 
     let message = state
         .device_service
-        .update_many_devices(user_id, modified_by, payload)
+        .update_many_devices(&claims, user_id, modified_by, payload)
         .await?;
 
     Ok(RestApiResponse::success_with_message(message, ()))
@@ -1,10 +1,16 @@
 use super::handlers::*;
 use crate::{
-    common::app_state::AppState,
-    domains::device::dto::device_dto::{CreateDeviceDto, DeviceDto, UpdateDeviceDto},
+    common::{
+        app_state::AppState,
+        permission::{require_permission, DEVICE_DELETE, DEVICE_READ, DEVICE_WRITE},
+    },
+    domains::device::dto::device_dto::{
+        CreateDeviceDto, DeviceDto, PatchDeviceDto, UpdateDeviceDto,
+    },
 };
 use axum::{
-    routing::{delete, get, post, put},
+    middleware,
+    routing::{delete, get, patch, post, put},
     Router,
 };
 
@@ -18,12 +24,15 @@ use utoipa::{
     paths(
         get_device_by_id,
         get_devices,
+        get_group_devices,
         create_device,
         update_device,
+        patch_device,
         update_many_devices,
         delete_device,
+        restore_device,
     ),
-    components(schemas(DeviceDto, CreateDeviceDto, UpdateDeviceDto)),
+    components(schemas(DeviceDto, CreateDeviceDto, UpdateDeviceDto, PatchDeviceDto)),
     tags(
         (name = "Device", description = "Device management endpoints")
     ),
@@ -55,10 +64,44 @@ impl utoipa::Modify for DeviceApiDoc {
 /// It defines the routes and their corresponding handlers.
 pub fn device_routes() -> Router<AppState> {
     Router::new()
-        .route("/", get(get_devices))
-        .route("/", post(create_device))
-        .route("/{id}", get(get_device_by_id))
-        .route("/{id}", put(update_device))
-        .route("/{id}", delete(delete_device))
-        .route("/batch/{user_id}", put(update_many_devices))
+        .route(
+            "/",
+            get(get_devices).route_layer(middleware::from_fn(require_permission(DEVICE_READ))),
+        )
+        .route(
+            "/",
+            post(create_device).route_layer(middleware::from_fn(require_permission(DEVICE_WRITE))),
+        )
+        .route(
+            "/{id}",
+            get(get_device_by_id).route_layer(middleware::from_fn(require_permission(DEVICE_READ))),
+        )
+        .route(
+            "/{id}",
+            put(update_device).route_layer(middleware::from_fn(require_permission(DEVICE_WRITE))),
+        )
+        .route(
+            "/{id}",
+            patch(patch_device).route_layer(middleware::from_fn(require_permission(DEVICE_WRITE))),
+        )
+        .route(
+            "/{id}",
+            delete(delete_device)
+                .route_layer(middleware::from_fn(require_permission(DEVICE_DELETE))),
+        )
+        .route(
+            "/{id}/restore",
+            post(restore_device)
+                .route_layer(middleware::from_fn(require_permission(DEVICE_DELETE))),
+        )
+        .route(
+            "/group/{group_id}",
+            get(get_group_devices)
+                .route_layer(middleware::from_fn(require_permission(DEVICE_READ))),
+        )
+        .route(
+            "/batch/{user_id}",
+            put(update_many_devices)
+                .route_layer(middleware::from_fn(require_permission(DEVICE_WRITE))),
+        )
 }
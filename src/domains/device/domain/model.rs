@@ -11,7 +11,10 @@ use sqlx::{
 use std::{fmt, str::FromStr};
 use utoipa::ToSchema;
 
-use crate::common::error::AppError;
+use crate::common::{
+    error::AppError,
+    pagination::{Keyset, SortValue},
+};
 
 /// Enum representing the possible statuses of a device in the system.
 #[derive(PartialEq, Debug, Clone, Serialize, Deserialize, ToSchema)]
@@ -145,4 +148,19 @@ pub struct Device {
     pub created_at: Option<DateTime<Utc>>,
     pub modified_by: Option<String>,
     pub modified_at: Option<DateTime<Utc>>,
+    /// Bumped on every change; returned as the ETag.
+    pub version: i64,
+    pub deleted_by: Option<String>,
+    pub deleted_at: Option<DateTime<Utc>>,
+}
+
+/// Device lists are ordered by creation time only.
+impl Keyset for Device {
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn sort_value(&self, _field: &str) -> SortValue {
+        SortValue::time(self.created_at)
+    }
 }
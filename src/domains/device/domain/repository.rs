@@ -1,6 +1,7 @@
 // This module defines the `DeviceRepository` trait, which abstracts
 // the database operations related to device management.
 
+use crate::common::pagination::PageRequest;
 use crate::domains::device::dto::device_dto::{
     CreateDeviceDto, UpdateDeviceDto, UpdateManyDevicesDto,
 };
@@ -8,18 +9,51 @@ use crate::domains::device::dto::device_dto::{
 use super::model::Device;
 
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
 use sqlx::{PgPool, Postgres, Transaction};
 
 #[async_trait]
 /// Trait representing repository-level operations for device entities.
 /// Provides an interface for data persistence and retrieval of device records.
 pub trait DeviceRepository: Send + Sync {
-    /// Retrieves all devices from the database.
-    async fn find_all(&self, pool: PgPool) -> Result<Vec<Device>, sqlx::Error>;
+    /// Retrieves a page of all devices, together with the total number of devices.
+    /// Soft-deleted devices are only included if `include_deleted` is set.
+    async fn find_all(
+        &self,
+        pool: PgPool,
+        page: &PageRequest,
+        include_deleted: bool,
+    ) -> Result<(Vec<Device>, i64), sqlx::Error>;
+
+    /// Retrieves a page of the devices owned by the given user,
+    /// together with the total number of devices of the user.
+    async fn find_by_user_id(
+        &self,
+        pool: PgPool,
+        user_id: String,
+        page: &PageRequest,
+    ) -> Result<(Vec<Device>, i64), sqlx::Error>;
+
+    /// Retrieves a page of the devices owned by the members of the given group,
+    /// together with the total number of these devices.
+    async fn find_by_group_id(
+        &self,
+        pool: PgPool,
+        group_id: String,
+        page: &PageRequest,
+    ) -> Result<(Vec<Device>, i64), sqlx::Error>;
 
     /// Finds a device by its unique identifier.
     async fn find_by_id(&self, pool: PgPool, id: String) -> Result<Option<Device>, sqlx::Error>;
 
+    /// Finds a device that is not deleted within the given transaction
+    /// and locks it until the transaction ends.
+    async fn find_for_update(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        id: String,
+    ) -> Result<Option<Device>, sqlx::Error>;
+
     /// Creates a new device record in the database within the given transaction.
     async fn create(
         &self,
@@ -36,18 +70,76 @@ pub trait DeviceRepository: Send + Sync {
     ) -> Result<Option<Device>, sqlx::Error>;
 
     /// Updates multiple devices for a given user with the specified changes.
+    /// Existing devices owned by another user are left untouched;
+    /// returns the number of rows inserted or updated.
     async fn update_many(
         &self,
         tx: &mut Transaction<'_, Postgres>,
         user_id: String,
         modified_by: String,
         update_devices: UpdateManyDevicesDto,
-    ) -> Result<(), sqlx::Error>;
+    ) -> Result<u64, sqlx::Error>;
 
-    /// Deletes a device record by its ID.
+    /// Soft-deletes a device record by its ID.
     async fn delete(
         &self,
         tx: &mut Transaction<'_, Postgres>,
         id: String,
+        deleted_by: String,
+    ) -> Result<bool, sqlx::Error>;
+
+    /// Restores a soft-deleted device whose owner is not deleted and returns it,
+    /// or `None` if there is no such deleted device.
+    async fn restore(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        id: String,
+        modified_by: String,
+    ) -> Result<Option<Device>, sqlx::Error>;
+
+    /// Checks whether a soft-deleted device belongs to a user who is deleted as well.
+    async fn owner_is_deleted(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        id: String,
+    ) -> Result<bool, sqlx::Error>;
+
+    /// Hands the devices of a user that are not deleted over to another user.
+    /// Returns the IDs of the reassigned devices.
+    async fn reassign_all(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        user_id: String,
+        new_owner: String,
+        modified_by: String,
+    ) -> Result<Vec<String>, sqlx::Error>;
+
+    /// Marks the devices of a user that are not deleted as decommissioned and soft-deletes them.
+    /// Returns the IDs of the decommissioned devices.
+    async fn decommission_all(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        user_id: String,
+        deleted_by: String,
+    ) -> Result<Vec<String>, sqlx::Error>;
+
+    /// Hard-deletes the devices soft-deleted before the given time
+    /// and returns their number.
+    async fn purge(&self, pool: PgPool, deleted_before: DateTime<Utc>) -> Result<u64, sqlx::Error>;
+
+    /// Hard-deletes every device of the given users, deleted or not, within an active
+    /// transaction and returns their number.
+    async fn purge_by_user_ids(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        user_ids: Vec<String>,
+    ) -> Result<u64, sqlx::Error>;
+
+    /// Checks that a user who is not deleted exists in the current tenant,
+    /// so that devices are never handed to users of another organization.
+    async fn user_exists(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        user_id: String,
     ) -> Result<bool, sqlx::Error>;
 }
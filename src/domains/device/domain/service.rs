@@ -3,12 +3,22 @@
 
 use std::sync::Arc;
 
-use sqlx::PgPool;
+use chrono::{DateTime, Utc};
+use serde_json::Value;
+use sqlx::{PgPool, Postgres, Transaction};
 
 use crate::{
-    common::error::AppError,
-    domains::device::dto::device_dto::{
-        CreateDeviceDto, DeviceDto, UpdateDeviceDto, UpdateManyDevicesDto,
+    common::{
+        concurrency::IfMatch,
+        error::AppError,
+        jwt::Claims,
+        pagination::{PageQuery, Paginated},
+    },
+    domains::{
+        device::dto::device_dto::{
+            CreateDeviceDto, DeviceDto, UpdateDeviceDto, UpdateManyDevicesDto,
+        },
+        group::GroupServiceTrait,
     },
 };
 
@@ -16,34 +26,97 @@ use crate::{
 /// Trait defining the contract for device-related business operations.
 /// This includes creating, retrieving, updating, and deleting devices,
 /// as well as batch updates for user-associated devices.
+/// Non-admin callers may only see and modify their own devices
+/// and those of the members of the groups they manage.
 pub trait DeviceServiceTrait: Send + Sync {
     /// constructor for the service.
-    fn create_service(pool: PgPool) -> Arc<dyn DeviceServiceTrait>
+    fn create_service(
+        pool: PgPool,
+        group_service: Arc<dyn GroupServiceTrait>,
+    ) -> Arc<dyn DeviceServiceTrait>
     where
         Self: Sized;
 
     /// Retrieves a device by its unique ID.
-    async fn get_device_by_id(&self, id: String) -> Result<DeviceDto, AppError>;
+    async fn get_device_by_id(&self, claims: &Claims, id: String) -> Result<DeviceDto, AppError>;
+
+    /// Retrieves a page of the devices visible to the caller.
+    /// Only administrators may include soft-deleted devices.
+    async fn get_devices(
+        &self,
+        claims: &Claims,
+        page: PageQuery,
+        include_deleted: Option<bool>,
+    ) -> Result<Paginated<DeviceDto>, AppError>;
 
-    /// Retrieves a list of all devices.
-    async fn get_devices(&self) -> Result<Vec<DeviceDto>, AppError>;
+    /// Retrieves a page of the devices of all members of a group.
+    /// Only admins and managers of the group may list them.
+    async fn get_group_devices(
+        &self,
+        claims: &Claims,
+        group_id: String,
+        page: PageQuery,
+    ) -> Result<Paginated<DeviceDto>, AppError>;
 
     /// Creates a new device from the provided payload.
-    async fn create_device(&self, payload: CreateDeviceDto) -> Result<DeviceDto, AppError>;
+    async fn create_device(
+        &self,
+        claims: &Claims,
+        payload: CreateDeviceDto,
+    ) -> Result<DeviceDto, AppError>;
 
     /// Updates an existing device with new data.
+    /// Fails with `PreconditionFailed` if the current version does not match `if_match`.
     async fn update_device(
         &self,
+        claims: &Claims,
         id: String,
         payload: UpdateDeviceDto,
+        if_match: &IfMatch,
+    ) -> Result<DeviceDto, AppError>;
+
+    /// Applies a JSON Merge Patch to the fields of an existing device.
+    /// Fails with `PreconditionFailed` if the current version does not match `if_match`.
+    async fn patch_device(
+        &self,
+        claims: &Claims,
+        id: String,
+        patch: Value,
+        if_match: &IfMatch,
     ) -> Result<DeviceDto, AppError>;
 
-    /// Deletes a device by its ID.
-    async fn delete_device(&self, id: String) -> Result<String, AppError>;
+    /// Soft-deletes a device by its ID.
+    async fn delete_device(&self, claims: &Claims, id: String) -> Result<String, AppError>;
+
+    /// Restores a soft-deleted device and returns it.
+    async fn restore_device(&self, claims: &Claims, id: String) -> Result<DeviceDto, AppError>;
+
+    /// Releases the devices of a user that is being deleted, within the given transaction.
+    /// The devices are handed over to `new_owner` if given, otherwise they are
+    /// decommissioned and soft-deleted. Returns the IDs of the released devices.
+    async fn release_user_devices(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        user_id: &str,
+        new_owner: Option<&str>,
+        modified_by: &str,
+    ) -> Result<Vec<String>, AppError>;
+
+    /// Hard-deletes the devices soft-deleted before the given time and returns their number.
+    async fn purge_deleted(&self, deleted_before: DateTime<Utc>) -> Result<u64, AppError>;
+
+    /// Hard-deletes every device of the given users within the given transaction,
+    /// as part of purging the users. Returns the number of purged devices.
+    async fn purge_user_devices(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        user_ids: &[String],
+    ) -> Result<u64, AppError>;
 
     /// Applies updates to multiple devices owned by a user.
     async fn update_many_devices(
         &self,
+        claims: &Claims,
         user_id: String,
         modified_by: String,
         payload: UpdateManyDevicesDto,
@@ -21,6 +21,10 @@ pub struct DeviceDto {
     pub modified_by: Option<String>,
     #[serde(with = "crate::common::ts_format::option")]
     pub modified_at: Option<DateTime<Utc>>,
+    pub version: i64,
+    pub deleted_by: Option<String>,
+    #[serde(with = "crate::common::ts_format::option")]
+    pub deleted_at: Option<DateTime<Utc>>,
 }
 
 #[derive(PartialEq, Debug, Deserialize, serde::Serialize, ToSchema)]
@@ -45,6 +49,46 @@ pub struct UpdateDeviceDto {
     pub modified_by: String,
 }
 
+/// The fields of a device that a `PATCH` may change.
+/// A JSON Merge Patch is applied to these fields of the current device;
+/// none of them may be removed, and members that are not fields are rejected.
+#[derive(PartialEq, Debug, Deserialize, serde::Serialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct PatchDeviceDto {
+    pub name: String,
+    pub user_id: String,
+    pub device_os: DeviceOS,
+    pub status: DeviceStatus,
+    #[serde(with = "crate::common::ts_format")]
+    pub registered_at: DateTime<Utc>,
+}
+
+impl From<&Device> for PatchDeviceDto {
+    fn from(device: &Device) -> Self {
+        Self {
+            name: device.name.clone(),
+            user_id: device.user_id.clone(),
+            device_os: device.device_os.clone(),
+            status: device.status.clone(),
+            registered_at: device.registered_at.unwrap_or_default(),
+        }
+    }
+}
+
+impl PatchDeviceDto {
+    /// Converts the patched fields into an update recorded as made by `modified_by`.
+    pub fn into_update(self, modified_by: String) -> UpdateDeviceDto {
+        UpdateDeviceDto {
+            name: Some(self.name),
+            user_id: Some(self.user_id),
+            device_os: Some(self.device_os),
+            status: Some(self.status),
+            registered_at: Some(self.registered_at),
+            modified_by,
+        }
+    }
+}
+
 #[derive(Debug, Deserialize, serde::Serialize, ToSchema)]
 pub struct UpdateManyDevicesDto {
     pub devices: Vec<UpdateDeviceDtoWithIdDto>,
@@ -1,8 +1,10 @@
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
 use sqlx::QueryBuilder;
 use sqlx::{PgPool, Postgres, Transaction};
 use uuid::Uuid;
 
+use crate::common::{pagination::PageRequest, tenant};
 use crate::domains::device::domain::model::Device;
 use crate::domains::device::domain::repository::DeviceRepository;
 use crate::domains::device::dto::device_dto::{
@@ -22,48 +24,147 @@ const FIND_DEVICE_INFO_QUERY: &str = r#"
         created_by,
         created_at,
         modified_by,
-        modified_at
+        modified_at,
+        version,
+        deleted_by,
+        deleted_at
     from
         devices
     where
         id = $1
+        and deleted_at is null
+        and ($2::varchar is null or org_id = $2)
     "#;
 
+const FIND_DEVICES_QUERY: &str = r#"
+    select
+        d.id,
+        d.user_id,
+        d.name,
+        d.status,
+        d.device_os,
+        d.registered_at,
+        d.created_by,
+        d.created_at,
+        d.modified_by,
+        d.modified_at,
+        d.version,
+        d.deleted_by,
+        d.deleted_at
+    from
+        devices d
+    where 1=1
+    "#;
+
+const COUNT_DEVICES_QUERY: &str = r#"
+    select count(*)
+    from devices d
+    where 1=1
+    "#;
+
+/// Owners of the devices of a list.
+enum Owners {
+    All,
+    User(String),
+    /// The members of a group.
+    Group(String),
+}
+
+/// Fetches a page of the devices of the given owners and counts all of them.
+async fn find_page(
+    pool: PgPool,
+    owners: Owners,
+    page: &PageRequest,
+    include_deleted: bool,
+) -> Result<(Vec<Device>, i64), sqlx::Error> {
+    let push_owner = |builder: &mut QueryBuilder<'_, Postgres>| {
+        tenant::push_condition(builder, "d");
+        if !include_deleted {
+            builder.push(" and d.deleted_at is null");
+        }
+        match &owners {
+            Owners::All => {}
+            Owners::User(user_id) => {
+                builder.push(" and d.user_id = ");
+                builder.push_bind(user_id.clone());
+            }
+            Owners::Group(group_id) => {
+                builder.push(
+                    " and d.user_id in (select m.user_id from group_members m where m.group_id = ",
+                );
+                builder.push_bind(group_id.clone());
+                builder.push(")");
+            }
+        }
+    };
+
+    let mut count = QueryBuilder::<Postgres>::new(COUNT_DEVICES_QUERY);
+    push_owner(&mut count);
+    let total = count.build_query_scalar::<i64>().fetch_one(&pool).await?;
+
+    let mut builder = QueryBuilder::<Postgres>::new(FIND_DEVICES_QUERY);
+    push_owner(&mut builder);
+    page.push_to(&mut builder, "d");
+
+    let devices = builder.build_query_as::<Device>().fetch_all(&pool).await?;
+
+    Ok((devices, total))
+}
+
 #[async_trait]
 impl DeviceRepository for DeviceRepo {
-    async fn find_all(&self, pool: PgPool) -> Result<Vec<Device>, sqlx::Error> {
-        let devices = sqlx::query_as::<_, Device>(
-            r#"
-            select
-                id,
-                user_id,
-                name,
-                status,
-                device_os,
-                registered_at,
-                created_by,
-                created_at,
-                modified_by,
-                modified_at
-            from
-                devices
-            "#,
-        )
-        .fetch_all(&pool)
-        .await?;
+    async fn find_all(
+        &self,
+        pool: PgPool,
+        page: &PageRequest,
+        include_deleted: bool,
+    ) -> Result<(Vec<Device>, i64), sqlx::Error> {
+        find_page(pool, Owners::All, page, include_deleted).await
+    }
+
+    async fn find_by_user_id(
+        &self,
+        pool: PgPool,
+        user_id: String,
+        page: &PageRequest,
+    ) -> Result<(Vec<Device>, i64), sqlx::Error> {
+        find_page(pool, Owners::User(user_id), page, false).await
+    }
 
-        Ok(devices)
+    async fn find_by_group_id(
+        &self,
+        pool: PgPool,
+        group_id: String,
+        page: &PageRequest,
+    ) -> Result<(Vec<Device>, i64), sqlx::Error> {
+        find_page(pool, Owners::Group(group_id), page, false).await
     }
 
     async fn find_by_id(&self, pool: PgPool, id: String) -> Result<Option<Device>, sqlx::Error> {
         let device = sqlx::query_as::<_, Device>(FIND_DEVICE_INFO_QUERY)
             .bind(id)
+            .bind(tenant::current())
             .fetch_optional(&pool)
             .await?;
 
         Ok(device)
     }
 
+    async fn find_for_update(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        id: String,
+    ) -> Result<Option<Device>, sqlx::Error> {
+        let query = format!("{FIND_DEVICE_INFO_QUERY} for update");
+        let device = sqlx::query_as::<_, Device>(&query)
+            .bind(id)
+            .bind(tenant::current())
+            .fetch_optional(&mut **tx)
+            .await?;
+
+        Ok(device)
+    }
+
     async fn create(
         &self,
         tx: &mut Transaction<'_, Postgres>,
@@ -74,8 +175,8 @@ impl DeviceRepository for DeviceRepo {
         sqlx::query!(
             r#"
             INSERT INTO devices 
-            (id, user_id, name, status, device_os, registered_at, created_by, created_at, modified_by, modified_at) 
-            VALUES ($1, $2, $3, $4, $5, $6, $7, now(), $8, now())
+            (id, user_id, name, status, device_os, registered_at, created_by, created_at, modified_by, modified_at, org_id) 
+            VALUES ($1, $2, $3, $4, $5, $6, $7, now(), $8, now(), (SELECT org_id FROM users WHERE id = $2::varchar))
             "#,
             id.clone(),
             device.user_id.clone(),
@@ -91,6 +192,7 @@ impl DeviceRepository for DeviceRepo {
 
         let inserted_device = sqlx::query_as::<_, Device>(FIND_DEVICE_INFO_QUERY)
             .bind(id)
+            .bind(tenant::current())
             .fetch_one(&mut **tx)
             .await?;
 
@@ -103,14 +205,23 @@ impl DeviceRepository for DeviceRepo {
         id: String,
         device: UpdateDeviceDto,
     ) -> Result<Option<Device>, sqlx::Error> {
-        let existing = sqlx::query!(r#"SELECT id FROM devices WHERE id = $1"#, id)
-            .fetch_optional(&mut **tx)
-            .await?;
+        let existing = sqlx::query!(
+            r#"
+            SELECT id FROM devices
+             WHERE id = $1
+               AND deleted_at IS NULL
+               AND ($2::varchar IS NULL OR org_id = $2)
+            "#,
+            id,
+            tenant::current()
+        )
+        .fetch_optional(&mut **tx)
+        .await?;
 
         if existing.is_some() {
             let mut builder = QueryBuilder::<_>::new("UPDATE devices SET ");
 
-            builder.push(" modified_at = NOW()");
+            builder.push(" modified_at = NOW(), version = version + 1");
 
             if let Some(value) = device.user_id {
                 builder.push(", user_id = ").push_bind(value);
@@ -138,6 +249,7 @@ impl DeviceRepository for DeviceRepo {
 
             let updated_device = sqlx::query_as::<_, Device>(FIND_DEVICE_INFO_QUERY)
                 .bind(&id)
+                .bind(tenant::current())
                 .fetch_one(&mut **tx)
                 .await?;
 
@@ -153,11 +265,15 @@ impl DeviceRepository for DeviceRepo {
         user_id: String,
         modified_by: String,
         update_devices: UpdateManyDevicesDto,
-    ) -> Result<(), sqlx::Error> {
+    ) -> Result<u64, sqlx::Error> {
+        let org_id = sqlx::query_scalar!(r#"SELECT org_id FROM users WHERE id = $1"#, user_id)
+            .fetch_one(&mut **tx)
+            .await?;
+
         let mut builder = QueryBuilder::<_>::new(
             r#"
             INSERT INTO devices 
-            (id, user_id, name, status, device_os, registered_at, created_by, created_at, modified_by, modified_at)
+            (id, user_id, name, status, device_os, registered_at, created_by, created_at, modified_by, modified_at, org_id)
             "#,
         );
 
@@ -178,7 +294,8 @@ impl DeviceRepository for DeviceRepo {
             .push_bind(&modified_by)
             .push_bind(now)
             .push_bind(&modified_by)
-            .push_bind(now);
+            .push_bind(now)
+            .push_bind(&org_id);
         });
 
         builder.push(
@@ -188,25 +305,217 @@ impl DeviceRepository for DeviceRepo {
             status = EXCLUDED.status,
             device_os = EXCLUDED.device_os,
             modified_by = EXCLUDED.modified_by,
-            modified_at = EXCLUDED.modified_at
+            modified_at = EXCLUDED.modified_at,
+            version = devices.version + 1
+            WHERE devices.user_id = EXCLUDED.user_id
+              AND devices.deleted_at IS NULL
             "#,
         );
 
         let query = builder.build();
-        query.execute(&mut **tx).await?;
+        let res = query.execute(&mut **tx).await?;
 
-        Ok(())
+        Ok(res.rows_affected())
     }
 
     async fn delete(
         &self,
         tx: &mut Transaction<'_, Postgres>,
         id: String,
+        deleted_by: String,
     ) -> Result<bool, sqlx::Error> {
-        let res = sqlx::query!(r#"DELETE FROM devices WHERE id = $1"#, id)
-            .execute(&mut **tx)
-            .await?;
+        let res = sqlx::query!(
+            r#"
+            UPDATE devices
+               SET deleted_at = NOW(),
+                   deleted_by = $2,
+                   version = version + 1
+             WHERE id = $1
+               AND deleted_at IS NULL
+               AND ($3::varchar IS NULL OR org_id = $3)
+            "#,
+            id,
+            deleted_by,
+            tenant::current()
+        )
+        .execute(&mut **tx)
+        .await?;
 
         Ok(res.rows_affected() > 0)
     }
+
+    async fn restore(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        id: String,
+        modified_by: String,
+    ) -> Result<Option<Device>, sqlx::Error> {
+        let device = sqlx::query_as::<_, Device>(
+            r#"
+            UPDATE devices
+               SET deleted_at = NULL,
+                   deleted_by = NULL,
+                   modified_by = $2,
+                   modified_at = NOW(),
+                   version = version + 1
+             WHERE id = $1
+               AND deleted_at IS NOT NULL
+               AND ($3::varchar IS NULL OR org_id = $3)
+               AND EXISTS (SELECT 1 FROM users u WHERE u.id = devices.user_id AND u.deleted_at IS NULL)
+            RETURNING id, user_id, name, status, device_os, registered_at, created_by,
+                      created_at, modified_by, modified_at, version, deleted_by, deleted_at
+            "#,
+        )
+        .bind(id)
+        .bind(modified_by)
+        .bind(tenant::current())
+        .fetch_optional(&mut **tx)
+        .await?;
+
+        Ok(device)
+    }
+
+    async fn owner_is_deleted(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        id: String,
+    ) -> Result<bool, sqlx::Error> {
+        let deleted = sqlx::query_scalar!(
+            r#"
+            SELECT EXISTS (
+                SELECT 1 FROM devices d
+                  JOIN users u ON u.id = d.user_id
+                 WHERE d.id = $1
+                   AND d.deleted_at IS NOT NULL
+                   AND u.deleted_at IS NOT NULL
+                   AND ($2::varchar IS NULL OR d.org_id = $2)
+            ) AS "deleted!"
+            "#,
+            id,
+            tenant::current()
+        )
+        .fetch_one(&mut **tx)
+        .await?;
+
+        Ok(deleted)
+    }
+
+    async fn reassign_all(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        user_id: String,
+        new_owner: String,
+        modified_by: String,
+    ) -> Result<Vec<String>, sqlx::Error> {
+        let ids = sqlx::query_scalar!(
+            r#"
+            UPDATE devices
+               SET user_id = $2,
+                   modified_by = $3,
+                   modified_at = NOW(),
+                   version = version + 1
+             WHERE user_id = $1
+               AND deleted_at IS NULL
+               AND ($4::varchar IS NULL OR org_id = $4)
+            RETURNING id
+            "#,
+            user_id,
+            new_owner,
+            modified_by,
+            tenant::current()
+        )
+        .fetch_all(&mut **tx)
+        .await?;
+
+        Ok(ids)
+    }
+
+    async fn decommission_all(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        user_id: String,
+        deleted_by: String,
+    ) -> Result<Vec<String>, sqlx::Error> {
+        let ids = sqlx::query_scalar!(
+            r#"
+            UPDATE devices
+               SET status = 'decommissioned',
+                   modified_by = $2,
+                   modified_at = NOW(),
+                   version = version + 1,
+                   deleted_by = $2,
+                   deleted_at = NOW()
+             WHERE user_id = $1
+               AND deleted_at IS NULL
+               AND ($3::varchar IS NULL OR org_id = $3)
+            RETURNING id
+            "#,
+            user_id,
+            deleted_by,
+            tenant::current()
+        )
+        .fetch_all(&mut **tx)
+        .await?;
+
+        Ok(ids)
+    }
+
+    async fn purge(&self, pool: PgPool, deleted_before: DateTime<Utc>) -> Result<u64, sqlx::Error> {
+        let res = sqlx::query!(
+            r#"
+            DELETE FROM devices
+             WHERE deleted_at < $1
+               AND ($2::varchar IS NULL OR org_id = $2)
+            "#,
+            deleted_before,
+            tenant::current()
+        )
+        .execute(&pool)
+        .await?;
+
+        Ok(res.rows_affected())
+    }
+
+    async fn purge_by_user_ids(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        user_ids: Vec<String>,
+    ) -> Result<u64, sqlx::Error> {
+        let res = sqlx::query!(
+            r#"
+            DELETE FROM devices
+             WHERE user_id = ANY($1)
+               AND ($2::varchar IS NULL OR org_id = $2)
+            "#,
+            &user_ids,
+            tenant::current()
+        )
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(res.rows_affected())
+    }
+
+    async fn user_exists(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        user_id: String,
+    ) -> Result<bool, sqlx::Error> {
+        let exists = sqlx::query_scalar!(
+            r#"
+            SELECT EXISTS (
+                SELECT 1 FROM users
+                 WHERE id = $1
+                   AND deleted_at IS NULL
+                   AND ($2::varchar IS NULL OR org_id = $2)
+            ) AS "exists!"
+            "#,
+            user_id,
+            tenant::current()
+        )
+        .fetch_one(&mut **tx)
+        .await?;
+
+        Ok(exists)
+    }
 }
@@ -1,65 +1,131 @@
 use crate::{
-    common::error::AppError,
-    domains::device::{
-        domain::{repository::DeviceRepository, service::DeviceServiceTrait},
-        dto::device_dto::{CreateDeviceDto, DeviceDto, UpdateDeviceDto, UpdateManyDevicesDto},
-        infra::impl_repository::DeviceRepo,
+    common::{
+        concurrency::IfMatch,
+        error::{is_unique_violation, AppError},
+        jwt::Claims,
+        merge_patch::apply_merge_patch,
+        pagination::{PageQuery, PageRequest, Paginated},
+    },
+    domains::{
+        device::{
+            domain::{model::Device, repository::DeviceRepository, service::DeviceServiceTrait},
+            dto::device_dto::{
+                CreateDeviceDto, DeviceDto, PatchDeviceDto, UpdateDeviceDto, UpdateManyDevicesDto,
+            },
+            infra::impl_repository::DeviceRepo,
+        },
+        group::GroupServiceTrait,
     },
 };
 
 use async_trait::async_trait;
-use sqlx::PgPool;
+use chrono::{DateTime, Utc};
+use serde_json::Value;
+use sqlx::{PgPool, Postgres, Transaction};
 use std::sync::Arc;
 
 /// Service struct for handling device-related operations
 /// such as creating, updating, deleting, and fetching devices.
 /// It uses a repository pattern to abstract the data access layer.
+/// Access through group management is checked with the group service.
 #[derive(Clone)]
 pub struct DeviceService {
     pool: PgPool,
     repo: Arc<dyn DeviceRepository + Send + Sync>,
+    group_service: Arc<dyn GroupServiceTrait>,
 }
 
 /// Implementation of the DeviceService struct
 #[async_trait]
 impl DeviceServiceTrait for DeviceService {
     /// constructor for the service.
-    fn create_service(pool: PgPool) -> Arc<dyn DeviceServiceTrait> {
+    fn create_service(
+        pool: PgPool,
+        group_service: Arc<dyn GroupServiceTrait>,
+    ) -> Arc<dyn DeviceServiceTrait> {
         Arc::new(Self {
             pool,
             repo: Arc::new(DeviceRepo {}),
+            group_service,
         })
     }
 
     /// get device by id
-    async fn get_device_by_id(&self, id: String) -> Result<DeviceDto, AppError> {
-        match self.repo.find_by_id(self.pool.clone(), id).await {
-            Ok(Some(device)) => Ok(DeviceDto::from(device)),
-            Ok(None) => Err(AppError::NotFound("Device not found".into())),
+    async fn get_device_by_id(&self, claims: &Claims, id: String) -> Result<DeviceDto, AppError> {
+        let device = self.find_accessible(claims, id).await?;
+        Ok(DeviceDto::from(device))
+    }
+
+    /// get devices
+    /// Admins see every device; other users only see their own.
+    async fn get_devices(
+        &self,
+        claims: &Claims,
+        page: PageQuery,
+        include_deleted: Option<bool>,
+    ) -> Result<Paginated<DeviceDto>, AppError> {
+        let include_deleted = claims.include_deleted(include_deleted)?;
+        let page = PageRequest::try_from(page)?;
+        let devices = if claims.is_admin() {
+            self.repo
+                .find_all(self.pool.clone(), &page, include_deleted)
+                .await
+        } else {
+            self.repo
+                .find_by_user_id(self.pool.clone(), claims.sub.clone(), &page)
+                .await
+        };
+
+        match devices {
+            Ok((devices, total)) => Ok(page.paginate(devices, total).map(DeviceDto::from)),
             Err(err) => {
-                tracing::error!("Error fetching device: {err}");
+                tracing::error!("Error fetching devices: {err}");
                 Err(AppError::DatabaseError(err))
             }
         }
     }
 
-    /// get devices
-    async fn get_devices(&self) -> Result<Vec<DeviceDto>, AppError> {
-        match self.repo.find_all(self.pool.clone()).await {
-            Ok(devices) => {
-                let device_dtos: Vec<DeviceDto> = devices.into_iter().map(Into::into).collect();
-                Ok(device_dtos)
-            }
+    /// get the devices of the members of a group
+    async fn get_group_devices(
+        &self,
+        claims: &Claims,
+        group_id: String,
+        page: PageQuery,
+    ) -> Result<Paginated<DeviceDto>, AppError> {
+        self.group_service
+            .check_group_manager(claims, &group_id)
+            .await?;
+
+        let page = PageRequest::try_from(page)?;
+        match self
+            .repo
+            .find_by_group_id(self.pool.clone(), group_id, &page)
+            .await
+        {
+            Ok((devices, total)) => Ok(page.paginate(devices, total).map(DeviceDto::from)),
             Err(err) => {
-                tracing::error!("Error fetching devices: {err}");
+                tracing::error!("Error fetching group devices: {err}");
                 Err(AppError::DatabaseError(err))
             }
         }
     }
 
     /// create device
-    async fn create_device(&self, payload: CreateDeviceDto) -> Result<DeviceDto, AppError> {
+    async fn create_device(
+        &self,
+        claims: &Claims,
+        payload: CreateDeviceDto,
+    ) -> Result<DeviceDto, AppError> {
+        if !self
+            .group_service
+            .can_access_user(claims, &payload.user_id)
+            .await?
+        {
+            return Err(AppError::Forbidden);
+        }
+
         let mut tx = self.pool.begin().await?;
+        self.check_user_exists(&mut tx, &payload.user_id).await?;
         match self.repo.create(&mut tx, payload).await {
             Ok(device) => {
                 tx.commit().await?;
@@ -76,61 +142,197 @@ impl DeviceServiceTrait for DeviceService {
     /// update device
     async fn update_device(
         &self,
+        claims: &Claims,
         id: String,
         payload: UpdateDeviceDto,
+        if_match: &IfMatch,
     ) -> Result<DeviceDto, AppError> {
+        self.update_locked(claims, id, if_match, |_| Ok(payload))
+            .await
+    }
+
+    /// patch device
+    async fn patch_device(
+        &self,
+        claims: &Claims,
+        id: String,
+        patch: Value,
+        if_match: &IfMatch,
+    ) -> Result<DeviceDto, AppError> {
+        let modified_by = claims.actor_id().to_string();
+
+        self.update_locked(claims, id, if_match, |device| {
+            let patched = apply_merge_patch(&PatchDeviceDto::from(device), patch)?;
+            Ok(patched.into_update(modified_by))
+        })
+        .await
+    }
+
+    /// delete device
+    async fn delete_device(&self, claims: &Claims, id: String) -> Result<String, AppError> {
+        self.find_accessible(claims, id.clone()).await?;
+
         let mut tx = self.pool.begin().await?;
-        match self.repo.update(&mut tx, id, payload).await {
-            Ok(Some(device)) => {
+        match self
+            .repo
+            .delete(&mut tx, id, claims.actor_id().to_string())
+            .await
+        {
+            Ok(true) => {
                 tx.commit().await?;
-                Ok(DeviceDto::from(device))
+                Ok("Device deleted".into())
             }
-            Ok(None) => {
+            Ok(false) => {
                 tx.rollback().await?;
                 Err(AppError::NotFound("Device not found".into()))
             }
             Err(err) => {
-                tracing::error!("Error updating device: {err}");
+                tracing::error!("Error deleting device: {err}");
                 tx.rollback().await?;
                 Err(AppError::DatabaseError(err))
             }
         }
     }
 
-    /// delete device
-    async fn delete_device(&self, id: String) -> Result<String, AppError> {
+    /// restore device
+    /// The restored device must not share its name with another device of the owner,
+    /// and its owner must not be deleted.
+    async fn restore_device(&self, claims: &Claims, id: String) -> Result<DeviceDto, AppError> {
         let mut tx = self.pool.begin().await?;
-        match self.repo.delete(&mut tx, id).await {
-            Ok(true) => {
+        match self
+            .repo
+            .restore(&mut tx, id.clone(), claims.actor_id().to_string())
+            .await
+        {
+            Ok(Some(device)) => {
+                if !self
+                    .group_service
+                    .can_access_user(claims, &device.user_id)
+                    .await?
+                {
+                    tx.rollback().await?;
+                    return Err(AppError::Forbidden);
+                }
                 tx.commit().await?;
-                Ok("Device deleted".into())
+                Ok(DeviceDto::from(device))
             }
-            Ok(false) => {
+            Ok(None) => {
+                let owner_deleted = self.repo.owner_is_deleted(&mut tx, id).await;
                 tx.rollback().await?;
-                Err(AppError::NotFound("Device not found".into()))
+                match owner_deleted {
+                    Ok(true) => Err(AppError::Conflict("Device owner is deleted".into())),
+                    Ok(false) => Err(AppError::NotFound("Deleted device not found".into())),
+                    Err(err) => {
+                        tracing::error!("Error fetching device owner: {err}");
+                        Err(AppError::DatabaseError(err))
+                    }
+                }
+            }
+            Err(err) if is_unique_violation(&err) => {
+                tx.rollback().await?;
+                Err(AppError::Conflict("Device name already exists".into()))
             }
             Err(err) => {
-                tracing::error!("Error deleting device: {err}");
+                tracing::error!("Error restoring device: {err}");
                 tx.rollback().await?;
                 Err(AppError::DatabaseError(err))
             }
         }
     }
 
+    /// release the devices of a deleted user
+    /// The new owner must not have a device with the same name as one of the handed over devices.
+    async fn release_user_devices(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        user_id: &str,
+        new_owner: Option<&str>,
+        modified_by: &str,
+    ) -> Result<Vec<String>, AppError> {
+        let released = match new_owner {
+            Some(new_owner) => {
+                self.repo
+                    .reassign_all(
+                        tx,
+                        user_id.to_string(),
+                        new_owner.to_string(),
+                        modified_by.to_string(),
+                    )
+                    .await
+            }
+            None => {
+                self.repo
+                    .decommission_all(tx, user_id.to_string(), modified_by.to_string())
+                    .await
+            }
+        };
+
+        released.map_err(|err| {
+            if is_unique_violation(&err) {
+                return AppError::Conflict(
+                    "The new owner already has a device with the same name".into(),
+                );
+            }
+            tracing::error!("Error releasing devices: {err}");
+            AppError::DatabaseError(err)
+        })
+    }
+
+    /// purge deleted devices
+    async fn purge_deleted(&self, deleted_before: DateTime<Utc>) -> Result<u64, AppError> {
+        self.repo
+            .purge(self.pool.clone(), deleted_before)
+            .await
+            .map_err(|err| {
+                tracing::error!("Error purging devices: {err}");
+                AppError::DatabaseError(err)
+            })
+    }
+
+    /// purge the devices of purged users
+    async fn purge_user_devices(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        user_ids: &[String],
+    ) -> Result<u64, AppError> {
+        self.repo
+            .purge_by_user_ids(tx, user_ids.to_vec())
+            .await
+            .map_err(|err| {
+                tracing::error!("Error purging devices of users: {err}");
+                AppError::DatabaseError(err)
+            })
+    }
+
     /// batch update device
+    /// Devices in the payload that already belong to another user are rejected
+    /// and the whole batch is rolled back.
     async fn update_many_devices(
         &self,
+        claims: &Claims,
         user_id: String,
         modified_by: String,
         payload: UpdateManyDevicesDto,
     ) -> Result<String, AppError> {
+        if !self.group_service.can_access_user(claims, &user_id).await? {
+            return Err(AppError::Forbidden);
+        }
+
+        let expected = payload.devices.len() as u64;
+
         let mut tx = self.pool.begin().await?;
+        self.check_user_exists(&mut tx, &user_id).await?;
         match self
             .repo
             .update_many(&mut tx, user_id, modified_by, payload)
             .await
         {
-            Ok(()) => {
+            Ok(affected) if affected < expected => {
+                tracing::error!("Batch update touched devices owned by another user");
+                tx.rollback().await?;
+                Err(AppError::Forbidden)
+            }
+            Ok(_) => {
                 tx.commit().await?;
                 Ok("Devices updated".into())
             }
@@ -142,3 +344,127 @@ impl DeviceServiceTrait for DeviceService {
         }
     }
 }
+
+/// Internal helper methods defined on `DeviceService`.
+impl DeviceService {
+    /// Updates a device with the payload built from its current state.
+    /// The device is locked while `if_match` is checked and the update applied,
+    /// so concurrent updates cannot overwrite each other.
+    async fn update_locked(
+        &self,
+        claims: &Claims,
+        id: String,
+        if_match: &IfMatch,
+        build: impl FnOnce(&Device) -> Result<UpdateDeviceDto, AppError> + Send,
+    ) -> Result<DeviceDto, AppError> {
+        let mut tx = self.pool.begin().await?;
+
+        match self
+            .update_in_tx(&mut tx, claims, id, if_match, build)
+            .await
+        {
+            Ok(device) => {
+                tx.commit().await?;
+                Ok(DeviceDto::from(device))
+            }
+            Err(err) => {
+                tx.rollback().await?;
+                Err(err)
+            }
+        }
+    }
+
+    /// Locks a device, checks access and `if_match`, and applies the payload
+    /// built from the current state.
+    async fn update_in_tx(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        claims: &Claims,
+        id: String,
+        if_match: &IfMatch,
+        build: impl FnOnce(&Device) -> Result<UpdateDeviceDto, AppError> + Send,
+    ) -> Result<Device, AppError> {
+        let current = match self.repo.find_for_update(tx, id.clone()).await {
+            Ok(Some(device)) => device,
+            Ok(None) => return Err(AppError::NotFound("Device not found".into())),
+            Err(err) => {
+                tracing::error!("Error fetching device: {err}");
+                return Err(AppError::DatabaseError(err));
+            }
+        };
+
+        if !self
+            .group_service
+            .can_access_user(claims, &current.user_id)
+            .await?
+        {
+            return Err(AppError::Forbidden);
+        }
+
+        if_match.check(current.version)?;
+        let payload = build(&current)?;
+
+        // Handing a device over to another user requires access to that user as well.
+        if let Some(new_owner) = &payload.user_id {
+            if !self
+                .group_service
+                .can_access_user(claims, new_owner)
+                .await?
+            {
+                return Err(AppError::Forbidden);
+            }
+            self.check_user_exists(tx, new_owner).await?;
+        }
+
+        match self.repo.update(tx, id, payload).await {
+            Ok(Some(device)) => Ok(device),
+            Ok(None) => Err(AppError::NotFound("Device not found".into())),
+            Err(err) if is_unique_violation(&err) => {
+                Err(AppError::Conflict("Device name already exists".into()))
+            }
+            Err(err) => {
+                tracing::error!("Error updating device: {err}");
+                Err(AppError::DatabaseError(err))
+            }
+        }
+    }
+
+    /// Fails with `NotFound` unless the user exists in the caller's organization.
+    async fn check_user_exists(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        user_id: &str,
+    ) -> Result<(), AppError> {
+        match self.repo.user_exists(tx, user_id.to_string()).await {
+            Ok(true) => Ok(()),
+            Ok(false) => Err(AppError::NotFound("User not found".into())),
+            Err(err) => {
+                tracing::error!("Error fetching user: {err}");
+                Err(AppError::DatabaseError(err))
+            }
+        }
+    }
+
+    /// Loads a device and checks that the caller owns it, manages a group
+    /// of its owner or is an admin.
+    async fn find_accessible(&self, claims: &Claims, id: String) -> Result<Device, AppError> {
+        let device = match self.repo.find_by_id(self.pool.clone(), id).await {
+            Ok(Some(device)) => device,
+            Ok(None) => return Err(AppError::NotFound("Device not found".into())),
+            Err(err) => {
+                tracing::error!("Error fetching device: {err}");
+                return Err(AppError::DatabaseError(err));
+            }
+        };
+
+        if !self
+            .group_service
+            .can_access_user(claims, &device.user_id)
+            .await?
+        {
+            return Err(AppError::Forbidden);
+        }
+
+        Ok(device)
+    }
+}
@@ -1,5 +1,5 @@
 mod api {
-    mod handlers;
+    pub(super) mod handlers;
     pub mod routes;
 }
 
@@ -19,6 +19,7 @@ mod infra {
 }
 
 // Re-export commonly used items for convenience
+pub use api::handlers::stream_file;
 pub use api::routes::{file_routes, FileApiDoc};
 pub use domain::service::FileServiceTrait;
 pub use dto::file_dto::FileDto;
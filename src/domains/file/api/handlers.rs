@@ -1,9 +1,16 @@
-use crate::common::{app_state::AppState, dto::RestApiResponse, error::AppError};
+use crate::common::{
+    app_state::AppState,
+    dto::{IncludeDeletedQuery, RestApiResponse},
+    error::AppError,
+    jwt::Claims,
+};
+use crate::domains::file::dto::file_dto::UploadedFileDto;
 use axum::{
     body::Body,
-    extract::{Path, State},
+    extract::{Path, Query, State},
     http::{header, StatusCode},
     response::{IntoResponse, Response},
+    Extension,
 };
 
 use std::path::Path as FilePath;
@@ -15,19 +22,38 @@ use tokio_util::io::ReaderStream;
 #[utoipa::path(
     get,
     path = "/file/{file_id}",
-    responses((status = 200, description = "Serve protected file")),
+    params(IncludeDeletedQuery),
+    responses(
+        (status = 200, description = "Serve protected file"),
+        (status = 403, description = "The file belongs to another user, or only admins may include deleted files"),
+        (status = 404, description = "File not found")
+    ),
     tag = "Files"
 )]
 /// Serve a protected file from the server's filesystem.
 pub async fn serve_protected_file(
     State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
     Path(file_id): Path<String>,
+    Query(deleted): Query<IncludeDeletedQuery>,
 ) -> Result<impl IntoResponse, AppError> {
-    let file_metadata = state.file_service.get_file_metadata(file_id).await?;
+    let file_metadata = state
+        .file_service
+        .get_file_metadata(&claims, file_id, deleted.include_deleted)
+        .await?;
 
     // If the file is not found, return a 404.
     let file_metadata = file_metadata.ok_or_else(|| AppError::NotFound("File not found".into()))?;
 
+    stream_file(&state, file_metadata).await
+}
+
+/// Streams a stored file as the response, with the file's MIME type as the content type.
+/// Returns a 404 error if the file is missing from the filesystem.
+pub async fn stream_file(
+    state: &AppState,
+    file_metadata: UploadedFileDto,
+) -> Result<Response, AppError> {
     // Build the full file system path.
     let assets_private_path = state.config.assets_private_path.clone();
     let base_dir = assets_private_path.as_str();
@@ -67,7 +93,7 @@ pub async fn serve_protected_file(
     Ok(response)
 }
 
-/// This function deletes a file from the server's filesystem and database.
+/// This function soft-deletes a file; it is removed from the filesystem when it is purged.
 /// It will return a success message if the deletion is successful, or an error if not.
 #[utoipa::path(
     delete,
@@ -75,11 +101,34 @@ pub async fn serve_protected_file(
     responses((status = 200, description = "Delete file")),
     tag = "Files"
 )]
-/// Delete a file from the server's filesystem and database.
+/// Soft-delete a file.
 pub async fn delete_file(
     State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
     Path(file_id): Path<String>,
 ) -> Result<impl IntoResponse, AppError> {
-    let message = state.file_service.delete_file(file_id).await?;
+    let message = state.file_service.delete_file(&claims, file_id).await?;
     Ok(RestApiResponse::success_with_message(message, ()))
 }
+
+/// This function restores a soft-deleted file.
+/// It will return the metadata of the restored file.
+#[utoipa::path(
+    post,
+    path = "/file/{file_id}/restore",
+    responses(
+        (status = 200, description = "Restore a deleted file", body = UploadedFileDto),
+        (status = 404, description = "No deleted file with this ID"),
+        (status = 409, description = "The owner already has a profile picture")
+    ),
+    tag = "Files"
+)]
+/// Restore a soft-deleted file.
+pub async fn restore_file(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(file_id): Path<String>,
+) -> Result<impl IntoResponse, AppError> {
+    let file = state.file_service.restore_file(&claims, file_id).await?;
+    Ok(RestApiResponse::success_with_message("File restored", file))
+}
@@ -1,7 +1,14 @@
 use super::handlers::*;
-use crate::{common::app_state::AppState, domains::file::dto::file_dto::UploadedFileDto};
+use crate::{
+    common::{
+        app_state::AppState,
+        permission::{require_permission, FILE_DELETE, FILE_READ},
+    },
+    domains::file::dto::file_dto::UploadedFileDto,
+};
 use axum::{
-    routing::{delete, get},
+    middleware,
+    routing::{delete, get, post},
     Router,
 };
 
@@ -15,6 +22,7 @@ use utoipa::{
     paths(
         serve_protected_file,
         delete_file,
+        restore_file,
     ),
     components(schemas(UploadedFileDto)),
     tags(
@@ -46,6 +54,17 @@ impl utoipa::Modify for FileApiDoc {
 
 pub fn file_routes() -> Router<AppState> {
     Router::new()
-        .route("/{file_id}", get(serve_protected_file))
-        .route("/{file_id}", delete(delete_file))
+        .route(
+            "/{file_id}",
+            get(serve_protected_file)
+                .route_layer(middleware::from_fn(require_permission(FILE_READ))),
+        )
+        .route(
+            "/{file_id}",
+            delete(delete_file).route_layer(middleware::from_fn(require_permission(FILE_DELETE))),
+        )
+        .route(
+            "/{file_id}/restore",
+            post(restore_file).route_layer(middleware::from_fn(require_permission(FILE_DELETE))),
+        )
 }
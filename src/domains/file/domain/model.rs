@@ -88,4 +88,6 @@ pub struct UploadedFile {
     pub created_at: DateTime<Utc>,
     pub modified_by: Option<String>,
     pub modified_at: DateTime<Utc>,
+    pub deleted_by: Option<String>,
+    pub deleted_at: Option<DateTime<Utc>>,
 }
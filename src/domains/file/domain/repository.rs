@@ -6,6 +6,7 @@ use crate::domains::file::dto::file_dto::CreateFileDto;
 use super::model::UploadedFile;
 
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
 use sqlx::{PgPool, Postgres, Transaction};
 
 #[async_trait]
@@ -20,23 +21,85 @@ pub trait FileRepository {
     ) -> Result<UploadedFile, sqlx::Error>;
 
     /// Finds a file record by its unique identifier.
+    /// A soft-deleted file is only found if `include_deleted` is set.
     async fn find_by_id(
         &self,
         pool: PgPool,
         id: String,
+        include_deleted: bool,
     ) -> Result<Option<UploadedFile>, sqlx::Error>;
 
-    /// Finds a file record associated with a specific user ID.
-    async fn find_by_user_id(
+    /// Finds the profile picture of a user that is not deleted.
+    async fn find_profile_picture(
         &self,
         pool: PgPool,
         user_id: String,
     ) -> Result<Option<UploadedFile>, sqlx::Error>;
 
-    /// Deletes a file record by its unique identifier using a transaction.
+    /// Soft-deletes a file record by its unique identifier using a transaction.
     async fn delete(
         &self,
         tx: &mut Transaction<'_, Postgres>,
         id: String,
+        deleted_by: String,
     ) -> Result<bool, sqlx::Error>;
+
+    /// Restores a soft-deleted file record and returns it,
+    /// or `None` if there is no such deleted file.
+    async fn restore(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        id: String,
+        modified_by: String,
+    ) -> Result<Option<UploadedFile>, sqlx::Error>;
+
+    /// Soft-deletes the profile picture of a user using a transaction and returns it,
+    /// or `None` if the user has no profile picture.
+    async fn delete_profile_picture(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        user_id: String,
+        deleted_by: String,
+    ) -> Result<Option<UploadedFile>, sqlx::Error>;
+
+    /// Hard-deletes the profile picture of a user that is not deleted and returns it,
+    /// so that the stored file can be removed once the transaction is committed.
+    async fn remove_profile_picture(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        user_id: String,
+    ) -> Result<Option<UploadedFile>, sqlx::Error>;
+
+    /// Soft-deletes every file record of a user that is not deleted yet and returns them.
+    async fn delete_by_user_id(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        user_id: String,
+        deleted_by: String,
+    ) -> Result<Vec<UploadedFile>, sqlx::Error>;
+
+    /// Restores the file records of a soft-deleted user that were deleted together with
+    /// the user, i.e. at the same time, and returns their number.
+    async fn restore_by_user_id(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        user_id: String,
+        modified_by: String,
+    ) -> Result<u64, sqlx::Error>;
+
+    /// Hard-deletes every file record of the given users, deleted or not, and returns them,
+    /// so that the stored files can be removed once the transaction is committed.
+    async fn purge_by_user_ids(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        user_ids: Vec<String>,
+    ) -> Result<Vec<UploadedFile>, sqlx::Error>;
+
+    /// Hard-deletes the file records soft-deleted before the given time
+    /// and returns them, so that the stored files can be removed as well.
+    async fn purge(
+        &self,
+        pool: PgPool,
+        deleted_before: DateTime<Utc>,
+    ) -> Result<Vec<UploadedFile>, sqlx::Error>;
 }
@@ -4,10 +4,11 @@
 use std::sync::Arc;
 
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
 use sqlx::{PgPool, Postgres, Transaction};
 
 use crate::{
-    common::{config::Config, error::AppError},
+    common::{config::Config, error::AppError, jwt::Claims},
     domains::file::dto::file_dto::{UploadFileDto, UploadedFileDto},
 };
 
@@ -29,10 +30,84 @@ pub trait FileServiceTrait: Send + Sync {
         upload_file_dto: &UploadFileDto,
     ) -> Result<Option<UploadedFileDto>, AppError>;
 
-    /// Retrieves file metadata by its file ID.
-    async fn get_file_metadata(&self, file_id: String)
+    /// Retrieves the current profile picture of a user, if any.
+    async fn get_profile_picture(&self, user_id: &str)
         -> Result<Option<UploadedFileDto>, AppError>;
 
-    /// Deletes a file by its file ID and returns a confirmation message.
-    async fn delete_file(&self, file_id: String) -> Result<String, AppError>;
+    /// Replaces the profile picture of a user within an active transaction.
+    /// Uploads the new picture and hard-deletes the record of the current one.
+    /// Returns the new picture and the replaced one, whose stored file is left
+    /// for `remove_stored_files`, to be called once the transaction is committed.
+    async fn replace_profile_picture(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        upload_file_dto: &UploadFileDto,
+    ) -> Result<(UploadedFileDto, Option<UploadedFileDto>), AppError>;
+
+    /// Soft-deletes the profile picture of a user within an active transaction
+    /// and returns it, or `None` if the user has no profile picture.
+    async fn delete_profile_picture(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        user_id: &str,
+        deleted_by: &str,
+    ) -> Result<Option<UploadedFileDto>, AppError>;
+
+    /// Retrieves file metadata by its file ID.
+    /// Only the owner of the file or an administrator may read it,
+    /// and only administrators may include soft-deleted files.
+    async fn get_file_metadata(
+        &self,
+        claims: &Claims,
+        file_id: String,
+        include_deleted: Option<bool>,
+    ) -> Result<Option<UploadedFileDto>, AppError>;
+
+    /// Soft-deletes a file by its file ID and returns a confirmation message.
+    /// The stored file is kept until the record is purged.
+    /// Only the owner of the file or an administrator may delete it.
+    async fn delete_file(&self, claims: &Claims, file_id: String) -> Result<String, AppError>;
+
+    /// Restores a soft-deleted file and returns its metadata.
+    /// A user cannot have two profile pictures that are not deleted.
+    async fn restore_file(
+        &self,
+        claims: &Claims,
+        file_id: String,
+    ) -> Result<UploadedFileDto, AppError>;
+
+    /// Soft-deletes every file of a user within an active transaction and returns
+    /// their metadata. The stored files are kept until the records are purged.
+    async fn delete_user_files(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        user_id: &str,
+        deleted_by: &str,
+    ) -> Result<Vec<UploadedFileDto>, AppError>;
+
+    /// Restores, within an active transaction, the files that were deleted together
+    /// with a soft-deleted user. Must be called before the user is restored.
+    async fn restore_user_files(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        user_id: &str,
+        modified_by: &str,
+    ) -> Result<(), AppError>;
+
+    /// Hard-deletes every file record of the given users within an active transaction,
+    /// as part of purging the users, and returns their metadata. The stored files are
+    /// left for `remove_stored_files`, to be called once the transaction is committed.
+    async fn purge_user_files(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        user_ids: &[String],
+    ) -> Result<Vec<UploadedFileDto>, AppError>;
+
+    /// Removes the stored files of the given records from the filesystem.
+    /// Files that cannot be removed are logged and left behind.
+    fn remove_stored_files(&self, files: &[UploadedFileDto]);
+
+    /// Hard-deletes the files soft-deleted before the given time, removes them from the
+    /// filesystem and returns their number.
+    async fn purge_deleted(&self, deleted_before: DateTime<Utc>) -> Result<u64, AppError>;
 }
@@ -50,4 +50,7 @@ pub struct UploadedFileDto {
     pub modified_by: Option<String>,
     #[serde(with = "crate::common::ts_format")]
     pub modified_at: DateTime<Utc>,
+    pub deleted_by: Option<String>,
+    #[serde(with = "crate::common::ts_format::option")]
+    pub deleted_at: Option<DateTime<Utc>>,
 }
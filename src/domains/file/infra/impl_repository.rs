@@ -1,8 +1,12 @@
-use crate::domains::file::{
-    domain::{model::UploadedFile, repository::FileRepository},
-    dto::file_dto::CreateFileDto,
+use crate::{
+    common::tenant,
+    domains::file::{
+        domain::{model::UploadedFile, repository::FileRepository},
+        dto::file_dto::CreateFileDto,
+    },
 };
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
 use sqlx::{PgPool, Postgres, Transaction};
 use uuid::Uuid;
 
@@ -13,9 +17,13 @@ const FIND_FILE_INFO_QUERY: &str = r#"
             content_type, file_size, file_type, created_by, 
             created_at, 
             modified_by,
-            modified_at
+            modified_at,
+            deleted_by,
+            deleted_at
     FROM uploaded_files 
     WHERE id = $1
+      AND ($2 OR deleted_at IS NULL)
+      AND ($3::varchar IS NULL OR org_id = $3)
 "#;
 
 #[async_trait]
@@ -30,8 +38,8 @@ impl FileRepository for FileRepo {
         sqlx::query!(
             r#"
             INSERT INTO uploaded_files
-              (id, user_id, file_name, origin_file_name, file_relative_path, file_url, content_type, file_size, file_type, created_by, modified_by)
-            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+              (id, user_id, file_name, origin_file_name, file_relative_path, file_url, content_type, file_size, file_type, created_by, modified_by, org_id)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, (SELECT org_id FROM users WHERE id = $2::varchar))
             "#,
             id.clone(),
             file.user_id.clone(),
@@ -50,13 +58,15 @@ impl FileRepository for FileRepo {
 
         let inserted_file = sqlx::query_as::<_, UploadedFile>(FIND_FILE_INFO_QUERY)
             .bind(id)
+            .bind(false)
+            .bind(tenant::current())
             .fetch_one(&mut **tx)
             .await?;
 
         Ok(inserted_file)
     }
 
-    async fn find_by_user_id(
+    async fn find_profile_picture(
         &self,
         pool: PgPool,
         user_id: String,
@@ -68,11 +78,17 @@ impl FileRepository for FileRepo {
                 content_type, file_size, file_type, created_by,
                 created_at,
                 modified_by,
-                modified_at
+                modified_at,
+                deleted_by,
+                deleted_at
             FROM uploaded_files 
             WHERE user_id = $1
+              AND file_type = 'profile_picture'
+              AND deleted_at IS NULL
+              AND ($2::varchar IS NULL OR org_id = $2)
             "#,
-            user_id
+            user_id,
+            tenant::current()
         )
         .fetch_optional(&pool)
         .await?;
@@ -84,9 +100,12 @@ impl FileRepository for FileRepo {
         &self,
         pool: PgPool,
         id: String,
+        include_deleted: bool,
     ) -> Result<Option<UploadedFile>, sqlx::Error> {
         let uploaded_file = sqlx::query_as::<_, UploadedFile>(FIND_FILE_INFO_QUERY)
             .bind(id)
+            .bind(include_deleted)
+            .bind(tenant::current())
             .fetch_optional(&pool)
             .await?;
 
@@ -97,11 +116,211 @@ impl FileRepository for FileRepo {
         &self,
         tx: &mut Transaction<'_, Postgres>,
         id: String,
+        deleted_by: String,
     ) -> Result<bool, sqlx::Error> {
-        let result = sqlx::query!(r#"DELETE FROM uploaded_files WHERE id = $1"#, id)
-            .execute(&mut **tx)
-            .await?;
+        let result = sqlx::query!(
+            r#"
+            UPDATE uploaded_files
+               SET deleted_at = NOW(),
+                   deleted_by = $2
+             WHERE id = $1
+               AND deleted_at IS NULL
+               AND ($3::varchar IS NULL OR org_id = $3)
+            "#,
+            id,
+            deleted_by,
+            tenant::current()
+        )
+        .execute(&mut **tx)
+        .await?;
 
         Ok(result.rows_affected() > 0)
     }
+
+    async fn restore(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        id: String,
+        modified_by: String,
+    ) -> Result<Option<UploadedFile>, sqlx::Error> {
+        let uploaded_file = sqlx::query_as::<_, UploadedFile>(
+            r#"
+            UPDATE uploaded_files
+               SET deleted_at = NULL,
+                   deleted_by = NULL,
+                   modified_by = $2,
+                   modified_at = NOW()
+             WHERE id = $1
+               AND deleted_at IS NOT NULL
+               AND ($3::varchar IS NULL OR org_id = $3)
+            RETURNING id, user_id, file_name, origin_file_name, file_relative_path, file_url,
+                      content_type, file_size, file_type, created_by, created_at,
+                      modified_by, modified_at, deleted_by, deleted_at
+            "#,
+        )
+        .bind(id)
+        .bind(modified_by)
+        .bind(tenant::current())
+        .fetch_optional(&mut **tx)
+        .await?;
+
+        Ok(uploaded_file)
+    }
+
+    async fn delete_profile_picture(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        user_id: String,
+        deleted_by: String,
+    ) -> Result<Option<UploadedFile>, sqlx::Error> {
+        let deleted = sqlx::query_as::<_, UploadedFile>(
+            r#"
+            UPDATE uploaded_files
+               SET deleted_at = NOW(),
+                   deleted_by = $2
+             WHERE user_id = $1
+               AND file_type = 'profile_picture'
+               AND deleted_at IS NULL
+               AND ($3::varchar IS NULL OR org_id = $3)
+            RETURNING id, user_id, file_name, origin_file_name, file_relative_path, file_url,
+                      content_type, file_size, file_type, created_by, created_at,
+                      modified_by, modified_at, deleted_by, deleted_at
+            "#,
+        )
+        .bind(user_id)
+        .bind(deleted_by)
+        .bind(tenant::current())
+        .fetch_optional(&mut **tx)
+        .await?;
+
+        Ok(deleted)
+    }
+
+    async fn remove_profile_picture(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        user_id: String,
+    ) -> Result<Option<UploadedFile>, sqlx::Error> {
+        let removed = sqlx::query_as::<_, UploadedFile>(
+            r#"
+            DELETE FROM uploaded_files
+             WHERE user_id = $1
+               AND file_type = 'profile_picture'
+               AND deleted_at IS NULL
+               AND ($2::varchar IS NULL OR org_id = $2)
+            RETURNING id, user_id, file_name, origin_file_name, file_relative_path, file_url,
+                      content_type, file_size, file_type, created_by, created_at,
+                      modified_by, modified_at, deleted_by, deleted_at
+            "#,
+        )
+        .bind(user_id)
+        .bind(tenant::current())
+        .fetch_optional(&mut **tx)
+        .await?;
+
+        Ok(removed)
+    }
+
+    async fn delete_by_user_id(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        user_id: String,
+        deleted_by: String,
+    ) -> Result<Vec<UploadedFile>, sqlx::Error> {
+        let deleted = sqlx::query_as::<_, UploadedFile>(
+            r#"
+            UPDATE uploaded_files
+               SET deleted_at = NOW(),
+                   deleted_by = $2
+             WHERE user_id = $1
+               AND deleted_at IS NULL
+               AND ($3::varchar IS NULL OR org_id = $3)
+            RETURNING id, user_id, file_name, origin_file_name, file_relative_path, file_url,
+                      content_type, file_size, file_type, created_by, created_at,
+                      modified_by, modified_at, deleted_by, deleted_at
+            "#,
+        )
+        .bind(user_id)
+        .bind(deleted_by)
+        .bind(tenant::current())
+        .fetch_all(&mut **tx)
+        .await?;
+
+        Ok(deleted)
+    }
+
+    async fn restore_by_user_id(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        user_id: String,
+        modified_by: String,
+    ) -> Result<u64, sqlx::Error> {
+        let res = sqlx::query(
+            r#"
+            UPDATE uploaded_files uf
+               SET deleted_at = NULL,
+                   deleted_by = NULL,
+                   modified_by = $2,
+                   modified_at = NOW()
+              FROM users u
+             WHERE u.id = $1
+               AND uf.user_id = u.id
+               AND uf.deleted_at = u.deleted_at
+               AND ($3::varchar IS NULL OR uf.org_id = $3)
+            "#,
+        )
+        .bind(user_id)
+        .bind(modified_by)
+        .bind(tenant::current())
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(res.rows_affected())
+    }
+
+    async fn purge_by_user_ids(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        user_ids: Vec<String>,
+    ) -> Result<Vec<UploadedFile>, sqlx::Error> {
+        let purged = sqlx::query_as::<_, UploadedFile>(
+            r#"
+            DELETE FROM uploaded_files
+             WHERE user_id = ANY($1)
+               AND ($2::varchar IS NULL OR org_id = $2)
+            RETURNING id, user_id, file_name, origin_file_name, file_relative_path, file_url,
+                      content_type, file_size, file_type, created_by, created_at,
+                      modified_by, modified_at, deleted_by, deleted_at
+            "#,
+        )
+        .bind(user_ids)
+        .bind(tenant::current())
+        .fetch_all(&mut **tx)
+        .await?;
+
+        Ok(purged)
+    }
+
+    async fn purge(
+        &self,
+        pool: PgPool,
+        deleted_before: DateTime<Utc>,
+    ) -> Result<Vec<UploadedFile>, sqlx::Error> {
+        let purged = sqlx::query_as::<_, UploadedFile>(
+            r#"
+            DELETE FROM uploaded_files
+             WHERE deleted_at < $1
+               AND ($2::varchar IS NULL OR org_id = $2)
+            RETURNING id, user_id, file_name, origin_file_name, file_relative_path, file_url,
+                      content_type, file_size, file_type, created_by, created_at,
+                      modified_by, modified_at, deleted_by, deleted_at
+            "#,
+        )
+        .bind(deleted_before)
+        .bind(tenant::current())
+        .fetch_all(&pool)
+        .await?;
+
+        Ok(purged)
+    }
 }
@@ -1,4 +1,9 @@
-use crate::common::{config::Config, error::AppError};
+use crate::common::{
+    config::Config,
+    error::{is_unique_violation, AppError},
+    jwt::Claims,
+    tenant,
+};
 use crate::domains::file::domain::model::FileType;
 use crate::domains::file::domain::repository::FileRepository;
 use crate::domains::file::domain::service::FileServiceTrait;
@@ -10,6 +15,7 @@ use std::path::Path as FilePath;
 use std::sync::Arc;
 
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
 
 /// Service struct for handling file-related operations
 /// such as uploading, deleting, and fetching files.
@@ -35,7 +41,8 @@ impl FileServiceTrait for FileService {
 
     /// Uploads a profile picture for a user.
     /// Validates the file, writes it to disk, and stores its metadata in the database.
-    /// Returns the uploaded file's metadata.
+    /// Returns the uploaded file's metadata; the written file is removed again if its
+    /// metadata cannot be stored.
     async fn process_profile_picture_upload(
         &self,
         tx: &mut Transaction<'_, Postgres>,
@@ -48,21 +55,23 @@ impl FileServiceTrait for FileService {
             return Err(AppError::InvalidFileData);
         }
 
+        let user_id = upload_file_dto
+            .user_id
+            .clone()
+            .ok_or(AppError::ValidationError("User ID is missing".into()))?;
+
         let (unique_filename, file_relative_path, file_path) =
             self.build_file_path(&file_dto.original_filename);
 
         self.write_file_to_disk(&file_path, &file_dto.data)?;
 
-        let file_url = format!(
-            "{}/profile/{}",
-            self.config.assets_private_url, &unique_filename
-        );
+        let file_url = format!("{}/{}", self.config.assets_private_url, &file_relative_path);
 
         let create_file_dto = CreateFileDto {
-            user_id: upload_file_dto.user_id.clone(),
+            user_id: Some(user_id),
             file_name: unique_filename,
             origin_file_name: file_dto.original_filename.clone(),
-            file_relative_path,
+            file_relative_path: file_relative_path.clone(),
             file_url,
             content_type: file_dto.content_type.clone(),
             file_size: file_dto.data.len() as u32,
@@ -70,29 +79,93 @@ impl FileServiceTrait for FileService {
             modified_by: upload_file_dto.modified_by.clone(),
         };
 
-        self.repo
-            .create_file(tx, create_file_dto)
+        match self.repo.create_file(tx, create_file_dto).await {
+            Ok(file) => Ok(Some(UploadedFileDto::from(file))),
+            Err(err) => {
+                tracing::error!("Error uploading file: {}", err);
+                self.remove_stored_file(&file_relative_path);
+                Err(AppError::DatabaseError(err))
+            }
+        }
+    }
+
+    /// Retrieves the profile picture of a user that is not deleted.
+    async fn get_profile_picture(
+        &self,
+        user_id: &str,
+    ) -> Result<Option<UploadedFileDto>, AppError> {
+        let uploaded_file = self
+            .repo
+            .find_profile_picture(self.pool.clone(), user_id.to_string())
             .await
             .map_err(|err| {
-                tracing::error!("Error uploading file: {}", err);
+                tracing::error!("Error retrieving file: {}", err);
                 AppError::DatabaseError(err)
             })?;
 
-        if let Some(user_id) = &upload_file_dto.user_id {
-            self.get_file_by_user(user_id.clone()).await
-        } else {
-            Err(AppError::ValidationError("User ID is missing".into()))
-        }
+        Ok(uploaded_file.map(UploadedFileDto::from))
+    }
+
+    /// Replaces the profile picture of a user within the given transaction.
+    /// The current record is removed first, so that the new one does not collide with it.
+    async fn replace_profile_picture(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        upload_file_dto: &UploadFileDto,
+    ) -> Result<(UploadedFileDto, Option<UploadedFileDto>), AppError> {
+        let user_id = upload_file_dto
+            .user_id
+            .clone()
+            .ok_or(AppError::ValidationError("User ID is missing".into()))?;
+
+        let replaced = self
+            .repo
+            .remove_profile_picture(tx, user_id)
+            .await
+            .map_err(|err| {
+                tracing::error!("Error removing profile picture: {}", err);
+                AppError::DatabaseError(err)
+            })?;
+
+        let uploaded = self
+            .process_profile_picture_upload(tx, upload_file_dto)
+            .await?
+            .ok_or(AppError::InternalError)?;
+
+        Ok((uploaded, replaced.map(UploadedFileDto::from)))
+    }
+
+    /// Soft-deletes the profile picture of a user within the given transaction.
+    /// The stored file is kept until the record is purged.
+    async fn delete_profile_picture(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        user_id: &str,
+        deleted_by: &str,
+    ) -> Result<Option<UploadedFileDto>, AppError> {
+        let deleted = self
+            .repo
+            .delete_profile_picture(tx, user_id.to_string(), deleted_by.to_string())
+            .await
+            .map_err(|err| {
+                tracing::error!("Error deleting profile picture: {}", err);
+                AppError::DatabaseError(err)
+            })?;
+
+        Ok(deleted.map(UploadedFileDto::from))
     }
 
     /// Retrieves the metadata of a file by its id.
     async fn get_file_metadata(
         &self,
+        claims: &Claims,
         file_id: String,
+        include_deleted: Option<bool>,
     ) -> Result<Option<UploadedFileDto>, AppError> {
+        let include_deleted = claims.include_deleted(include_deleted)?;
         let uploaded_file = self
             .repo
-            .find_by_id(self.pool.clone(), file_id.clone())
+            .find_by_id(self.pool.clone(), file_id.clone(), include_deleted)
             .await
             .map_err(|err| {
                 tracing::error!("Error retrieving file: {}", err);
@@ -100,74 +173,197 @@ impl FileServiceTrait for FileService {
             });
 
         match uploaded_file {
-            Ok(Some(file)) => Ok(Some(UploadedFileDto::from(file))),
+            Ok(Some(file)) if claims.can_access(&file.user_id) => {
+                Ok(Some(UploadedFileDto::from(file)))
+            }
+            Ok(Some(_)) => Err(AppError::Forbidden),
             Ok(None) => Ok(None),
             Err(err) => Err(err),
         }
     }
 
-    /// Deletes a file by its id.
-    /// Removes the file from the filesystem and deletes its metadata from the database.
+    /// Soft-deletes a file by its id.
+    /// The file stays on the filesystem until the purge job removes it.
     /// Returns a success message if the deletion was successful.
-    async fn delete_file(&self, file_id: String) -> Result<String, AppError> {
+    async fn delete_file(&self, claims: &Claims, file_id: String) -> Result<String, AppError> {
         let mut tx = self.pool.begin().await?;
 
         let to_delete_file = self
             .repo
-            .find_by_id(self.pool.clone(), file_id.clone())
+            .find_by_id(self.pool.clone(), file_id.clone(), false)
             .await
             .map_err(|err| {
                 tracing::error!("Error retrieving file: {}", err);
                 AppError::DatabaseError(err)
             })?;
 
-        if to_delete_file.is_none() {
-            return Err(AppError::NotFound("File not found".into()));
+        let to_delete_file =
+            to_delete_file.ok_or_else(|| AppError::NotFound("File not found".into()))?;
+
+        if !claims.can_access(&to_delete_file.user_id) {
+            return Err(AppError::Forbidden);
         }
 
-        let deletion_result = self.repo.delete(&mut tx, file_id).await.map_err(|err| {
-            tracing::error!("Error deleting file: {}", err);
-            AppError::DatabaseError(err)
-        })?;
+        let deletion_result = self
+            .repo
+            .delete(&mut tx, file_id, claims.actor_id().to_string())
+            .await
+            .map_err(|err| {
+                tracing::error!("Error deleting file: {}", err);
+                AppError::DatabaseError(err)
+            })?;
 
         if !deletion_result {
             return Err(AppError::NotFound("File not found".into()));
         }
 
-        let file_path = FilePath::new(self.config.assets_private_path.as_str())
-            .join(to_delete_file.unwrap().file_relative_path);
+        tx.commit().await?;
 
-        if std::fs::remove_file(&file_path).is_err() {
-            tracing::error!(
-                "Error deleting file from filesystem: {}",
-                file_path.to_str().unwrap()
-            );
-            return Err(AppError::InternalError);
+        Ok("File deleted successfully".into())
+    }
+
+    /// Restores a soft-deleted file by its id.
+    /// Only the owner of the file or an administrator may restore it.
+    async fn restore_file(
+        &self,
+        claims: &Claims,
+        file_id: String,
+    ) -> Result<UploadedFileDto, AppError> {
+        let mut tx = self.pool.begin().await?;
+
+        match self
+            .repo
+            .restore(&mut tx, file_id, claims.actor_id().to_string())
+            .await
+        {
+            Ok(Some(file)) if claims.can_access(&file.user_id) => {
+                tx.commit().await?;
+                Ok(UploadedFileDto::from(file))
+            }
+            Ok(Some(_)) => {
+                tx.rollback().await?;
+                Err(AppError::Forbidden)
+            }
+            Ok(None) => {
+                tx.rollback().await?;
+                Err(AppError::NotFound("Deleted file not found".into()))
+            }
+            Err(err) if is_unique_violation(&err) => {
+                tx.rollback().await?;
+                Err(AppError::Conflict(
+                    "User already has a profile picture".into(),
+                ))
+            }
+            Err(err) => {
+                tracing::error!("Error restoring file: {}", err);
+                tx.rollback().await?;
+                Err(AppError::DatabaseError(err))
+            }
         }
+    }
 
-        tx.commit().await?;
+    /// Hard-deletes the files soft-deleted before the given time.
+    /// Stored files that cannot be removed are logged and left behind.
+    async fn purge_deleted(&self, deleted_before: DateTime<Utc>) -> Result<u64, AppError> {
+        let purged = self
+            .repo
+            .purge(self.pool.clone(), deleted_before)
+            .await
+            .map_err(|err| {
+                tracing::error!("Error purging files: {}", err);
+                AppError::DatabaseError(err)
+            })?;
 
-        Ok("File deleted successfully".into())
+        for file in &purged {
+            self.remove_stored_file(&file.file_relative_path);
+        }
+
+        Ok(purged.len() as u64)
     }
-}
 
-/// Internal helper methods defined on `FileService`.
-impl FileService {
-    /// Retrieves file metadata associated with a given user ID from the repository.
-    async fn get_file_by_user(&self, user_id: String) -> Result<Option<UploadedFileDto>, AppError> {
-        let uploaded_file = self
+    /// Soft-deletes every file record of a user within the given transaction.
+    async fn delete_user_files(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        user_id: &str,
+        deleted_by: &str,
+    ) -> Result<Vec<UploadedFileDto>, AppError> {
+        let deleted = self
             .repo
-            .find_by_user_id(self.pool.clone(), user_id)
+            .delete_by_user_id(tx, user_id.to_string(), deleted_by.to_string())
             .await
             .map_err(|err| {
-                tracing::error!("Error retrieving file: {}", err);
+                tracing::error!("Error deleting files of user: {}", err);
                 AppError::DatabaseError(err)
-            });
+            })?;
 
-        match uploaded_file {
-            Ok(Some(file)) => Ok(Some(UploadedFileDto::from(file))),
-            Ok(None) => Ok(None),
-            Err(err) => Err(err),
+        Ok(deleted.into_iter().map(UploadedFileDto::from).collect())
+    }
+
+    async fn restore_user_files(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        user_id: &str,
+        modified_by: &str,
+    ) -> Result<(), AppError> {
+        match self
+            .repo
+            .restore_by_user_id(tx, user_id.to_string(), modified_by.to_string())
+            .await
+        {
+            Ok(_) => Ok(()),
+            Err(err) if is_unique_violation(&err) => Err(AppError::Conflict(
+                "User already has a profile picture".into(),
+            )),
+            Err(err) => {
+                tracing::error!("Error restoring files of user: {}", err);
+                Err(AppError::DatabaseError(err))
+            }
+        }
+    }
+
+    /// Hard-deletes every file record of the given users within the given transaction.
+    async fn purge_user_files(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        user_ids: &[String],
+    ) -> Result<Vec<UploadedFileDto>, AppError> {
+        let purged = self
+            .repo
+            .purge_by_user_ids(tx, user_ids.to_vec())
+            .await
+            .map_err(|err| {
+                tracing::error!("Error purging files of users: {}", err);
+                AppError::DatabaseError(err)
+            })?;
+
+        Ok(purged.into_iter().map(UploadedFileDto::from).collect())
+    }
+
+    /// Removes the stored files of the given records from the filesystem.
+    fn remove_stored_files(&self, files: &[UploadedFileDto]) {
+        for file in files {
+            self.remove_stored_file(&file.file_relative_path);
+        }
+    }
+}
+
+/// Internal helper methods defined on `FileService`.
+impl FileService {
+    /// Removes a stored file from the filesystem. A file that is already gone is not an error;
+    /// other failures are logged, as the record of the file no longer exists.
+    fn remove_stored_file(&self, file_relative_path: &str) {
+        let file_path =
+            FilePath::new(self.config.assets_private_path.as_str()).join(file_relative_path);
+
+        match std::fs::remove_file(&file_path) {
+            Ok(()) => {}
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+            Err(err) => tracing::error!(
+                "Error deleting file from filesystem: {}: {}",
+                file_path.display(),
+                err
+            ),
         }
     }
 
@@ -199,10 +395,13 @@ impl FileService {
     }
 
     /// Constructs a unique filename, relative path, and absolute disk path for the upload.
+    /// Files are stored in the directory of the current organization.
     fn build_file_path(&self, original_filename: &str) -> (String, String, std::path::PathBuf) {
+        let org_id = tenant::current_or_default();
         let base_dir = self.config.assets_private_path.as_str();
-        let base_dir_with_profile =
-            FilePath::new(base_dir).join(FileType::ProfilePicture.to_string());
+        let base_dir_with_profile = FilePath::new(base_dir)
+            .join(&org_id)
+            .join(FileType::ProfilePicture.to_string());
 
         let unique_filename = FileService::generate_unique_filename(
             original_filename,
@@ -210,7 +409,12 @@ impl FileService {
         );
         let file_path = base_dir_with_profile.join(&unique_filename);
 
-        let relative_path = format!("{}/{}", FileType::ProfilePicture, unique_filename);
+        let relative_path = format!(
+            "{}/{}/{}",
+            org_id,
+            FileType::ProfilePicture,
+            unique_filename
+        );
         (unique_filename, relative_path, file_path)
     }
 
@@ -0,0 +1,25 @@
+mod api {
+    mod handlers;
+    pub mod routes;
+}
+
+mod domain {
+    pub mod model;
+    pub mod repository;
+    pub mod service;
+}
+
+pub mod dto {
+    pub mod group_dto;
+}
+
+mod infra {
+    mod impl_repository;
+    pub mod impl_service;
+}
+
+// Re-export commonly used items for convenience
+pub use api::routes::{group_routes, GroupApiDoc};
+pub use domain::model::GroupRole;
+pub use domain::service::GroupServiceTrait;
+pub use infra::impl_service::GroupService;
@@ -0,0 +1,221 @@
+use crate::common::dto::RestApiResponse;
+use crate::common::{
+    app_state::AppState,
+    error::AppError,
+    jwt::Claims,
+    pagination::{PageQuery, Paginated},
+};
+
+use crate::domains::group::dto::group_dto::{
+    AddGroupMemberDto, GroupDto, GroupMemberDto, MembershipDto, SaveGroupDto,
+};
+use axum::{
+    extract::{Path, Query, State},
+    response::IntoResponse,
+    Extension, Json,
+};
+use validator::Validate;
+
+/// This function creates a router for getting all groups
+/// Admins get every group of their organization, other users the groups they belong to
+#[utoipa::path(
+    get,
+    path = "/group",
+    params(PageQuery),
+    responses((status = 200, description = "List groups", body = Paginated<GroupDto>)),
+    tag = "Groups"
+)]
+pub async fn get_groups(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Query(page): Query<PageQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    let groups = state.group_service.get_groups(&claims, page).await?;
+    Ok(RestApiResponse::success(groups))
+}
+
+/// This function creates a router for getting a group by ID
+/// It will return the group if the caller is a member or an admin
+#[utoipa::path(
+    get,
+    path = "/group/{id}",
+    responses(
+        (status = 200, description = "Get group by ID", body = GroupDto),
+        (status = 403, description = "The caller is not a member of the group"),
+        (status = 404, description = "Group not found")
+    ),
+    tag = "Groups"
+)]
+pub async fn get_group_by_id(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, AppError> {
+    let group = state.group_service.get_group_by_id(&claims, id).await?;
+    Ok(RestApiResponse::success(group))
+}
+
+/// This function creates a router for creating a new group
+/// It will return the created group
+#[utoipa::path(
+    post,
+    path = "/group",
+    request_body = SaveGroupDto,
+    responses(
+        (status = 200, description = "Create a new group", body = GroupDto),
+        (status = 409, description = "Group name already exists")
+    ),
+    tag = "Groups"
+)]
+pub async fn create_group(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Json(payload): Json<SaveGroupDto>,
+) -> Result<impl IntoResponse, AppError> {
+    payload.validate()?;
+
+    let group = state.group_service.create_group(&claims, payload).await?;
+    Ok(RestApiResponse::success(group))
+}
+
+/// This function creates a router for updating a group
+/// It will replace the name and description of the group
+#[utoipa::path(
+    put,
+    path = "/group/{id}",
+    request_body = SaveGroupDto,
+    responses(
+        (status = 200, description = "Update group", body = GroupDto),
+        (status = 404, description = "Group not found"),
+        (status = 409, description = "Group name already exists")
+    ),
+    tag = "Groups"
+)]
+pub async fn update_group(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<String>,
+    Json(payload): Json<SaveGroupDto>,
+) -> Result<impl IntoResponse, AppError> {
+    payload.validate()?;
+
+    let group = state
+        .group_service
+        .update_group(&claims, id, payload)
+        .await?;
+    Ok(RestApiResponse::success(group))
+}
+
+/// This function creates a router for deleting a group
+/// The users of the group are kept; only their memberships are removed
+#[utoipa::path(
+    delete,
+    path = "/group/{id}",
+    responses(
+        (status = 200, description = "Group deleted"),
+        (status = 404, description = "Group not found")
+    ),
+    tag = "Groups"
+)]
+pub async fn delete_group(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, AppError> {
+    let message = state.group_service.delete_group(&claims, id).await?;
+
+    Ok(RestApiResponse::success_with_message(message, ()))
+}
+
+/// This function creates a router for getting the members of a group
+#[utoipa::path(
+    get,
+    path = "/group/{id}/members",
+    responses(
+        (status = 200, description = "List the members of a group", body = Vec<GroupMemberDto>),
+        (status = 403, description = "The caller is not a member of the group"),
+        (status = 404, description = "Group not found")
+    ),
+    tag = "Groups"
+)]
+pub async fn get_group_members(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, AppError> {
+    let members = state.group_service.get_members(&claims, id).await?;
+    Ok(RestApiResponse::success(members))
+}
+
+/// This function creates a router for adding a user to a group
+/// Adding a member again changes its role
+#[utoipa::path(
+    put,
+    path = "/group/{id}/members/{user_id}",
+    request_body = AddGroupMemberDto,
+    responses(
+        (status = 200, description = "Add a member to a group", body = GroupMemberDto),
+        (status = 404, description = "Group or user not found")
+    ),
+    tag = "Groups"
+)]
+pub async fn add_group_member(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path((id, user_id)): Path<(String, String)>,
+    payload: Option<Json<AddGroupMemberDto>>,
+) -> Result<impl IntoResponse, AppError> {
+    let payload = payload.map(|Json(payload)| payload).unwrap_or_default();
+
+    let member = state
+        .group_service
+        .add_member(&claims, id, user_id, payload)
+        .await?;
+    Ok(RestApiResponse::success(member))
+}
+
+/// This function creates a router for removing a user from a group
+#[utoipa::path(
+    delete,
+    path = "/group/{id}/members/{user_id}",
+    responses(
+        (status = 200, description = "Group member removed"),
+        (status = 404, description = "Group or member not found")
+    ),
+    tag = "Groups"
+)]
+pub async fn remove_group_member(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path((id, user_id)): Path<(String, String)>,
+) -> Result<impl IntoResponse, AppError> {
+    let message = state
+        .group_service
+        .remove_member(&claims, id, user_id)
+        .await?;
+
+    Ok(RestApiResponse::success_with_message(message, ()))
+}
+
+/// This function creates a router for getting the groups of a user
+/// Users may list their own groups; admins those of any user
+#[utoipa::path(
+    get,
+    path = "/group/user/{user_id}",
+    responses(
+        (status = 200, description = "List the groups of a user", body = Vec<MembershipDto>),
+        (status = 403, description = "The caller may not see the groups of this user")
+    ),
+    tag = "Groups"
+)]
+pub async fn get_user_groups(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(user_id): Path<String>,
+) -> Result<impl IntoResponse, AppError> {
+    let groups = state
+        .group_service
+        .get_user_groups(&claims, user_id)
+        .await?;
+    Ok(RestApiResponse::success(groups))
+}
@@ -0,0 +1,114 @@
+use super::handlers::*;
+use crate::{
+    common::{
+        app_state::AppState,
+        permission::{require_permission, GROUP_READ, GROUP_WRITE},
+    },
+    domains::group::{
+        dto::group_dto::{
+            AddGroupMemberDto, GroupDto, GroupMemberDto, MembershipDto, SaveGroupDto,
+        },
+        GroupRole,
+    },
+};
+use axum::{
+    middleware,
+    routing::{delete, get, post, put},
+    Router,
+};
+
+use utoipa::{
+    openapi::security::{HttpAuthScheme, HttpBuilder, SecurityScheme},
+    OpenApi,
+};
+
+#[derive(OpenApi)]
+#[openapi(
+    paths(
+        get_groups,
+        get_group_by_id,
+        create_group,
+        update_group,
+        delete_group,
+        get_group_members,
+        add_group_member,
+        remove_group_member,
+        get_user_groups,
+    ),
+    components(schemas(
+        GroupDto,
+        SaveGroupDto,
+        GroupMemberDto,
+        AddGroupMemberDto,
+        MembershipDto,
+        GroupRole
+    )),
+    tags(
+        (name = "Group", description = "Group management endpoints")
+    ),
+    security(
+        ("bearer_auth" = [])
+    ),
+    modifiers(&GroupApiDoc)
+)]
+/// This struct is used to generate OpenAPI documentation for the group routes.
+pub struct GroupApiDoc;
+
+impl utoipa::Modify for GroupApiDoc {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.as_mut().unwrap();
+        components.add_security_scheme(
+            "bearer_auth",
+            SecurityScheme::Http(
+                HttpBuilder::new()
+                    .scheme(HttpAuthScheme::Bearer)
+                    .bearer_format("JWT")
+                    .description(Some("Input your `<your‑jwt>`"))
+                    .build(),
+            ),
+        )
+    }
+}
+
+/// This function creates a router for the group routes.
+/// It defines the routes and their corresponding handlers.
+pub fn group_routes() -> Router<AppState> {
+    Router::new()
+        .route(
+            "/",
+            get(get_groups).route_layer(middleware::from_fn(require_permission(GROUP_READ))),
+        )
+        .route(
+            "/",
+            post(create_group).route_layer(middleware::from_fn(require_permission(GROUP_WRITE))),
+        )
+        .route(
+            "/user/{user_id}",
+            get(get_user_groups).route_layer(middleware::from_fn(require_permission(GROUP_READ))),
+        )
+        .route(
+            "/{id}",
+            get(get_group_by_id).route_layer(middleware::from_fn(require_permission(GROUP_READ))),
+        )
+        .route(
+            "/{id}",
+            put(update_group).route_layer(middleware::from_fn(require_permission(GROUP_WRITE))),
+        )
+        .route(
+            "/{id}",
+            delete(delete_group).route_layer(middleware::from_fn(require_permission(GROUP_WRITE))),
+        )
+        .route(
+            "/{id}/members",
+            get(get_group_members).route_layer(middleware::from_fn(require_permission(GROUP_READ))),
+        )
+        .route(
+            "/{id}/members/{user_id}",
+            put(add_group_member).route_layer(middleware::from_fn(require_permission(GROUP_WRITE))),
+        )
+        .route(
+            "/{id}/members/{user_id}",
+            delete(remove_group_member)
+                .route_layer(middleware::from_fn(require_permission(GROUP_WRITE))),
+        )
+}
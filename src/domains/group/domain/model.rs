@@ -0,0 +1,113 @@
+//! Domain model definitions for groups, i.e. teams of users within an organization,
+//! and their memberships.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{
+    decode::Decode,
+    postgres::{PgTypeInfo, PgValueRef},
+    FromRow, Postgres, Type,
+};
+use std::{fmt, str::FromStr};
+use utoipa::ToSchema;
+
+use crate::common::{
+    error::AppError,
+    pagination::{Keyset, SortValue},
+};
+
+/// Enum representing the role of a user within a group.
+/// Managers may access the devices of every member of their groups.
+#[derive(PartialEq, Debug, Clone, Copy, Default, Serialize, Deserialize, ToSchema)]
+pub enum GroupRole {
+    #[default]
+    #[serde(rename = "member")]
+    Member,
+    #[serde(rename = "manager")]
+    Manager,
+}
+
+impl fmt::Display for GroupRole {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            GroupRole::Member => "member",
+            GroupRole::Manager => "manager",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+impl FromStr for GroupRole {
+    type Err = AppError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "member" => Ok(GroupRole::Member),
+            "manager" => Ok(GroupRole::Manager),
+            _ => Err(AppError::ValidationError(format!(
+                "Invalid group role: {s}"
+            ))),
+        }
+    }
+}
+
+impl<'r> Decode<'r, Postgres> for GroupRole {
+    fn decode(value: PgValueRef<'r>) -> Result<Self, sqlx::error::BoxDynError> {
+        let s = <&str as Decode<Postgres>>::decode(value)?;
+        Ok(GroupRole::from_str(s)?)
+    }
+}
+
+impl Type<Postgres> for GroupRole {
+    fn type_info() -> PgTypeInfo {
+        <&str as Type<Postgres>>::type_info()
+    }
+
+    fn compatible(ty: &PgTypeInfo) -> bool {
+        <&str as Type<Postgres>>::compatible(ty)
+    }
+}
+
+/// Domain model representing a group of users.
+#[derive(Debug, Clone, FromRow)]
+pub struct Group {
+    pub id: String,
+    pub org_id: String,
+    pub name: String,
+    pub description: Option<String>,
+    pub created_by: Option<String>,
+    pub created_at: Option<DateTime<Utc>>,
+    pub modified_by: Option<String>,
+    pub modified_at: Option<DateTime<Utc>>,
+}
+
+/// Group lists are ordered by creation time only.
+impl Keyset for Group {
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn sort_value(&self, _field: &str) -> SortValue {
+        SortValue::time(self.created_at)
+    }
+}
+
+/// A member of a group, with the username of the user.
+#[derive(Debug, Clone, FromRow)]
+pub struct GroupMember {
+    pub group_id: String,
+    pub user_id: String,
+    pub username: String,
+    pub role: GroupRole,
+    pub created_by: Option<String>,
+    pub created_at: Option<DateTime<Utc>>,
+}
+
+/// A group a user belongs to, with the role of the user in it.
+#[derive(Debug, Clone, FromRow)]
+pub struct Membership {
+    pub group_id: String,
+    pub name: String,
+    pub description: Option<String>,
+    pub role: GroupRole,
+    pub created_at: Option<DateTime<Utc>>,
+}
@@ -0,0 +1,114 @@
+// This module defines the `GroupRepository` trait, which abstracts
+// the database operations related to groups and their members.
+
+use crate::common::pagination::PageRequest;
+use crate::domains::group::dto::group_dto::SaveGroupDto;
+
+use super::model::{Group, GroupMember, GroupRole, Membership};
+
+use async_trait::async_trait;
+use sqlx::{PgPool, Postgres, Transaction};
+
+#[async_trait]
+/// Trait representing repository-level operations for groups and group memberships.
+/// Every operation is restricted to the groups of the current tenant.
+pub trait GroupRepository: Send + Sync {
+    /// Retrieves a page of all groups, together with the total number of groups.
+    async fn find_all(
+        &self,
+        pool: PgPool,
+        page: &PageRequest,
+    ) -> Result<(Vec<Group>, i64), sqlx::Error>;
+
+    /// Retrieves a page of the groups the given user belongs to,
+    /// together with the total number of these groups.
+    async fn find_by_member(
+        &self,
+        pool: PgPool,
+        user_id: String,
+        page: &PageRequest,
+    ) -> Result<(Vec<Group>, i64), sqlx::Error>;
+
+    /// Finds a group by its unique identifier.
+    async fn find_by_id(&self, pool: PgPool, id: String) -> Result<Option<Group>, sqlx::Error>;
+
+    /// Creates a new group in the current tenant within the given transaction.
+    async fn create(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        group: SaveGroupDto,
+        created_by: String,
+    ) -> Result<Group, sqlx::Error>;
+
+    /// Replaces the name and description of a group and returns it,
+    /// or `None` if there is no such group.
+    async fn update(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        id: String,
+        group: SaveGroupDto,
+        modified_by: String,
+    ) -> Result<Option<Group>, sqlx::Error>;
+
+    /// Deletes a group together with its memberships.
+    async fn delete(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        id: String,
+    ) -> Result<bool, sqlx::Error>;
+
+    /// Retrieves the members of a group that are not deleted, ordered by username.
+    async fn find_members(
+        &self,
+        pool: PgPool,
+        group_id: String,
+    ) -> Result<Vec<GroupMember>, sqlx::Error>;
+
+    /// Returns the role of a user in a group, or `None` if the user is not a member.
+    async fn find_role(
+        &self,
+        pool: PgPool,
+        group_id: String,
+        user_id: String,
+    ) -> Result<Option<GroupRole>, sqlx::Error>;
+
+    /// Adds a user to a group, or changes the role of a member, and returns the member.
+    async fn add_member(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        group_id: String,
+        user_id: String,
+        role: GroupRole,
+        created_by: String,
+    ) -> Result<GroupMember, sqlx::Error>;
+
+    /// Removes a user from a group.
+    async fn remove_member(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        group_id: String,
+        user_id: String,
+    ) -> Result<bool, sqlx::Error>;
+
+    /// Retrieves the groups a user belongs to, with the role of the user, ordered by name.
+    async fn find_memberships(
+        &self,
+        pool: PgPool,
+        user_id: String,
+    ) -> Result<Vec<Membership>, sqlx::Error>;
+
+    /// Returns `true` if `manager_id` manages a group that `user_id` belongs to.
+    async fn manages_user(
+        &self,
+        pool: PgPool,
+        manager_id: String,
+        user_id: String,
+    ) -> Result<bool, sqlx::Error>;
+
+    /// Checks that a user who is not deleted exists in the current tenant.
+    async fn user_exists(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        user_id: String,
+    ) -> Result<bool, sqlx::Error>;
+}
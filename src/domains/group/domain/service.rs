@@ -0,0 +1,97 @@
+//! This module defines the `GroupServiceTrait` which encapsulates the business logic
+//! for managing groups of users and their members.
+
+use std::sync::Arc;
+
+use sqlx::PgPool;
+
+use crate::{
+    common::{
+        error::AppError,
+        jwt::Claims,
+        pagination::{PageQuery, Paginated},
+    },
+    domains::group::dto::group_dto::{
+        AddGroupMemberDto, GroupDto, GroupMemberDto, MembershipDto, SaveGroupDto,
+    },
+};
+
+#[async_trait::async_trait]
+/// Trait defining the contract for group-related business operations.
+/// Admins manage groups and their members; other users may only read
+/// the groups they belong to. Managers of a group may access the devices
+/// of its members, see `can_access_user`.
+pub trait GroupServiceTrait: Send + Sync {
+    /// constructor for the service.
+    fn create_service(pool: PgPool) -> Arc<dyn GroupServiceTrait>
+    where
+        Self: Sized;
+
+    /// Retrieves a page of the groups visible to the caller:
+    /// every group for admins, the groups they belong to for everyone else.
+    async fn get_groups(
+        &self,
+        claims: &Claims,
+        page: PageQuery,
+    ) -> Result<Paginated<GroupDto>, AppError>;
+
+    /// Retrieves a group by its unique ID.
+    async fn get_group_by_id(&self, claims: &Claims, id: String) -> Result<GroupDto, AppError>;
+
+    /// Creates a new group in the caller's organization.
+    async fn create_group(
+        &self,
+        claims: &Claims,
+        payload: SaveGroupDto,
+    ) -> Result<GroupDto, AppError>;
+
+    /// Replaces the name and description of a group.
+    async fn update_group(
+        &self,
+        claims: &Claims,
+        id: String,
+        payload: SaveGroupDto,
+    ) -> Result<GroupDto, AppError>;
+
+    /// Deletes a group together with its memberships.
+    async fn delete_group(&self, claims: &Claims, id: String) -> Result<String, AppError>;
+
+    /// Retrieves the members of a group.
+    async fn get_members(
+        &self,
+        claims: &Claims,
+        id: String,
+    ) -> Result<Vec<GroupMemberDto>, AppError>;
+
+    /// Adds a user to a group, or changes the role of a member.
+    async fn add_member(
+        &self,
+        claims: &Claims,
+        id: String,
+        user_id: String,
+        payload: AddGroupMemberDto,
+    ) -> Result<GroupMemberDto, AppError>;
+
+    /// Removes a user from a group.
+    async fn remove_member(
+        &self,
+        claims: &Claims,
+        id: String,
+        user_id: String,
+    ) -> Result<String, AppError>;
+
+    /// Retrieves the groups a user belongs to.
+    async fn get_user_groups(
+        &self,
+        claims: &Claims,
+        user_id: String,
+    ) -> Result<Vec<MembershipDto>, AppError>;
+
+    /// Returns `true` if the caller may act on the resources owned by `user_id`:
+    /// their own, any as an admin, and those of the members of the groups they manage.
+    async fn can_access_user(&self, claims: &Claims, user_id: &str) -> Result<bool, AppError>;
+
+    /// Checks that the caller may access the resources of every member of a group,
+    /// i.e. is an admin or a manager of the group.
+    async fn check_group_manager(&self, claims: &Claims, id: &str) -> Result<(), AppError>;
+}
@@ -0,0 +1,66 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use simple_dto_mapper_derive::DtoFrom;
+use utoipa::ToSchema;
+use validator::Validate;
+
+use crate::domains::group::domain::model::{Group, GroupMember, GroupRole, Membership};
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema, DtoFrom)]
+#[dto(from = Group)]
+pub struct GroupDto {
+    pub id: String,
+    pub org_id: String,
+    pub name: String,
+    pub description: Option<String>,
+    pub created_by: Option<String>,
+    #[serde(with = "crate::common::ts_format::option")]
+    pub created_at: Option<DateTime<Utc>>,
+    pub modified_by: Option<String>,
+    #[serde(with = "crate::common::ts_format::option")]
+    pub modified_at: Option<DateTime<Utc>>,
+}
+
+/// Name and description of a group, for creating it or replacing them.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema, Validate)]
+pub struct SaveGroupDto {
+    #[validate(length(
+        min = 1,
+        max = 128,
+        message = "Group name must be between 1 and 128 characters"
+    ))]
+    pub name: String,
+    #[validate(length(max = 255, message = "Description cannot exceed 255 characters"))]
+    pub description: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema, DtoFrom)]
+#[dto(from = GroupMember)]
+pub struct GroupMemberDto {
+    pub group_id: String,
+    pub user_id: String,
+    pub username: String,
+    pub role: GroupRole,
+    pub created_by: Option<String>,
+    #[serde(with = "crate::common::ts_format::option")]
+    pub created_at: Option<DateTime<Utc>>,
+}
+
+/// Role of a user being added to a group; `member` if left out.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema)]
+pub struct AddGroupMemberDto {
+    #[serde(default)]
+    pub role: GroupRole,
+}
+
+/// A group a user belongs to.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema, DtoFrom)]
+#[dto(from = Membership)]
+pub struct MembershipDto {
+    pub group_id: String,
+    pub name: String,
+    pub description: Option<String>,
+    pub role: GroupRole,
+    #[serde(with = "crate::common::ts_format::option")]
+    pub created_at: Option<DateTime<Utc>>,
+}
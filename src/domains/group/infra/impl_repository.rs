@@ -0,0 +1,351 @@
+use async_trait::async_trait;
+use sqlx::{PgPool, Postgres, QueryBuilder, Transaction};
+use uuid::Uuid;
+
+use crate::common::{pagination::PageRequest, tenant};
+use crate::domains::group::domain::model::{Group, GroupMember, GroupRole, Membership};
+use crate::domains::group::domain::repository::GroupRepository;
+use crate::domains::group::dto::group_dto::SaveGroupDto;
+
+pub struct GroupRepo;
+
+const FIND_GROUP_INFO_QUERY: &str = r#"
+    SELECT id, org_id, name, description, created_by, created_at, modified_by, modified_at
+    FROM groups
+    WHERE id = $1
+      AND ($2::varchar IS NULL OR org_id = $2)
+    "#;
+
+const FIND_GROUPS_QUERY: &str = r#"
+    SELECT g.id, g.org_id, g.name, g.description, g.created_by, g.created_at,
+           g.modified_by, g.modified_at
+    FROM groups g
+    WHERE 1=1
+    "#;
+
+const COUNT_GROUPS_QUERY: &str = r#"
+    SELECT COUNT(*)
+    FROM groups g
+    WHERE 1=1
+    "#;
+
+const FIND_MEMBER_QUERY: &str = r#"
+    SELECT m.group_id, m.user_id, u.username, m.role, m.created_by, m.created_at
+    FROM group_members m
+    JOIN users u ON u.id = m.user_id
+    WHERE m.group_id = $1
+      AND u.deleted_at IS NULL
+    "#;
+
+/// Fetches a page of groups, optionally only those of one member, and counts all of them.
+async fn find_page(
+    pool: PgPool,
+    member_id: Option<String>,
+    page: &PageRequest,
+) -> Result<(Vec<Group>, i64), sqlx::Error> {
+    let push_member = |builder: &mut QueryBuilder<'_, Postgres>| {
+        tenant::push_condition(builder, "g");
+        if let Some(member_id) = &member_id {
+            builder.push(
+                " AND EXISTS (SELECT 1 FROM group_members m WHERE m.group_id = g.id AND m.user_id = ",
+            );
+            builder.push_bind(member_id.clone());
+            builder.push(")");
+        }
+    };
+
+    let mut count = QueryBuilder::<Postgres>::new(COUNT_GROUPS_QUERY);
+    push_member(&mut count);
+    let total = count.build_query_scalar::<i64>().fetch_one(&pool).await?;
+
+    let mut builder = QueryBuilder::<Postgres>::new(FIND_GROUPS_QUERY);
+    push_member(&mut builder);
+    page.push_to(&mut builder, "g");
+
+    let groups = builder.build_query_as::<Group>().fetch_all(&pool).await?;
+
+    Ok((groups, total))
+}
+
+#[async_trait]
+impl GroupRepository for GroupRepo {
+    async fn find_all(
+        &self,
+        pool: PgPool,
+        page: &PageRequest,
+    ) -> Result<(Vec<Group>, i64), sqlx::Error> {
+        find_page(pool, None, page).await
+    }
+
+    async fn find_by_member(
+        &self,
+        pool: PgPool,
+        user_id: String,
+        page: &PageRequest,
+    ) -> Result<(Vec<Group>, i64), sqlx::Error> {
+        find_page(pool, Some(user_id), page).await
+    }
+
+    async fn find_by_id(&self, pool: PgPool, id: String) -> Result<Option<Group>, sqlx::Error> {
+        let group = sqlx::query_as::<_, Group>(FIND_GROUP_INFO_QUERY)
+            .bind(id)
+            .bind(tenant::current())
+            .fetch_optional(&pool)
+            .await?;
+
+        Ok(group)
+    }
+
+    async fn create(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        group: SaveGroupDto,
+        created_by: String,
+    ) -> Result<Group, sqlx::Error> {
+        let id = Uuid::new_v4().to_string();
+
+        sqlx::query!(
+            r#"
+            INSERT INTO groups (id, org_id, name, description, created_by, modified_by)
+            VALUES ($1, $2, $3, $4, $5, $5)
+            "#,
+            id.clone(),
+            tenant::current_or_default(),
+            group.name,
+            group.description,
+            created_by
+        )
+        .execute(&mut **tx)
+        .await?;
+
+        let inserted_group = sqlx::query_as::<_, Group>(FIND_GROUP_INFO_QUERY)
+            .bind(id)
+            .bind(tenant::current())
+            .fetch_one(&mut **tx)
+            .await?;
+
+        Ok(inserted_group)
+    }
+
+    async fn update(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        id: String,
+        group: SaveGroupDto,
+        modified_by: String,
+    ) -> Result<Option<Group>, sqlx::Error> {
+        let res = sqlx::query!(
+            r#"
+            UPDATE groups
+               SET name = $2,
+                   description = $3,
+                   modified_by = $4,
+                   modified_at = NOW()
+             WHERE id = $1
+               AND ($5::varchar IS NULL OR org_id = $5)
+            "#,
+            id.clone(),
+            group.name,
+            group.description,
+            modified_by,
+            tenant::current()
+        )
+        .execute(&mut **tx)
+        .await?;
+
+        if res.rows_affected() == 0 {
+            return Ok(None);
+        }
+
+        let updated_group = sqlx::query_as::<_, Group>(FIND_GROUP_INFO_QUERY)
+            .bind(id)
+            .bind(tenant::current())
+            .fetch_one(&mut **tx)
+            .await?;
+
+        Ok(Some(updated_group))
+    }
+
+    async fn delete(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        id: String,
+    ) -> Result<bool, sqlx::Error> {
+        let res = sqlx::query!(
+            r#"
+            DELETE FROM groups
+             WHERE id = $1
+               AND ($2::varchar IS NULL OR org_id = $2)
+            "#,
+            id,
+            tenant::current()
+        )
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(res.rows_affected() > 0)
+    }
+
+    async fn find_members(
+        &self,
+        pool: PgPool,
+        group_id: String,
+    ) -> Result<Vec<GroupMember>, sqlx::Error> {
+        let query = format!("{FIND_MEMBER_QUERY} ORDER BY u.username");
+        let members = sqlx::query_as::<_, GroupMember>(&query)
+            .bind(group_id)
+            .fetch_all(&pool)
+            .await?;
+
+        Ok(members)
+    }
+
+    async fn find_role(
+        &self,
+        pool: PgPool,
+        group_id: String,
+        user_id: String,
+    ) -> Result<Option<GroupRole>, sqlx::Error> {
+        let role = sqlx::query_scalar::<_, GroupRole>(
+            r#"
+            SELECT m.role
+            FROM group_members m
+            JOIN groups g ON g.id = m.group_id
+            WHERE m.group_id = $1
+              AND m.user_id = $2
+              AND ($3::varchar IS NULL OR g.org_id = $3)
+            "#,
+        )
+        .bind(group_id)
+        .bind(user_id)
+        .bind(tenant::current())
+        .fetch_optional(&pool)
+        .await?;
+
+        Ok(role)
+    }
+
+    async fn add_member(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        group_id: String,
+        user_id: String,
+        role: GroupRole,
+        created_by: String,
+    ) -> Result<GroupMember, sqlx::Error> {
+        sqlx::query!(
+            r#"
+            INSERT INTO group_members (group_id, user_id, role, created_by)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (group_id, user_id) DO UPDATE SET role = EXCLUDED.role
+            "#,
+            group_id.clone(),
+            user_id.clone(),
+            role.to_string(),
+            created_by
+        )
+        .execute(&mut **tx)
+        .await?;
+
+        let query = format!("{FIND_MEMBER_QUERY} AND m.user_id = $2");
+        let member = sqlx::query_as::<_, GroupMember>(&query)
+            .bind(group_id)
+            .bind(user_id)
+            .fetch_one(&mut **tx)
+            .await?;
+
+        Ok(member)
+    }
+
+    async fn remove_member(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        group_id: String,
+        user_id: String,
+    ) -> Result<bool, sqlx::Error> {
+        let res = sqlx::query!(
+            r#"DELETE FROM group_members WHERE group_id = $1 AND user_id = $2"#,
+            group_id,
+            user_id
+        )
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(res.rows_affected() > 0)
+    }
+
+    async fn find_memberships(
+        &self,
+        pool: PgPool,
+        user_id: String,
+    ) -> Result<Vec<Membership>, sqlx::Error> {
+        let memberships = sqlx::query_as::<_, Membership>(
+            r#"
+            SELECT g.id AS group_id, g.name, g.description, m.role, m.created_at
+            FROM group_members m
+            JOIN groups g ON g.id = m.group_id
+            WHERE m.user_id = $1
+              AND ($2::varchar IS NULL OR g.org_id = $2)
+            ORDER BY g.name
+            "#,
+        )
+        .bind(user_id)
+        .bind(tenant::current())
+        .fetch_all(&pool)
+        .await?;
+
+        Ok(memberships)
+    }
+
+    async fn manages_user(
+        &self,
+        pool: PgPool,
+        manager_id: String,
+        user_id: String,
+    ) -> Result<bool, sqlx::Error> {
+        let manages = sqlx::query_scalar!(
+            r#"
+            SELECT EXISTS (
+                SELECT 1
+                  FROM group_members manager
+                  JOIN group_members member ON member.group_id = manager.group_id
+                  JOIN groups g ON g.id = manager.group_id
+                 WHERE manager.user_id = $1
+                   AND manager.role = 'manager'
+                   AND member.user_id = $2
+                   AND ($3::varchar IS NULL OR g.org_id = $3)
+            ) AS "exists!"
+            "#,
+            manager_id,
+            user_id,
+            tenant::current()
+        )
+        .fetch_one(&pool)
+        .await?;
+
+        Ok(manages)
+    }
+
+    async fn user_exists(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        user_id: String,
+    ) -> Result<bool, sqlx::Error> {
+        let exists = sqlx::query_scalar!(
+            r#"
+            SELECT EXISTS (
+                SELECT 1 FROM users
+                 WHERE id = $1
+                   AND deleted_at IS NULL
+                   AND ($2::varchar IS NULL OR org_id = $2)
+            ) AS "exists!"
+            "#,
+            user_id,
+            tenant::current()
+        )
+        .fetch_one(&mut **tx)
+        .await?;
+
+        Ok(exists)
+    }
+}
@@ -0,0 +1,346 @@
+use crate::{
+    common::{
+        error::{is_unique_violation, AppError},
+        jwt::Claims,
+        pagination::{PageQuery, PageRequest, Paginated},
+    },
+    domains::group::{
+        domain::{
+            model::{Group, GroupRole},
+            repository::GroupRepository,
+            service::GroupServiceTrait,
+        },
+        dto::group_dto::{
+            AddGroupMemberDto, GroupDto, GroupMemberDto, MembershipDto, SaveGroupDto,
+        },
+        infra::impl_repository::GroupRepo,
+    },
+};
+
+use async_trait::async_trait;
+use sqlx::PgPool;
+use std::sync::Arc;
+
+/// Service struct for handling group-related operations
+/// such as creating groups, managing their members and
+/// checking the access granted through them.
+#[derive(Clone)]
+pub struct GroupService {
+    pool: PgPool,
+    repo: Arc<dyn GroupRepository + Send + Sync>,
+}
+
+/// Implementation of the GroupService struct
+#[async_trait]
+impl GroupServiceTrait for GroupService {
+    /// constructor for the service.
+    fn create_service(pool: PgPool) -> Arc<dyn GroupServiceTrait> {
+        Arc::new(Self {
+            pool,
+            repo: Arc::new(GroupRepo {}),
+        })
+    }
+
+    /// get groups
+    /// Admins see every group; other users only see their own.
+    async fn get_groups(
+        &self,
+        claims: &Claims,
+        page: PageQuery,
+    ) -> Result<Paginated<GroupDto>, AppError> {
+        let page = PageRequest::try_from(page)?;
+        let groups = if claims.is_admin() {
+            self.repo.find_all(self.pool.clone(), &page).await
+        } else {
+            self.repo
+                .find_by_member(self.pool.clone(), claims.sub.clone(), &page)
+                .await
+        };
+
+        match groups {
+            Ok((groups, total)) => Ok(page.paginate(groups, total).map(GroupDto::from)),
+            Err(err) => {
+                tracing::error!("Error fetching groups: {err}");
+                Err(AppError::DatabaseError(err))
+            }
+        }
+    }
+
+    /// get group by id
+    async fn get_group_by_id(&self, claims: &Claims, id: String) -> Result<GroupDto, AppError> {
+        let group = self.find_readable(claims, &id).await?;
+        Ok(GroupDto::from(group))
+    }
+
+    /// create group
+    async fn create_group(
+        &self,
+        claims: &Claims,
+        payload: SaveGroupDto,
+    ) -> Result<GroupDto, AppError> {
+        if !claims.is_admin() {
+            return Err(AppError::Forbidden);
+        }
+
+        let mut tx = self.pool.begin().await?;
+        match self
+            .repo
+            .create(&mut tx, payload, claims.actor_id().to_string())
+            .await
+        {
+            Ok(group) => {
+                tx.commit().await?;
+                Ok(GroupDto::from(group))
+            }
+            Err(err) if is_unique_violation(&err) => {
+                tx.rollback().await?;
+                Err(AppError::Conflict("Group name already exists".into()))
+            }
+            Err(err) => {
+                tracing::error!("Error creating group: {err}");
+                tx.rollback().await?;
+                Err(AppError::DatabaseError(err))
+            }
+        }
+    }
+
+    /// update group
+    async fn update_group(
+        &self,
+        claims: &Claims,
+        id: String,
+        payload: SaveGroupDto,
+    ) -> Result<GroupDto, AppError> {
+        if !claims.is_admin() {
+            return Err(AppError::Forbidden);
+        }
+
+        let mut tx = self.pool.begin().await?;
+        match self
+            .repo
+            .update(&mut tx, id, payload, claims.actor_id().to_string())
+            .await
+        {
+            Ok(Some(group)) => {
+                tx.commit().await?;
+                Ok(GroupDto::from(group))
+            }
+            Ok(None) => {
+                tx.rollback().await?;
+                Err(AppError::NotFound("Group not found".into()))
+            }
+            Err(err) if is_unique_violation(&err) => {
+                tx.rollback().await?;
+                Err(AppError::Conflict("Group name already exists".into()))
+            }
+            Err(err) => {
+                tracing::error!("Error updating group: {err}");
+                tx.rollback().await?;
+                Err(AppError::DatabaseError(err))
+            }
+        }
+    }
+
+    /// delete group
+    async fn delete_group(&self, claims: &Claims, id: String) -> Result<String, AppError> {
+        if !claims.is_admin() {
+            return Err(AppError::Forbidden);
+        }
+
+        let mut tx = self.pool.begin().await?;
+        match self.repo.delete(&mut tx, id).await {
+            Ok(true) => {
+                tx.commit().await?;
+                Ok("Group deleted".into())
+            }
+            Ok(false) => {
+                tx.rollback().await?;
+                Err(AppError::NotFound("Group not found".into()))
+            }
+            Err(err) => {
+                tracing::error!("Error deleting group: {err}");
+                tx.rollback().await?;
+                Err(AppError::DatabaseError(err))
+            }
+        }
+    }
+
+    /// get members of a group
+    async fn get_members(
+        &self,
+        claims: &Claims,
+        id: String,
+    ) -> Result<Vec<GroupMemberDto>, AppError> {
+        self.find_readable(claims, &id).await?;
+
+        match self.repo.find_members(self.pool.clone(), id).await {
+            Ok(members) => Ok(members.into_iter().map(GroupMemberDto::from).collect()),
+            Err(err) => {
+                tracing::error!("Error fetching group members: {err}");
+                Err(AppError::DatabaseError(err))
+            }
+        }
+    }
+
+    /// add a member to a group
+    /// Adding a member again only changes its role.
+    async fn add_member(
+        &self,
+        claims: &Claims,
+        id: String,
+        user_id: String,
+        payload: AddGroupMemberDto,
+    ) -> Result<GroupMemberDto, AppError> {
+        if !claims.is_admin() {
+            return Err(AppError::Forbidden);
+        }
+        self.find_group(&id).await?;
+
+        let mut tx = self.pool.begin().await?;
+        match self.repo.user_exists(&mut tx, user_id.clone()).await {
+            Ok(true) => {}
+            Ok(false) => return Err(AppError::NotFound("User not found".into())),
+            Err(err) => {
+                tracing::error!("Error fetching user: {err}");
+                return Err(AppError::DatabaseError(err));
+            }
+        }
+
+        match self
+            .repo
+            .add_member(
+                &mut tx,
+                id,
+                user_id,
+                payload.role,
+                claims.actor_id().to_string(),
+            )
+            .await
+        {
+            Ok(member) => {
+                tx.commit().await?;
+                Ok(GroupMemberDto::from(member))
+            }
+            Err(err) => {
+                tracing::error!("Error adding group member: {err}");
+                tx.rollback().await?;
+                Err(AppError::DatabaseError(err))
+            }
+        }
+    }
+
+    /// remove a member from a group
+    async fn remove_member(
+        &self,
+        claims: &Claims,
+        id: String,
+        user_id: String,
+    ) -> Result<String, AppError> {
+        if !claims.is_admin() {
+            return Err(AppError::Forbidden);
+        }
+        self.find_group(&id).await?;
+
+        let mut tx = self.pool.begin().await?;
+        match self.repo.remove_member(&mut tx, id, user_id).await {
+            Ok(true) => {
+                tx.commit().await?;
+                Ok("Group member removed".into())
+            }
+            Ok(false) => {
+                tx.rollback().await?;
+                Err(AppError::NotFound("Group member not found".into()))
+            }
+            Err(err) => {
+                tracing::error!("Error removing group member: {err}");
+                tx.rollback().await?;
+                Err(AppError::DatabaseError(err))
+            }
+        }
+    }
+
+    /// get the groups of a user
+    async fn get_user_groups(
+        &self,
+        claims: &Claims,
+        user_id: String,
+    ) -> Result<Vec<MembershipDto>, AppError> {
+        if !claims.can_access(&user_id) {
+            return Err(AppError::Forbidden);
+        }
+
+        match self.repo.find_memberships(self.pool.clone(), user_id).await {
+            Ok(memberships) => Ok(memberships.into_iter().map(MembershipDto::from).collect()),
+            Err(err) => {
+                tracing::error!("Error fetching group memberships: {err}");
+                Err(AppError::DatabaseError(err))
+            }
+        }
+    }
+
+    async fn can_access_user(&self, claims: &Claims, user_id: &str) -> Result<bool, AppError> {
+        if claims.can_access(user_id) {
+            return Ok(true);
+        }
+
+        self.repo
+            .manages_user(self.pool.clone(), claims.sub.clone(), user_id.to_string())
+            .await
+            .map_err(|err| {
+                tracing::error!("Error checking group membership: {err}");
+                AppError::DatabaseError(err)
+            })
+    }
+
+    async fn check_group_manager(&self, claims: &Claims, id: &str) -> Result<(), AppError> {
+        self.find_group(id).await?;
+        if claims.is_admin() {
+            return Ok(());
+        }
+
+        match self.find_role(id, &claims.sub).await? {
+            Some(GroupRole::Manager) => Ok(()),
+            _ => Err(AppError::Forbidden),
+        }
+    }
+}
+
+/// Internal helper methods defined on `GroupService`.
+impl GroupService {
+    /// Loads a group of the caller's organization.
+    async fn find_group(&self, id: &str) -> Result<Group, AppError> {
+        match self
+            .repo
+            .find_by_id(self.pool.clone(), id.to_string())
+            .await
+        {
+            Ok(Some(group)) => Ok(group),
+            Ok(None) => Err(AppError::NotFound("Group not found".into())),
+            Err(err) => {
+                tracing::error!("Error fetching group: {err}");
+                Err(AppError::DatabaseError(err))
+            }
+        }
+    }
+
+    /// Returns the role of a user in a group, if the user is a member.
+    async fn find_role(&self, id: &str, user_id: &str) -> Result<Option<GroupRole>, AppError> {
+        self.repo
+            .find_role(self.pool.clone(), id.to_string(), user_id.to_string())
+            .await
+            .map_err(|err| {
+                tracing::error!("Error fetching group role: {err}");
+                AppError::DatabaseError(err)
+            })
+    }
+
+    /// Loads a group and checks that the caller is a member or an admin.
+    async fn find_readable(&self, claims: &Claims, id: &str) -> Result<Group, AppError> {
+        let group = self.find_group(id).await?;
+        if !claims.is_admin() && self.find_role(id, &claims.sub).await?.is_none() {
+            return Err(AppError::Forbidden);
+        }
+
+        Ok(group)
+    }
+}
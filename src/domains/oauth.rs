@@ -0,0 +1,26 @@
+mod api {
+    mod handlers;
+    pub mod routes;
+}
+
+mod domain {
+    pub mod error;
+    pub mod model;
+    pub mod repository;
+    pub mod service;
+}
+
+pub mod dto {
+    pub mod oauth_dto;
+}
+
+mod infra {
+    mod impl_repository;
+    pub mod impl_service;
+}
+
+// Re-export commonly used items for convenience
+pub use api::routes::{oauth_routes, protected_oauth_routes, OAuthApiDoc};
+pub use domain::error::OAuthError;
+pub use domain::service::OAuthServiceTrait;
+pub use infra::impl_service::OAuthService;
@@ -0,0 +1,173 @@
+use crate::{
+    common::{
+        app_state::AppState, client_info::ClientInfo, dto::RestApiResponse, error::AppError,
+        jwt::Claims,
+    },
+    domains::oauth::{
+        domain::error::OAuthError,
+        dto::oauth_dto::{
+            AuthorizeQuery, CreateOAuthClientDto, CreatedOAuthClientDto, OAuthClientDto,
+            OAuthErrorDto, TokenRequestDto, TokenResponseDto,
+        },
+    },
+};
+use axum::{
+    extract::{Path, Query, State},
+    http::{
+        header::{AUTHORIZATION, CACHE_CONTROL, LOCATION, PRAGMA},
+        HeaderMap, StatusCode,
+    },
+    response::IntoResponse,
+    Extension, Form, Json,
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+
+/// this function creates a router for the token endpoint (RFC 6749, section 3.2)
+/// clients authenticate with HTTP Basic or with `client_id` and `client_secret` in the form
+#[utoipa::path(
+    post,
+    path = "/oauth/token",
+    request_body(content = TokenRequestDto, content_type = "application/x-www-form-urlencoded"),
+    responses(
+        (status = 200, description = "Tokens issued", body = TokenResponseDto),
+        (status = 400, description = "OAuth error", body = OAuthErrorDto),
+        (status = 401, description = "Client authentication failed", body = OAuthErrorDto)
+    ),
+    tag = "OAuth"
+)]
+pub async fn token(
+    State(state): State<AppState>,
+    client: ClientInfo,
+    headers: HeaderMap,
+    Form(mut payload): Form<TokenRequestDto>,
+) -> Result<impl IntoResponse, OAuthError> {
+    if let Some((client_id, client_secret)) = basic_credentials(&headers)? {
+        if payload.client_secret.is_some()
+            || payload
+                .client_id
+                .as_ref()
+                .is_some_and(|id| *id != client_id)
+        {
+            return Err(OAuthError::InvalidRequest(
+                "Client credentials must be sent only once".into(),
+            ));
+        }
+        payload.client_id = Some(client_id);
+        payload.client_secret = Some(client_secret);
+    }
+
+    let response = state.oauth_service.token(payload, &client).await?;
+    Ok((
+        [(CACHE_CONTROL, "no-store"), (PRAGMA, "no-cache")],
+        Json(response),
+    ))
+}
+
+/// Reads the client credentials of an `Authorization: Basic` header, if present.
+fn basic_credentials(headers: &HeaderMap) -> Result<Option<(String, String)>, OAuthError> {
+    let Some(value) = headers.get(AUTHORIZATION) else {
+        return Ok(None);
+    };
+    let Some(encoded) = value
+        .to_str()
+        .ok()
+        .and_then(|value| value.strip_prefix("Basic "))
+    else {
+        return Err(OAuthError::InvalidClient);
+    };
+
+    let decoded = STANDARD
+        .decode(encoded.trim())
+        .ok()
+        .and_then(|bytes| String::from_utf8(bytes).ok())
+        .ok_or(OAuthError::InvalidClient)?;
+    let (client_id, client_secret) = decoded.split_once(':').ok_or(OAuthError::InvalidClient)?;
+
+    Ok(Some((client_id.to_string(), client_secret.to_string())))
+}
+
+/// this function creates a router for the authorization endpoint (RFC 6749, section 3.1)
+/// the authenticated user is redirected back to the client with a code or an error
+#[utoipa::path(
+    get,
+    path = "/oauth/authorize",
+    params(AuthorizeQuery),
+    responses(
+        (status = 302, description = "Redirect to the client with a code or an error"),
+        (status = 400, description = "Unknown client or invalid redirect URI")
+    ),
+    tag = "OAuth",
+    security(("bearer_auth" = []))
+)]
+pub async fn authorize(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Query(query): Query<AuthorizeQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    let location = state.oauth_service.authorize(&claims, query).await?;
+    Ok((StatusCode::FOUND, [(LOCATION, location)]))
+}
+
+/// this function creates a router for registering an OAuth client
+#[utoipa::path(
+    post,
+    path = "/oauth/clients",
+    request_body = CreateOAuthClientDto,
+    responses(
+        (status = 200, description = "Register an OAuth client", body = CreatedOAuthClientDto),
+        (status = 400, description = "Invalid registration")
+    ),
+    tag = "OAuth",
+    security(("bearer_auth" = []))
+)]
+pub async fn create_client(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Json(payload): Json<CreateOAuthClientDto>,
+) -> Result<impl IntoResponse, AppError> {
+    let client = state.oauth_service.create_client(&claims, payload).await?;
+    Ok(RestApiResponse::success(client))
+}
+
+/// this function creates a router for listing the registered OAuth clients
+#[utoipa::path(
+    get,
+    path = "/oauth/clients",
+    responses((status = 200, description = "List OAuth clients", body = [OAuthClientDto])),
+    tag = "OAuth",
+    security(("bearer_auth" = []))
+)]
+pub async fn list_clients(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+) -> Result<impl IntoResponse, AppError> {
+    let clients = state.oauth_service.list_clients(&claims).await?;
+    Ok(RestApiResponse::success(clients))
+}
+
+/// this function creates a router for deleting an OAuth client
+/// the sessions issued to the client end with it
+#[utoipa::path(
+    delete,
+    path = "/oauth/clients/{client_id}",
+    responses(
+        (status = 200, description = "OAuth client deleted"),
+        (status = 404, description = "OAuth client not found")
+    ),
+    tag = "OAuth",
+    security(("bearer_auth" = []))
+)]
+pub async fn delete_client(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(client_id): Path<String>,
+) -> Result<impl IntoResponse, AppError> {
+    state
+        .oauth_service
+        .delete_client(&claims, client_id)
+        .await?;
+    Ok(RestApiResponse::success_with_message(
+        "OAuth client deleted",
+        (),
+    ))
+}
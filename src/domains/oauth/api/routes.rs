@@ -0,0 +1,81 @@
+use crate::common::{
+    app_state::AppState,
+    permission::{require_permission, AUTH_MANAGE},
+};
+use axum::{
+    middleware,
+    routing::{delete, get, post},
+    Router,
+};
+
+use super::handlers;
+
+use utoipa::{
+    openapi::security::{HttpAuthScheme, HttpBuilder, SecurityScheme},
+    OpenApi,
+};
+
+/// Import the necessary modules for OpenAPI documentation generation
+#[derive(OpenApi)]
+#[openapi(
+    paths(
+        super::handlers::token,
+        super::handlers::authorize,
+        super::handlers::create_client,
+        super::handlers::list_clients,
+        super::handlers::delete_client,
+    ),
+    components(schemas(
+        crate::domains::oauth::dto::oauth_dto::CreateOAuthClientDto,
+        crate::domains::oauth::dto::oauth_dto::CreatedOAuthClientDto,
+        crate::domains::oauth::dto::oauth_dto::OAuthClientDto,
+        crate::domains::oauth::dto::oauth_dto::TokenRequestDto,
+        crate::domains::oauth::dto::oauth_dto::TokenResponseDto,
+        crate::domains::oauth::dto::oauth_dto::OAuthErrorDto,
+    )),
+    tags(
+        (name = "OAuth", description = "OAuth 2.0 authorization server endpoints")
+    ),
+    modifiers(&OAuthApiDoc)
+)]
+/// This struct is used to generate OpenAPI documentation for the OAuth routes.
+pub struct OAuthApiDoc;
+
+impl utoipa::Modify for OAuthApiDoc {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.as_mut().unwrap();
+        components.add_security_scheme(
+            "bearer_auth",
+            SecurityScheme::Http(
+                HttpBuilder::new()
+                    .scheme(HttpAuthScheme::Bearer)
+                    .bearer_format("JWT")
+                    .description(Some("Input your `<your‑jwt>`"))
+                    .build(),
+            ),
+        )
+    }
+}
+
+/// This function creates a router for the public OAuth routes.
+pub fn oauth_routes() -> Router<AppState> {
+    Router::new().route("/token", post(handlers::token))
+}
+
+/// This function creates a router for the OAuth routes that require a JWT.
+/// It is nested under `/oauth` inside the protected router.
+pub fn protected_oauth_routes() -> Router<AppState> {
+    Router::new()
+        .route("/authorize", get(handlers::authorize))
+        .route(
+            "/clients",
+            post(handlers::create_client)
+                .get(handlers::list_clients)
+                .route_layer(middleware::from_fn(require_permission(AUTH_MANAGE))),
+        )
+        .route(
+            "/clients/{client_id}",
+            delete(handlers::delete_client)
+                .route_layer(middleware::from_fn(require_permission(AUTH_MANAGE))),
+        )
+}
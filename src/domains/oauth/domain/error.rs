@@ -0,0 +1,100 @@
+//! This module defines `OAuthError`, the errors of the token endpoint as specified
+//! by RFC 6749, section 5.2.
+
+use axum::{
+    http::{
+        header::{CACHE_CONTROL, WWW_AUTHENTICATE},
+        HeaderValue, StatusCode,
+    },
+    response::{IntoResponse, Response},
+    Json,
+};
+use thiserror::Error;
+
+use crate::{common::error::AppError, domains::oauth::dto::oauth_dto::OAuthErrorDto};
+
+/// Errors returned by the token endpoint.
+/// They are rendered as `{"error": ..., "error_description": ...}` rather than the usual envelope,
+/// so that standard OAuth client libraries can interpret them.
+#[derive(Error, Debug)]
+pub enum OAuthError {
+    #[error("{0}")]
+    InvalidRequest(String),
+    #[error("Client authentication failed")]
+    InvalidClient,
+    #[error("{0}")]
+    InvalidGrant(String),
+    #[error("The client is not authorized to use this grant type")]
+    UnauthorizedClient,
+    #[error("The grant type is not supported")]
+    UnsupportedGrantType,
+    #[error("{0}")]
+    InvalidScope(String),
+    /// Errors outside of the OAuth vocabulary, e.g. throttling or database failures,
+    /// keep their usual response.
+    #[error(transparent)]
+    App(AppError),
+}
+
+impl OAuthError {
+    /// The `error` code of RFC 6749.
+    pub fn code(&self) -> &'static str {
+        match self {
+            OAuthError::InvalidRequest(_) => "invalid_request",
+            OAuthError::InvalidClient => "invalid_client",
+            OAuthError::InvalidGrant(_) => "invalid_grant",
+            OAuthError::UnauthorizedClient => "unauthorized_client",
+            OAuthError::UnsupportedGrantType => "unsupported_grant_type",
+            OAuthError::InvalidScope(_) => "invalid_scope",
+            OAuthError::App(_) => "server_error",
+        }
+    }
+}
+
+/// Maps the errors of the auth service to OAuth errors.
+/// Failed credentials, tokens and second factors are all an `invalid_grant`.
+impl From<AppError> for OAuthError {
+    fn from(err: AppError) -> Self {
+        match err {
+            AppError::WrongCredentials
+            | AppError::MissingCredentials
+            | AppError::InvalidToken
+            | AppError::TokenExpired
+            | AppError::UserNotFound
+            | AppError::MfaRequired => OAuthError::InvalidGrant(err.to_string()),
+            err => OAuthError::App(err),
+        }
+    }
+}
+
+impl From<sqlx::Error> for OAuthError {
+    fn from(err: sqlx::Error) -> Self {
+        OAuthError::App(AppError::DatabaseError(err))
+    }
+}
+
+impl IntoResponse for OAuthError {
+    fn into_response(self) -> Response {
+        let status = match self {
+            OAuthError::App(err) => return err.into_response(),
+            OAuthError::InvalidClient => StatusCode::UNAUTHORIZED,
+            _ => StatusCode::BAD_REQUEST,
+        };
+
+        let body = Json(OAuthErrorDto {
+            error: self.code().to_string(),
+            error_description: Some(self.to_string()),
+        });
+
+        let mut response = (status, body).into_response();
+        let headers = response.headers_mut();
+        headers.insert(CACHE_CONTROL, HeaderValue::from_static("no-store"));
+        if status == StatusCode::UNAUTHORIZED {
+            headers.insert(
+                WWW_AUTHENTICATE,
+                HeaderValue::from_static("Basic realm=\"oauth\""),
+            );
+        }
+        response
+    }
+}
@@ -0,0 +1,39 @@
+//! This module defines the `OAuthClient` model for registered third-party applications
+//! and the `AuthorizationCode` model of the authorization code grant.
+
+use chrono::{DateTime, Utc};
+use sqlx::prelude::FromRow;
+
+/// Represents a registered OAuth client.
+/// Confidential clients authenticate with a secret, of which only the SHA-256 hash is stored;
+/// public clients have none and must use PKCE.
+#[derive(Debug, Clone, FromRow)]
+pub struct OAuthClient {
+    pub client_id: String,
+    pub client_secret_hash: Option<String>,
+    pub name: String,
+    pub redirect_uris: Vec<String>,
+    pub grant_types: Vec<String>,
+    pub scopes: Vec<String>,
+    /// The user that client credentials tokens act as.
+    pub user_id: Option<String>,
+    pub created_by: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Represents a single-use authorization code.
+/// Only the SHA-256 hash of the code is stored, together with the PKCE challenge.
+#[derive(Debug, Clone, FromRow)]
+pub struct AuthorizationCode {
+    pub id: String,
+    pub code_hash: String,
+    pub client_id: String,
+    pub user_id: String,
+    pub redirect_uri: String,
+    pub scopes: Vec<String>,
+    pub code_challenge: String,
+    pub expires_at: DateTime<Utc>,
+    pub used_at: Option<DateTime<Utc>>,
+    /// Session started with the code, revoked if the code is presented again.
+    pub session_id: Option<String>,
+}
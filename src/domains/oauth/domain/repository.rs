@@ -0,0 +1,59 @@
+//! This module defines the `OAuthRepository` trait, which provides an abstraction
+//! over database operations related to OAuth clients and authorization codes.
+
+use super::model::{AuthorizationCode, OAuthClient};
+
+use async_trait::async_trait;
+use sqlx::{PgPool, Postgres, Transaction};
+
+#[async_trait]
+/// Trait representing the repository contract for OAuth data.
+pub trait OAuthRepository: Send + Sync {
+    /// Inserts a new client.
+    async fn create_client(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        client: OAuthClient,
+    ) -> Result<(), sqlx::Error>;
+
+    /// Finds a client by its client ID.
+    async fn find_client(
+        &self,
+        pool: PgPool,
+        client_id: String,
+    ) -> Result<Option<OAuthClient>, sqlx::Error>;
+
+    /// Returns every registered client, newest first.
+    async fn find_clients(&self, pool: PgPool) -> Result<Vec<OAuthClient>, sqlx::Error>;
+
+    /// Deletes a client together with its codes and sessions.
+    /// Returns the number of deleted clients.
+    async fn delete_client(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        client_id: String,
+    ) -> Result<u64, sqlx::Error>;
+
+    /// Inserts a new authorization code.
+    async fn create_authorization_code(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        code: AuthorizationCode,
+    ) -> Result<(), sqlx::Error>;
+
+    /// Finds an authorization code by its hash and locks the row,
+    /// so that concurrent redemptions of the same code are serialized.
+    async fn find_authorization_code_for_update(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        code_hash: String,
+    ) -> Result<Option<AuthorizationCode>, sqlx::Error>;
+
+    /// Marks an authorization code as redeemed for the given session.
+    async fn mark_authorization_code_used(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        id: String,
+        session_id: String,
+    ) -> Result<(), sqlx::Error>;
+}
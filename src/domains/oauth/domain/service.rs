@@ -0,0 +1,58 @@
+//! This module defines the OAuth service trait, which registers clients
+//! and implements the authorization and token endpoints.
+
+use std::sync::Arc;
+
+use sqlx::PgPool;
+
+use crate::{
+    common::{client_info::ClientInfo, config::Config, error::AppError, jwt::Claims},
+    domains::{
+        auth::AuthServiceTrait,
+        oauth::dto::oauth_dto::{
+            AuthorizeQuery, CreateOAuthClientDto, CreatedOAuthClientDto, OAuthClientDto,
+            TokenRequestDto, TokenResponseDto,
+        },
+    },
+};
+
+use super::error::OAuthError;
+
+#[async_trait::async_trait]
+/// Trait defining the contract for OAuth 2.0 operations.
+/// Sessions and tokens are delegated to the auth service, so that OAuth sessions
+/// can be revoked and throttled like first-party logins.
+pub trait OAuthServiceTrait: Send + Sync {
+    /// constructor for the service.
+    fn create_service(
+        config: Config,
+        pool: PgPool,
+        auth_service: Arc<dyn AuthServiceTrait>,
+    ) -> Arc<dyn OAuthServiceTrait>
+    where
+        Self: Sized;
+
+    /// Registers a client. Administrators only; the secret is returned once.
+    async fn create_client(
+        &self,
+        claims: &Claims,
+        payload: CreateOAuthClientDto,
+    ) -> Result<CreatedOAuthClientDto, AppError>;
+
+    /// Lists the registered clients. Administrators only.
+    async fn list_clients(&self, claims: &Claims) -> Result<Vec<OAuthClientDto>, AppError>;
+
+    /// Deletes a client and ends every session issued to it. Administrators only.
+    async fn delete_client(&self, claims: &Claims, client_id: String) -> Result<(), AppError>;
+
+    /// Issues an authorization code for the calling user and returns the URI to redirect to.
+    /// Errors that can be reported to the client are part of the returned URI.
+    async fn authorize(&self, claims: &Claims, query: AuthorizeQuery) -> Result<String, AppError>;
+
+    /// Exchanges a grant for tokens. The client credentials are part of the payload.
+    async fn token(
+        &self,
+        payload: TokenRequestDto,
+        client: &ClientInfo,
+    ) -> Result<TokenResponseDto, OAuthError>;
+}
@@ -0,0 +1,129 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+use validator::Validate;
+
+use crate::domains::oauth::domain::model::OAuthClient;
+
+/// Grant types accepted at `/oauth/token`.
+pub const GRANT_AUTHORIZATION_CODE: &str = "authorization_code";
+pub const GRANT_CLIENT_CREDENTIALS: &str = "client_credentials";
+pub const GRANT_PASSWORD: &str = "password";
+pub const GRANT_REFRESH_TOKEN: &str = "refresh_token";
+
+/// Registers a client. Only administrators can register clients.
+#[derive(Debug, Serialize, Deserialize, ToSchema, Validate)]
+pub struct CreateOAuthClientDto {
+    #[validate(length(min = 1, max = 64, message = "Name must be 1 to 64 characters"))]
+    pub name: String,
+    /// Absolute URIs that authorization codes may be sent to. Compared exactly.
+    #[serde(default)]
+    pub redirect_uris: Vec<String>,
+    /// Any of `authorization_code`, `client_credentials`, `password` and `refresh_token`.
+    #[validate(length(min = 1, message = "At least one grant type is required"))]
+    pub grant_types: Vec<String>,
+    /// Permissions the client may request, e.g. `device:read`. Each must be held by the caller.
+    #[validate(length(min = 1, message = "At least one scope is required"))]
+    pub scopes: Vec<String>,
+    /// Confidential clients get a secret; public clients, such as mobile apps, do not.
+    pub confidential: bool,
+    /// The user that client credentials tokens act as. Required for that grant.
+    pub user_id: Option<String>,
+}
+
+/// A newly registered client. The secret is only returned once.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct CreatedOAuthClientDto {
+    pub client_secret: Option<String>,
+    #[serde(flatten)]
+    pub client: OAuthClientDto,
+}
+
+/// A registered client without its secret.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct OAuthClientDto {
+    pub client_id: String,
+    pub name: String,
+    pub confidential: bool,
+    pub redirect_uris: Vec<String>,
+    pub grant_types: Vec<String>,
+    pub scopes: Vec<String>,
+    pub user_id: Option<String>,
+    pub created_by: Option<String>,
+    #[serde(with = "crate::common::ts_format")]
+    pub created_at: DateTime<Utc>,
+}
+
+impl From<OAuthClient> for OAuthClientDto {
+    fn from(client: OAuthClient) -> Self {
+        Self {
+            client_id: client.client_id,
+            name: client.name,
+            confidential: client.client_secret_hash.is_some(),
+            redirect_uris: client.redirect_uris,
+            grant_types: client.grant_types,
+            scopes: client.scopes,
+            user_id: client.user_id,
+            created_by: client.created_by,
+            created_at: client.created_at,
+        }
+    }
+}
+
+/// Query of `/oauth/authorize` (RFC 6749, section 4.1.1, with PKCE of RFC 7636).
+#[derive(Debug, Serialize, Deserialize, ToSchema, IntoParams)]
+pub struct AuthorizeQuery {
+    /// Must be `code`.
+    pub response_type: String,
+    pub client_id: String,
+    /// Optional if the client has exactly one redirect URI.
+    pub redirect_uri: Option<String>,
+    /// Space-separated permissions. Defaults to every scope of the client.
+    pub scope: Option<String>,
+    /// Returned unchanged with the code; protects the client against CSRF.
+    pub state: Option<String>,
+    /// BASE64URL(SHA-256(code_verifier)).
+    pub code_challenge: Option<String>,
+    /// Must be `S256`.
+    pub code_challenge_method: Option<String>,
+}
+
+/// Form of `/oauth/token`. Which fields are required depends on the grant type.
+/// Clients authenticate with HTTP Basic or with `client_id` and `client_secret` in the form.
+#[derive(Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct TokenRequestDto {
+    pub grant_type: String,
+    pub client_id: Option<String>,
+    pub client_secret: Option<String>,
+    /// `authorization_code` grant.
+    pub code: Option<String>,
+    pub redirect_uri: Option<String>,
+    pub code_verifier: Option<String>,
+    /// `password` grant.
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// `refresh_token` grant.
+    pub refresh_token: Option<String>,
+    /// Space-separated permissions for the `password` and `client_credentials` grants.
+    pub scope: Option<String>,
+}
+
+/// Successful response of `/oauth/token` (RFC 6749, section 5.1).
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct TokenResponseDto {
+    pub access_token: String,
+    pub token_type: String,
+    pub expires_in: i64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub refresh_token: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+}
+
+/// Error response of `/oauth/token` (RFC 6749, section 5.2).
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct OAuthErrorDto {
+    pub error: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error_description: Option<String>,
+}
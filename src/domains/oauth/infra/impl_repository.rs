@@ -0,0 +1,163 @@
+use async_trait::async_trait;
+use sqlx::{PgPool, Postgres, Transaction};
+
+use crate::domains::oauth::domain::model::{AuthorizationCode, OAuthClient};
+use crate::domains::oauth::domain::repository::OAuthRepository;
+pub struct OAuthRepo;
+
+#[async_trait]
+impl OAuthRepository for OAuthRepo {
+    async fn create_client(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        client: OAuthClient,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            r#"
+            INSERT INTO oauth_clients
+            (client_id, client_secret_hash, name, redirect_uris, grant_types, scopes,
+             user_id, created_by, created_at)
+            VALUES
+            ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+            "#,
+            client.client_id,
+            client.client_secret_hash,
+            client.name,
+            &client.redirect_uris,
+            &client.grant_types,
+            &client.scopes,
+            client.user_id,
+            client.created_by,
+            client.created_at
+        )
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn find_client(
+        &self,
+        pool: PgPool,
+        client_id: String,
+    ) -> Result<Option<OAuthClient>, sqlx::Error> {
+        let client = sqlx::query_as!(
+            OAuthClient,
+            r#"
+            SELECT client_id, client_secret_hash, name, redirect_uris, grant_types, scopes,
+                   user_id, created_by, created_at
+              FROM oauth_clients
+             WHERE client_id = $1
+            "#,
+            client_id
+        )
+        .fetch_optional(&pool)
+        .await?;
+
+        Ok(client)
+    }
+
+    async fn find_clients(&self, pool: PgPool) -> Result<Vec<OAuthClient>, sqlx::Error> {
+        let clients = sqlx::query_as!(
+            OAuthClient,
+            r#"
+            SELECT client_id, client_secret_hash, name, redirect_uris, grant_types, scopes,
+                   user_id, created_by, created_at
+              FROM oauth_clients
+             ORDER BY created_at DESC
+            "#
+        )
+        .fetch_all(&pool)
+        .await?;
+
+        Ok(clients)
+    }
+
+    async fn delete_client(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        client_id: String,
+    ) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query!(
+            r#"
+            DELETE FROM oauth_clients
+             WHERE client_id = $1
+            "#,
+            client_id
+        )
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    async fn create_authorization_code(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        code: AuthorizationCode,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            r#"
+            INSERT INTO oauth_authorization_codes
+            (id, code_hash, client_id, user_id, redirect_uri, scopes, code_challenge, expires_at)
+            VALUES
+            ($1, $2, $3, $4, $5, $6, $7, $8)
+            "#,
+            code.id,
+            code.code_hash,
+            code.client_id,
+            code.user_id,
+            code.redirect_uri,
+            &code.scopes,
+            code.code_challenge,
+            code.expires_at
+        )
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn find_authorization_code_for_update(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        code_hash: String,
+    ) -> Result<Option<AuthorizationCode>, sqlx::Error> {
+        let code = sqlx::query_as!(
+            AuthorizationCode,
+            r#"
+            SELECT id, code_hash, client_id, user_id, redirect_uri, scopes, code_challenge,
+                   expires_at, used_at, session_id
+              FROM oauth_authorization_codes
+             WHERE code_hash = $1
+               FOR UPDATE
+            "#,
+            code_hash
+        )
+        .fetch_optional(&mut **tx)
+        .await?;
+
+        Ok(code)
+    }
+
+    async fn mark_authorization_code_used(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        id: String,
+        session_id: String,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            r#"
+            UPDATE oauth_authorization_codes
+               SET used_at = NOW(), session_id = $2
+             WHERE id = $1
+            "#,
+            id,
+            session_id
+        )
+        .execute(&mut **tx)
+        .await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,582 @@
+use std::sync::Arc;
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::{Duration, Utc};
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use url::Url;
+use uuid::Uuid;
+use validator::{Validate, ValidationError};
+
+use crate::{
+    common::{
+        client_info::ClientInfo,
+        config::Config,
+        error::{is_foreign_key_violation, AppError},
+        hash_util,
+        jwt::{AuthPayload, Claims},
+    },
+    domains::{
+        auth::{
+            dto::auth_dto::{ClientScope, RefreshTokenDto},
+            AuthServiceTrait,
+        },
+        oauth::{
+            domain::{
+                error::OAuthError,
+                model::{AuthorizationCode, OAuthClient},
+                repository::OAuthRepository,
+                service::OAuthServiceTrait,
+            },
+            dto::oauth_dto::{
+                AuthorizeQuery, CreateOAuthClientDto, CreatedOAuthClientDto, OAuthClientDto,
+                TokenRequestDto, TokenResponseDto, GRANT_AUTHORIZATION_CODE,
+                GRANT_CLIENT_CREDENTIALS, GRANT_PASSWORD, GRANT_REFRESH_TOKEN,
+            },
+            infra::impl_repository::OAuthRepo,
+        },
+    },
+};
+
+/// Grant types a client can be registered for.
+const GRANT_TYPES: [&str; 4] = [
+    GRANT_AUTHORIZATION_CODE,
+    GRANT_CLIENT_CREDENTIALS,
+    GRANT_PASSWORD,
+    GRANT_REFRESH_TOKEN,
+];
+
+/// Service implementing the OAuth 2.0 endpoints on top of the auth service.
+#[derive(Clone)]
+pub struct OAuthService {
+    config: Config,
+    pool: PgPool,
+    repo: Arc<dyn OAuthRepository + Send + Sync>,
+    auth_service: Arc<dyn AuthServiceTrait>,
+}
+
+#[async_trait::async_trait]
+impl OAuthServiceTrait for OAuthService {
+    /// constructor for the service.
+    fn create_service(
+        config: Config,
+        pool: PgPool,
+        auth_service: Arc<dyn AuthServiceTrait>,
+    ) -> Arc<dyn OAuthServiceTrait> {
+        Arc::new(Self {
+            config,
+            pool,
+            repo: Arc::new(OAuthRepo {}),
+            auth_service,
+        })
+    }
+
+    /// Validates the registration and stores the client.
+    /// Only the hash of the secret of confidential clients is stored.
+    async fn create_client(
+        &self,
+        claims: &Claims,
+        payload: CreateOAuthClientDto,
+    ) -> Result<CreatedOAuthClientDto, AppError> {
+        if !claims.is_admin() {
+            return Err(AppError::Forbidden);
+        }
+
+        let mut errors = payload.validate().err().unwrap_or_default();
+        for grant_type in &payload.grant_types {
+            if !GRANT_TYPES.contains(&grant_type.as_str()) {
+                errors.add(
+                    "grant_types",
+                    ValidationError::new("grant_type")
+                        .with_message(format!("Unsupported grant type {grant_type}").into()),
+                );
+            }
+        }
+        for redirect_uri in &payload.redirect_uris {
+            if !is_valid_redirect_uri(redirect_uri) {
+                errors.add(
+                    "redirect_uris",
+                    ValidationError::new("redirect_uri").with_message(
+                        format!("{redirect_uri} is not an absolute URI without fragment").into(),
+                    ),
+                );
+            }
+        }
+        if has_grant(&payload.grant_types, GRANT_AUTHORIZATION_CODE)
+            && payload.redirect_uris.is_empty()
+        {
+            errors.add(
+                "redirect_uris",
+                ValidationError::new("required")
+                    .with_message("The authorization code grant requires a redirect URI".into()),
+            );
+        }
+        if has_grant(&payload.grant_types, GRANT_CLIENT_CREDENTIALS)
+            && (!payload.confidential || payload.user_id.is_none())
+        {
+            errors.add(
+                "user_id",
+                ValidationError::new("required").with_message(
+                    "The client credentials grant requires a confidential client and a user_id"
+                        .into(),
+                ),
+            );
+        }
+        for scope in &payload.scopes {
+            if !claims.has_permission(scope) {
+                errors.add(
+                    "scopes",
+                    ValidationError::new("scope")
+                        .with_message(format!("Scope {scope} is not granted to the caller").into()),
+                );
+            }
+        }
+        if !errors.is_empty() {
+            return Err(AppError::InvalidInput(errors));
+        }
+
+        let client_secret = payload.confidential.then(hash_util::generate_token);
+        let client = OAuthClient {
+            client_id: Uuid::new_v4().to_string(),
+            client_secret_hash: client_secret.as_deref().map(hash_util::hash_token),
+            name: payload.name,
+            redirect_uris: payload.redirect_uris,
+            grant_types: dedup(payload.grant_types),
+            scopes: dedup(payload.scopes),
+            user_id: payload.user_id,
+            created_by: Some(claims.sub.clone()),
+            created_at: Utc::now(),
+        };
+
+        let mut tx = self.pool.begin().await?;
+        self.repo
+            .create_client(&mut tx, client.clone())
+            .await
+            .map_err(|err| {
+                if is_foreign_key_violation(&err) {
+                    return AppError::NotFound("User not found".into());
+                }
+                tracing::error!("Error creating OAuth client: {err}");
+                AppError::DatabaseError(err)
+            })?;
+        tx.commit().await?;
+
+        tracing::info!(
+            "OAuth client {} created by {}",
+            client.client_id,
+            claims.sub
+        );
+
+        Ok(CreatedOAuthClientDto {
+            client_secret,
+            client: client.into(),
+        })
+    }
+
+    async fn list_clients(&self, claims: &Claims) -> Result<Vec<OAuthClientDto>, AppError> {
+        if !claims.is_admin() {
+            return Err(AppError::Forbidden);
+        }
+
+        let clients = self.repo.find_clients(self.pool.clone()).await?;
+        Ok(clients.into_iter().map(OAuthClientDto::from).collect())
+    }
+
+    async fn delete_client(&self, claims: &Claims, client_id: String) -> Result<(), AppError> {
+        if !claims.is_admin() {
+            return Err(AppError::Forbidden);
+        }
+
+        let mut tx = self.pool.begin().await?;
+        let deleted = self.repo.delete_client(&mut tx, client_id.clone()).await?;
+        tx.commit().await?;
+
+        if deleted == 0 {
+            return Err(AppError::NotFound("OAuth client not found".into()));
+        }
+
+        tracing::info!("OAuth client {client_id} deleted by {}", claims.sub);
+        Ok(())
+    }
+
+    /// The client and redirect URI are checked first: if either is invalid, the user must not
+    /// be redirected and an error is returned instead. Later errors are reported to the client
+    /// through the redirect URI (RFC 6749, section 4.1.2.1).
+    /// The authenticated request of the user counts as consent.
+    async fn authorize(&self, claims: &Claims, query: AuthorizeQuery) -> Result<String, AppError> {
+        if claims.api_key_id.is_some() || claims.client_id.is_some() {
+            return Err(AppError::Forbidden);
+        }
+
+        let client = self
+            .repo
+            .find_client(self.pool.clone(), query.client_id.clone())
+            .await?
+            .ok_or_else(|| AppError::ValidationError("Unknown client_id".into()))?;
+
+        let redirect_uri = match query.redirect_uri.as_deref() {
+            Some(uri) if client.redirect_uris.iter().any(|allowed| allowed == uri) => {
+                uri.to_string()
+            }
+            None if client.redirect_uris.len() == 1 => client.redirect_uris[0].clone(),
+            _ => return Err(AppError::ValidationError("Invalid redirect_uri".into())),
+        };
+
+        let error = |error: OAuthError| {
+            redirect_to(
+                &redirect_uri,
+                &[
+                    ("error", error.code()),
+                    ("error_description", &error.to_string()),
+                ],
+                query.state.as_deref(),
+            )
+        };
+
+        if query.response_type != "code" {
+            return error(OAuthError::InvalidRequest(
+                "response_type must be code".into(),
+            ));
+        }
+        if !has_grant(&client.grant_types, GRANT_AUTHORIZATION_CODE) {
+            return error(OAuthError::UnauthorizedClient);
+        }
+        let scopes = match requested_scopes(&client, query.scope.as_deref()) {
+            Ok(scopes) => scopes,
+            Err(err) => return error(err),
+        };
+        let Some(code_challenge) = query.code_challenge.filter(|c| !c.is_empty()) else {
+            return error(OAuthError::InvalidRequest(
+                "code_challenge is required".into(),
+            ));
+        };
+        if query.code_challenge_method.as_deref() != Some("S256") {
+            return error(OAuthError::InvalidRequest(
+                "code_challenge_method must be S256".into(),
+            ));
+        }
+
+        let code = hash_util::generate_token();
+        let authorization_code = AuthorizationCode {
+            id: Uuid::new_v4().to_string(),
+            code_hash: hash_util::hash_token(&code),
+            client_id: client.client_id,
+            user_id: claims.sub.clone(),
+            redirect_uri: redirect_uri.clone(),
+            scopes,
+            code_challenge,
+            expires_at: Utc::now() + Duration::seconds(self.config.oauth_code_ttl_secs),
+            used_at: None,
+            session_id: None,
+        };
+
+        let mut tx = self.pool.begin().await?;
+        self.repo
+            .create_authorization_code(&mut tx, authorization_code)
+            .await?;
+        tx.commit().await?;
+
+        redirect_to(&redirect_uri, &[("code", &code)], query.state.as_deref())
+    }
+
+    async fn token(
+        &self,
+        payload: TokenRequestDto,
+        client: &ClientInfo,
+    ) -> Result<TokenResponseDto, OAuthError> {
+        if !GRANT_TYPES.contains(&payload.grant_type.as_str()) {
+            return Err(OAuthError::UnsupportedGrantType);
+        }
+
+        let oauth_client = self.authenticate_client(&payload).await?;
+        if !has_grant(&oauth_client.grant_types, &payload.grant_type) {
+            return Err(OAuthError::UnauthorizedClient);
+        }
+
+        match payload.grant_type.as_str() {
+            GRANT_AUTHORIZATION_CODE => self.authorization_code_grant(oauth_client, payload).await,
+            GRANT_CLIENT_CREDENTIALS => self.client_credentials_grant(oauth_client, payload).await,
+            GRANT_PASSWORD => self.password_grant(oauth_client, payload, client).await,
+            GRANT_REFRESH_TOKEN => {
+                self.refresh_token_grant(oauth_client, payload, client)
+                    .await
+            }
+            _ => Err(OAuthError::UnsupportedGrantType),
+        }
+    }
+}
+
+impl OAuthService {
+    /// Looks up the client and checks its secret.
+    /// Confidential clients must present their secret; public clients must not present one.
+    async fn authenticate_client(
+        &self,
+        payload: &TokenRequestDto,
+    ) -> Result<OAuthClient, OAuthError> {
+        let client_id = payload
+            .client_id
+            .clone()
+            .filter(|id| !id.is_empty())
+            .ok_or(OAuthError::InvalidClient)?;
+
+        let client = self
+            .repo
+            .find_client(self.pool.clone(), client_id)
+            .await?
+            .ok_or(OAuthError::InvalidClient)?;
+
+        let presented = payload.client_secret.as_deref().map(hash_util::hash_token);
+        if presented != client.client_secret_hash {
+            tracing::error!("Client authentication failed for {}", client.client_id);
+            return Err(OAuthError::InvalidClient);
+        }
+
+        Ok(client)
+    }
+
+    /// Redeems a code issued by `authorize` after checking the client, redirect URI and PKCE verifier.
+    /// A code presented twice revokes the session started with it (RFC 6749, section 4.1.2).
+    async fn authorization_code_grant(
+        &self,
+        client: OAuthClient,
+        payload: TokenRequestDto,
+    ) -> Result<TokenResponseDto, OAuthError> {
+        let (Some(code), Some(redirect_uri), Some(code_verifier)) =
+            (payload.code, payload.redirect_uri, payload.code_verifier)
+        else {
+            return Err(OAuthError::InvalidRequest(
+                "code, redirect_uri and code_verifier are required".into(),
+            ));
+        };
+
+        let mut tx = self.pool.begin().await?;
+
+        let stored = self
+            .repo
+            .find_authorization_code_for_update(&mut tx, hash_util::hash_token(&code))
+            .await?
+            .filter(|stored| stored.client_id == client.client_id)
+            .ok_or_else(|| OAuthError::InvalidGrant("Invalid authorization code".into()))?;
+
+        if stored.used_at.is_some() {
+            if let Some(session_id) = stored.session_id.as_deref() {
+                tracing::warn!("Authorization code reuse detected; revoking session {session_id}");
+                self.auth_service.revoke_session(session_id).await?;
+            }
+            return Err(OAuthError::InvalidGrant(
+                "Invalid authorization code".into(),
+            ));
+        }
+
+        if stored.expires_at <= Utc::now() {
+            return Err(OAuthError::InvalidGrant(
+                "Authorization code expired".into(),
+            ));
+        }
+
+        if stored.redirect_uri != redirect_uri {
+            return Err(OAuthError::InvalidGrant("redirect_uri mismatch".into()));
+        }
+
+        if !is_valid_code_verifier(&code_verifier)
+            || pkce_challenge(&code_verifier) != stored.code_challenge
+        {
+            return Err(OAuthError::InvalidGrant("Invalid code_verifier".into()));
+        }
+
+        let session_id = Uuid::new_v4().to_string();
+        self.repo
+            .mark_authorization_code_used(&mut tx, stored.id, session_id.clone())
+            .await?;
+        tx.commit().await?;
+
+        let scope = ClientScope {
+            client_id: client.client_id,
+            scopes: stored.scopes,
+        };
+        let scope_value = scope.scopes.join(" ");
+        let auth_body = self
+            .auth_service
+            .create_client_session(&stored.user_id, &session_id, scope)
+            .await?;
+
+        Ok(TokenResponseDto {
+            access_token: auth_body.access_token,
+            token_type: auth_body.token_type,
+            expires_in: auth_body.expires_in,
+            refresh_token: Some(auth_body.refresh_token),
+            scope: Some(scope_value),
+        })
+    }
+
+    /// Issues an access token that acts as the user configured for the client.
+    /// No refresh token is issued (RFC 6749, section 4.4.3), and none at all
+    /// once that user is soft-deleted.
+    async fn client_credentials_grant(
+        &self,
+        client: OAuthClient,
+        payload: TokenRequestDto,
+    ) -> Result<TokenResponseDto, OAuthError> {
+        let user_id = client
+            .user_id
+            .clone()
+            .ok_or(OAuthError::UnauthorizedClient)?;
+        let scopes = requested_scopes(&client, payload.scope.as_deref())?;
+        let scope_value = scopes.join(" ");
+
+        let access_token = self
+            .auth_service
+            .issue_client_access_token(
+                &user_id,
+                ClientScope {
+                    client_id: client.client_id,
+                    scopes,
+                },
+            )
+            .await?;
+
+        Ok(TokenResponseDto {
+            access_token,
+            token_type: "Bearer".to_string(),
+            expires_in: self.config.jwt_access_token_ttl_secs,
+            refresh_token: None,
+            scope: Some(scope_value),
+        })
+    }
+
+    /// Checks the user's password with the same throttling as `/auth/login`.
+    async fn password_grant(
+        &self,
+        client: OAuthClient,
+        payload: TokenRequestDto,
+        client_info: &ClientInfo,
+    ) -> Result<TokenResponseDto, OAuthError> {
+        let (Some(username), Some(password)) = (payload.username, payload.password) else {
+            return Err(OAuthError::InvalidRequest(
+                "username and password are required".into(),
+            ));
+        };
+        let scopes = requested_scopes(&client, payload.scope.as_deref())?;
+        let scope_value = scopes.join(" ");
+
+        let auth_body = self
+            .auth_service
+            .login_client(
+                AuthPayload {
+                    client_id: username,
+                    client_secret: password,
+                },
+                client_info,
+                ClientScope {
+                    client_id: client.client_id,
+                    scopes,
+                },
+            )
+            .await?;
+
+        Ok(TokenResponseDto {
+            access_token: auth_body.access_token,
+            token_type: auth_body.token_type,
+            expires_in: auth_body.expires_in,
+            refresh_token: Some(auth_body.refresh_token),
+            scope: Some(scope_value),
+        })
+    }
+
+    /// Rotates a refresh token of the client. The session keeps its original scopes.
+    async fn refresh_token_grant(
+        &self,
+        client: OAuthClient,
+        payload: TokenRequestDto,
+        client_info: &ClientInfo,
+    ) -> Result<TokenResponseDto, OAuthError> {
+        let refresh_token = payload
+            .refresh_token
+            .ok_or_else(|| OAuthError::InvalidRequest("refresh_token is required".into()))?;
+
+        let auth_body = self
+            .auth_service
+            .refresh_client_session(
+                RefreshTokenDto { refresh_token },
+                &client.client_id,
+                client_info,
+            )
+            .await?;
+
+        Ok(TokenResponseDto {
+            access_token: auth_body.access_token,
+            token_type: auth_body.token_type,
+            expires_in: auth_body.expires_in,
+            refresh_token: Some(auth_body.refresh_token),
+            scope: None,
+        })
+    }
+}
+
+/// Parses the space-separated `scope` parameter.
+/// Every scope must be allowed for the client; without a parameter all of them are requested.
+fn requested_scopes(client: &OAuthClient, scope: Option<&str>) -> Result<Vec<String>, OAuthError> {
+    let Some(scope) = scope.filter(|scope| !scope.trim().is_empty()) else {
+        return Ok(client.scopes.clone());
+    };
+
+    let scopes = dedup(scope.split_whitespace().map(str::to_string).collect());
+    if let Some(unknown) = scopes.iter().find(|scope| !client.scopes.contains(scope)) {
+        return Err(OAuthError::InvalidScope(format!(
+            "Scope {unknown} is not allowed for this client"
+        )));
+    }
+    Ok(scopes)
+}
+
+fn has_grant(grant_types: &[String], grant_type: &str) -> bool {
+    grant_types.iter().any(|g| g == grant_type)
+}
+
+fn dedup(mut values: Vec<String>) -> Vec<String> {
+    values.sort();
+    values.dedup();
+    values
+}
+
+/// Redirect URIs must be absolute and must not contain a fragment (RFC 6749, section 3.1.2).
+fn is_valid_redirect_uri(uri: &str) -> bool {
+    Url::parse(uri).is_ok_and(|url| url.fragment().is_none() && !url.cannot_be_a_base())
+}
+
+/// Appends the parameters, and the state if any, to the query of the redirect URI.
+fn redirect_to(
+    redirect_uri: &str,
+    params: &[(&str, &str)],
+    state: Option<&str>,
+) -> Result<String, AppError> {
+    let mut url = Url::parse(redirect_uri).map_err(|err| {
+        tracing::error!("Invalid stored redirect URI {redirect_uri}: {err}");
+        AppError::InternalError
+    })?;
+
+    {
+        let mut query = url.query_pairs_mut();
+        for (name, value) in params {
+            query.append_pair(name, value);
+        }
+        if let Some(state) = state {
+            query.append_pair("state", state);
+        }
+    }
+
+    Ok(url.into())
+}
+
+/// Code verifiers are 43 to 128 unreserved characters (RFC 7636, section 4.1).
+fn is_valid_code_verifier(verifier: &str) -> bool {
+    (43..=128).contains(&verifier.len())
+        && verifier
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b"-._~".contains(&b))
+}
+
+/// The S256 code challenge of a verifier: BASE64URL(SHA-256(verifier)).
+fn pkce_challenge(verifier: &str) -> String {
+    URL_SAFE_NO_PAD.encode(Sha256::digest(verifier.as_bytes()))
+}
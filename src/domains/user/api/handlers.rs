@@ -1,26 +1,43 @@
 use crate::{
     common::{
-        app_state::AppState, dto::RestApiResponse, error::AppError, jwt::Claims,
+        app_state::AppState,
+        concurrency::{etag_header, IfMatch},
+        dto::RestApiResponse,
+        error::AppError,
+        jwt::Claims,
         multipart_helper::parse_multipart_to_maps,
+        pagination::{PageQuery, Paginated},
+        record_format::RecordFormat,
     },
     domains::{
-        file::dto::file_dto::UploadFileDto,
-        user::dto::user_dto::{CreateUserMultipartDto, SearchUserDto, UpdateUserDto, UserDto},
+        file::{dto::file_dto::UploadFileDto, stream_file},
+        user::dto::user_dto::{
+            CreateUserMultipartDto, DeleteUserQuery, DeleteUserSummaryDto, ExportUsersQuery,
+            ImportUsersQuery, ImportUsersReportDto, ProfilePictureMultipartDto, SearchUserDto,
+            UpdateUserDto, UserDto,
+        },
     },
 };
 
 use axum::{
-    extract::{Multipart, State},
+    body::{Body, Bytes},
+    extract::{Multipart, Query, State},
+    http::{header, HeaderMap},
     response::IntoResponse,
     Extension, Json,
 };
 
+use futures_util::StreamExt;
+use serde_json::Value;
 use validator::Validate;
 
 #[utoipa::path(
     get,
     path = "/user/{id}",
-    responses((status = 200, description = "Get user by ID", body = UserDto)),
+    responses(
+        (status = 200, description = "Get user by ID", body = UserDto,
+            headers(("ETag" = String, description = "Version of the user, for `If-Match`")))
+    ),
     tag = "Users"
 )]
 pub async fn get_user_by_id(
@@ -28,35 +45,140 @@ pub async fn get_user_by_id(
     axum::extract::Path(id): axum::extract::Path<String>,
 ) -> Result<impl IntoResponse, AppError> {
     let user = state.user_service.get_user_by_id(id).await?;
-    Ok(RestApiResponse::success(user))
+    Ok((etag_header(user.version), RestApiResponse::success(user)))
 }
 
 #[utoipa::path(
     post,
     path = "/user/list",
+    params(PageQuery),
     request_body = SearchUserDto,
-    responses((status = 200, description = "List users by condition", body = [UserDto])),
+    responses((status = 200, description = "List users by condition", body = Paginated<UserDto>)),
     tag = "Users"
 )]
 pub async fn get_user_list(
     State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Query(page): Query<PageQuery>,
     Json(payload): Json<SearchUserDto>,
 ) -> Result<impl IntoResponse, AppError> {
-    let users = state.user_service.get_user_list(payload).await?;
+    let users = state
+        .user_service
+        .get_user_list(&claims, payload, page)
+        .await?;
     Ok(RestApiResponse::success(users))
 }
 
 #[utoipa::path(
     get,
     path = "/user",
-    responses((status = 200, description = "List all users", body = [UserDto])),
+    params(SearchUserDto, PageQuery),
+    responses(
+        (status = 200, description = "List users, filtered and sorted by the query", body = Paginated<UserDto>),
+        (status = 400, description = "Invalid filter, sort field or page"),
+        (status = 403, description = "Only admins may include deleted users")
+    ),
     tag = "Users"
 )]
-pub async fn get_users(State(state): State<AppState>) -> Result<impl IntoResponse, AppError> {
-    let users = state.user_service.get_users().await?;
+pub async fn get_users(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Query(search): Query<SearchUserDto>,
+    Query(page): Query<PageQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    let users = state
+        .user_service
+        .get_user_list(&claims, search, page)
+        .await?;
     Ok(RestApiResponse::success(users))
 }
 
+#[utoipa::path(
+    post,
+    path = "/user/import",
+    params(ImportUsersQuery),
+    request_body(
+        content = String,
+        content_type = "text/csv",
+        description = "Users to create, as CSV with a `username,email` header or as NDJSON \
+            (`application/x-ndjson`) with one `{\"username\", \"email\"}` object per line"
+    ),
+    responses(
+        (status = 200, description = "Report of the import, row by row", body = ImportUsersReportDto),
+        (status = 400, description = "Unknown format or no rows"),
+        (status = 403, description = "Only admins may import users")
+    ),
+    tag = "Users"
+)]
+pub async fn import_users(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Query(query): Query<ImportUsersQuery>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<impl IntoResponse, AppError> {
+    let format = query
+        .format
+        .or_else(|| {
+            headers
+                .get(header::CONTENT_TYPE)
+                .and_then(|value| value.to_str().ok())
+                .and_then(RecordFormat::from_content_type)
+        })
+        .ok_or(AppError::ValidationError(
+            "Send text/csv or application/x-ndjson, or set the format".into(),
+        ))?;
+
+    let rows = format.parse(&body);
+    let dry_run = query.dry_run.unwrap_or(false);
+    let report = state
+        .user_service
+        .import_users(&claims, rows, query.mode.unwrap_or_default(), dry_run)
+        .await?;
+
+    let message = match (report.dry_run, report.committed) {
+        (true, _) => "Dry run, nothing was imported",
+        (false, true) => "Users imported",
+        (false, false) => "Import failed, nothing was imported",
+    };
+    Ok(RestApiResponse::success_with_message(message, report))
+}
+
+#[utoipa::path(
+    get,
+    path = "/user/export",
+    params(SearchUserDto, ExportUsersQuery),
+    responses(
+        (status = 200, description = "Stream the users matching the query as CSV or NDJSON",
+            content_type = "text/csv", body = String),
+        (status = 400, description = "Invalid filter or sort field"),
+        (status = 403, description = "Only admins may include deleted users")
+    ),
+    tag = "Users"
+)]
+pub async fn export_users(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Query(search): Query<SearchUserDto>,
+    Query(query): Query<ExportUsersQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    let format = query.format.unwrap_or_default();
+    let users = state.user_service.export_users(&claims, search).await?;
+
+    let mut encoder = format.encoder();
+    let body =
+        Body::from_stream(users.map(move |user| user.and_then(|user| encoder.encode(&user))));
+
+    let disposition = format!("attachment; filename=\"users.{}\"", format.extension());
+    Ok((
+        [
+            (header::CONTENT_TYPE, format.content_type().to_string()),
+            (header::CONTENT_DISPOSITION, disposition),
+        ],
+        body,
+    ))
+}
+
 #[utoipa::path(
     post,
     path = "/user",
@@ -73,7 +195,7 @@ pub async fn create_user(
     Extension(claims): Extension<Claims>,
     multipart: Multipart,
 ) -> Result<impl IntoResponse, AppError> {
-    let modified_by = claims.sub.clone().to_string();
+    let modified_by = claims.actor_id().to_string();
 
     let (mut fields, mut files) =
         parse_multipart_to_maps(multipart, &state.config.asset_allowed_extensions_pattern).await?;
@@ -95,9 +217,7 @@ pub async fn create_user(
     };
 
     // Validate the CreateUser DTO.
-    create_user
-        .validate()
-        .map_err(|err| AppError::ValidationError(format!("Invalid input: {}", err)))?;
+    create_user.validate()?;
 
     let mut upload_file_dto = None;
 
@@ -118,7 +238,7 @@ pub async fn create_user(
 
     let user = state
         .user_service
-        .create_user(create_user, upload_file_dto.as_mut())
+        .create_user(&claims, create_user, upload_file_dto.as_mut())
         .await?;
 
     Ok(RestApiResponse::success(user))
@@ -127,39 +247,200 @@ pub async fn create_user(
 #[utoipa::path(
     put,
     path = "/user/{id}",
+    params(("If-Match" = Option<String>, Header, description = "ETag of the version being replaced")),
     request_body = UpdateUserDto,
-    responses((status = 200, description = "Update user", body = UserDto)),
+    responses(
+        (status = 200, description = "Update user", body = UserDto,
+            headers(("ETag" = String, description = "Version of the updated user"))),
+        (status = 412, description = "The user was modified since it was read")
+    ),
     tag = "Users"
 )]
 pub async fn update_user(
     State(state): State<AppState>,
     Extension(claims): Extension<Claims>,
     axum::extract::Path(id): axum::extract::Path<String>,
+    if_match: IfMatch,
     Json(payload): Json<UpdateUserDto>,
 ) -> Result<impl IntoResponse, AppError> {
     payload.validate().map_err(|err| {
         tracing::error!("Validation error: {err}");
-        AppError::ValidationError(format!("Invalid input: {}", err))
+        AppError::InvalidInput(err)
     })?;
 
-    // Set the modified_by field to the current user's ID.
+    // Set the modified_by field to the acting user's ID, i.e. the admin when impersonating.
     let mut payload = payload;
-    payload.modified_by = claims.sub.clone().to_string();
+    payload.modified_by = claims.actor_id().to_string();
 
-    let user = state.user_service.update_user(id, payload).await?;
-    Ok(RestApiResponse::success(user))
+    let user = state
+        .user_service
+        .update_user(&claims, id, payload, &if_match)
+        .await?;
+    Ok((etag_header(user.version), RestApiResponse::success(user)))
+}
+
+/// Applies a JSON Merge Patch to the username and email of a user:
+/// members that are left out keep their value.
+#[utoipa::path(
+    patch,
+    path = "/user/{id}",
+    params(("If-Match" = Option<String>, Header, description = "ETag of the version being patched")),
+    request_body(content = UpdateUserDto, content_type = "application/merge-patch+json",
+        description = "The members of the user to change"),
+    responses(
+        (status = 200, description = "Patch user", body = UserDto,
+            headers(("ETag" = String, description = "Version of the patched user"))),
+        (status = 400, description = "Invalid merge patch"),
+        (status = 412, description = "The user was modified since it was read")
+    ),
+    tag = "Users"
+)]
+pub async fn patch_user(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    axum::extract::Path(id): axum::extract::Path<String>,
+    if_match: IfMatch,
+    Json(patch): Json<Value>,
+) -> Result<impl IntoResponse, AppError> {
+    let user = state
+        .user_service
+        .patch_user(&claims, id, patch, &if_match)
+        .await?;
+    Ok((etag_header(user.version), RestApiResponse::success(user)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/user/{id}/profile-picture",
+    responses(
+        (status = 200, description = "Stream the profile picture of the user"),
+        (status = 403, description = "The picture of another user"),
+        (status = 404, description = "User or profile picture not found")
+    ),
+    tag = "Users"
+)]
+pub async fn get_profile_picture(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    axum::extract::Path(id): axum::extract::Path<String>,
+) -> Result<impl IntoResponse, AppError> {
+    let file = state.user_service.get_profile_picture(&claims, id).await?;
+    stream_file(&state, file).await
+}
+
+#[utoipa::path(
+    put,
+    path = "/user/{id}/profile-picture",
+    params(("If-Match" = Option<String>, Header, description = "ETag of the user being updated")),
+    request_body(
+        content = ProfilePictureMultipartDto,
+        content_type = "multipart/form-data",
+        description = "The new profile picture"
+    ),
+    responses(
+        (status = 200, description = "Replace the profile picture of the user", body = UserDto,
+            headers(("ETag" = String, description = "Version of the updated user"))),
+        (status = 400, description = "Missing or invalid profile picture"),
+        (status = 412, description = "The user was modified since it was read")
+    ),
+    tag = "Users"
+)]
+pub async fn replace_profile_picture(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    axum::extract::Path(id): axum::extract::Path<String>,
+    if_match: IfMatch,
+    multipart: Multipart,
+) -> Result<impl IntoResponse, AppError> {
+    let (_, mut files) =
+        parse_multipart_to_maps(multipart, &state.config.asset_allowed_extensions_pattern).await?;
+
+    let file = files
+        .remove("profile_picture")
+        .and_then(|files| files.into_iter().next())
+        .ok_or(AppError::ValidationError("Missing profile picture".into()))?;
+
+    let upload_file_dto = UploadFileDto {
+        file,
+        user_id: Some(id.clone()),
+        modified_by: claims.actor_id().to_string(),
+    };
+
+    let user = state
+        .user_service
+        .replace_profile_picture(&claims, id, upload_file_dto, &if_match)
+        .await?;
+    Ok((etag_header(user.version), RestApiResponse::success(user)))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/user/{id}/profile-picture",
+    params(("If-Match" = Option<String>, Header, description = "ETag of the user being updated")),
+    responses(
+        (status = 200, description = "Delete the profile picture of the user", body = UserDto,
+            headers(("ETag" = String, description = "Version of the updated user"))),
+        (status = 404, description = "User or profile picture not found"),
+        (status = 412, description = "The user was modified since it was read")
+    ),
+    tag = "Users"
+)]
+pub async fn delete_profile_picture(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    axum::extract::Path(id): axum::extract::Path<String>,
+    if_match: IfMatch,
+) -> Result<impl IntoResponse, AppError> {
+    let user = state
+        .user_service
+        .delete_profile_picture(&claims, id, &if_match)
+        .await?;
+    Ok((
+        etag_header(user.version),
+        RestApiResponse::success_with_message("Profile picture deleted", user),
+    ))
 }
 
 #[utoipa::path(
     delete,
     path = "/user/{id}",
-    responses((status = 200, description = "User deleted")),
+    params(DeleteUserQuery),
+    responses(
+        (status = 200, description = "User deleted, or what would be deleted in a dry run", body = DeleteUserSummaryDto),
+        (status = 400, description = "Invalid new device owner"),
+        (status = 409, description = "The new owner already has a device with the same name")
+    ),
     tag = "Users"
 )]
 pub async fn delete_user(
     State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    axum::extract::Path(id): axum::extract::Path<String>,
+    Query(options): Query<DeleteUserQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    let summary = state.user_service.delete_user(&claims, id, options).await?;
+    let message = match summary.dry_run {
+        true => "Dry run, nothing was deleted",
+        false => "User deleted",
+    };
+    Ok(RestApiResponse::success_with_message(message, summary))
+}
+
+#[utoipa::path(
+    post,
+    path = "/user/{id}/restore",
+    responses(
+        (status = 200, description = "Restore a deleted user and the files deleted with them", body = UserDto),
+        (status = 404, description = "No deleted user with this ID"),
+        (status = 409, description = "A restored profile picture conflicts with the user's current one")
+    ),
+    tag = "Users"
+)]
+pub async fn restore_user(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
     axum::extract::Path(id): axum::extract::Path<String>,
 ) -> Result<impl IntoResponse, AppError> {
-    let message = state.user_service.delete_user(id).await?;
-    Ok(RestApiResponse::success_with_message(message, ()))
+    let user = state.user_service.restore_user(&claims, id).await?;
+    Ok(RestApiResponse::success_with_message("User restored", user))
 }
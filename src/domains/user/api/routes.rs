@@ -1,11 +1,20 @@
 use super::handlers::*;
 use crate::{
-    common::app_state::AppState,
-    domains::user::dto::user_dto::{CreateUserMultipartDto, SearchUserDto, UpdateUserDto, UserDto},
+    common::{
+        app_state::AppState,
+        permission::{require_permission, USER_DELETE, USER_READ, USER_WRITE},
+        record_format::RecordFormat,
+    },
+    domains::user::dto::user_dto::{
+        CreateUserMultipartDto, DeleteUserSummaryDto, ImportMode, ImportRowResultDto,
+        ImportRowStatus, ImportUserRow, ImportUsersReportDto, ProfilePictureMultipartDto,
+        SearchUserDto, UpdateUserDto, UserDto,
+    },
 };
 
 use axum::{
-    routing::{delete, get, post, put},
+    middleware,
+    routing::{delete, get, patch, post, put},
     Router,
 };
 
@@ -21,10 +30,30 @@ use utoipa::{
         get_users,
         get_user_list,
         create_user,
+        import_users,
+        export_users,
         update_user,
+        patch_user,
+        get_profile_picture,
+        replace_profile_picture,
+        delete_profile_picture,
         delete_user,
+        restore_user,
     ),
-    components(schemas(UserDto, SearchUserDto, CreateUserMultipartDto, UpdateUserDto)),
+    components(schemas(
+        UserDto,
+        SearchUserDto,
+        CreateUserMultipartDto,
+        UpdateUserDto,
+        ProfilePictureMultipartDto,
+        ImportUserRow,
+        ImportMode,
+        ImportRowStatus,
+        ImportRowResultDto,
+        ImportUsersReportDto,
+        RecordFormat,
+        DeleteUserSummaryDto
+    )),
     tags(
         (name = "Users", description = "User management endpoints")
     ),
@@ -54,10 +83,59 @@ impl utoipa::Modify for UserApiDoc {
 
 pub fn user_routes() -> Router<AppState> {
     Router::new()
-        .route("/", get(get_users))
-        .route("/", post(create_user))
-        .route("/list", post(get_user_list))
-        .route("/{id}", get(get_user_by_id))
-        .route("/{id}", put(update_user))
-        .route("/{id}", delete(delete_user))
+        .route(
+            "/",
+            get(get_users).route_layer(middleware::from_fn(require_permission(USER_READ))),
+        )
+        .route(
+            "/",
+            post(create_user).route_layer(middleware::from_fn(require_permission(USER_WRITE))),
+        )
+        .route(
+            "/list",
+            post(get_user_list).route_layer(middleware::from_fn(require_permission(USER_READ))),
+        )
+        .route(
+            "/import",
+            post(import_users).route_layer(middleware::from_fn(require_permission(USER_WRITE))),
+        )
+        .route(
+            "/export",
+            get(export_users).route_layer(middleware::from_fn(require_permission(USER_READ))),
+        )
+        .route(
+            "/{id}",
+            get(get_user_by_id).route_layer(middleware::from_fn(require_permission(USER_READ))),
+        )
+        .route(
+            "/{id}",
+            put(update_user).route_layer(middleware::from_fn(require_permission(USER_WRITE))),
+        )
+        .route(
+            "/{id}",
+            patch(patch_user).route_layer(middleware::from_fn(require_permission(USER_WRITE))),
+        )
+        .route(
+            "/{id}",
+            delete(delete_user).route_layer(middleware::from_fn(require_permission(USER_DELETE))),
+        )
+        .route(
+            "/{id}/profile-picture",
+            get(get_profile_picture)
+                .route_layer(middleware::from_fn(require_permission(USER_READ))),
+        )
+        .route(
+            "/{id}/profile-picture",
+            put(replace_profile_picture)
+                .route_layer(middleware::from_fn(require_permission(USER_WRITE))),
+        )
+        .route(
+            "/{id}/profile-picture",
+            delete(delete_profile_picture)
+                .route_layer(middleware::from_fn(require_permission(USER_WRITE))),
+        )
+        .route(
+            "/{id}/restore",
+            post(restore_user).route_layer(middleware::from_fn(require_permission(USER_DELETE))),
+        )
 }
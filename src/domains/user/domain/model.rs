@@ -1,15 +1,38 @@
 use chrono::{DateTime, Utc};
 use sqlx::FromRow;
+
+use crate::common::pagination::{Keyset, SortValue};
+
 /// Domain model representing a user in the application.
 #[derive(Debug, Clone, FromRow)]
 pub struct User {
     pub id: String,
     pub username: String,
     pub email: Option<String>,
+    pub org_id: String,
     pub created_by: Option<String>,
     pub created_at: Option<DateTime<Utc>>,
     pub modified_by: Option<String>,
     pub modified_at: Option<DateTime<Utc>>,
+    /// Bumped on every change; returned as the ETag.
+    pub version: i64,
+    pub deleted_by: Option<String>,
+    pub deleted_at: Option<DateTime<Utc>>,
     pub file_id: Option<String>,
     pub origin_file_name: Option<String>,
 }
+
+impl Keyset for User {
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn sort_value(&self, field: &str) -> SortValue {
+        match field {
+            "username" => SortValue::Text(self.username.clone()),
+            "email" => SortValue::Text(self.email.clone().unwrap_or_default()),
+            "modified_at" => SortValue::time(self.modified_at),
+            _ => SortValue::time(self.created_at),
+        }
+    }
+}
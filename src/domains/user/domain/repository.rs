@@ -1,31 +1,52 @@
 //! This module defines the `UserRepository` trait, which abstracts
 //! the database operations related to user entities.
 
-use crate::domains::user::dto::user_dto::{CreateUserMultipartDto, SearchUserDto, UpdateUserDto};
+use crate::{
+    common::pagination::PageRequest,
+    domains::user::dto::user_dto::{CreateUserMultipartDto, SearchUserDto, UpdateUserDto},
+};
 
 use super::model::User;
 
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
 use sqlx::{PgPool, Postgres, Transaction};
 
 #[async_trait]
 /// Trait representing repository-level operations for user entities.
 /// Provides methods for creating, retrieving, updating, and deleting users in the database.
 pub trait UserRepository: Send + Sync {
-    /// Retrieves all users from the database.
-    async fn find_all(&self, pool: PgPool) -> Result<Vec<User>, sqlx::Error>;
-
     /// Finds a user by their unique identifier.
     async fn find_by_id(&self, pool: PgPool, id: String) -> Result<Option<User>, sqlx::Error>;
 
-    /// Finds user list by condition
+    /// Finds a user that is not deleted within an active transaction
+    /// and locks it until the transaction ends.
+    async fn find_for_update(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        id: String,
+    ) -> Result<Option<User>, sqlx::Error>;
+
+    /// Finds a page of the users matching the condition, in the order of the page request,
+    /// together with the total number of matching users.
     async fn find_list(
         &self,
         pool: PgPool,
         search_user_dto: SearchUserDto,
+        page: &PageRequest,
+    ) -> Result<(Vec<User>, i64), sqlx::Error>;
+
+    /// Finds a page of the users matching the condition, without counting them.
+    /// Used to walk through long lists, e.g. for exports.
+    async fn find_page(
+        &self,
+        pool: PgPool,
+        search_user_dto: &SearchUserDto,
+        page: &PageRequest,
     ) -> Result<Vec<User>, sqlx::Error>;
 
     /// Creates a new user record using the provided data within an active transaction.
+    /// An empty `modified_by` records the new user as its own creator.
     async fn create(
         &self,
         tx: &mut Transaction<'_, Postgres>,
@@ -40,10 +61,71 @@ pub trait UserRepository: Send + Sync {
         user: UpdateUserDto,
     ) -> Result<Option<User>, sqlx::Error>;
 
-    /// Deletes a user by their unique identifier within an active transaction.
+    /// Records a change to a user that is not deleted, e.g. of their profile picture,
+    /// within an active transaction and returns the updated user.
+    async fn touch(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        id: String,
+        modified_by: String,
+    ) -> Result<Option<User>, sqlx::Error>;
+
+    /// Soft-deletes a user by their unique identifier within an active transaction.
+    /// Returns `false` if there is no such user or the user is already deleted.
     async fn delete(
         &self,
         tx: &mut Transaction<'_, Postgres>,
         id: String,
+        deleted_by: String,
     ) -> Result<bool, sqlx::Error>;
+
+    /// Revokes every session of a user within an active transaction
+    /// and returns the number of revoked refresh tokens.
+    async fn revoke_sessions(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        id: String,
+    ) -> Result<u64, sqlx::Error>;
+
+    /// Revokes every API key of a user within an active transaction
+    /// and returns the number of revoked keys.
+    async fn revoke_api_keys(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        id: String,
+    ) -> Result<u64, sqlx::Error>;
+
+    /// Invalidates the outstanding password reset tokens and magic links of a user
+    /// within an active transaction and returns their number.
+    async fn expire_login_tokens(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        id: String,
+    ) -> Result<u64, sqlx::Error>;
+
+    /// Restores a soft-deleted user within an active transaction.
+    /// Returns `false` if there is no such deleted user.
+    async fn restore(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        id: String,
+        modified_by: String,
+    ) -> Result<bool, sqlx::Error>;
+
+    /// Locks the users soft-deleted before the given time within an active transaction
+    /// and returns their IDs.
+    async fn find_purgeable(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        deleted_before: DateTime<Utc>,
+    ) -> Result<Vec<String>, sqlx::Error>;
+
+    /// Hard-deletes the given users within an active transaction, together with their
+    /// credentials and sessions. Their devices and files must be purged first.
+    /// Returns the number of purged users.
+    async fn purge(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        ids: Vec<String>,
+    ) -> Result<u64, sqlx::Error>;
 }
@@ -2,14 +2,26 @@
 //! It abstracts operations such as user creation, retrieval, update, and deletion.
 
 use crate::{
-    common::error::AppError,
-    domains::file::dto::file_dto::UploadFileDto,
-    domains::user::dto::user_dto::{CreateUserMultipartDto, SearchUserDto, UpdateUserDto, UserDto},
+    common::{
+        concurrency::IfMatch,
+        error::AppError,
+        jwt::Claims,
+        pagination::{PageQuery, Paginated},
+    },
+    domains::file::dto::file_dto::{UploadFileDto, UploadedFileDto},
+    domains::user::dto::user_dto::{
+        CreateUserMultipartDto, DeleteUserQuery, DeleteUserSummaryDto, ImportMode, ImportUserRow,
+        ImportUsersReportDto, SearchUserDto, UpdateUserDto, UserDto,
+    },
 };
 
+use crate::domains::device::DeviceServiceTrait;
 use crate::domains::file::FileServiceTrait;
 use async_trait::async_trait;
-use sqlx::PgPool;
+use chrono::{DateTime, Utc};
+use futures_util::stream::BoxStream;
+use serde_json::Value;
+use sqlx::{PgPool, Postgres, Transaction};
 use std::sync::Arc;
 
 #[async_trait]
@@ -20,6 +32,7 @@ pub trait UserServiceTrait: Send + Sync {
     fn create_service(
         pool: PgPool,
         file_service: Arc<dyn FileServiceTrait>,
+        device_service: Arc<dyn DeviceServiceTrait>,
     ) -> Arc<dyn UserServiceTrait>
     where
         Self: Sized;
@@ -27,23 +40,127 @@ pub trait UserServiceTrait: Send + Sync {
     /// Retrieves a user by their unique identifier.
     async fn get_user_by_id(&self, id: String) -> Result<UserDto, AppError>;
 
-    /// Retrieves user list by condition
-    async fn get_user_list(&self, search_user_dto: SearchUserDto)
-        -> Result<Vec<UserDto>, AppError>;
-
-    /// Retrieves all users.
-    async fn get_users(&self) -> Result<Vec<UserDto>, AppError>;
+    /// Retrieves a page of the users matching the condition, in the requested order.
+    /// Only administrators may include soft-deleted users.
+    async fn get_user_list(
+        &self,
+        claims: &Claims,
+        search_user_dto: SearchUserDto,
+        page: PageQuery,
+    ) -> Result<Paginated<UserDto>, AppError>;
 
     /// Creates a new user with optional profile picture upload.
+    /// Only administrators may create users.
     async fn create_user(
         &self,
+        claims: &Claims,
         create_user: CreateUserMultipartDto,
         upload_file_dto: Option<&mut UploadFileDto>,
     ) -> Result<UserDto, AppError>;
 
+    /// Creates a user within an active transaction and returns its ID.
+    /// Used by self-registration, which stores the credentials in the same transaction.
+    /// A duplicate username is reported as `AppError::Conflict`.
+    async fn create_user_in_tx(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        create_user: CreateUserMultipartDto,
+    ) -> Result<String, AppError>;
+
     /// Updates an existing user with the given payload.
-    async fn update_user(&self, id: String, payload: UpdateUserDto) -> Result<UserDto, AppError>;
+    /// Users may update themselves; administrators may update anyone.
+    /// Fails with `PreconditionFailed` if the current version does not match `if_match`.
+    async fn update_user(
+        &self,
+        claims: &Claims,
+        id: String,
+        payload: UpdateUserDto,
+        if_match: &IfMatch,
+    ) -> Result<UserDto, AppError>;
+
+    /// Applies a JSON Merge Patch to the username and email of an existing user.
+    /// Users may patch themselves; administrators may patch anyone.
+    /// Fails with `PreconditionFailed` if the current version does not match `if_match`.
+    async fn patch_user(
+        &self,
+        claims: &Claims,
+        id: String,
+        patch: Value,
+        if_match: &IfMatch,
+    ) -> Result<UserDto, AppError>;
+
+    /// Retrieves the metadata of the current profile picture of a user.
+    /// Only the user themselves or an administrator may read it.
+    async fn get_profile_picture(
+        &self,
+        claims: &Claims,
+        id: String,
+    ) -> Result<UploadedFileDto, AppError>;
+
+    /// Replaces the profile picture of a user, or sets it if there is none, and returns
+    /// the updated user. The record of the previous picture is deleted in the same transaction
+    /// and its stored file once the transaction is committed.
+    /// Users may change their own picture; administrators may change anyone's.
+    /// Fails with `PreconditionFailed` if the current version does not match `if_match`.
+    async fn replace_profile_picture(
+        &self,
+        claims: &Claims,
+        id: String,
+        upload_file_dto: UploadFileDto,
+        if_match: &IfMatch,
+    ) -> Result<UserDto, AppError>;
+
+    /// Soft-deletes the profile picture of a user and returns the updated user.
+    /// Users may delete their own picture; administrators may delete anyone's.
+    /// Fails with `PreconditionFailed` if the current version does not match `if_match`.
+    async fn delete_profile_picture(
+        &self,
+        claims: &Claims,
+        id: String,
+        if_match: &IfMatch,
+    ) -> Result<UserDto, AppError>;
+
+    /// Soft-deletes a user by their unique identifier in one transaction with what depends on them:
+    /// their devices are handed over to another user or decommissioned, their uploaded files
+    /// are soft-deleted, and their sessions are revoked. Their password is kept, as deleted
+    /// users cannot sign in, so that restoring the user gives them their account back.
+    /// Requires the `user:delete` permission, which only the admin role is seeded with;
+    /// holders of the permission without the admin role may only delete themselves.
+    /// Handing devices over to another user requires access to that user as well.
+    async fn delete_user(
+        &self,
+        claims: &Claims,
+        id: String,
+        options: DeleteUserQuery,
+    ) -> Result<DeleteUserSummaryDto, AppError>;
+
+    /// Restores a soft-deleted user and returns it.
+    /// Deleted users cannot sign in, so in practice only administrators restore users.
+    /// The files deleted together with the user are restored and their password works again;
+    /// decommissioned devices and revoked sessions are not restored.
+    async fn restore_user(&self, claims: &Claims, id: String) -> Result<UserDto, AppError>;
+
+    /// Creates users from the rows of a bulk import, validating each row like a single
+    /// user creation, and reports the outcome of every row. In all-or-nothing mode no user
+    /// is kept unless every row can be created; a dry run never keeps any.
+    /// Only administrators may import users.
+    async fn import_users(
+        &self,
+        claims: &Claims,
+        rows: Vec<Result<ImportUserRow, String>>,
+        mode: ImportMode,
+        dry_run: bool,
+    ) -> Result<ImportUsersReportDto, AppError>;
+
+    /// Streams all users matching the condition, in the requested order.
+    /// The users are fetched in batches, so the export is never held in memory.
+    async fn export_users(
+        &self,
+        claims: &Claims,
+        search_user_dto: SearchUserDto,
+    ) -> Result<BoxStream<'static, Result<UserDto, AppError>>, AppError>;
 
-    /// Deletes a user by their unique identifier.
-    async fn delete_user(&self, id: String) -> Result<String, AppError>;
+    /// Hard-deletes the users soft-deleted before the given time and returns their number.
+    /// Their devices and files are purged with them, whenever they were deleted.
+    async fn purge_deleted(&self, deleted_before: DateTime<Utc>) -> Result<u64, AppError>;
 }
@@ -1,8 +1,13 @@
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use simple_dto_mapper_derive::DtoFrom;
-use utoipa::ToSchema;
-use validator::Validate;
+use utoipa::{IntoParams, ToSchema};
+use validator::{Validate, ValidationError};
+
+use crate::common::{
+    pagination::{SortField, SortKind, CREATED_AT},
+    record_format::RecordFormat,
+};
 
 use crate::domains::user::domain::model::User;
 
@@ -12,21 +17,81 @@ pub struct UserDto {
     pub id: String,
     pub username: String,
     pub email: Option<String>,
+    /// Organization the user belongs to.
+    pub org_id: String,
     pub created_by: Option<String>,
     #[serde(with = "crate::common::ts_format::option")]
     pub created_at: Option<DateTime<Utc>>,
     pub modified_by: Option<String>,
     #[serde(with = "crate::common::ts_format::option")]
     pub modified_at: Option<DateTime<Utc>>,
+    pub version: i64,
+    pub deleted_by: Option<String>,
+    #[serde(with = "crate::common::ts_format::option")]
+    pub deleted_at: Option<DateTime<Utc>>,
     pub file_id: Option<String>,
     pub origin_file_name: Option<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+/// Fields users may be sorted by.
+pub const USER_SORT_FIELDS: &[SortField] = &[
+    SortField {
+        name: "username",
+        kind: SortKind::Text,
+    },
+    SortField {
+        name: "email",
+        kind: SortKind::Text,
+    },
+    CREATED_AT,
+    SortField {
+        name: "modified_at",
+        kind: SortKind::Time,
+    },
+];
+
+/// Filters and ordering of the user lists. Text filters match case-insensitively
+/// anywhere in the value; ranges include their start and exclude their end.
+#[derive(Debug, Default, Clone, Serialize, Deserialize, ToSchema, IntoParams, Validate)]
+#[into_params(parameter_in = Query)]
+#[validate(schema(function = "validate_search_ranges"))]
 pub struct SearchUserDto {
     pub id: Option<String>,
     pub username: Option<String>,
     pub email: Option<String>,
+    /// Only users created at or after this time (RFC 3339).
+    #[serde(default, with = "crate::common::ts_format::option")]
+    pub created_from: Option<DateTime<Utc>>,
+    /// Only users created before this time (RFC 3339).
+    #[serde(default, with = "crate::common::ts_format::option")]
+    pub created_to: Option<DateTime<Utc>>,
+    /// Only users modified at or after this time (RFC 3339).
+    #[serde(default, with = "crate::common::ts_format::option")]
+    pub modified_from: Option<DateTime<Utc>>,
+    /// Only users modified before this time (RFC 3339).
+    #[serde(default, with = "crate::common::ts_format::option")]
+    pub modified_to: Option<DateTime<Utc>>,
+    pub has_profile_picture: Option<bool>,
+    /// Also return soft-deleted users. Only admins may set this.
+    pub include_deleted: Option<bool>,
+    /// Comma-separated sort fields, `-` for descending, e.g. `-created_at,username`.
+    /// Allowed fields are username, email, created_at and modified_at.
+    pub sort: Option<String>,
+}
+
+fn validate_search_ranges(search: &SearchUserDto) -> Result<(), ValidationError> {
+    let ranges = [
+        (search.created_from, search.created_to),
+        (search.modified_from, search.modified_to),
+    ];
+    if ranges
+        .iter()
+        .any(|range| matches!(range, (Some(from), Some(to)) if from > to))
+    {
+        return Err(ValidationError::new("range")
+            .with_message("The start of a range must not be after its end".into()));
+    }
+    Ok(())
 }
 #[derive(Debug, Serialize, Deserialize, ToSchema, Validate)]
 pub struct CreateUserMultipartDto {
@@ -41,7 +106,17 @@ pub struct CreateUserMultipartDto {
     pub profile_picture: Option<String>,
 }
 
+/// Multipart form of a profile picture upload.
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct ProfilePictureMultipartDto {
+    #[schema(value_type = String, format = "binary", example = "profile_picture.png")]
+    pub profile_picture: String,
+}
+
+/// The fields of a user that `PUT` replaces and `PATCH` merges into.
+/// Members that are not fields are rejected.
 #[derive(Debug, Serialize, Deserialize, ToSchema, Validate)]
+#[serde(deny_unknown_fields)]
 pub struct UpdateUserDto {
     #[validate(length(max = 64, message = "Username cannot exceed 64 characters"))]
     pub username: String,
@@ -49,3 +124,109 @@ pub struct UpdateUserDto {
     pub email: String,
     pub modified_by: String,
 }
+
+/// A user record of a bulk import. Further columns or members, like those of an export,
+/// are ignored.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ImportUserRow {
+    pub username: String,
+    pub email: String,
+}
+
+/// How an import treats rows that cannot be created.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportMode {
+    /// Create no user at all unless every row can be created.
+    #[default]
+    AllOrNothing,
+    /// Create the rows that can be created and report the others.
+    BestEffort,
+}
+
+/// Options of the bulk user import.
+#[derive(Debug, Default, Clone, Serialize, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ImportUsersQuery {
+    /// Format of the body. Defaults to the one named by its `Content-Type`.
+    pub format: Option<RecordFormat>,
+    pub mode: Option<ImportMode>,
+    /// Validate and check every row without creating any user.
+    pub dry_run: Option<bool>,
+}
+
+/// Outcome of a single import row.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportRowStatus {
+    /// The user was created.
+    Created,
+    /// The user can be created, but was not, because of a dry run or of other rows.
+    Valid,
+    /// The row could not be parsed or failed validation.
+    Invalid,
+    /// The username or email is already taken, possibly by an earlier row.
+    Conflict,
+}
+
+/// Result of a single import row.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ImportRowResultDto {
+    /// 1-based position of the row, not counting the CSV header.
+    pub row: usize,
+    pub status: ImportRowStatus,
+    pub username: Option<String>,
+    pub user_id: Option<String>,
+    pub errors: Vec<String>,
+}
+
+/// Report of a bulk user import.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ImportUsersReportDto {
+    pub mode: ImportMode,
+    pub dry_run: bool,
+    /// Whether the created users were kept.
+    pub committed: bool,
+    pub total: usize,
+    pub created: usize,
+    pub failed: usize,
+    pub rows: Vec<ImportRowResultDto>,
+}
+
+/// Options of the user export, next to the user filters.
+#[derive(Debug, Default, Clone, Serialize, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ExportUsersQuery {
+    /// Format of the export, CSV by default.
+    pub format: Option<RecordFormat>,
+}
+
+/// Options of the user deletion.
+#[derive(Debug, Default, Clone, Serialize, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct DeleteUserQuery {
+    /// Report what would be removed without changing anything.
+    pub dry_run: Option<bool>,
+    /// Hand the user's devices over to this user instead of decommissioning them.
+    pub reassign_devices_to: Option<String>,
+}
+
+/// What the deletion of a user removed, or would remove in a dry run.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct DeleteUserSummaryDto {
+    pub user_id: String,
+    pub dry_run: bool,
+    /// Devices that were decommissioned and deleted together with the user.
+    pub decommissioned_devices: Vec<String>,
+    /// Devices that were handed over to `reassigned_to`.
+    pub reassigned_devices: Vec<String>,
+    pub reassigned_to: Option<String>,
+    /// Uploaded files that were soft-deleted with the user; restoring the user restores them.
+    pub removed_files: Vec<String>,
+    /// Number of the user's sessions that were revoked.
+    pub sessions_revoked: u64,
+    /// Number of the user's API keys that were revoked.
+    pub api_keys_revoked: u64,
+    /// Number of outstanding password reset tokens and magic links that were invalidated.
+    pub login_tokens_expired: u64,
+}
@@ -1,8 +1,12 @@
-use crate::domains::user::{
-    domain::{model::User, repository::UserRepository},
-    dto::user_dto::{CreateUserMultipartDto, SearchUserDto, UpdateUserDto},
+use crate::{
+    common::{pagination::PageRequest, tenant},
+    domains::user::{
+        domain::{model::User, repository::UserRepository},
+        dto::user_dto::{CreateUserMultipartDto, SearchUserDto, UpdateUserDto},
+    },
 };
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
 
 use sqlx::{PgPool, Postgres, QueryBuilder, Transaction};
 use uuid::Uuid;
@@ -14,15 +18,19 @@ const FIND_USER_QUERY: &str = r#"
         u.id,
         u.username,
         u.email,
+        u.org_id,
         u.created_by,
         u.created_at,
         u.modified_by,
         u.modified_at,
+        u.version,
+        u.deleted_by,
+        u.deleted_at,
         uf.id as file_id,
         uf.origin_file_name
     FROM users u
     LEFT JOIN uploaded_files uf 
-            ON uf.user_id = u.id and uf.file_type = 'profile_picture'
+            ON uf.user_id = u.id and uf.file_type = 'profile_picture' and uf.deleted_at IS NULL
     WHERE 1=1
     "#;
 
@@ -31,65 +39,149 @@ const FIND_USER_INFO_QUERY: &str = r#"
         u.id,
         u.username,
         u.email,
+        u.org_id,
         u.created_by,
         u.created_at,
         u.modified_by,
         u.modified_at,
+        u.version,
+        u.deleted_by,
+        u.deleted_at,
         uf.id as file_id,
         uf.origin_file_name
     FROM users u
     LEFT JOIN uploaded_files uf 
-           ON uf.user_id = u.id and uf.file_type = 'profile_picture'
-    WHERE u.id = $1
+           ON uf.user_id = u.id and uf.file_type = 'profile_picture' and uf.deleted_at IS NULL
+    WHERE u.id = $1 AND u.deleted_at IS NULL
+      AND ($2::varchar IS NULL OR u.org_id = $2)
     "#;
 
-#[async_trait]
-impl UserRepository for UserRepo {
-    async fn find_all(&self, pool: PgPool) -> Result<Vec<User>, sqlx::Error> {
-        let users = sqlx::query_as::<_, User>(FIND_USER_QUERY)
-            .fetch_all(&pool)
-            .await?;
-        Ok(users)
+const COUNT_USER_QUERY: &str = r#"
+    SELECT COUNT(*)
+    FROM users u
+    WHERE 1=1
+    "#;
+
+/// Appends the conditions of the search to a query that ends in its `WHERE` clause.
+/// Text conditions match case-insensitively anywhere in the value.
+/// Soft-deleted users only match if the search includes them.
+fn push_search_conditions(
+    builder: &mut QueryBuilder<'_, Postgres>,
+    search_user_dto: &SearchUserDto,
+) {
+    tenant::push_condition(builder, "u");
+
+    if search_user_dto.include_deleted != Some(true) {
+        builder.push(" AND u.deleted_at IS NULL");
+    }
+
+    let non_blank = |s: &Option<String>| {
+        s.as_deref()
+            .filter(|s| !s.trim().is_empty())
+            .map(str::to_string)
+    };
+
+    if let Some(s) = non_blank(&search_user_dto.id) {
+        builder.push(" AND u.id = ");
+        builder.push_bind(s);
+    }
+
+    if let Some(s) = non_blank(&search_user_dto.username) {
+        builder.push(" AND u.username ILIKE ");
+        builder.push_bind(contains_pattern(&s));
+    }
+
+    if let Some(s) = non_blank(&search_user_dto.email) {
+        builder.push(" AND u.email ILIKE ");
+        builder.push_bind(contains_pattern(&s));
     }
 
+    let ranges = [
+        ("u.created_at >= ", search_user_dto.created_from),
+        ("u.created_at < ", search_user_dto.created_to),
+        ("u.modified_at >= ", search_user_dto.modified_from),
+        ("u.modified_at < ", search_user_dto.modified_to),
+    ];
+    for (condition, bound) in ranges {
+        if let Some(bound) = bound {
+            builder.push(format!(" AND {condition}"));
+            builder.push_bind(bound);
+        }
+    }
+
+    if let Some(has_profile_picture) = search_user_dto.has_profile_picture {
+        builder.push(match has_profile_picture {
+            true => " AND EXISTS",
+            false => " AND NOT EXISTS",
+        });
+        builder.push(
+            " (SELECT 1 FROM uploaded_files pf WHERE pf.user_id = u.id AND pf.file_type = 'profile_picture' AND pf.deleted_at IS NULL)",
+        );
+    }
+}
+
+/// Builds a `LIKE` pattern matching the value anywhere, with its wildcards escaped.
+fn contains_pattern(value: &str) -> String {
+    let escaped = value
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_");
+    format!("%{escaped}%")
+}
+
+#[async_trait]
+impl UserRepository for UserRepo {
     async fn find_list(
         &self,
         pool: PgPool,
         search_user_dto: SearchUserDto,
-    ) -> Result<Vec<User>, sqlx::Error> {
-        let mut builder = QueryBuilder::<_>::new(FIND_USER_QUERY);
+        page: &PageRequest,
+    ) -> Result<(Vec<User>, i64), sqlx::Error> {
+        let mut count = QueryBuilder::<Postgres>::new(COUNT_USER_QUERY);
+        push_search_conditions(&mut count, &search_user_dto);
+        let total = count.build_query_scalar::<i64>().fetch_one(&pool).await?;
 
-        if let Some(s) = search_user_dto
-            .id
-            .as_deref()
-            .filter(|s| !s.trim().is_empty())
-        {
-            builder.push(" AND u.id = ");
-            builder.push_bind(s);
-        }
+        let users = self.find_page(pool, &search_user_dto, page).await?;
+        Ok((users, total))
+    }
 
-        if let Some(s) = search_user_dto
-            .username
-            .as_deref()
-            .filter(|s| !s.trim().is_empty())
-        {
-            builder.push(" AND u.username like ");
-            builder.push_bind(format!("%{}%", s));
-        }
+    async fn find_page(
+        &self,
+        pool: PgPool,
+        search_user_dto: &SearchUserDto,
+        page: &PageRequest,
+    ) -> Result<Vec<User>, sqlx::Error> {
+        let mut builder = QueryBuilder::<Postgres>::new(FIND_USER_QUERY);
+        push_search_conditions(&mut builder, search_user_dto);
+        page.push_to(&mut builder, "u");
 
         let query = builder.build_query_as::<User>();
-        let users = query.fetch_all(&pool).await?;
-        Ok(users)
+        query.fetch_all(&pool).await
     }
 
     async fn find_by_id(&self, pool: PgPool, id: String) -> Result<Option<User>, sqlx::Error> {
         let user = sqlx::query_as::<_, User>(FIND_USER_INFO_QUERY)
             .bind(id)
+            .bind(tenant::current())
             .fetch_optional(&pool)
             .await?;
         Ok(user)
     }
 
+    async fn find_for_update(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        id: String,
+    ) -> Result<Option<User>, sqlx::Error> {
+        let query = format!("{FIND_USER_INFO_QUERY} FOR UPDATE OF u");
+        let user = sqlx::query_as::<_, User>(&query)
+            .bind(id)
+            .bind(tenant::current())
+            .fetch_optional(&mut **tx)
+            .await?;
+        Ok(user)
+    }
+
     async fn create(
         &self,
         tx: &mut Transaction<'_, Postgres>,
@@ -97,16 +189,23 @@ impl UserRepository for UserRepo {
     ) -> Result<String, sqlx::Error> {
         let id = Uuid::new_v4().to_string();
 
+        // Self-registered users have no creator other than themselves.
+        let modified_by = match user.modified_by.is_empty() {
+            true => id.clone(),
+            false => user.modified_by,
+        };
+
         sqlx::query!(
             r#"
-                INSERT INTO users (id, username, email, created_by, modified_by)
-                VALUES ($1, $2, $3, $4, $5)
+                INSERT INTO users (id, username, email, created_by, modified_by, org_id)
+                VALUES ($1, $2, LOWER(TRIM($3)), $4, $5, $6)
                 "#,
             id.clone(),
             user.username.clone(),
             user.email.clone(),
-            user.modified_by.clone(),
-            user.modified_by
+            modified_by.clone(),
+            modified_by,
+            tenant::current_or_default()
         )
         .execute(&mut **tx)
         .await?;
@@ -122,6 +221,7 @@ impl UserRepository for UserRepo {
     ) -> Result<Option<User>, sqlx::Error> {
         let existing = sqlx::query_as::<_, User>(FIND_USER_INFO_QUERY)
             .bind(id.clone())
+            .bind(tenant::current())
             .fetch_optional(&mut **tx)
             .await?;
 
@@ -130,9 +230,10 @@ impl UserRepository for UserRepo {
                 r#"
                 UPDATE users 
                 SET username = $1,
-                    email = $2, 
+                    email = LOWER(TRIM($2)),
                     modified_by = $3, 
-                    modified_at = NOW() 
+                    modified_at = NOW(),
+                    version = version + 1
                 WHERE id = $4
                 "#,
                 user.username.clone(),
@@ -145,6 +246,7 @@ impl UserRepository for UserRepo {
 
             let updated_user = sqlx::query_as::<_, User>(FIND_USER_INFO_QUERY)
                 .bind(id)
+                .bind(tenant::current())
                 .fetch_one(&mut **tx)
                 .await?;
 
@@ -153,14 +255,203 @@ impl UserRepository for UserRepo {
         Ok(None)
     }
 
+    async fn touch(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        id: String,
+        modified_by: String,
+    ) -> Result<Option<User>, sqlx::Error> {
+        sqlx::query!(
+            r#"
+            UPDATE users
+               SET modified_by = $2,
+                   modified_at = NOW(),
+                   version = version + 1
+             WHERE id = $1
+               AND deleted_at IS NULL
+               AND ($3::varchar IS NULL OR org_id = $3)
+            "#,
+            id,
+            modified_by,
+            tenant::current()
+        )
+        .execute(&mut **tx)
+        .await?;
+
+        let user = sqlx::query_as::<_, User>(FIND_USER_INFO_QUERY)
+            .bind(id)
+            .bind(tenant::current())
+            .fetch_optional(&mut **tx)
+            .await?;
+        Ok(user)
+    }
+
     async fn delete(
         &self,
         tx: &mut Transaction<'_, Postgres>,
         id: String,
+        deleted_by: String,
     ) -> Result<bool, sqlx::Error> {
-        let res = sqlx::query!(r#"DELETE FROM users WHERE id = $1"#, id)
-            .execute(&mut **tx)
-            .await?;
+        let res = sqlx::query!(
+            r#"
+            UPDATE users
+               SET deleted_at = NOW(),
+                   deleted_by = $2,
+                   version = version + 1
+             WHERE id = $1
+               AND deleted_at IS NULL
+               AND ($3::varchar IS NULL OR org_id = $3)
+            "#,
+            id,
+            deleted_by,
+            tenant::current()
+        )
+        .execute(&mut **tx)
+        .await?;
         Ok(res.rows_affected() > 0)
     }
+
+    async fn revoke_sessions(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        id: String,
+    ) -> Result<u64, sqlx::Error> {
+        let res = sqlx::query!(
+            r#"
+            UPDATE refresh_tokens
+               SET revoked_at = NOW()
+             WHERE user_id = $1
+               AND revoked_at IS NULL
+               AND user_id IN (SELECT id FROM users WHERE $2::varchar IS NULL OR org_id = $2)
+            "#,
+            id,
+            tenant::current()
+        )
+        .execute(&mut **tx)
+        .await?;
+        Ok(res.rows_affected())
+    }
+
+    async fn revoke_api_keys(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        id: String,
+    ) -> Result<u64, sqlx::Error> {
+        let res = sqlx::query!(
+            r#"
+            UPDATE api_keys
+               SET revoked_at = NOW()
+             WHERE user_id = $1
+               AND revoked_at IS NULL
+               AND user_id IN (SELECT id FROM users WHERE $2::varchar IS NULL OR org_id = $2)
+            "#,
+            id,
+            tenant::current()
+        )
+        .execute(&mut **tx)
+        .await?;
+        Ok(res.rows_affected())
+    }
+
+    async fn expire_login_tokens(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        id: String,
+    ) -> Result<u64, sqlx::Error> {
+        let resets = sqlx::query!(
+            r#"
+            UPDATE password_reset_tokens
+               SET used_at = NOW()
+             WHERE user_id = $1
+               AND used_at IS NULL
+               AND user_id IN (SELECT id FROM users WHERE $2::varchar IS NULL OR org_id = $2)
+            "#,
+            id,
+            tenant::current()
+        )
+        .execute(&mut **tx)
+        .await?;
+        let links = sqlx::query!(
+            r#"
+            UPDATE magic_links
+               SET used_at = NOW()
+             WHERE user_id = $1
+               AND used_at IS NULL
+               AND user_id IN (SELECT id FROM users WHERE $2::varchar IS NULL OR org_id = $2)
+            "#,
+            id,
+            tenant::current()
+        )
+        .execute(&mut **tx)
+        .await?;
+        Ok(resets.rows_affected() + links.rows_affected())
+    }
+
+    async fn restore(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        id: String,
+        modified_by: String,
+    ) -> Result<bool, sqlx::Error> {
+        let res = sqlx::query!(
+            r#"
+            UPDATE users
+               SET deleted_at = NULL,
+                   deleted_by = NULL,
+                   modified_by = $2,
+                   modified_at = NOW(),
+                   version = version + 1
+             WHERE id = $1
+               AND deleted_at IS NOT NULL
+               AND ($3::varchar IS NULL OR org_id = $3)
+            "#,
+            id,
+            modified_by,
+            tenant::current()
+        )
+        .execute(&mut **tx)
+        .await?;
+        Ok(res.rows_affected() > 0)
+    }
+
+    async fn find_purgeable(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        deleted_before: DateTime<Utc>,
+    ) -> Result<Vec<String>, sqlx::Error> {
+        let ids = sqlx::query_scalar!(
+            r#"
+            SELECT id
+              FROM users
+             WHERE deleted_at < $1
+               AND ($2::varchar IS NULL OR org_id = $2)
+               FOR UPDATE
+            "#,
+            deleted_before,
+            tenant::current()
+        )
+        .fetch_all(&mut **tx)
+        .await?;
+        Ok(ids)
+    }
+
+    async fn purge(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        ids: Vec<String>,
+    ) -> Result<u64, sqlx::Error> {
+        let res = sqlx::query!(
+            r#"
+            DELETE FROM users
+             WHERE id = ANY($1)
+               AND deleted_at IS NOT NULL
+               AND ($2::varchar IS NULL OR org_id = $2)
+            "#,
+            &ids,
+            tenant::current()
+        )
+        .execute(&mut **tx)
+        .await?;
+        Ok(res.rows_affected())
+    }
 }
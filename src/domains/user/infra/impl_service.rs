@@ -1,17 +1,42 @@
 use crate::{
-    common::error::AppError,
+    common::{
+        concurrency::IfMatch,
+        error::{is_unique_violation, unique_violation_constraint, AppError},
+        jwt::Claims,
+        merge_patch::apply_merge_patch,
+        pagination::{Cursor, PageQuery, PageRequest, Paginated, SortKey},
+        tenant,
+    },
     domains::{
-        file::{dto::file_dto::UploadFileDto, FileServiceTrait},
+        device::DeviceServiceTrait,
+        file::{
+            dto::file_dto::{UploadFileDto, UploadedFileDto},
+            FileServiceTrait,
+        },
         user::{
-            domain::{repository::UserRepository, service::UserServiceTrait},
-            dto::user_dto::{CreateUserMultipartDto, SearchUserDto, UpdateUserDto, UserDto},
+            domain::{model::User, repository::UserRepository, service::UserServiceTrait},
+            dto::user_dto::{
+                CreateUserMultipartDto, DeleteUserQuery, DeleteUserSummaryDto, ImportMode,
+                ImportRowResultDto, ImportRowStatus, ImportUserRow, ImportUsersReportDto,
+                SearchUserDto, UpdateUserDto, UserDto, USER_SORT_FIELDS,
+            },
             infra::impl_repository::UserRepo,
         },
     },
 };
 use async_trait::async_trait;
-use sqlx::PgPool;
+use chrono::{DateTime, Utc};
+use futures_util::{
+    stream::{self, BoxStream},
+    StreamExt, TryStreamExt,
+};
+use serde_json::Value;
+use sqlx::{Acquire, PgPool, Postgres, Transaction};
 use std::sync::Arc;
+use validator::{Validate, ValidationErrors};
+
+/// Number of users fetched at a time by the export.
+const EXPORT_BATCH_SIZE: i64 = 500;
 
 /// Service struct for handling user-related operations
 /// such as creating, updating, deleting, and fetching users.
@@ -21,6 +46,7 @@ pub struct UserService {
     pub pool: PgPool,
     pub repo: Arc<dyn UserRepository + Send + Sync>,
     pub file_service: Arc<dyn FileServiceTrait>,
+    pub device_service: Arc<dyn DeviceServiceTrait>,
 }
 
 #[async_trait]
@@ -29,11 +55,13 @@ impl UserServiceTrait for UserService {
     fn create_service(
         pool: PgPool,
         file_service: Arc<dyn FileServiceTrait>,
+        device_service: Arc<dyn DeviceServiceTrait>,
     ) -> Arc<dyn UserServiceTrait> {
         Arc::new(Self {
             pool,
             repo: Arc::new(UserRepo {}),
             file_service,
+            device_service,
         })
     }
 
@@ -50,20 +78,28 @@ impl UserServiceTrait for UserService {
     }
 
     /// Retrieves user list by condition
-    /// Returns a vector of UserDto objects.
+    /// Returns a page of UserDto objects.
     async fn get_user_list(
         &self,
-        search_user_dto: SearchUserDto,
-    ) -> Result<Vec<UserDto>, AppError> {
+        claims: &Claims,
+        mut search_user_dto: SearchUserDto,
+        page: PageQuery,
+    ) -> Result<Paginated<UserDto>, AppError> {
+        search_user_dto.validate()?;
+        search_user_dto.include_deleted =
+            Some(claims.include_deleted(search_user_dto.include_deleted)?);
+        let order = SortKey::parse_list(
+            search_user_dto.sort.as_deref().unwrap_or_default(),
+            USER_SORT_FIELDS,
+        )?;
+        let page = PageRequest::sorted(page, order)?;
+
         match self
             .repo
-            .find_list(self.pool.clone(), search_user_dto)
+            .find_list(self.pool.clone(), search_user_dto, &page)
             .await
         {
-            Ok(users) => {
-                let user_dtos: Vec<UserDto> = users.into_iter().map(Into::into).collect();
-                Ok(user_dtos)
-            }
+            Ok((users, total)) => Ok(page.paginate(users, total).map(UserDto::from)),
             Err(err) => {
                 tracing::error!("Error fetching users: {err}");
                 Err(AppError::DatabaseError(err))
@@ -71,35 +107,25 @@ impl UserServiceTrait for UserService {
         }
     }
 
-    /// Retrieves all users.
-    /// Returns a vector of UserDto objects.
-    async fn get_users(&self) -> Result<Vec<UserDto>, AppError> {
-        match self.repo.find_all(self.pool.clone()).await {
-            Ok(users) => {
-                let user_dtos: Vec<UserDto> = users.into_iter().map(Into::into).collect();
-                Ok(user_dtos)
-            }
-            Err(err) => {
-                tracing::error!("Error fetching users: {err}");
-                Err(AppError::DatabaseError(err))
-            }
-        }
-    }
     /// Creates a new user.
     /// Takes a CreateUserMultipartDto object and an optional UploadFileDto object.
     async fn create_user(
         &self,
+        claims: &Claims,
         create_user: CreateUserMultipartDto,
         upload_file_dto: Option<&mut UploadFileDto>,
     ) -> Result<UserDto, AppError> {
+        if !claims.is_admin() {
+            return Err(AppError::Forbidden);
+        }
+
         let mut tx = self.pool.begin().await?;
 
-        let user_id = match self.repo.create(&mut tx, create_user).await {
+        let user_id = match self.create_user_in_tx(&mut tx, create_user).await {
             Ok(user_id) => user_id,
             Err(err) => {
-                tracing::error!("Error creating user: {err}");
                 tx.rollback().await?;
-                return Err(AppError::DatabaseError(err));
+                return Err(err);
             }
         };
 
@@ -122,45 +148,680 @@ impl UserServiceTrait for UserService {
         }
     }
 
+    /// Creates a user within the given transaction.
+    async fn create_user_in_tx(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        create_user: CreateUserMultipartDto,
+    ) -> Result<String, AppError> {
+        self.repo.create(tx, create_user).await.map_err(|err| {
+            if is_unique_violation(&err) {
+                return user_conflict(&err);
+            }
+            tracing::error!("Error creating user: {err}");
+            AppError::DatabaseError(err)
+        })
+    }
+
     /// Updates an existing user.
-    async fn update_user(&self, id: String, payload: UpdateUserDto) -> Result<UserDto, AppError> {
+    async fn update_user(
+        &self,
+        claims: &Claims,
+        id: String,
+        payload: UpdateUserDto,
+        if_match: &IfMatch,
+    ) -> Result<UserDto, AppError> {
+        self.update_locked(claims, id, if_match, |_| Ok(payload))
+            .await
+    }
+
+    /// Applies a JSON Merge Patch to the username and email of a user.
+    /// The patched user is validated like a full update.
+    async fn patch_user(
+        &self,
+        claims: &Claims,
+        id: String,
+        patch: Value,
+        if_match: &IfMatch,
+    ) -> Result<UserDto, AppError> {
+        let modified_by = claims.actor_id().to_string();
+
+        self.update_locked(claims, id, if_match, |user| {
+            let current = UpdateUserDto {
+                username: user.username.clone(),
+                email: user.email.clone().unwrap_or_default(),
+                modified_by: modified_by.clone(),
+            };
+
+            let mut payload = apply_merge_patch(&current, patch)?;
+            payload.modified_by = modified_by;
+            payload.validate()?;
+            Ok(payload)
+        })
+        .await
+    }
+
+    /// Retrieves the profile picture of a user that is not deleted.
+    async fn get_profile_picture(
+        &self,
+        claims: &Claims,
+        id: String,
+    ) -> Result<UploadedFileDto, AppError> {
+        if !claims.can_access(&id) {
+            return Err(AppError::Forbidden);
+        }
+
+        self.get_user_by_id(id.clone()).await?;
+
+        self.file_service
+            .get_profile_picture(&id)
+            .await?
+            .ok_or_else(|| AppError::NotFound("Profile picture not found".into()))
+    }
+
+    /// Replaces the profile picture of a user.
+    /// The new stored file is removed again if the transaction cannot be committed.
+    async fn replace_profile_picture(
+        &self,
+        claims: &Claims,
+        id: String,
+        upload_file_dto: UploadFileDto,
+        if_match: &IfMatch,
+    ) -> Result<UserDto, AppError> {
+        if !claims.can_access(&id) {
+            return Err(AppError::Forbidden);
+        }
+
+        let mut upload_file_dto = upload_file_dto;
+        upload_file_dto.user_id = Some(id.clone());
+        upload_file_dto.modified_by = claims.actor_id().to_string();
+
         let mut tx = self.pool.begin().await?;
 
-        match self.repo.update(&mut tx, id.to_string(), payload).await {
-            Ok(Some(user)) => {
+        let (user, uploaded, replaced) = match self
+            .replace_profile_picture_in_tx(&mut tx, &id, &upload_file_dto, if_match)
+            .await
+        {
+            Ok(replaced) => replaced,
+            Err(err) => {
+                tx.rollback().await?;
+                return Err(err);
+            }
+        };
+
+        if let Err(err) = tx.commit().await {
+            self.file_service.remove_stored_files(&[uploaded]);
+            return Err(AppError::DatabaseError(err));
+        }
+        self.file_service.remove_stored_files(replaced.as_slice());
+
+        Ok(UserDto::from(user))
+    }
+
+    /// Soft-deletes the profile picture of a user.
+    async fn delete_profile_picture(
+        &self,
+        claims: &Claims,
+        id: String,
+        if_match: &IfMatch,
+    ) -> Result<UserDto, AppError> {
+        if !claims.can_access(&id) {
+            return Err(AppError::Forbidden);
+        }
+
+        let mut tx = self.pool.begin().await?;
+
+        match self
+            .delete_profile_picture_in_tx(&mut tx, &id, claims.actor_id(), if_match)
+            .await
+        {
+            Ok(user) => {
                 tx.commit().await?;
                 Ok(UserDto::from(user))
             }
-            Ok(None) => {
+            Err(err) => {
                 tx.rollback().await?;
-                Err(AppError::NotFound("User not found".into()))
+                Err(err)
             }
+        }
+    }
+
+    /// Soft-deletes a user by their ID together with what depends on them.
+    /// A dry run rolls the transaction back, so nothing is changed.
+    async fn delete_user(
+        &self,
+        claims: &Claims,
+        id: String,
+        options: DeleteUserQuery,
+    ) -> Result<DeleteUserSummaryDto, AppError> {
+        if !claims.can_access(&id) {
+            return Err(AppError::Forbidden);
+        }
+
+        let new_owner = options
+            .reassign_devices_to
+            .filter(|new_owner| !new_owner.trim().is_empty());
+        if let Some(new_owner) = &new_owner {
+            self.check_new_device_owner(claims, &id, new_owner).await?;
+        }
+
+        let mut tx = self.pool.begin().await?;
+
+        let mut summary = match self
+            .delete_user_in_tx(&mut tx, &id, new_owner, claims.actor_id())
+            .await
+        {
+            Ok(summary) => summary,
             Err(err) => {
-                tracing::error!("Error updating user: {err}");
                 tx.rollback().await?;
-                Err(AppError::DatabaseError(err))
+                return Err(err);
+            }
+        };
+
+        summary.dry_run = options.dry_run.unwrap_or(false);
+        if summary.dry_run {
+            tx.rollback().await?;
+        } else {
+            tx.commit().await?;
+        }
+
+        Ok(summary)
+    }
+
+    /// Restores a soft-deleted user by their ID, together with the files deleted with them.
+    /// The files are restored first, as they are matched by the user's deletion time.
+    async fn restore_user(&self, claims: &Claims, id: String) -> Result<UserDto, AppError> {
+        if !claims.can_access(&id) {
+            return Err(AppError::Forbidden);
+        }
+
+        let mut tx = self.pool.begin().await?;
+
+        if let Err(err) = self
+            .file_service
+            .restore_user_files(&mut tx, &id, claims.actor_id())
+            .await
+        {
+            tx.rollback().await?;
+            return Err(err);
+        }
+
+        match self
+            .repo
+            .restore(&mut tx, id.clone(), claims.actor_id().to_string())
+            .await
+        {
+            Ok(true) => tx.commit().await?,
+            Ok(false) => {
+                tx.rollback().await?;
+                return Err(AppError::NotFound("Deleted user not found".into()));
+            }
+            Err(err) => {
+                tracing::error!("Error restoring user: {err}");
+                tx.rollback().await?;
+                return Err(AppError::DatabaseError(err));
             }
         }
+
+        self.get_user_by_id(id).await
+    }
+
+    /// Creates every row in one transaction, which is only committed when the mode
+    /// and the dry-run flag allow it; otherwise created rows are reported as valid.
+    async fn import_users(
+        &self,
+        claims: &Claims,
+        rows: Vec<Result<ImportUserRow, String>>,
+        mode: ImportMode,
+        dry_run: bool,
+    ) -> Result<ImportUsersReportDto, AppError> {
+        if !claims.is_admin() {
+            return Err(AppError::Forbidden);
+        }
+        if rows.is_empty() {
+            return Err(AppError::ValidationError(
+                "The import contains no rows".into(),
+            ));
+        }
+
+        let mut tx = self.pool.begin().await?;
+
+        let mut results = Vec::with_capacity(rows.len());
+        for (index, row) in rows.into_iter().enumerate() {
+            results.push(self.import_row(&mut tx, claims, index + 1, row).await?);
+        }
+
+        let failed = results
+            .iter()
+            .filter(|result| result.status != ImportRowStatus::Created)
+            .count();
+        let committed = !dry_run && (mode == ImportMode::BestEffort || failed == 0);
+        if committed {
+            tx.commit().await?;
+        } else {
+            tx.rollback().await?;
+            for result in results
+                .iter_mut()
+                .filter(|result| result.status == ImportRowStatus::Created)
+            {
+                result.status = ImportRowStatus::Valid;
+                result.user_id = None;
+            }
+        }
+
+        Ok(ImportUsersReportDto {
+            mode,
+            dry_run,
+            committed,
+            total: results.len(),
+            created: match committed {
+                true => results.len() - failed,
+                false => 0,
+            },
+            failed,
+            rows: results,
+        })
+    }
+
+    async fn export_users(
+        &self,
+        claims: &Claims,
+        mut search_user_dto: SearchUserDto,
+    ) -> Result<BoxStream<'static, Result<UserDto, AppError>>, AppError> {
+        search_user_dto.validate()?;
+        search_user_dto.include_deleted =
+            Some(claims.include_deleted(search_user_dto.include_deleted)?);
+        let order = SortKey::parse_list(
+            search_user_dto.sort.as_deref().unwrap_or_default(),
+            USER_SORT_FIELDS,
+        )?;
+        let page = PageRequest {
+            size: EXPORT_BATCH_SIZE,
+            ..PageRequest::sorted(PageQuery::default(), order)?
+        };
+
+        let pool = self.pool.clone();
+        let repo = self.repo.clone();
+        // The response body is streamed after the request scope ended.
+        let org_id = tenant::current();
+        // Each step fetches a batch and moves the cursor past it, until a batch comes up short.
+        let batches = stream::try_unfold(Some(page), move |page| {
+            let pool = pool.clone();
+            let repo = repo.clone();
+            let search_user_dto = search_user_dto.clone();
+            tenant::scope(org_id.clone(), async move {
+                let Some(mut page) = page else {
+                    return Ok::<_, AppError>(None);
+                };
+                let mut users = repo
+                    .find_page(pool, &search_user_dto, &page)
+                    .await
+                    .map_err(|err| {
+                        tracing::error!("Error exporting users: {err}");
+                        AppError::DatabaseError(err)
+                    })?;
+
+                let has_next = users.len() as i64 > page.size;
+                users.truncate(page.size as usize);
+                page.after = users.last().map(|user| Cursor::new(user, &page.order));
+                let next = Some(page).filter(|_| has_next);
+                Ok(Some((users, next)))
+            })
+        });
+
+        Ok(batches
+            .map_ok(|users| stream::iter(users.into_iter().map(|user| Ok(UserDto::from(user)))))
+            .try_flatten()
+            .boxed())
     }
 
-    /// Deletes a user by their ID.
-    async fn delete_user(&self, id: String) -> Result<String, AppError> {
+    /// Hard-deletes the users soft-deleted before the given time in one transaction,
+    /// together with their devices and files. The stored files are only removed once
+    /// the purge is committed.
+    async fn purge_deleted(&self, deleted_before: DateTime<Utc>) -> Result<u64, AppError> {
         let mut tx = self.pool.begin().await?;
 
-        match self.repo.delete(&mut tx, id.to_string()).await {
-            Ok(true) => {
+        match self.purge_in_tx(&mut tx, deleted_before).await {
+            Ok((purged, files)) => {
                 tx.commit().await?;
-                Ok("User deleted".into())
+                self.file_service.remove_stored_files(&files);
+                Ok(purged)
             }
-            Ok(false) => {
+            Err(err) => {
                 tx.rollback().await?;
-                Err(AppError::NotFound("User not found".into()))
+                Err(err)
+            }
+        }
+    }
+}
+
+/// Internal helper methods defined on `UserService`.
+impl UserService {
+    /// Creates the user of an import row in a savepoint of the import transaction,
+    /// so that a conflicting row does not abort the rows after it.
+    async fn import_row(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        claims: &Claims,
+        row: usize,
+        import_row: Result<ImportUserRow, String>,
+    ) -> Result<ImportRowResultDto, AppError> {
+        let mut result = ImportRowResultDto {
+            row,
+            status: ImportRowStatus::Invalid,
+            username: None,
+            user_id: None,
+            errors: Vec::new(),
+        };
+
+        let import_row = match import_row {
+            Ok(import_row) => import_row,
+            Err(err) => {
+                result.errors.push(err);
+                return Ok(result);
+            }
+        };
+        result.username = Some(import_row.username.clone());
+
+        let create_user = CreateUserMultipartDto {
+            username: import_row.username,
+            email: import_row.email,
+            modified_by: claims.actor_id().to_string(),
+            profile_picture: None,
+        };
+        if let Err(errors) = create_user.validate() {
+            result.errors = validation_messages(&errors);
+            return Ok(result);
+        }
+
+        let mut savepoint = tx.begin().await?;
+        match self.create_user_in_tx(&mut savepoint, create_user).await {
+            Ok(user_id) => {
+                savepoint.commit().await?;
+                result.status = ImportRowStatus::Created;
+                result.user_id = Some(user_id);
+            }
+            Err(AppError::Conflict(message)) => {
+                savepoint.rollback().await?;
+                result.status = ImportRowStatus::Conflict;
+                result.errors.push(message);
+            }
+            Err(err) => return Err(err),
+        }
+        Ok(result)
+    }
+
+    /// Updates a user with the payload built from its current state.
+    /// The user is locked while `if_match` is checked and the update applied,
+    /// so concurrent updates cannot overwrite each other.
+    async fn update_locked(
+        &self,
+        claims: &Claims,
+        id: String,
+        if_match: &IfMatch,
+        build: impl FnOnce(&User) -> Result<UpdateUserDto, AppError> + Send,
+    ) -> Result<UserDto, AppError> {
+        if !claims.can_access(&id) {
+            return Err(AppError::Forbidden);
+        }
+
+        let mut tx = self.pool.begin().await?;
+
+        match self.update_in_tx(&mut tx, id, if_match, build).await {
+            Ok(user) => {
+                tx.commit().await?;
+                Ok(UserDto::from(user))
             }
             Err(err) => {
-                tracing::error!("Error deleting user: {err}");
                 tx.rollback().await?;
+                Err(err)
+            }
+        }
+    }
+
+    /// Locks a user, checks `if_match` and applies the payload built from the current state.
+    async fn update_in_tx(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        id: String,
+        if_match: &IfMatch,
+        build: impl FnOnce(&User) -> Result<UpdateUserDto, AppError> + Send,
+    ) -> Result<User, AppError> {
+        let current = self.lock_user(tx, &id, if_match).await?;
+        let payload = build(&current)?;
+
+        match self.repo.update(tx, id, payload).await {
+            Ok(Some(user)) => Ok(user),
+            Ok(None) => Err(AppError::NotFound("User not found".into())),
+            Err(err) if is_unique_violation(&err) => Err(user_conflict(&err)),
+            Err(err) => {
+                tracing::error!("Error updating user: {err}");
                 Err(AppError::DatabaseError(err))
             }
         }
     }
+
+    /// Locks a user that is not deleted until the transaction ends
+    /// and checks `if_match` against its current version.
+    async fn lock_user(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        id: &str,
+        if_match: &IfMatch,
+    ) -> Result<User, AppError> {
+        let user = match self.repo.find_for_update(tx, id.to_string()).await {
+            Ok(Some(user)) => user,
+            Ok(None) => return Err(AppError::NotFound("User not found".into())),
+            Err(err) => {
+                tracing::error!("Error retrieving user: {err}");
+                return Err(AppError::DatabaseError(err));
+            }
+        };
+
+        if_match.check(user.version)?;
+        Ok(user)
+    }
+
+    /// Records a change to a locked user, bumping its version, and returns the updated user.
+    async fn touch_user(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        id: &str,
+        modified_by: &str,
+    ) -> Result<User, AppError> {
+        match self
+            .repo
+            .touch(tx, id.to_string(), modified_by.to_string())
+            .await
+        {
+            Ok(Some(user)) => Ok(user),
+            Ok(None) => Err(AppError::NotFound("User not found".into())),
+            Err(err) => {
+                tracing::error!("Error updating user: {err}");
+                Err(AppError::DatabaseError(err))
+            }
+        }
+    }
+
+    /// Replaces the profile picture of a locked user within the given transaction.
+    /// Returns the updated user, the new picture and the replaced one.
+    async fn replace_profile_picture_in_tx(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        id: &str,
+        upload_file_dto: &UploadFileDto,
+        if_match: &IfMatch,
+    ) -> Result<(User, UploadedFileDto, Option<UploadedFileDto>), AppError> {
+        self.lock_user(tx, id, if_match).await?;
+
+        let (uploaded, replaced) = self
+            .file_service
+            .replace_profile_picture(tx, upload_file_dto)
+            .await?;
+
+        match self.touch_user(tx, id, &upload_file_dto.modified_by).await {
+            Ok(user) => Ok((user, uploaded, replaced)),
+            Err(err) => {
+                self.file_service.remove_stored_files(&[uploaded]);
+                Err(err)
+            }
+        }
+    }
+
+    /// Soft-deletes the profile picture of a locked user within the given transaction
+    /// and returns the updated user.
+    async fn delete_profile_picture_in_tx(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        id: &str,
+        deleted_by: &str,
+        if_match: &IfMatch,
+    ) -> Result<User, AppError> {
+        self.lock_user(tx, id, if_match).await?;
+
+        self.file_service
+            .delete_profile_picture(tx, id, deleted_by)
+            .await?
+            .ok_or_else(|| AppError::NotFound("Profile picture not found".into()))?;
+
+        self.touch_user(tx, id, deleted_by).await
+    }
+
+    /// Checks that the devices of a deleted user may be handed over to `new_owner`.
+    async fn check_new_device_owner(
+        &self,
+        claims: &Claims,
+        id: &str,
+        new_owner: &str,
+    ) -> Result<(), AppError> {
+        if new_owner == id {
+            return Err(AppError::ValidationError(
+                "Devices cannot be reassigned to the deleted user".into(),
+            ));
+        }
+
+        if !claims.can_access(new_owner) {
+            return Err(AppError::Forbidden);
+        }
+
+        match self
+            .repo
+            .find_by_id(self.pool.clone(), new_owner.to_string())
+            .await
+        {
+            Ok(Some(_)) => Ok(()),
+            Ok(None) => Err(AppError::ValidationError(
+                "New device owner not found".into(),
+            )),
+            Err(err) => {
+                tracing::error!("Error retrieving user: {err}");
+                Err(AppError::DatabaseError(err))
+            }
+        }
+    }
+
+    /// Soft-deletes a user, releases their devices, soft-deletes their uploaded files,
+    /// revokes their sessions and API keys and invalidates their outstanding reset and
+    /// magic-link tokens, all within the given transaction. The password and MFA enrolment
+    /// are kept so that `restore_user` gives the user their account back; deleted users
+    /// cannot sign in with them, and they are removed when the user is purged.
+    async fn delete_user_in_tx(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        id: &str,
+        new_owner: Option<String>,
+        actor_id: &str,
+    ) -> Result<DeleteUserSummaryDto, AppError> {
+        match self
+            .repo
+            .delete(tx, id.to_string(), actor_id.to_string())
+            .await
+        {
+            Ok(true) => {}
+            Ok(false) => return Err(AppError::NotFound("User not found".into())),
+            Err(err) => {
+                tracing::error!("Error deleting user: {err}");
+                return Err(AppError::DatabaseError(err));
+            }
+        }
+
+        let devices = self
+            .device_service
+            .release_user_devices(tx, id, new_owner.as_deref(), actor_id)
+            .await?;
+        let removed_files = self
+            .file_service
+            .delete_user_files(tx, id, actor_id)
+            .await?;
+
+        let sessions_revoked = self.repo.revoke_sessions(tx, id.to_string()).await?;
+        let api_keys_revoked = self.repo.revoke_api_keys(tx, id.to_string()).await?;
+        let login_tokens_expired = self.repo.expire_login_tokens(tx, id.to_string()).await?;
+
+        let (decommissioned_devices, reassigned_devices) = match new_owner {
+            Some(_) => (Vec::new(), devices),
+            None => (devices, Vec::new()),
+        };
+
+        let summary = DeleteUserSummaryDto {
+            user_id: id.to_string(),
+            dry_run: false,
+            decommissioned_devices,
+            reassigned_devices,
+            reassigned_to: new_owner,
+            removed_files: removed_files.into_iter().map(|file| file.id).collect(),
+            sessions_revoked,
+            api_keys_revoked,
+            login_tokens_expired,
+        };
+
+        Ok(summary)
+    }
+
+    /// Purges the given users' devices and files, then the users themselves.
+    /// Returns the number of purged users and the purged files, whose stored files are still there.
+    async fn purge_in_tx(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        deleted_before: DateTime<Utc>,
+    ) -> Result<(u64, Vec<UploadedFileDto>), AppError> {
+        let ids = self.repo.find_purgeable(tx, deleted_before).await?;
+        if ids.is_empty() {
+            return Ok((0, Vec::new()));
+        }
+
+        self.device_service.purge_user_devices(tx, &ids).await?;
+        let files = self.file_service.purge_user_files(tx, &ids).await?;
+
+        let purged = self.repo.purge(tx, ids).await.map_err(|err| {
+            tracing::error!("Error purging users: {err}");
+            AppError::DatabaseError(err)
+        })?;
+
+        Ok((purged, files))
+    }
+}
+
+/// Flattens field validation errors into `field: message` lines.
+fn validation_messages(errors: &ValidationErrors) -> Vec<String> {
+    let mut messages: Vec<String> = errors
+        .field_errors()
+        .into_iter()
+        .flat_map(|(field, errors)| {
+            errors.iter().map(move |error| match &error.message {
+                Some(message) => format!("{field}: {message}"),
+                None => format!("{field}: {}", error.code),
+            })
+        })
+        .collect();
+    messages.sort();
+    messages
+}
+
+/// Maps a unique violation on the users table to a conflict naming the taken field.
+fn user_conflict(err: &sqlx::Error) -> AppError {
+    match unique_violation_constraint(err) {
+        Some("users_email_key") => AppError::Conflict("Email already exists".into()),
+        _ => AppError::Conflict("Username already exists".into()),
+    }
 }
@@ -1,8 +1,9 @@
 use clean_axum_demo::{app::create_router, common};
 use common::{
-    bootstrap::{build_app_state, shutdown_signal},
+    bootstrap::{build_app_state, shutdown_signal, spawn_purge_job},
     config::{setup_database, Config},
 };
+use std::net::SocketAddr;
 use tracing::info;
 
 #[cfg(not(feature = "opentelemetry"))]
@@ -36,6 +37,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     let config = Config::from_env()?;
     let pool = setup_database(&config).await?;
     let state = build_app_state(pool, config.clone());
+    spawn_purge_job(state.clone());
     let app = create_router(state);
 
     let addr = format!("{}:{}", config.service_host, config.service_port);
@@ -44,9 +46,13 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
 
     let listener = tokio::net::TcpListener::bind(&addr).await?;
 
-    axum::serve(listener, app)
-        .with_graceful_shutdown(shutdown_signal())
-        .await?;
+    // Connection info exposes the client IP to the `ClientInfo` extractor.
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .with_graceful_shutdown(shutdown_signal())
+    .await?;
 
     #[cfg(feature = "opentelemetry")]
     shutdown_opentelemetry(opentelemetry_tracer_provider)?;
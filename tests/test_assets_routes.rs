@@ -15,7 +15,10 @@ async fn test_public_assets() {
 
 #[tokio::test]
 async fn test_private_assets_without_auth() {
-    let response = request(Method::GET, "/assets/private/profile_picture/images.jpeg");
+    let response = request(
+        Method::GET,
+        "/assets/private/00000000-0000-0000-0000-000000000001/profile_picture/images.jpeg",
+    );
 
     let (parts, _) = response.await.into_parts();
     // println!("parts.status: {:?}", parts.status);
@@ -24,7 +27,10 @@ async fn test_private_assets_without_auth() {
 
 #[tokio::test]
 async fn test_private_assets_with_auth() {
-    let response = request_with_auth(Method::GET, "/assets/private/profile_picture/images.jpeg");
+    let response = request_with_auth(
+        Method::GET,
+        "/assets/private/00000000-0000-0000-0000-000000000001/profile_picture/images.jpeg",
+    );
 
     let (parts, _) = response.await.into_parts();
     // println!("parts.status: {:?}", parts.status);
@@ -1,10 +1,50 @@
-use axum::http::{Method, StatusCode};
+use axum::{
+    body::Body,
+    http::{
+        header::{
+            ACCESS_CONTROL_ALLOW_CREDENTIALS, ACCESS_CONTROL_ALLOW_HEADERS,
+            ACCESS_CONTROL_ALLOW_METHODS, ACCESS_CONTROL_ALLOW_ORIGIN,
+            ACCESS_CONTROL_EXPOSE_HEADERS, ACCESS_CONTROL_REQUEST_HEADERS,
+            ACCESS_CONTROL_REQUEST_METHOD, AUTHORIZATION, CONTENT_TYPE, COOKIE, ORIGIN, SET_COOKIE,
+        },
+        Method, Request, Response, StatusCode,
+    },
+};
 
-use clean_axum_demo::common::{
-    dto::RestApiResponse,
-    jwt::{AuthBody, AuthPayload},
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use clean_axum_demo::{
+    common::{
+        config::{Config, RegistrationMode, SessionMode},
+        dto::RestApiResponse,
+        jwt::{make_jwt_token, AuthBody, AuthPayload, Claims},
+        notifier::Notification,
+        session_cookie::CookieSessionBody,
+        totp,
+    },
+    domains::{
+        auth::dto::auth_dto::{
+            ApiKeyDto, AuthEventDto, AuthUserDto, ChangePasswordDto, CreateApiKeyDto,
+            CreateInviteDto, CreatedApiKeyDto, ForgotPasswordDto, ImpersonationTokenDto, InviteDto,
+            MagicLinkRequestDto, MagicLinkVerifyDto, MfaChallengeDto, MfaCodeDto, MfaEnrollmentDto,
+            MfaRecoveryCodesDto, MfaVerifyDto, RefreshTokenDto, RegisterUserDto, ResetPasswordDto,
+            UnlockAccountDto,
+        },
+        device::{
+            dto::device_dto::{CreateDeviceDto, DeviceDto},
+            DeviceOS, DeviceStatus,
+        },
+        user::dto::user_dto::UserDto,
+    },
+};
+use jsonwebtoken::{decode, decode_header, jwk::JwkSet, DecodingKey, Validation};
+use test_helpers::{
+    create_test_router, create_test_router_with_config, deserialize_json_body, get_bearer_token,
+    login, request, request_with_body, request_with_body_and_config, request_with_body_and_headers,
+    request_with_token, request_with_token_and_body, setup_test_db, TEST_CLIENT_ID,
+    TEST_CLIENT_SECRET, TEST_NON_ADMIN_CLIENT_ID, TEST_USER_ID,
 };
-use test_helpers::{deserialize_json_body, request_with_body, TEST_CLIENT_ID, TEST_CLIENT_SECRET};
+
+use tower::ServiceExt;
 
 mod test_helpers;
 
@@ -29,12 +69,16 @@ async fn test_login_user() {
 
     assert_eq!(auth_body.token_type, "Bearer");
     assert!(!auth_body.access_token.is_empty());
+    assert!(!auth_body.refresh_token.is_empty());
+    assert!(auth_body.expires_in > 0);
 }
 
 #[tokio::test]
 async fn test_login_user_fail() {
+    // A fresh user, since failed logins delay further logins of the account.
+    let user = register_test_user().await;
     let payload = AuthPayload {
-        client_id: TEST_CLIENT_ID.to_string(),
+        client_id: user.username,
         client_secret: uuid::Uuid::new_v4().to_string(),
     };
 
@@ -47,6 +91,7 @@ async fn test_login_user_fail() {
     let response_body: RestApiResponse<()> = deserialize_json_body(body).await.unwrap();
 
     assert_eq!(response_body.0.status, StatusCode::UNAUTHORIZED);
+    assert_eq!(response_body.0.message, "Wrong credentials");
     // println!("response_body.0.status: {:?}", response_body.0.status);
     // println!("response_body.0.message: {:?}", response_body.0.message);
 }
@@ -64,11 +109,1497 @@ async fn test_login_user_not_found() {
 
     let (parts, body) = response.await.into_parts();
 
-    assert_eq!(parts.status, StatusCode::NOT_FOUND);
+    // Unknown users are indistinguishable from wrong passwords.
+    assert_eq!(parts.status, StatusCode::UNAUTHORIZED);
 
     let response_body: RestApiResponse<()> = deserialize_json_body(body).await.unwrap();
 
-    assert_eq!(response_body.0.status, StatusCode::NOT_FOUND);
+    assert_eq!(response_body.0.status, StatusCode::UNAUTHORIZED);
+    assert_eq!(response_body.0.message, "Wrong credentials");
     println!("response_body.0.status: {:?}", response_body.0.status);
     println!("response_body.0.message: {:?}", response_body.0.message);
 }
+
+#[tokio::test]
+async fn test_refresh_token_rotation_and_reuse() {
+    let auth_body = login(TEST_CLIENT_ID, TEST_CLIENT_SECRET).await;
+
+    let payload = RefreshTokenDto {
+        refresh_token: auth_body.refresh_token.clone(),
+    };
+
+    let response = request_with_body(Method::POST, "/auth/refresh", &payload);
+    let (parts, body) = response.await.into_parts();
+    assert_eq!(parts.status, StatusCode::OK);
+
+    let response_body: RestApiResponse<AuthBody> = deserialize_json_body(body).await.unwrap();
+    let rotated = response_body.0.data.unwrap();
+    assert_ne!(rotated.refresh_token, auth_body.refresh_token);
+
+    // Presenting the rotated-out token again is treated as reuse.
+    let response = request_with_body(Method::POST, "/auth/refresh", &payload);
+    let (parts, _) = response.await.into_parts();
+    assert_eq!(parts.status, StatusCode::UNAUTHORIZED);
+
+    // Reuse revokes the whole family, including the newest refresh token ...
+    let payload = RefreshTokenDto {
+        refresh_token: rotated.refresh_token.clone(),
+    };
+    let response = request_with_body(Method::POST, "/auth/refresh", &payload);
+    let (parts, _) = response.await.into_parts();
+    assert_eq!(parts.status, StatusCode::UNAUTHORIZED);
+
+    // ... and the access tokens issued for the session.
+    let token = format!("{} {}", rotated.token_type, rotated.access_token);
+    let response = request_with_token(Method::GET, "/user", &token);
+    let (parts, _) = response.await.into_parts();
+    assert_eq!(parts.status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn test_logout_revokes_session() {
+    let auth_body = login(TEST_CLIENT_ID, TEST_CLIENT_SECRET).await;
+    let token = format!("{} {}", auth_body.token_type, auth_body.access_token);
+
+    let response = request_with_token(Method::GET, "/user", &token);
+    let (parts, _) = response.await.into_parts();
+    assert_eq!(parts.status, StatusCode::OK);
+
+    let payload = RefreshTokenDto {
+        refresh_token: auth_body.refresh_token.clone(),
+    };
+    let response = request_with_body(Method::POST, "/auth/logout", &payload);
+    let (parts, _) = response.await.into_parts();
+    assert_eq!(parts.status, StatusCode::OK);
+
+    let response = request_with_token(Method::GET, "/user", &token);
+    let (parts, _) = response.await.into_parts();
+    assert_eq!(parts.status, StatusCode::UNAUTHORIZED);
+
+    let response = request_with_body(Method::POST, "/auth/refresh", &payload);
+    let (parts, _) = response.await.into_parts();
+    assert_eq!(parts.status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn test_deleted_user_cannot_sign_in() {
+    let user = register_test_user().await;
+    let auth_body = login(&user.username, &user.password).await;
+    let token = format!("Bearer {}", auth_body.access_token);
+    let user_id = token_claims(&auth_body.access_token).sub;
+
+    let admin_token = get_bearer_token(TEST_CLIENT_ID, TEST_CLIENT_SECRET).await;
+    let uri = format!("/user/{user_id}");
+    let response = request_with_token(Method::DELETE, &uri, &admin_token);
+    assert_eq!(response.await.status(), StatusCode::OK);
+
+    // The sessions of a deleted user stop working.
+    let response = request_with_token(Method::GET, "/device", &token);
+    assert_eq!(response.await.status(), StatusCode::UNAUTHORIZED);
+
+    let payload = RefreshTokenDto {
+        refresh_token: auth_body.refresh_token.clone(),
+    };
+    let response = request_with_body(Method::POST, "/auth/refresh", &payload);
+    assert_eq!(response.await.status(), StatusCode::UNAUTHORIZED);
+
+    // Signing in with the email leaves the backoff of the username untouched.
+    assert_eq!(
+        login_status(&user.email, &user.password).await,
+        StatusCode::UNAUTHORIZED
+    );
+
+    // Restoring brings the password back, but not the revoked sessions.
+    let uri = format!("/user/{user_id}/restore");
+    let response = request_with_token(Method::POST, &uri, &admin_token);
+    assert_eq!(response.await.status(), StatusCode::OK);
+
+    let response = request_with_token(Method::GET, "/device", &token);
+    assert_eq!(response.await.status(), StatusCode::UNAUTHORIZED);
+
+    login(&user.username, &user.password).await;
+}
+
+#[tokio::test]
+async fn test_jwks_verifies_issued_token() {
+    let response = request(Method::GET, "/.well-known/jwks.json");
+
+    let (parts, body) = response.await.into_parts();
+
+    assert_eq!(parts.status, StatusCode::OK);
+
+    let jwks: JwkSet = deserialize_json_body(body).await.unwrap();
+
+    assert!(!jwks.keys.is_empty());
+
+    // The issued access token can be verified with the published key named by its kid.
+    let auth_body = login(TEST_CLIENT_ID, TEST_CLIENT_SECRET).await;
+    let header = decode_header(&auth_body.access_token).unwrap();
+    let kid = header.kid.expect("access token must carry a kid");
+    let jwk = jwks.find(&kid).expect("signing key must be published");
+
+    let decoding = DecodingKey::from_jwk(jwk).unwrap();
+    let mut validation = Validation::new(header.alg);
+    validation.set_audience(&["clean_axum_demo"]);
+    let token_data = decode::<serde_json::Value>(&auth_body.access_token, &decoding, &validation);
+
+    assert!(token_data.is_ok());
+}
+
+/// Signs claims for `TEST_USER_ID` after applying `modify` and calls a protected route with them.
+/// Returns the status code and the error message of the response.
+async fn request_with_claims(modify: impl FnOnce(&mut Claims)) -> (StatusCode, String) {
+    setup_test_db().await.unwrap();
+    let config = Config::from_env().unwrap();
+
+    let mut claims = Claims::new(&config, TEST_USER_ID, 60);
+    modify(&mut claims);
+    let token = format!("Bearer {}", make_jwt_token(&claims).unwrap());
+
+    let response = request_with_token(Method::GET, "/user", &token);
+
+    let (parts, body) = response.await.into_parts();
+    let response_body: RestApiResponse<()> = deserialize_json_body(body).await.unwrap();
+
+    (parts.status, response_body.0.message)
+}
+
+#[tokio::test]
+async fn test_token_claims_validation() {
+    let now = chrono::Utc::now().timestamp() as usize;
+
+    let (status, message) = request_with_claims(|claims| {
+        claims.iat = now - 3600;
+        claims.nbf = now - 3600;
+        claims.exp = now - 600;
+    })
+    .await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert_eq!(message, "Token expired");
+
+    let (status, message) = request_with_claims(|claims| claims.nbf = now + 600).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert_eq!(message, "Token not yet valid");
+
+    let (status, message) =
+        request_with_claims(|claims| claims.aud = vec!["billing".to_string()]).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert_eq!(message, "Invalid token audience");
+
+    let (status, message) =
+        request_with_claims(|claims| claims.iss = "someone-else".to_string()).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert_eq!(message, "Invalid token issuer");
+
+    // Any of the accepted audiences is enough; this one has no permissions, so the route forbids it.
+    let (status, _) = request_with_claims(|claims| {
+        claims.aud = vec!["billing".to_string(), "reporting".to_string()]
+    })
+    .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let response = request_with_token(Method::GET, "/user", "Bearer not-a-jwt");
+    let (parts, body) = response.await.into_parts();
+    let response_body: RestApiResponse<()> = deserialize_json_body(body).await.unwrap();
+    assert_eq!(parts.status, StatusCode::UNAUTHORIZED);
+    assert_eq!(response_body.0.message, "Malformed token");
+}
+
+fn register_payload(invite_token: Option<String>, email: Option<&str>) -> RegisterUserDto {
+    let username = format!("signup-{}", uuid::Uuid::new_v4());
+    RegisterUserDto {
+        email: email
+            .map(str::to_string)
+            .unwrap_or_else(|| format!("{}@test.com", username)),
+        username,
+        password: "signup_password".to_string(),
+        invite_token,
+    }
+}
+
+async fn create_invite(email: Option<&str>) -> InviteDto {
+    let token = get_bearer_token(TEST_CLIENT_ID, TEST_CLIENT_SECRET).await;
+    let payload = CreateInviteDto {
+        email: email.map(str::to_string),
+    };
+
+    let response = request_with_token_and_body(Method::POST, "/auth/invites", &token, &payload);
+    let (parts, body) = response.await.into_parts();
+
+    assert_eq!(parts.status, StatusCode::OK);
+
+    let response_body: RestApiResponse<InviteDto> = deserialize_json_body(body).await.unwrap();
+    response_body.0.data.unwrap()
+}
+
+#[tokio::test]
+async fn test_register_requires_invite() {
+    let payload = register_payload(None, None);
+
+    let response = request_with_body(Method::POST, "/auth/register", &payload);
+    let (parts, _) = response.await.into_parts();
+
+    assert_eq!(parts.status, StatusCode::FORBIDDEN);
+
+    let payload = register_payload(Some("unknown-invite".to_string()), None);
+
+    let response = request_with_body(Method::POST, "/auth/register", &payload);
+    let (parts, _) = response.await.into_parts();
+
+    assert_eq!(parts.status, StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn test_register_with_invite() {
+    let email = format!("invited-{}@test.com", uuid::Uuid::new_v4());
+    let invite = create_invite(Some(&email)).await;
+
+    // The invite is bound to its email address.
+    let payload = register_payload(Some(invite.invite_token.clone()), None);
+    let response = request_with_body(Method::POST, "/auth/register", &payload);
+    let (parts, _) = response.await.into_parts();
+    assert_eq!(parts.status, StatusCode::FORBIDDEN);
+
+    let payload = register_payload(Some(invite.invite_token.clone()), Some(&email));
+    let response = request_with_body(Method::POST, "/auth/register", &payload);
+    let (parts, body) = response.await.into_parts();
+    assert_eq!(parts.status, StatusCode::OK);
+
+    let response_body: RestApiResponse<UserDto> = deserialize_json_body(body).await.unwrap();
+    let user = response_body.0.data.unwrap();
+    assert_eq!(user.username, payload.username);
+    assert_eq!(user.created_by.as_deref(), Some(user.id.as_str()));
+
+    // The new user can log in and holds the regular user role.
+    let auth_body = login(&payload.username, &payload.password).await;
+    let token = format!("Bearer {}", auth_body.access_token);
+    let response = request_with_token(Method::GET, "/device", &token);
+    assert_eq!(response.await.status(), StatusCode::OK);
+    let url = format!("/user/{}", TEST_USER_ID);
+    let response = request_with_token(Method::DELETE, &url, &token);
+    assert_eq!(response.await.status(), StatusCode::FORBIDDEN);
+
+    // Invites are single-use.
+    let payload = register_payload(Some(invite.invite_token), Some(&email));
+    let response = request_with_body(Method::POST, "/auth/register", &payload);
+    assert_eq!(response.await.status(), StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn test_login_with_email() {
+    let user = register_test_user().await;
+    let by_username = login(&user.username, &user.password).await;
+
+    // Emails are matched case-insensitively.
+    let by_email = login(&user.email.to_uppercase(), &user.password).await;
+    assert_eq!(
+        token_claims(&by_email.access_token).sub,
+        token_claims(&by_username.access_token).sub
+    );
+
+    // An email belongs to a single account.
+    let invite = create_invite(None).await;
+    let duplicate = register_payload(Some(invite.invite_token), Some(&user.email.to_uppercase()));
+    let response = request_with_body(Method::POST, "/auth/register", &duplicate);
+    assert_eq!(response.await.status(), StatusCode::CONFLICT);
+}
+
+#[tokio::test]
+async fn test_login_with_username_in_other_case() {
+    let user = register_test_user().await;
+    let by_username = login(&user.username, &user.password).await;
+
+    // Usernames are matched case-insensitively as well.
+    let by_upper = login(&user.username.to_uppercase(), &user.password).await;
+    assert_eq!(
+        token_claims(&by_upper.access_token).sub,
+        token_claims(&by_username.access_token).sub
+    );
+
+    // So they may not differ from another account only in case.
+    let invite = create_invite(None).await;
+    let mut duplicate = register_payload(Some(invite.invite_token), None);
+    duplicate.username = user.username.to_uppercase();
+    let response = request_with_body(Method::POST, "/auth/register", &duplicate);
+    assert_eq!(response.await.status(), StatusCode::CONFLICT);
+}
+
+#[tokio::test]
+async fn test_register_open_mode_duplicate_username() {
+    let open = |config: &mut Config| config.registration_mode = RegistrationMode::Open;
+    let payload = register_payload(None, None);
+
+    let response = request_with_body_and_config(open, Method::POST, "/auth/register", &payload);
+    assert_eq!(response.await.status(), StatusCode::OK);
+
+    let response = request_with_body_and_config(open, Method::POST, "/auth/register", &payload);
+    let (parts, body) = response.await.into_parts();
+    assert_eq!(parts.status, StatusCode::CONFLICT);
+
+    let response_body: RestApiResponse<()> = deserialize_json_body(body).await.unwrap();
+    assert_eq!(response_body.0.status, StatusCode::CONFLICT);
+}
+
+#[tokio::test]
+async fn test_register_disabled() {
+    for mode in [RegistrationMode::Disabled, RegistrationMode::AdminOnly] {
+        let payload = register_payload(None, None);
+        let response = request_with_body_and_config(
+            |config| config.registration_mode = mode,
+            Method::POST,
+            "/auth/register",
+            &payload,
+        );
+        assert_eq!(response.await.status(), StatusCode::FORBIDDEN);
+    }
+}
+
+#[tokio::test]
+async fn test_create_credentials() {
+    // apitest01 already has credentials.
+    let payload = AuthUserDto {
+        user_id: "00000000-0000-0000-0000-000000000021".to_string(),
+        password: "another_password".to_string(),
+    };
+
+    let token = get_bearer_token(TEST_CLIENT_ID, TEST_CLIENT_SECRET).await;
+    let response = request_with_token_and_body(Method::POST, "/auth/credentials", &token, &payload);
+    assert_eq!(response.await.status(), StatusCode::CONFLICT);
+
+    let token = get_bearer_token(TEST_NON_ADMIN_CLIENT_ID, TEST_CLIENT_SECRET).await;
+    let response = request_with_token_and_body(Method::POST, "/auth/credentials", &token, &payload);
+    assert_eq!(response.await.status(), StatusCode::FORBIDDEN);
+
+    let response = request_with_body(Method::POST, "/auth/credentials", &payload);
+    assert_eq!(response.await.status(), StatusCode::UNAUTHORIZED);
+}
+
+/// Registers a fresh user through an invite, so that tests changing
+/// passwords or revoking sessions do not affect the shared test users.
+async fn register_test_user() -> RegisterUserDto {
+    let invite = create_invite(None).await;
+    let payload = register_payload(Some(invite.invite_token), None);
+
+    let response = request_with_body(Method::POST, "/auth/register", &payload);
+    assert_eq!(response.await.status(), StatusCode::OK);
+
+    payload
+}
+
+/// Reads the latest reset token sent to `email` from the notifier file.
+fn read_reset_token(email: &str) -> Option<String> {
+    let config = Config::from_env().unwrap();
+    let outbox = std::fs::read_to_string(config.notifier_file.unwrap()).ok()?;
+
+    outbox
+        .lines()
+        .rev()
+        .filter_map(|line| serde_json::from_str::<Notification>(line).ok())
+        .find(|notification| notification.recipient == email)
+        .and_then(|notification| notification.body.lines().last().map(str::to_string))
+}
+
+async fn login_status(username: &str, password: &str) -> StatusCode {
+    let payload = AuthPayload {
+        client_id: username.to_string(),
+        client_secret: password.to_string(),
+    };
+
+    request_with_body(Method::POST, "/auth/login", &payload)
+        .await
+        .status()
+}
+
+#[tokio::test]
+async fn test_change_password() {
+    let user = register_test_user().await;
+    let auth_body = login(&user.username, &user.password).await;
+    let token = format!("Bearer {}", auth_body.access_token);
+
+    let payload = ChangePasswordDto {
+        current_password: "wrong_password".to_string(),
+        new_password: "changed_password".to_string(),
+    };
+    let response =
+        request_with_token_and_body(Method::POST, "/auth/password/change", &token, &payload);
+    assert_eq!(response.await.status(), StatusCode::UNAUTHORIZED);
+
+    let payload = ChangePasswordDto {
+        current_password: user.password.clone(),
+        new_password: "short".to_string(),
+    };
+    let response =
+        request_with_token_and_body(Method::POST, "/auth/password/change", &token, &payload);
+    assert_eq!(response.await.status(), StatusCode::BAD_REQUEST);
+
+    let payload = ChangePasswordDto {
+        current_password: user.password.clone(),
+        new_password: "changed_password".to_string(),
+    };
+    let response =
+        request_with_token_and_body(Method::POST, "/auth/password/change", &token, &payload);
+    let (parts, body) = response.await.into_parts();
+    assert_eq!(parts.status, StatusCode::OK);
+
+    let response_body: RestApiResponse<AuthBody> = deserialize_json_body(body).await.unwrap();
+    let new_auth_body = response_body.0.data.unwrap();
+
+    // The previous session is revoked ...
+    let response = request_with_token(Method::GET, "/device", &token);
+    assert_eq!(response.await.status(), StatusCode::UNAUTHORIZED);
+    let payload = RefreshTokenDto {
+        refresh_token: auth_body.refresh_token,
+    };
+    let response = request_with_body(Method::POST, "/auth/refresh", &payload);
+    assert_eq!(response.await.status(), StatusCode::UNAUTHORIZED);
+
+    // ... while the session returned by the change is active.
+    let token = format!("Bearer {}", new_auth_body.access_token);
+    let response = request_with_token(Method::GET, "/device", &token);
+    assert_eq!(response.await.status(), StatusCode::OK);
+
+    assert_eq!(
+        login_status(&user.username, "changed_password").await,
+        StatusCode::OK
+    );
+    assert_eq!(
+        login_status(&user.username, &user.password).await,
+        StatusCode::UNAUTHORIZED
+    );
+}
+
+#[tokio::test]
+async fn test_change_password_requires_token() {
+    let payload = ChangePasswordDto {
+        current_password: TEST_CLIENT_SECRET.to_string(),
+        new_password: "changed_password".to_string(),
+    };
+    let response = request_with_body(Method::POST, "/auth/password/change", &payload);
+    assert_eq!(response.await.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn test_password_reset() {
+    let user = register_test_user().await;
+    let auth_body = login(&user.username, &user.password).await;
+
+    // Unknown users get the same response.
+    let payload = ForgotPasswordDto {
+        username: format!("unknown-{}", uuid::Uuid::new_v4()),
+    };
+    let response = request_with_body(Method::POST, "/auth/password/forgot", &payload);
+    assert_eq!(response.await.status(), StatusCode::OK);
+
+    let payload = ForgotPasswordDto {
+        username: user.username.clone(),
+    };
+    let response = request_with_body(Method::POST, "/auth/password/forgot", &payload);
+    assert_eq!(response.await.status(), StatusCode::OK);
+    let first_token = read_reset_token(&user.email).unwrap();
+
+    // Requesting another reset supersedes the previous token.
+    let response = request_with_body(Method::POST, "/auth/password/forgot", &payload);
+    assert_eq!(response.await.status(), StatusCode::OK);
+    let reset_token = read_reset_token(&user.email).unwrap();
+    assert_ne!(first_token, reset_token);
+
+    let payload = ResetPasswordDto {
+        reset_token: first_token,
+        new_password: "reset_password".to_string(),
+    };
+    let response = request_with_body(Method::POST, "/auth/password/reset", &payload);
+    assert_eq!(response.await.status(), StatusCode::UNAUTHORIZED);
+
+    let payload = ResetPasswordDto {
+        reset_token: reset_token.clone(),
+        new_password: "reset_password".to_string(),
+    };
+    let response = request_with_body(Method::POST, "/auth/password/reset", &payload);
+    assert_eq!(response.await.status(), StatusCode::OK);
+
+    // Reset tokens are single-use.
+    let response = request_with_body(Method::POST, "/auth/password/reset", &payload);
+    assert_eq!(response.await.status(), StatusCode::UNAUTHORIZED);
+
+    // Existing sessions are revoked.
+    let token = format!("Bearer {}", auth_body.access_token);
+    let response = request_with_token(Method::GET, "/device", &token);
+    assert_eq!(response.await.status(), StatusCode::UNAUTHORIZED);
+
+    assert_eq!(
+        login_status(&user.username, "reset_password").await,
+        StatusCode::OK
+    );
+    assert_eq!(
+        login_status(&user.username, &user.password).await,
+        StatusCode::UNAUTHORIZED
+    );
+}
+
+fn retry_after(response: &axum::response::Response) -> u64 {
+    response.headers()["retry-after"]
+        .to_str()
+        .unwrap()
+        .parse()
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_login_backoff() {
+    let user = register_test_user().await;
+
+    assert_eq!(
+        login_status(&user.username, "wrong_password").await,
+        StatusCode::UNAUTHORIZED
+    );
+
+    // The next attempt is delayed, even with the right password ...
+    let payload = AuthPayload {
+        client_id: user.username.clone(),
+        client_secret: user.password.clone(),
+    };
+    let response = request_with_body(Method::POST, "/auth/login", &payload).await;
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert!(retry_after(&response) <= 1);
+
+    // ... until the backoff has passed.
+    tokio::time::sleep(std::time::Duration::from_millis(1100)).await;
+    assert_eq!(
+        login_status(&user.username, &user.password).await,
+        StatusCode::OK
+    );
+}
+
+#[tokio::test]
+async fn test_login_lockout_and_unlock() {
+    let user = register_test_user().await;
+    let no_backoff = |config: &mut Config| config.login_backoff_base_secs = 0;
+    let wrong = AuthPayload {
+        client_id: user.username.clone(),
+        client_secret: "wrong_password".to_string(),
+    };
+
+    // LOGIN_MAX_ATTEMPTS is 3 in the test environment.
+    for _ in 0..3 {
+        let response =
+            request_with_body_and_config(no_backoff, Method::POST, "/auth/login", &wrong);
+        assert_eq!(response.await.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    let right = AuthPayload {
+        client_id: user.username.clone(),
+        client_secret: user.password.clone(),
+    };
+    let response =
+        request_with_body_and_config(no_backoff, Method::POST, "/auth/login", &right).await;
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert!(retry_after(&response) > 60);
+
+    let payload = UnlockAccountDto {
+        username: Some(user.username.clone()),
+        ip: None,
+    };
+    let token = get_bearer_token(TEST_NON_ADMIN_CLIENT_ID, TEST_CLIENT_SECRET).await;
+    let response = request_with_token_and_body(Method::POST, "/auth/unlock", &token, &payload);
+    assert_eq!(response.await.status(), StatusCode::FORBIDDEN);
+
+    let token = get_bearer_token(TEST_CLIENT_ID, TEST_CLIENT_SECRET).await;
+    let response = request_with_token_and_body(Method::POST, "/auth/unlock", &token, &payload);
+    assert_eq!(response.await.status(), StatusCode::OK);
+
+    assert_eq!(
+        login_status(&user.username, &user.password).await,
+        StatusCode::OK
+    );
+}
+
+#[tokio::test]
+async fn test_login_ip_lockout() {
+    let ip = format!("10.{}.{}.{}", rand_octet(), rand_octet(), rand_octet());
+    let headers = [("x-forwarded-for", ip.as_str())];
+
+    // LOGIN_MAX_ATTEMPTS_PER_IP is 5 in the test environment.
+    for _ in 0..5 {
+        let payload = AuthPayload {
+            client_id: format!("unknown-{}", uuid::Uuid::new_v4()),
+            client_secret: "wrong_password".to_string(),
+        };
+        let response =
+            request_with_body_and_headers(Method::POST, "/auth/login", &headers, &payload);
+        assert_eq!(response.await.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    // The IP is locked for every account, while other clients are unaffected.
+    let payload = AuthPayload {
+        client_id: TEST_CLIENT_ID.to_string(),
+        client_secret: TEST_CLIENT_SECRET.to_string(),
+    };
+    let response = request_with_body_and_headers(Method::POST, "/auth/login", &headers, &payload);
+    assert_eq!(response.await.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(
+        login_status(TEST_CLIENT_ID, TEST_CLIENT_SECRET).await,
+        StatusCode::OK
+    );
+
+    let unlock = UnlockAccountDto {
+        username: None,
+        ip: Some(ip.clone()),
+    };
+    let token = get_bearer_token(TEST_CLIENT_ID, TEST_CLIENT_SECRET).await;
+    let response = request_with_token_and_body(Method::POST, "/auth/unlock", &token, &unlock);
+    assert_eq!(response.await.status(), StatusCode::OK);
+
+    let response = request_with_body_and_headers(Method::POST, "/auth/login", &headers, &payload);
+    assert_eq!(response.await.status(), StatusCode::OK);
+}
+
+fn rand_octet() -> u8 {
+    uuid::Uuid::new_v4().as_bytes()[0]
+}
+
+/// Logs in a user with MFA enabled and returns the challenge token.
+async fn login_mfa_challenge(username: &str, password: &str) -> String {
+    let payload = AuthPayload {
+        client_id: username.to_string(),
+        client_secret: password.to_string(),
+    };
+
+    let response = request_with_body(Method::POST, "/auth/login", &payload);
+    let (parts, body) = response.await.into_parts();
+    assert_eq!(parts.status, StatusCode::OK);
+
+    let response_body: RestApiResponse<MfaChallengeDto> =
+        deserialize_json_body(body).await.unwrap();
+    assert_eq!(response_body.0.message, "MFA required");
+    response_body.0.data.unwrap().mfa_token
+}
+
+async fn verify_mfa_status(mfa_token: &str, code: &str) -> StatusCode {
+    let payload = MfaVerifyDto {
+        mfa_token: mfa_token.to_string(),
+        code: code.to_string(),
+    };
+
+    request_with_body(Method::POST, "/auth/mfa/verify", &payload)
+        .await
+        .status()
+}
+
+#[tokio::test]
+async fn test_mfa_enrollment_and_login() {
+    let user = register_test_user().await;
+    let auth_body = login(&user.username, &user.password).await;
+    let token = format!("Bearer {}", auth_body.access_token);
+
+    let payload = MfaCodeDto {
+        code: "123456".to_string(),
+    };
+    let response = request_with_token_and_body(Method::POST, "/auth/mfa/confirm", &token, &payload);
+    assert_eq!(response.await.status(), StatusCode::NOT_FOUND);
+
+    let response = request_with_token(Method::POST, "/auth/mfa/enroll", &token);
+    let (parts, body) = response.await.into_parts();
+    assert_eq!(parts.status, StatusCode::OK);
+
+    let response_body: RestApiResponse<MfaEnrollmentDto> =
+        deserialize_json_body(body).await.unwrap();
+    let enrollment = response_body.0.data.unwrap();
+    assert!(enrollment.otpauth_uri.starts_with("otpauth://totp/"));
+    assert!(enrollment.otpauth_uri.contains(&enrollment.secret));
+
+    let now = chrono::Utc::now().timestamp() as u64;
+    let payload = MfaCodeDto {
+        code: totp::generate_code(&enrollment.secret, now).unwrap(),
+    };
+    let response = request_with_token_and_body(Method::POST, "/auth/mfa/confirm", &token, &payload);
+    let (parts, body) = response.await.into_parts();
+    assert_eq!(parts.status, StatusCode::OK);
+
+    let response_body: RestApiResponse<MfaRecoveryCodesDto> =
+        deserialize_json_body(body).await.unwrap();
+    let recovery_codes = response_body.0.data.unwrap().recovery_codes;
+    assert_eq!(recovery_codes.len(), 10);
+
+    let response = request_with_token(Method::POST, "/auth/mfa/enroll", &token);
+    assert_eq!(response.await.status(), StatusCode::CONFLICT);
+
+    // The password alone only yields a challenge.
+    let mfa_token = login_mfa_challenge(&user.username, &user.password).await;
+
+    // The code used for confirmation cannot be replayed; the next one is accepted.
+    let used_code = totp::generate_code(&enrollment.secret, now).unwrap();
+    assert_eq!(
+        verify_mfa_status(&mfa_token, &used_code).await,
+        StatusCode::UNAUTHORIZED
+    );
+    let next_code = totp::generate_code(&enrollment.secret, now + 30).unwrap();
+    let payload = MfaVerifyDto {
+        mfa_token: mfa_token.clone(),
+        code: next_code.clone(),
+    };
+    let response = request_with_body(Method::POST, "/auth/mfa/verify", &payload);
+    let (parts, body) = response.await.into_parts();
+    assert_eq!(parts.status, StatusCode::OK);
+
+    let response_body: RestApiResponse<AuthBody> = deserialize_json_body(body).await.unwrap();
+    let token = format!("Bearer {}", response_body.0.data.unwrap().access_token);
+    let response = request_with_token(Method::GET, "/device", &token);
+    assert_eq!(response.await.status(), StatusCode::OK);
+
+    // Challenges are single-use.
+    assert_eq!(
+        verify_mfa_status(&mfa_token, &next_code).await,
+        StatusCode::UNAUTHORIZED
+    );
+
+    // Recovery codes work once, regardless of case.
+    let mfa_token = login_mfa_challenge(&user.username, &user.password).await;
+    assert_eq!(
+        verify_mfa_status(&mfa_token, &recovery_codes[0].to_uppercase()).await,
+        StatusCode::OK
+    );
+    let mfa_token = login_mfa_challenge(&user.username, &user.password).await;
+    assert_eq!(
+        verify_mfa_status(&mfa_token, &recovery_codes[0]).await,
+        StatusCode::UNAUTHORIZED
+    );
+
+    let payload = MfaCodeDto {
+        code: recovery_codes[1].clone(),
+    };
+    let response = request_with_token_and_body(Method::POST, "/auth/mfa/disable", &token, &payload);
+    assert_eq!(response.await.status(), StatusCode::OK);
+
+    login(&user.username, &user.password).await;
+}
+
+#[tokio::test]
+async fn test_mfa_challenge_attempt_limit() {
+    let user = register_test_user().await;
+    let auth_body = login(&user.username, &user.password).await;
+    let token = format!("Bearer {}", auth_body.access_token);
+
+    let response = request_with_token(Method::POST, "/auth/mfa/enroll", &token);
+    let (_, body) = response.await.into_parts();
+    let response_body: RestApiResponse<MfaEnrollmentDto> =
+        deserialize_json_body(body).await.unwrap();
+    let secret = response_body.0.data.unwrap().secret;
+
+    let now = chrono::Utc::now().timestamp() as u64;
+    let payload = MfaCodeDto {
+        code: totp::generate_code(&secret, now).unwrap(),
+    };
+    let response = request_with_token_and_body(Method::POST, "/auth/mfa/confirm", &token, &payload);
+    assert_eq!(response.await.status(), StatusCode::OK);
+
+    let mfa_token = login_mfa_challenge(&user.username, &user.password).await;
+    for _ in 0..5 {
+        assert_eq!(
+            verify_mfa_status(&mfa_token, "not-a-recovery-code").await,
+            StatusCode::UNAUTHORIZED
+        );
+    }
+
+    // The challenge is invalidated, even for a valid code.
+    let code = totp::generate_code(&secret, now + 30).unwrap();
+    assert_eq!(
+        verify_mfa_status(&mfa_token, &code).await,
+        StatusCode::UNAUTHORIZED
+    );
+}
+
+/// Reads the stored password hash of `username` straight from the database.
+async fn stored_password_hash(username: &str) -> String {
+    let pool = setup_test_db().await.unwrap();
+    sqlx::query_scalar(
+        "SELECT ua.password_hash FROM user_auth ua JOIN users u ON u.id = ua.user_id WHERE u.username = $1",
+    )
+    .bind(username)
+    .fetch_one(&pool)
+    .await
+    .unwrap()
+}
+
+#[tokio::test]
+async fn test_register_password_policy() {
+    let policy = |config: &mut Config| {
+        config.registration_mode = RegistrationMode::Open;
+        config.password_policy.require_uppercase = true;
+        config.password_policy.require_digit = true;
+    };
+
+    let mut payload = register_payload(None, None);
+    payload.password = "password".to_string();
+
+    let response = request_with_body_and_config(policy, Method::POST, "/auth/register", &payload);
+    let (parts, body) = response.await.into_parts();
+    assert_eq!(parts.status, StatusCode::BAD_REQUEST);
+
+    let response_body: RestApiResponse<serde_json::Value> =
+        deserialize_json_body(body).await.unwrap();
+    let codes: Vec<&str> = response_body.0.data.as_ref().unwrap()["password"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|error| error["code"].as_str().unwrap())
+        .collect();
+    assert_eq!(codes, ["uppercase", "digit", "common_password"]);
+
+    // Field errors of the payload are reported together with the policy errors.
+    payload.password = "short".to_string();
+    payload.email = "not-an-email".to_string();
+    let response = request_with_body_and_config(policy, Method::POST, "/auth/register", &payload);
+    let (parts, body) = response.await.into_parts();
+    assert_eq!(parts.status, StatusCode::BAD_REQUEST);
+
+    let response_body: RestApiResponse<serde_json::Value> =
+        deserialize_json_body(body).await.unwrap();
+    let data = response_body.0.data.unwrap();
+    assert_eq!(data["email"][0]["code"], "email");
+    assert_eq!(data["password"][0]["code"], "length");
+
+    payload.password = "Str0ng-enough".to_string();
+    payload.email = format!("{}@test.com", payload.username);
+    let response = request_with_body_and_config(policy, Method::POST, "/auth/register", &payload);
+    assert_eq!(response.await.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_login_upgrades_password_hash() {
+    let user = register_test_user().await;
+    let hash = stored_password_hash(&user.username).await;
+    assert!(hash.starts_with("$argon2id$v=19$m=19456,t=2,p=1$"));
+
+    let payload = AuthPayload {
+        client_id: user.username.clone(),
+        client_secret: user.password.clone(),
+    };
+    let response = request_with_body_and_config(
+        |config| {
+            config.password_hash_params = argon2::Params::new(19456, 3, 1, None).unwrap();
+        },
+        Method::POST,
+        "/auth/login",
+        &payload,
+    );
+    assert_eq!(response.await.status(), StatusCode::OK);
+
+    let upgraded = stored_password_hash(&user.username).await;
+    assert!(upgraded.starts_with("$argon2id$v=19$m=19456,t=3,p=1$"));
+
+    // Logins with the default parameters keep the stronger hash.
+    assert_eq!(
+        login_status(&user.username, &user.password).await,
+        StatusCode::OK
+    );
+    assert_eq!(stored_password_hash(&user.username).await, upgraded);
+}
+
+async fn create_api_key(token: &str, scopes: &[&str], expires_in: Option<i64>) -> CreatedApiKeyDto {
+    let payload = CreateApiKeyDto {
+        name: "ci".to_string(),
+        scopes: scopes.iter().map(|scope| scope.to_string()).collect(),
+        expires_in,
+    };
+
+    let response = request_with_token_and_body(Method::POST, "/auth/api-keys", token, &payload);
+    let (parts, body) = response.await.into_parts();
+    assert_eq!(parts.status, StatusCode::OK);
+
+    let response_body: RestApiResponse<CreatedApiKeyDto> =
+        deserialize_json_body(body).await.unwrap();
+    response_body.0.data.unwrap()
+}
+
+#[tokio::test]
+async fn test_api_key_lifecycle() {
+    let token = get_bearer_token(TEST_CLIENT_ID, TEST_CLIENT_SECRET).await;
+    let created = create_api_key(&token, &["user:read"], None).await;
+    assert!(created.api_key.starts_with("ak_"));
+    let api_key = format!("ApiKey {}", created.api_key);
+
+    // The key authenticates as its owner, limited to its scopes.
+    let response = request_with_token(Method::GET, "/user", &api_key);
+    assert_eq!(response.await.status(), StatusCode::OK);
+
+    let uri = format!("/user/{}", uuid::Uuid::new_v4());
+    let response = request_with_token(Method::DELETE, &uri, &api_key);
+    assert_eq!(response.await.status(), StatusCode::FORBIDDEN);
+
+    // API keys cannot create further keys.
+    let payload = CreateApiKeyDto {
+        name: "nested".to_string(),
+        scopes: vec!["user:read".to_string()],
+        expires_in: None,
+    };
+    let response = request_with_token_and_body(Method::POST, "/auth/api-keys", &api_key, &payload);
+    assert_eq!(response.await.status(), StatusCode::FORBIDDEN);
+
+    // The listing shows when the key was used, but never the key itself.
+    let response = request_with_token(Method::GET, "/auth/api-keys", &token);
+    let (parts, body) = response.await.into_parts();
+    assert_eq!(parts.status, StatusCode::OK);
+
+    let response_body: RestApiResponse<Vec<ApiKeyDto>> = deserialize_json_body(body).await.unwrap();
+    let listed = response_body
+        .0
+        .data
+        .unwrap()
+        .into_iter()
+        .find(|api_key| api_key.id == created.id)
+        .unwrap();
+    assert!(created.api_key.starts_with(&listed.key_prefix));
+    assert!(listed.last_used_at.is_some());
+    assert!(listed.revoked_at.is_none());
+
+    // Other users cannot see or revoke the key.
+    let uri = format!("/auth/api-keys/{}", created.id);
+    let other_token = get_bearer_token(TEST_NON_ADMIN_CLIENT_ID, TEST_CLIENT_SECRET).await;
+    let response = request_with_token(Method::DELETE, &uri, &other_token);
+    assert_eq!(response.await.status(), StatusCode::NOT_FOUND);
+
+    let response = request_with_token(Method::DELETE, &uri, &token);
+    assert_eq!(response.await.status(), StatusCode::OK);
+
+    let response = request_with_token(Method::GET, "/user", &api_key);
+    assert_eq!(response.await.status(), StatusCode::UNAUTHORIZED);
+
+    let response = request_with_token(Method::GET, "/user", "ApiKey ak_unknown");
+    assert_eq!(response.await.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn test_scoped_api_key_of_admin_is_not_admin() {
+    let token = get_bearer_token(TEST_CLIENT_ID, TEST_CLIENT_SECRET).await;
+    let created = create_api_key(&token, &["device:read", "auth:manage"], None).await;
+    let api_key = format!("ApiKey {}", created.api_key);
+
+    // The admin bypass is gone together with the permissions outside of the scopes:
+    // device02-1 is seeded for user02.
+    let uri = "/device/00000000-0000-0000-0000-000000000005";
+    let response = request_with_token(Method::GET, uri, &api_key);
+    assert_eq!(response.await.status(), StatusCode::FORBIDDEN);
+
+    let response = request_with_token(Method::GET, "/device?include_deleted=true", &api_key);
+    assert_eq!(response.await.status(), StatusCode::FORBIDDEN);
+
+    let response = request_with_token(Method::GET, "/auth/events", &api_key);
+    assert_eq!(response.await.status(), StatusCode::FORBIDDEN);
+
+    // The admin itself still reads the device.
+    let response = request_with_token(Method::GET, uri, &token);
+    assert_eq!(response.await.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_api_key_scopes_must_be_granted() {
+    let token = get_bearer_token(TEST_NON_ADMIN_CLIENT_ID, TEST_CLIENT_SECRET).await;
+    let payload = CreateApiKeyDto {
+        name: "gateway".to_string(),
+        scopes: vec!["device:read".to_string(), "auth:manage".to_string()],
+        expires_in: None,
+    };
+
+    let response = request_with_token_and_body(Method::POST, "/auth/api-keys", &token, &payload);
+    let (parts, body) = response.await.into_parts();
+    assert_eq!(parts.status, StatusCode::BAD_REQUEST);
+
+    let response_body: RestApiResponse<serde_json::Value> =
+        deserialize_json_body(body).await.unwrap();
+    let data = response_body.0.data.unwrap();
+    assert_eq!(data["scopes"].as_array().unwrap().len(), 1);
+    assert_eq!(data["scopes"][0]["code"], "scope");
+}
+
+#[tokio::test]
+async fn test_api_key_expiry() {
+    let token = get_bearer_token(TEST_NON_ADMIN_CLIENT_ID, TEST_CLIENT_SECRET).await;
+    let created = create_api_key(&token, &["device:read"], Some(1)).await;
+    assert!(created.expires_at.is_some());
+    let api_key = format!("ApiKey {}", created.api_key);
+
+    let response = request_with_token(Method::GET, "/device", &api_key);
+    assert_eq!(response.await.status(), StatusCode::OK);
+
+    tokio::time::sleep(std::time::Duration::from_millis(1100)).await;
+
+    let response = request_with_token(Method::GET, "/device", &api_key);
+    let (parts, body) = response.await.into_parts();
+    assert_eq!(parts.status, StatusCode::UNAUTHORIZED);
+
+    let response_body: RestApiResponse<()> = deserialize_json_body(body).await.unwrap();
+    assert_eq!(response_body.0.message, "Token expired");
+}
+
+fn cookie_mode(config: &mut Config) {
+    config.session_mode = SessionMode::Cookie;
+}
+
+/// Sends a request to a router in cookie session mode.
+async fn send_in_cookie_mode(request: Request<Body>) -> Response<Body> {
+    create_test_router_with_config(cookie_mode)
+        .await
+        .oneshot(request)
+        .await
+        .unwrap()
+}
+
+/// Collects the `name=value` pairs of the `Set-Cookie` headers of a response.
+fn set_cookies(response: &Response<Body>) -> Vec<String> {
+    response
+        .headers()
+        .get_all(SET_COOKIE)
+        .iter()
+        .map(|value| value.to_str().unwrap().to_string())
+        .collect()
+}
+
+fn cookie_value(set_cookies: &[String], name: &str) -> String {
+    set_cookies
+        .iter()
+        .find_map(|cookie| cookie.split(';').next()?.strip_prefix(&format!("{name}=")))
+        .unwrap()
+        .to_string()
+}
+
+#[tokio::test]
+async fn test_cookie_session() {
+    let payload = AuthPayload {
+        client_id: TEST_NON_ADMIN_CLIENT_ID.to_string(),
+        client_secret: TEST_CLIENT_SECRET.to_string(),
+    };
+    let request = Request::post("/auth/login")
+        .header(CONTENT_TYPE, "application/json")
+        .body(Body::from(serde_json::to_string(&payload).unwrap()))
+        .unwrap();
+    let response = send_in_cookie_mode(request).await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // The tokens are only set as HttpOnly cookies; the CSRF token is readable.
+    let cookies = set_cookies(&response);
+    let access_cookie = cookies
+        .iter()
+        .find(|c| c.starts_with("access_token="))
+        .unwrap();
+    assert!(access_cookie.contains("HttpOnly"));
+    assert!(access_cookie.contains("Secure"));
+    assert!(access_cookie.contains("SameSite=Strict"));
+    let csrf_cookie = cookies
+        .iter()
+        .find(|c| c.starts_with("csrf_token="))
+        .unwrap();
+    assert!(!csrf_cookie.contains("HttpOnly"));
+
+    let response_body: RestApiResponse<CookieSessionBody> =
+        deserialize_json_body(response.into_body()).await.unwrap();
+    let session = response_body.0.data.unwrap();
+    assert_eq!(session.csrf_token, cookie_value(&cookies, "csrf_token"));
+
+    let cookie_header = format!(
+        "access_token={}; refresh_token={}; csrf_token={}",
+        cookie_value(&cookies, "access_token"),
+        cookie_value(&cookies, "refresh_token"),
+        session.csrf_token
+    );
+
+    // Safe requests are authenticated by the cookie alone.
+    let request = Request::get(format!("/user/{TEST_USER_ID}"))
+        .header(COOKIE, &cookie_header)
+        .body(Body::empty())
+        .unwrap();
+    assert_eq!(send_in_cookie_mode(request).await.status(), StatusCode::OK);
+
+    // State-changing requests must echo the CSRF token.
+    let refresh = |csrf_token: Option<&str>| {
+        let mut request = Request::post("/auth/refresh").header(COOKIE, &cookie_header);
+        if let Some(csrf_token) = csrf_token {
+            request = request.header("x-csrf-token", csrf_token);
+        }
+        request.body(Body::empty()).unwrap()
+    };
+    let response = send_in_cookie_mode(refresh(None)).await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let response = send_in_cookie_mode(refresh(Some("forged"))).await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    let response = send_in_cookie_mode(refresh(Some(&session.csrf_token))).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let refreshed = set_cookies(&response);
+    assert_ne!(
+        cookie_value(&refreshed, "access_token"),
+        cookie_value(&cookies, "access_token")
+    );
+
+    // Bearer mode ignores session cookies.
+    let request = Request::get(format!("/user/{TEST_USER_ID}"))
+        .header(COOKIE, &cookie_header)
+        .body(Body::empty())
+        .unwrap();
+    let response = create_test_router().await.oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+/// A preflight for a conditional `PATCH`, as sent before an `If-Match` update.
+fn preflight(origin: &str) -> Request<Body> {
+    Request::options("/user")
+        .header(ORIGIN, origin)
+        .header(ACCESS_CONTROL_REQUEST_METHOD, "PATCH")
+        .header(ACCESS_CONTROL_REQUEST_HEADERS, "if-match")
+        .body(Body::empty())
+        .unwrap()
+}
+
+/// Checks that a preflight allows conditional `PATCH` requests.
+fn assert_allows_conditional_patch(response: &Response<Body>) {
+    let header = |name| response.headers()[name].to_str().unwrap().to_lowercase();
+    assert!(header(ACCESS_CONTROL_ALLOW_METHODS).contains("patch"));
+    assert!(header(ACCESS_CONTROL_ALLOW_HEADERS).contains("if-match"));
+}
+
+/// Checks that scripts of the origin can read the `ETag` of a response.
+async fn assert_exposes_etag(router: axum::Router, origin: &str) {
+    let request = Request::get("/.well-known/jwks.json")
+        .header(ORIGIN, origin)
+        .body(Body::empty())
+        .unwrap();
+    let response = router.oneshot(request).await.unwrap();
+    let exposed = response.headers()[ACCESS_CONTROL_EXPOSE_HEADERS]
+        .to_str()
+        .unwrap()
+        .to_lowercase();
+    assert!(exposed.contains("etag"));
+}
+
+#[tokio::test]
+async fn test_bearer_mode_cors() {
+    let origin = "https://any.example.com";
+    let response = create_test_router()
+        .await
+        .oneshot(preflight(origin))
+        .await
+        .unwrap();
+    assert_eq!(response.headers()[ACCESS_CONTROL_ALLOW_ORIGIN], "*");
+    assert_allows_conditional_patch(&response);
+
+    assert_exposes_etag(create_test_router().await, origin).await;
+}
+
+#[tokio::test]
+async fn test_cookie_mode_cors_allow_list() {
+    let origin = "https://console.example.com";
+    let response = create_test_router_with_config(|config| {
+        cookie_mode(config);
+        config.cors_allowed_origins = vec![origin.to_string()];
+    })
+    .await
+    .oneshot(preflight(origin))
+    .await
+    .unwrap();
+    assert_eq!(response.headers()[ACCESS_CONTROL_ALLOW_ORIGIN], origin);
+    assert_eq!(response.headers()[ACCESS_CONTROL_ALLOW_CREDENTIALS], "true");
+    assert_allows_conditional_patch(&response);
+
+    let router = create_test_router_with_config(|config| {
+        cookie_mode(config);
+        config.cors_allowed_origins = vec![origin.to_string()];
+    })
+    .await;
+    assert_exposes_etag(router, origin).await;
+
+    let response = send_in_cookie_mode(preflight("https://evil.example.com")).await;
+    assert!(!response.headers().contains_key(ACCESS_CONTROL_ALLOW_ORIGIN));
+}
+
+async fn list_auth_events(token: &str, query: &str) -> Vec<AuthEventDto> {
+    let uri = format!("/auth/events?{query}");
+    let response = request_with_token(Method::GET, &uri, token);
+    let (parts, body) = response.await.into_parts();
+    assert_eq!(parts.status, StatusCode::OK);
+
+    let response_body: RestApiResponse<Vec<AuthEventDto>> =
+        deserialize_json_body(body).await.unwrap();
+    response_body.0.data.unwrap()
+}
+
+#[tokio::test]
+async fn test_auth_events() {
+    let user = register_test_user().await;
+    let request_id = format!("audit-{}", uuid::Uuid::new_v4());
+
+    let auth_body = login(&user.username, &user.password).await;
+    let payload = RefreshTokenDto {
+        refresh_token: auth_body.refresh_token,
+    };
+    let response = request_with_body(Method::POST, "/auth/logout", &payload);
+    assert_eq!(response.await.status(), StatusCode::OK);
+
+    // Failed logins delay the next attempt, so this one comes last.
+    let payload = AuthPayload {
+        client_id: user.username.clone(),
+        client_secret: "wrong-password".to_string(),
+    };
+    let response = request_with_body_and_headers(
+        Method::POST,
+        "/auth/login",
+        &[("x-request-id", &request_id)],
+        &payload,
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(response.headers()["x-request-id"], request_id.as_str());
+
+    // Events are listed newest first, with the outcome and the client of each request.
+    let token = get_bearer_token(TEST_CLIENT_ID, TEST_CLIENT_SECRET).await;
+    let events = list_auth_events(&token, &format!("username={}", user.username)).await;
+    let kinds: Vec<_> = events
+        .iter()
+        .map(|event| (event.event_type.as_str(), event.success))
+        .collect();
+    assert_eq!(kinds, [("login", false), ("login", true)]);
+
+    let failed = &events[0];
+    assert_eq!(failed.request_id.as_deref(), Some(request_id.as_str()));
+    assert_eq!(failed.detail.as_deref(), Some("Wrong credentials"));
+    let failed_id = failed.id.clone();
+    let user_id = failed.user_id.clone().unwrap();
+
+    // Filtering by user also finds the events that carry no username.
+    let events = list_auth_events(&token, &format!("user_id={user_id}")).await;
+    assert_eq!(events.len(), 3);
+    assert_eq!(events[1].event_type, "logout");
+    assert!(events[1].request_id.is_some());
+
+    let events = list_auth_events(
+        &token,
+        &format!("user_id={user_id}&event_type=login&limit=1"),
+    )
+    .await;
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].id, failed_id);
+
+    let response = request_with_token(Method::GET, "/auth/events?limit=0", &token);
+    assert_eq!(response.await.status(), StatusCode::BAD_REQUEST);
+
+    let other_token = get_bearer_token(TEST_NON_ADMIN_CLIENT_ID, TEST_CLIENT_SECRET).await;
+    let response = request_with_token(Method::GET, "/auth/events", &other_token);
+    assert_eq!(response.await.status(), StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn test_auth_events_record_rejected_tokens() {
+    let request_id = format!("audit-{}", uuid::Uuid::new_v4());
+    let request = Request::get("/user")
+        .header(AUTHORIZATION, "Bearer not-a-token")
+        .header("x-request-id", &request_id)
+        .body(Body::empty())
+        .unwrap();
+    let response = create_test_router().await.oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    let token = get_bearer_token(TEST_CLIENT_ID, TEST_CLIENT_SECRET).await;
+    let events = list_auth_events(&token, "event_type=token_rejected&limit=1000").await;
+    let rejected = events
+        .iter()
+        .find(|event| event.request_id.as_deref() == Some(request_id.as_str()))
+        .unwrap();
+    assert!(!rejected.success);
+    assert!(rejected.user_id.is_none());
+}
+
+/// Reads the claims of a token without verifying it.
+fn token_claims(token: &str) -> Claims {
+    let payload = token.split('.').nth(1).unwrap();
+    serde_json::from_slice(&URL_SAFE_NO_PAD.decode(payload).unwrap()).unwrap()
+}
+
+#[tokio::test]
+async fn test_impersonation() {
+    let invite = create_invite(None).await;
+    let payload = register_payload(Some(invite.invite_token), None);
+    let response = request_with_body(Method::POST, "/auth/register", &payload);
+    let (parts, body) = response.await.into_parts();
+    assert_eq!(parts.status, StatusCode::OK);
+    let response_body: RestApiResponse<UserDto> = deserialize_json_body(body).await.unwrap();
+    let user = response_body.0.data.unwrap();
+
+    // A dedicated admin session, so that logging out does not affect other tests.
+    let admin = login(TEST_CLIENT_ID, TEST_CLIENT_SECRET).await;
+    let admin_token = format!("Bearer {}", admin.access_token);
+    let admin_id = token_claims(&admin.access_token).sub;
+
+    let uri = format!("/auth/impersonate/{}", user.id);
+    let response = request_with_token(Method::POST, &uri, &admin_token);
+    let (parts, body) = response.await.into_parts();
+    assert_eq!(parts.status, StatusCode::OK);
+    let response_body: RestApiResponse<ImpersonationTokenDto> =
+        deserialize_json_body(body).await.unwrap();
+    let impersonation = response_body.0.data.unwrap();
+    assert_eq!(impersonation.user_id, user.id);
+    assert_eq!(impersonation.expires_in, 600);
+
+    // The token acts as the user, with the admin as the actor.
+    let claims = token_claims(&impersonation.access_token);
+    assert_eq!(claims.sub, user.id);
+    assert_eq!(claims.act.unwrap().sub, admin_id);
+    assert!(!claims.roles.contains(&"admin".to_string()));
+
+    // Changes made while impersonating record the admin as the author.
+    let token = format!("Bearer {}", impersonation.access_token);
+    let device = CreateDeviceDto {
+        name: "Impersonated".to_string(),
+        user_id: user.id.clone(),
+        device_os: DeviceOS::Android,
+        status: DeviceStatus::Active,
+        registered_at: Some(chrono::Utc::now()),
+        modified_by: String::new(),
+    };
+    let response = request_with_token_and_body(Method::POST, "/device", &token, &device);
+    let (parts, body) = response.await.into_parts();
+    assert_eq!(parts.status, StatusCode::OK);
+    let response_body: RestApiResponse<DeviceDto> = deserialize_json_body(body).await.unwrap();
+    let device = response_body.0.data.unwrap();
+    assert_eq!(device.user_id, user.id);
+    assert_eq!(device.created_by.as_deref(), Some(admin_id.as_str()));
+    assert_eq!(device.modified_by.as_deref(), Some(admin_id.as_str()));
+
+    // Account settings require the user's own login.
+    let response = request_with_token(Method::POST, "/auth/mfa/enroll", &token);
+    assert_eq!(response.await.status(), StatusCode::FORBIDDEN);
+
+    // Only admins impersonate, and not themselves.
+    let user_token = get_bearer_token(TEST_NON_ADMIN_CLIENT_ID, TEST_CLIENT_SECRET).await;
+    let response = request_with_token(Method::POST, &uri, &user_token);
+    assert_eq!(response.await.status(), StatusCode::FORBIDDEN);
+
+    let self_uri = format!("/auth/impersonate/{admin_id}");
+    let response = request_with_token(Method::POST, &self_uri, &admin_token);
+    assert_eq!(response.await.status(), StatusCode::BAD_REQUEST);
+
+    let unknown_uri = format!("/auth/impersonate/{}", uuid::Uuid::new_v4());
+    let response = request_with_token(Method::POST, &unknown_uri, &admin_token);
+    assert_eq!(response.await.status(), StatusCode::NOT_FOUND);
+
+    // The impersonation is audited and ends with the admin's session.
+    let events = list_auth_events(
+        &admin_token,
+        &format!("user_id={}&event_type=impersonate", user.id),
+    )
+    .await;
+    assert_eq!(events.len(), 1);
+    assert_eq!(
+        events[0].detail.as_deref(),
+        Some(format!("Impersonated by {admin_id}").as_str())
+    );
+
+    let payload = RefreshTokenDto {
+        refresh_token: admin.refresh_token,
+    };
+    let response = request_with_body(Method::POST, "/auth/logout", &payload);
+    assert_eq!(response.await.status(), StatusCode::OK);
+
+    let response = request_with_token(Method::GET, "/device", &token);
+    assert_eq!(response.await.status(), StatusCode::UNAUTHORIZED);
+}
+
+/// Returns the token and the one-time code of the last magic link sent to the address.
+fn read_magic_link(email: &str) -> Option<(String, String)> {
+    let config = Config::from_env().unwrap();
+    let outbox = std::fs::read_to_string(config.notifier_file.unwrap()).ok()?;
+    let notification = outbox
+        .lines()
+        .rev()
+        .filter_map(|line| serde_json::from_str::<Notification>(line).ok())
+        .find(|notification| notification.recipient == email)?;
+
+    let token = notification
+        .body
+        .lines()
+        .find_map(|line| line.split_once("?token="))
+        .map(|(_, token)| token.to_string())?;
+    let code = notification.body.lines().last()?.to_string();
+    Some((token, code))
+}
+
+async fn request_magic_link(username: &str) -> StatusCode {
+    let payload = MagicLinkRequestDto {
+        username: username.to_string(),
+    };
+    request_with_body(Method::POST, "/auth/magic-link", &payload)
+        .await
+        .status()
+}
+
+async fn verify_magic_link(payload: MagicLinkVerifyDto) -> Response<Body> {
+    request_with_body(Method::POST, "/auth/magic-link/verify", &payload).await
+}
+
+#[tokio::test]
+async fn test_magic_link_login() {
+    let user = register_test_user().await;
+    assert_eq!(request_magic_link(&user.username).await, StatusCode::OK);
+    let (token, code) = read_magic_link(&user.email).unwrap();
+    assert_eq!(code.len(), 6);
+
+    let by_token = MagicLinkVerifyDto {
+        token: Some(token.clone()),
+        username: None,
+        code: None,
+    };
+    let (parts, body) = verify_magic_link(by_token).await.into_parts();
+    assert_eq!(parts.status, StatusCode::OK);
+    let response_body: RestApiResponse<AuthBody> = deserialize_json_body(body).await.unwrap();
+    let auth_body = response_body.0.data.unwrap();
+    assert!(!token_claims(&auth_body.access_token).sub.is_empty());
+
+    // Links are single use.
+    let reused = MagicLinkVerifyDto {
+        token: Some(token),
+        username: None,
+        code: None,
+    };
+    assert_eq!(
+        verify_magic_link(reused).await.status(),
+        StatusCode::UNAUTHORIZED
+    );
+
+    // A new request replaces the previous link.
+    assert_eq!(request_magic_link(&user.username).await, StatusCode::OK);
+    let (first_token, _) = read_magic_link(&user.email).unwrap();
+    assert_eq!(request_magic_link(&user.username).await, StatusCode::OK);
+    let (_, code) = read_magic_link(&user.email).unwrap();
+
+    let replaced = MagicLinkVerifyDto {
+        token: Some(first_token),
+        username: None,
+        code: None,
+    };
+    assert_eq!(
+        verify_magic_link(replaced).await.status(),
+        StatusCode::UNAUTHORIZED
+    );
+
+    let by_code = MagicLinkVerifyDto {
+        token: None,
+        username: Some(user.username.clone()),
+        code: Some(code),
+    };
+    let (parts, body) = verify_magic_link(by_code).await.into_parts();
+    assert_eq!(parts.status, StatusCode::OK);
+    let response_body: RestApiResponse<AuthBody> = deserialize_json_body(body).await.unwrap();
+    assert!(response_body.0.data.is_some());
+
+    // Unknown accounts are not revealed.
+    assert_eq!(request_magic_link("no-such-user").await, StatusCode::OK);
+
+    // Wrong codes fail like wrong passwords.
+    assert_eq!(request_magic_link(&user.username).await, StatusCode::OK);
+    let wrong_code = MagicLinkVerifyDto {
+        token: None,
+        username: Some(user.username),
+        code: Some("not-a-code".to_string()),
+    };
+    assert_eq!(
+        verify_magic_link(wrong_code).await.status(),
+        StatusCode::UNAUTHORIZED
+    );
+}
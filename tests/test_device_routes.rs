@@ -1,6 +1,6 @@
 use axum::http::{Method, StatusCode};
 
-use clean_axum_demo::common::dto::RestApiResponse;
+use clean_axum_demo::common::{dto::RestApiResponse, pagination::Paginated};
 use clean_axum_demo::domains::device::dto::device_dto::{
     CreateDeviceDto, DeviceDto, UpdateDeviceDto, UpdateDeviceDtoWithIdDto, UpdateManyDevicesDto,
 };
@@ -9,10 +9,14 @@ use clean_axum_demo::domains::device::{DeviceOS, DeviceStatus};
 use uuid::Uuid;
 mod test_helpers;
 use test_helpers::{
-    deserialize_json_body, request_with_auth, request_with_auth_and_body, TEST_USER_ID,
+    create_device_for, create_user, deserialize_json_body, get_bearer_token, request_with_auth,
+    request_with_auth_and_body, request_with_auth_and_headers, request_with_body_and_headers,
+    request_with_token, request_with_token_and_body, TEST_CLIENT_SECRET, TEST_NON_ADMIN_CLIENT_ID,
+    TEST_OTHER_USER_ID, TEST_USER_ID,
 };
 
 use chrono::{Duration, Utc};
+use serde_json::json;
 
 async fn create_test_device() -> DeviceDto {
     let name = format!("test-device-{}", Uuid::new_v4()).to_string();
@@ -78,16 +82,42 @@ async fn test_get_devices() {
 
     assert_eq!(parts.status, StatusCode::OK);
 
-    let response_body: RestApiResponse<Vec<DeviceDto>> = deserialize_json_body(body).await.unwrap();
+    let response_body: RestApiResponse<Paginated<DeviceDto>> =
+        deserialize_json_body(body).await.unwrap();
 
     assert_eq!(response_body.0.status, StatusCode::OK);
 
-    let devices = response_body.0.data.unwrap();
+    let devices = response_body.0.data.unwrap().items;
 
     // println!("devices: {:?}", devices);
     assert!(!devices.is_empty());
 }
 
+#[tokio::test]
+async fn test_get_devices_paginated() {
+    let response = request_with_auth(Method::GET, "/device?size=3");
+    let (parts, body) = response.await.into_parts();
+    assert_eq!(parts.status, StatusCode::OK);
+    let response_body: RestApiResponse<Paginated<DeviceDto>> =
+        deserialize_json_body(body).await.unwrap();
+    let first = response_body.0.data.unwrap();
+    assert_eq!(first.items.len(), 3);
+    assert!(first.total >= 80);
+
+    let uri = format!("/device?size=3&cursor={}", first.next_cursor.unwrap());
+    let response = request_with_auth(Method::GET, &uri);
+    let (parts, body) = response.await.into_parts();
+    assert_eq!(parts.status, StatusCode::OK);
+    let response_body: RestApiResponse<Paginated<DeviceDto>> =
+        deserialize_json_body(body).await.unwrap();
+    let second = response_body.0.data.unwrap();
+    assert_eq!(second.items.len(), 3);
+    assert!(second
+        .items
+        .iter()
+        .all(|device| first.items.iter().all(|seen| seen.id != device.id)));
+}
+
 #[tokio::test]
 async fn test_get_device_by_id() {
     let device = create_test_device().await;
@@ -113,6 +143,81 @@ async fn test_get_device_by_id() {
     assert_eq!(response_device.status, device.status);
 }
 
+/// Sends a JSON Merge Patch for a device with the given `If-Match` header
+/// and returns the status together with the new ETag.
+async fn patch_device(
+    id: &str,
+    if_match: &str,
+    patch: serde_json::Value,
+) -> (StatusCode, Option<String>, Option<DeviceDto>) {
+    let url = format!("/device/{id}");
+    let headers = [
+        ("content-type", "application/merge-patch+json"),
+        ("if-match", if_match),
+    ];
+
+    let response = request_with_auth_and_headers(Method::PATCH, url.as_str(), &headers, &patch);
+    let (parts, body) = response.await.into_parts();
+    let etag = parts
+        .headers
+        .get("etag")
+        .map(|value| value.to_str().unwrap().to_string());
+
+    // Failed validations return the field errors as data.
+    let response_body: RestApiResponse<serde_json::Value> =
+        deserialize_json_body(body).await.unwrap();
+    let data = response_body
+        .0
+        .data
+        .filter(|_| parts.status == StatusCode::OK)
+        .map(|data| serde_json::from_value(data).unwrap());
+    (parts.status, etag, data)
+}
+
+#[tokio::test]
+async fn test_patch_device() {
+    let device = create_test_device().await;
+
+    let url = format!("/device/{}", device.id);
+    let response = request_with_auth(Method::GET, url.as_str()).await;
+    let etag = response.headers()["etag"].to_str().unwrap().to_string();
+
+    let (status, new_etag, patched) =
+        patch_device(&device.id, &etag, json!({ "status": "inactive" })).await;
+    assert_eq!(status, StatusCode::OK);
+    let patched = patched.unwrap();
+    assert_eq!(patched.status, DeviceStatus::Inactive);
+    assert_eq!(patched.name, device.name);
+    assert_eq!(patched.device_os, device.device_os);
+    assert_eq!(patched.version, device.version + 1);
+    let new_etag = new_etag.unwrap();
+    assert_ne!(new_etag, etag);
+
+    // A concurrent edit based on the old version is rejected.
+    let (status, _, _) = patch_device(&device.id, &etag, json!({ "name": "stale" })).await;
+    assert_eq!(status, StatusCode::PRECONDITION_FAILED);
+
+    let (status, _, _) = patch_device(&device.id, &new_etag, json!({ "name": null })).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, _, _) = patch_device(&device.id, &new_etag, json!({ "status": "lost" })).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    // Unknown members are rejected rather than silently dropped.
+    let (status, _, _) = patch_device(&device.id, &new_etag, json!({ "colour": "red" })).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    // Handing the device over to another user is still limited to admins.
+    let token = get_bearer_token(TEST_NON_ADMIN_CLIENT_ID, TEST_CLIENT_SECRET).await;
+    let headers = [
+        ("content-type", "application/merge-patch+json"),
+        ("authorization", token.as_str()),
+    ];
+    let patch = json!({ "user_id": TEST_OTHER_USER_ID });
+    let response = request_with_body_and_headers(Method::PATCH, url.as_str(), &headers, &patch);
+    assert_eq!(response.await.status(), StatusCode::FORBIDDEN);
+}
+
 #[tokio::test]
 async fn test_update_device() {
     let existent_device = &create_test_device().await;
@@ -232,3 +337,210 @@ async fn test_update_many_devices() {
     // println!("response_body.0.status: {:?}", response_body.0.status);
     // println!("response_body.0.message: {:?}", response_body.0.message);
 }
+
+#[tokio::test]
+async fn test_get_devices_only_own_for_non_admin() {
+    let token = get_bearer_token(TEST_NON_ADMIN_CLIENT_ID, TEST_CLIENT_SECRET).await;
+
+    let response = request_with_token(Method::GET, "/device", &token);
+
+    let (parts, body) = response.await.into_parts();
+
+    assert_eq!(parts.status, StatusCode::OK);
+
+    let response_body: RestApiResponse<Paginated<DeviceDto>> =
+        deserialize_json_body(body).await.unwrap();
+
+    let devices = response_body.0.data.unwrap();
+    assert!(!devices.items.is_empty());
+    assert!(devices
+        .items
+        .iter()
+        .all(|device| device.user_id == TEST_USER_ID));
+}
+
+#[tokio::test]
+async fn test_get_other_users_device_forbidden() {
+    let token = get_bearer_token(TEST_NON_ADMIN_CLIENT_ID, TEST_CLIENT_SECRET).await;
+
+    // device02-1 is seeded for user02
+    let url = "/device/00000000-0000-0000-0000-000000000005";
+    let response = request_with_token(Method::GET, url, &token);
+
+    let (parts, _) = response.await.into_parts();
+
+    assert_eq!(parts.status, StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn test_update_many_devices_of_other_user_forbidden() {
+    let token = get_bearer_token(TEST_NON_ADMIN_CLIENT_ID, TEST_CLIENT_SECRET).await;
+
+    let payload = UpdateManyDevicesDto {
+        devices: vec![UpdateDeviceDtoWithIdDto {
+            id: None,
+            name: format!("many-update-device-{}", Uuid::new_v4()),
+            device_os: DeviceOS::Android,
+            status: DeviceStatus::Pending,
+        }],
+    };
+
+    let url = format!("/device/batch/{}", TEST_OTHER_USER_ID);
+
+    let response = request_with_token_and_body(Method::PUT, url.as_str(), &token, &payload);
+
+    let (parts, body) = response.await.into_parts();
+
+    assert_eq!(parts.status, StatusCode::FORBIDDEN);
+
+    let response_body: RestApiResponse<()> = deserialize_json_body(body).await.unwrap();
+
+    assert_eq!(response_body.0.status, StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn test_update_many_devices_cannot_take_over_device() {
+    let token = get_bearer_token(TEST_NON_ADMIN_CLIENT_ID, TEST_CLIENT_SECRET).await;
+
+    // device02-2 is seeded for user02; referencing it in user01's batch must not move it.
+    let foreign_device_id = "00000000-0000-0000-0000-000000000006";
+
+    let payload = UpdateManyDevicesDto {
+        devices: vec![UpdateDeviceDtoWithIdDto {
+            id: Some(foreign_device_id.to_string()),
+            name: format!("many-update-device-{}", Uuid::new_v4()),
+            device_os: DeviceOS::IOS,
+            status: DeviceStatus::Blocked,
+        }],
+    };
+
+    let url = format!("/device/batch/{}", TEST_USER_ID);
+
+    let response = request_with_token_and_body(Method::PUT, url.as_str(), &token, &payload);
+
+    let (parts, _) = response.await.into_parts();
+
+    assert_eq!(parts.status, StatusCode::FORBIDDEN);
+
+    let url = format!("/device/{}", foreign_device_id);
+    let response = request_with_auth(Method::GET, url.as_str());
+
+    let (parts, body) = response.await.into_parts();
+
+    assert_eq!(parts.status, StatusCode::OK);
+
+    let response_body: RestApiResponse<DeviceDto> = deserialize_json_body(body).await.unwrap();
+    let device = response_body.0.data.unwrap();
+
+    assert_eq!(device.user_id, TEST_OTHER_USER_ID);
+    assert_eq!(device.name, "device02-2");
+}
+
+#[tokio::test]
+async fn test_restore_device() {
+    let device = create_test_device().await;
+
+    let url = format!("/device/{}", device.id);
+    let response = request_with_auth(Method::DELETE, url.as_str());
+    assert_eq!(response.await.status(), StatusCode::OK);
+
+    let response = request_with_auth(Method::GET, url.as_str());
+    assert_eq!(response.await.status(), StatusCode::NOT_FOUND);
+
+    // The name of a deleted device is free again.
+    let payload = CreateDeviceDto {
+        name: device.name.clone(),
+        user_id: device.user_id.clone(),
+        device_os: DeviceOS::IOS,
+        status: DeviceStatus::Active,
+        registered_at: Some(Utc::now()),
+        modified_by: TEST_USER_ID.to_string(),
+    };
+    let response = request_with_auth_and_body(Method::POST, "/device", &payload);
+    let (parts, body) = response.await.into_parts();
+    assert_eq!(parts.status, StatusCode::OK);
+    let response_body: RestApiResponse<DeviceDto> = deserialize_json_body(body).await.unwrap();
+    let replacement = response_body.0.data.unwrap();
+
+    let restore_url = format!("/device/{}/restore", device.id);
+    let response = request_with_auth(Method::POST, restore_url.as_str());
+    assert_eq!(response.await.status(), StatusCode::CONFLICT);
+
+    let url = format!("/device/{}", replacement.id);
+    let response = request_with_auth(Method::DELETE, url.as_str());
+    assert_eq!(response.await.status(), StatusCode::OK);
+
+    // Owners may restore their own devices.
+    let token = get_bearer_token(TEST_NON_ADMIN_CLIENT_ID, TEST_CLIENT_SECRET).await;
+    let response = request_with_token(Method::POST, restore_url.as_str(), &token);
+    let (parts, body) = response.await.into_parts();
+    assert_eq!(parts.status, StatusCode::OK);
+
+    let response_body: RestApiResponse<DeviceDto> = deserialize_json_body(body).await.unwrap();
+    let restored = response_body.0.data.unwrap();
+    assert_eq!(restored.id, device.id);
+    assert_eq!(restored.name, device.name);
+    assert!(restored.deleted_at.is_none());
+
+    let response = request_with_token(Method::POST, restore_url.as_str(), &token);
+    assert_eq!(response.await.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_restore_device_of_deleted_user_conflict() {
+    let (_, user) = create_user().await;
+    let device = create_device_for(&user.id).await;
+
+    // Deleting the user decommissions the device.
+    let user_url = format!("/user/{}", user.id);
+    let response = request_with_auth(Method::DELETE, user_url.as_str());
+    assert_eq!(response.await.status(), StatusCode::OK);
+
+    let restore_url = format!("/device/{}/restore", device.id);
+    let response = request_with_auth(Method::POST, restore_url.as_str());
+    assert_eq!(response.await.status(), StatusCode::CONFLICT);
+
+    // The device can be restored once its owner is back.
+    let url = format!("/user/{}/restore", user.id);
+    let response = request_with_auth(Method::POST, url.as_str());
+    assert_eq!(response.await.status(), StatusCode::OK);
+
+    let response = request_with_auth(Method::POST, restore_url.as_str());
+    assert_eq!(response.await.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_restore_other_users_device_forbidden() {
+    let payload = CreateDeviceDto {
+        name: format!("test-device-{}", Uuid::new_v4()),
+        user_id: TEST_OTHER_USER_ID.to_string(),
+        device_os: DeviceOS::Android,
+        status: DeviceStatus::Active,
+        registered_at: Some(Utc::now()),
+        modified_by: TEST_USER_ID.to_string(),
+    };
+    let response = request_with_auth_and_body(Method::POST, "/device", &payload);
+    let (parts, body) = response.await.into_parts();
+    assert_eq!(parts.status, StatusCode::OK);
+    let response_body: RestApiResponse<DeviceDto> = deserialize_json_body(body).await.unwrap();
+    let device = response_body.0.data.unwrap();
+
+    let url = format!("/device/{}", device.id);
+    let response = request_with_auth(Method::DELETE, url.as_str());
+    assert_eq!(response.await.status(), StatusCode::OK);
+
+    let token = get_bearer_token(TEST_NON_ADMIN_CLIENT_ID, TEST_CLIENT_SECRET).await;
+    let restore_url = format!("/device/{}/restore", device.id);
+    let response = request_with_token(Method::POST, restore_url.as_str(), &token);
+    assert_eq!(response.await.status(), StatusCode::FORBIDDEN);
+
+    let response = request_with_token(Method::GET, "/device?include_deleted=true", &token);
+    assert_eq!(response.await.status(), StatusCode::FORBIDDEN);
+
+    // The forbidden restore was rolled back.
+    let response = request_with_auth(Method::GET, url.as_str());
+    assert_eq!(response.await.status(), StatusCode::NOT_FOUND);
+
+    let response = request_with_auth(Method::GET, "/device?include_deleted=true");
+    assert_eq!(response.await.status(), StatusCode::OK);
+}
@@ -0,0 +1,328 @@
+use axum::http::{Method, StatusCode};
+use chrono::Utc;
+use serde_json::json;
+
+use clean_axum_demo::{
+    common::{dto::RestApiResponse, pagination::Paginated},
+    domains::{
+        device::{
+            dto::device_dto::{CreateDeviceDto, DeviceDto},
+            DeviceOS, DeviceStatus,
+        },
+        group::{
+            dto::group_dto::{
+                AddGroupMemberDto, GroupDto, GroupMemberDto, MembershipDto, SaveGroupDto,
+            },
+            GroupRole,
+        },
+    },
+};
+
+mod test_helpers;
+
+use test_helpers::{
+    create_device_for, create_user, deserialize_json_body, get_bearer_token, request_with_auth,
+    request_with_auth_and_body, request_with_token, request_with_token_and_body,
+    TEST_CLIENT_SECRET, TEST_NON_ADMIN_CLIENT_ID, TEST_USER_ID,
+};
+
+async fn create_group() -> GroupDto {
+    let payload = SaveGroupDto {
+        name: format!("team-{}", uuid::Uuid::new_v4()),
+        description: Some("Field team".into()),
+    };
+
+    let response = request_with_auth_and_body(Method::POST, "/group", &payload);
+    let (parts, body) = response.await.into_parts();
+    assert_eq!(parts.status, StatusCode::OK);
+
+    let response_body: RestApiResponse<GroupDto> = deserialize_json_body(body).await.unwrap();
+    let group = response_body.0.data.unwrap();
+    assert_eq!(group.name, payload.name);
+    group
+}
+
+async fn add_member(group_id: &str, user_id: &str, role: &str) -> GroupMemberDto {
+    let uri = format!("/group/{group_id}/members/{user_id}");
+    let payload = json!({ "role": role });
+    let response = request_with_auth_and_body(Method::PUT, &uri, &payload);
+    let (parts, body) = response.await.into_parts();
+    assert_eq!(parts.status, StatusCode::OK);
+
+    let response_body: RestApiResponse<GroupMemberDto> = deserialize_json_body(body).await.unwrap();
+    response_body.0.data.unwrap()
+}
+
+async fn non_admin_token() -> String {
+    get_bearer_token(TEST_NON_ADMIN_CLIENT_ID, TEST_CLIENT_SECRET).await
+}
+
+#[tokio::test]
+async fn test_group_crud() {
+    let group = create_group().await;
+    let uri = format!("/group/{}", group.id);
+
+    let response = request_with_auth(Method::GET, &uri);
+    let (parts, body) = response.await.into_parts();
+    assert_eq!(parts.status, StatusCode::OK);
+    let response_body: RestApiResponse<GroupDto> = deserialize_json_body(body).await.unwrap();
+    assert_eq!(
+        response_body.0.data.unwrap().description.as_deref(),
+        Some("Field team")
+    );
+
+    let renamed = SaveGroupDto {
+        name: format!("{}-renamed", group.name),
+        description: None,
+    };
+    let response = request_with_auth_and_body(Method::PUT, &uri, &renamed);
+    let (parts, body) = response.await.into_parts();
+    assert_eq!(parts.status, StatusCode::OK);
+    let response_body: RestApiResponse<GroupDto> = deserialize_json_body(body).await.unwrap();
+    let updated = response_body.0.data.unwrap();
+    assert_eq!(updated.name, renamed.name);
+    assert_eq!(updated.description, None);
+
+    // Group names are unique within the organization.
+    let other = create_group().await;
+    let uri_other = format!("/group/{}", other.id);
+    let response = request_with_auth_and_body(Method::PUT, &uri_other, &renamed);
+    assert_eq!(response.await.status(), StatusCode::CONFLICT);
+
+    let response = request_with_auth(Method::GET, "/group?size=100");
+    let (parts, body) = response.await.into_parts();
+    assert_eq!(parts.status, StatusCode::OK);
+    let response_body: RestApiResponse<Paginated<GroupDto>> =
+        deserialize_json_body(body).await.unwrap();
+    assert!(response_body.0.data.unwrap().total >= 2);
+
+    let response = request_with_auth(Method::DELETE, &uri);
+    assert_eq!(response.await.status(), StatusCode::OK);
+    let response = request_with_auth(Method::GET, &uri);
+    assert_eq!(response.await.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_group_requires_admin_to_manage() {
+    let token = non_admin_token().await;
+    let payload = SaveGroupDto {
+        name: format!("team-{}", uuid::Uuid::new_v4()),
+        description: None,
+    };
+
+    let response = request_with_token_and_body(Method::POST, "/group", &token, &payload);
+    assert_eq!(response.await.status(), StatusCode::FORBIDDEN);
+
+    // Non-members may not read a group.
+    let group = create_group().await;
+    let uri = format!("/group/{}", group.id);
+    let response = request_with_token(Method::GET, &uri, &token);
+    assert_eq!(response.await.status(), StatusCode::FORBIDDEN);
+    let members_uri = format!("/group/{}/members", group.id);
+    let response = request_with_token(Method::GET, &members_uri, &token);
+    assert_eq!(response.await.status(), StatusCode::FORBIDDEN);
+
+    let empty = SaveGroupDto {
+        name: String::new(),
+        description: None,
+    };
+    let response = request_with_auth_and_body(Method::POST, "/group", &empty);
+    assert_eq!(response.await.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_group_membership() {
+    let group = create_group().await;
+    let user = create_user().await.1;
+
+    let member = add_member(&group.id, &user.id, "member").await;
+    assert_eq!(member.username, user.username);
+    assert_eq!(member.role, GroupRole::Member);
+
+    // Adding a member again changes the role.
+    let member = add_member(&group.id, &user.id, "manager").await;
+    assert_eq!(member.role, GroupRole::Manager);
+    add_member(&group.id, TEST_USER_ID, "member").await;
+
+    let unknown = format!("/group/{}/members/{}", group.id, uuid::Uuid::new_v4());
+    let payload = AddGroupMemberDto::default();
+    let response = request_with_auth_and_body(Method::PUT, &unknown, &payload);
+    assert_eq!(response.await.status(), StatusCode::NOT_FOUND);
+
+    // Members may read the group and its members.
+    let token = non_admin_token().await;
+    let members_uri = format!("/group/{}/members", group.id);
+    let response = request_with_token(Method::GET, &members_uri, &token);
+    let (parts, body) = response.await.into_parts();
+    assert_eq!(parts.status, StatusCode::OK);
+    let response_body: RestApiResponse<Vec<GroupMemberDto>> =
+        deserialize_json_body(body).await.unwrap();
+    assert_eq!(response_body.0.data.unwrap().len(), 2);
+
+    let response = request_with_token(Method::GET, "/group?size=100", &token);
+    let (parts, body) = response.await.into_parts();
+    assert_eq!(parts.status, StatusCode::OK);
+    let response_body: RestApiResponse<Paginated<GroupDto>> =
+        deserialize_json_body(body).await.unwrap();
+    assert!(response_body
+        .0
+        .data
+        .unwrap()
+        .items
+        .iter()
+        .any(|g| g.id == group.id));
+
+    // Users list their own groups; admins those of anyone.
+    let uri = format!("/group/user/{TEST_USER_ID}");
+    let response = request_with_token(Method::GET, &uri, &token);
+    let (parts, body) = response.await.into_parts();
+    assert_eq!(parts.status, StatusCode::OK);
+    let response_body: RestApiResponse<Vec<MembershipDto>> =
+        deserialize_json_body(body).await.unwrap();
+    let membership = response_body
+        .0
+        .data
+        .unwrap()
+        .into_iter()
+        .find(|m| m.group_id == group.id)
+        .unwrap();
+    assert_eq!(membership.role, GroupRole::Member);
+
+    let uri = format!("/group/user/{}", user.id);
+    let response = request_with_token(Method::GET, &uri, &token);
+    assert_eq!(response.await.status(), StatusCode::FORBIDDEN);
+    let response = request_with_auth(Method::GET, &uri);
+    assert_eq!(response.await.status(), StatusCode::OK);
+
+    let member_uri = format!("/group/{}/members/{}", group.id, user.id);
+    let response = request_with_auth(Method::DELETE, &member_uri);
+    assert_eq!(response.await.status(), StatusCode::OK);
+    let response = request_with_auth(Method::DELETE, &member_uri);
+    assert_eq!(response.await.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_group_manager_device_access() {
+    let group = create_group().await;
+    let user = create_user().await.1;
+    let device = create_device_for(&user.id).await;
+
+    let token = non_admin_token().await;
+    let device_uri = format!("/device/{}", device.id);
+    let group_devices_uri = format!("/device/group/{}", group.id);
+
+    let response = request_with_token(Method::GET, &device_uri, &token);
+    assert_eq!(response.await.status(), StatusCode::FORBIDDEN);
+
+    // Plain members do not get access to the devices of the group.
+    add_member(&group.id, &user.id, "member").await;
+    add_member(&group.id, TEST_USER_ID, "member").await;
+    let response = request_with_token(Method::GET, &device_uri, &token);
+    assert_eq!(response.await.status(), StatusCode::FORBIDDEN);
+    let response = request_with_token(Method::GET, &group_devices_uri, &token);
+    assert_eq!(response.await.status(), StatusCode::FORBIDDEN);
+
+    add_member(&group.id, TEST_USER_ID, "manager").await;
+    let response = request_with_token(Method::GET, &device_uri, &token);
+    assert_eq!(response.await.status(), StatusCode::OK);
+
+    let payload = json!({ "status": "inactive", "registered_at": null, "modified_by": "" });
+    let response = request_with_token_and_body(Method::PUT, &device_uri, &token, &payload);
+    assert_eq!(response.await.status(), StatusCode::OK);
+
+    let response = request_with_token(Method::GET, &group_devices_uri, &token);
+    let (parts, body) = response.await.into_parts();
+    assert_eq!(parts.status, StatusCode::OK);
+    let response_body: RestApiResponse<Paginated<DeviceDto>> =
+        deserialize_json_body(body).await.unwrap();
+    let devices = response_body.0.data.unwrap();
+    assert!(devices.items.iter().any(|d| d.id == device.id));
+    assert!(devices
+        .items
+        .iter()
+        .all(|d| d.user_id == user.id || d.user_id == TEST_USER_ID));
+
+    // Access ends with the membership.
+    let member_uri = format!("/group/{}/members/{}", group.id, user.id);
+    let response = request_with_auth(Method::DELETE, &member_uri);
+    assert_eq!(response.await.status(), StatusCode::OK);
+    let response = request_with_token(Method::GET, &device_uri, &token);
+    assert_eq!(response.await.status(), StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn test_group_manager_manages_member_devices() {
+    let group = create_group().await;
+    let user = create_user().await.1;
+    let token = non_admin_token().await;
+
+    let payload = CreateDeviceDto {
+        name: format!("device-{}", uuid::Uuid::new_v4()),
+        user_id: user.id.clone(),
+        device_os: DeviceOS::Android,
+        status: DeviceStatus::Active,
+        registered_at: Some(Utc::now()),
+        modified_by: TEST_USER_ID.to_string(),
+    };
+    let response = request_with_token_and_body(Method::POST, "/device", &token, &payload);
+    assert_eq!(response.await.status(), StatusCode::FORBIDDEN);
+
+    add_member(&group.id, &user.id, "member").await;
+    add_member(&group.id, TEST_USER_ID, "manager").await;
+
+    let response = request_with_token_and_body(Method::POST, "/device", &token, &payload);
+    let (parts, body) = response.await.into_parts();
+    assert_eq!(parts.status, StatusCode::OK);
+    let response_body: RestApiResponse<DeviceDto> = deserialize_json_body(body).await.unwrap();
+    let device = response_body.0.data.unwrap();
+    assert_eq!(device.user_id, user.id);
+
+    let batch_uri = format!("/device/batch/{}", user.id);
+    let batch = json!({
+        "devices": [{ "id": device.id, "name": device.name, "device_os": "Android", "status": "inactive" }]
+    });
+    let response = request_with_token_and_body(Method::PUT, &batch_uri, &token, &batch);
+    assert_eq!(response.await.status(), StatusCode::OK);
+
+    let device_uri = format!("/device/{}", device.id);
+    let response = request_with_token(Method::DELETE, &device_uri, &token);
+    assert_eq!(response.await.status(), StatusCode::OK);
+    let restore_uri = format!("/device/{}/restore", device.id);
+    let response = request_with_token(Method::POST, &restore_uri, &token);
+    assert_eq!(response.await.status(), StatusCode::OK);
+
+    // Without the membership, every write path is refused again.
+    let member_uri = format!("/group/{}/members/{}", group.id, user.id);
+    let response = request_with_auth(Method::DELETE, &member_uri);
+    assert_eq!(response.await.status(), StatusCode::OK);
+    let response = request_with_token_and_body(Method::POST, "/device", &token, &payload);
+    assert_eq!(response.await.status(), StatusCode::FORBIDDEN);
+    let response = request_with_token_and_body(Method::PUT, &batch_uri, &token, &batch);
+    assert_eq!(response.await.status(), StatusCode::FORBIDDEN);
+    let response = request_with_auth(Method::DELETE, &device_uri);
+    assert_eq!(response.await.status(), StatusCode::OK);
+    let response = request_with_token(Method::POST, &restore_uri, &token);
+    assert_eq!(response.await.status(), StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn test_groups_are_scoped_to_tenant() {
+    let group = create_group().await;
+    let acme = get_bearer_token("acmeadmin01", TEST_CLIENT_SECRET).await;
+
+    let uri = format!("/group/{}", group.id);
+    let response = request_with_token(Method::GET, &uri, &acme);
+    assert_eq!(response.await.status(), StatusCode::NOT_FOUND);
+    let uri = format!("/device/group/{}", group.id);
+    let response = request_with_token(Method::GET, &uri, &acme);
+    assert_eq!(response.await.status(), StatusCode::NOT_FOUND);
+
+    // Users of another organization cannot join the group.
+    let uri = format!(
+        "/group/{}/members/00000000-0000-0000-0000-000000000023",
+        group.id
+    );
+    let payload = AddGroupMemberDto::default();
+    let response = request_with_auth_and_body(Method::PUT, &uri, &payload);
+    assert_eq!(response.await.status(), StatusCode::NOT_FOUND);
+}
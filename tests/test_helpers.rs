@@ -1,4 +1,4 @@
-use std::sync::Once;
+use std::{io::Write, sync::Once};
 
 use axum::{
     body::Body,
@@ -9,17 +9,26 @@ use axum::{
     Router,
 };
 
+use chrono::Utc;
 use dotenvy::from_filename;
 use http_body_util::BodyExt;
 
 use clean_axum_demo::{
     app::create_router,
     common::{
+        app_state::AppState,
         bootstrap::build_app_state,
         config::Config,
         dto::RestApiResponse,
         jwt::{AuthBody, AuthPayload},
     },
+    domains::{
+        device::{
+            dto::device_dto::{CreateDeviceDto, DeviceDto},
+            DeviceOS, DeviceStatus,
+        },
+        user::dto::user_dto::{CreateUserMultipartDto, UserDto},
+    },
 };
 
 use sqlx::{postgres::PgPoolOptions, PgPool};
@@ -37,6 +46,15 @@ pub const TEST_CLIENT_SECRET: &str = "test_password";
 #[allow(dead_code)]
 pub const TEST_USER_ID: &str = "00000000-0000-0000-0000-000000000001";
 
+/// Credentials of a seeded user that only holds the regular `user` role.
+/// The account belongs to `TEST_USER_ID`.
+#[allow(dead_code)]
+pub const TEST_NON_ADMIN_CLIENT_ID: &str = "user01";
+
+/// A seeded user that is neither the admin nor `TEST_USER_ID`.
+#[allow(dead_code)]
+pub const TEST_OTHER_USER_ID: &str = "00000000-0000-0000-0000-000000000002";
+
 /// Helper function to load environment variables from .env.test file
 fn load_test_env() {
     INIT.call_once(|| {
@@ -64,22 +82,36 @@ pub async fn setup_test_db() -> Result<PgPool, Box<dyn std::error::Error>> {
 
 /// Helper function to create a test router
 pub async fn create_test_router() -> Router {
+    create_test_router_with_config(|_| {}).await
+}
+
+/// Helper function to build the application state with the test configuration,
+/// for tests that call the services directly
+#[allow(dead_code)]
+pub async fn create_test_state() -> AppState {
     let pool = setup_test_db().await.unwrap();
     let config = Config::from_env().unwrap();
-    let state = build_app_state(pool, config.clone());
-    let app = create_router(state);
+    build_app_state(pool, config)
+}
 
-    app
+/// Helper function to create a test router
+/// with the test configuration adjusted by `modify`
+pub async fn create_test_router_with_config(modify: impl FnOnce(&mut Config)) -> Router {
+    let pool = setup_test_db().await.unwrap();
+    let mut config = Config::from_env().unwrap();
+    modify(&mut config);
+    let state = build_app_state(pool, config);
+
+    create_router(state)
 }
 
-/// Helper function gets the authentication token
-/// for the test client
-/// This function is used to authenticate the test client
+/// Helper function logs in with the given credentials
+/// and returns the issued token pair
 #[allow(dead_code)]
-async fn get_authentication_token() -> String {
+pub async fn login(client_id: &str, client_secret: &str) -> AuthBody {
     let payload = AuthPayload {
-        client_id: TEST_CLIENT_ID.to_string(),
-        client_secret: TEST_CLIENT_SECRET.to_string(),
+        client_id: client_id.to_string(),
+        client_secret: client_secret.to_string(),
     };
 
     let response = request_with_body(Method::POST, "/auth/login", &payload);
@@ -89,9 +121,23 @@ async fn get_authentication_token() -> String {
     assert_eq!(parts.status, StatusCode::OK);
 
     let response_body: RestApiResponse<AuthBody> = deserialize_json_body(body).await.unwrap();
-    let auth_body = response_body.0.data.unwrap();
-    let token = format!("{} {}", auth_body.token_type, auth_body.access_token);
-    token
+    response_body.0.data.unwrap()
+}
+
+/// Helper function gets the authentication token
+/// for the test client
+/// This function is used to authenticate the test client
+#[allow(dead_code)]
+async fn get_authentication_token() -> String {
+    get_bearer_token(TEST_CLIENT_ID, TEST_CLIENT_SECRET).await
+}
+
+/// Helper function logs in with the given credentials
+/// and returns the authorization header value
+#[allow(dead_code)]
+pub async fn get_bearer_token(client_id: &str, client_secret: &str) -> String {
+    let auth_body = login(client_id, client_secret).await;
+    format!("{} {}", auth_body.token_type, auth_body.access_token)
 }
 
 /// Helper function to deserialize the body of a request into a specific type
@@ -145,6 +191,58 @@ pub async fn request_with_body<T: serde::Serialize>(
     app.oneshot(request.await).await.unwrap()
 }
 
+/// Helper function to create a request with a body
+/// against a router whose configuration is adjusted by `modify`
+#[allow(dead_code)]
+pub async fn request_with_body_and_config<T: serde::Serialize>(
+    modify: impl FnOnce(&mut Config),
+    method: Method,
+    uri: &str,
+    payload: &T,
+) -> Response<Body> {
+    let json_payload = serde_json::to_string(payload).expect("Failed to serialize payload");
+    let request = get_request_with_body(method, uri, &json_payload);
+    let app = create_test_router_with_config(modify).await;
+
+    app.oneshot(request.await).await.unwrap()
+}
+
+/// Helper function to create a request with a body and extra headers
+#[allow(dead_code)]
+pub async fn request_with_body_and_headers<T: serde::Serialize>(
+    method: Method,
+    uri: &str,
+    headers: &[(&str, &str)],
+    payload: &T,
+) -> Response<Body> {
+    let json_payload = serde_json::to_string(payload).expect("Failed to serialize payload");
+    let mut request = get_request_with_body(method, uri, &json_payload).await;
+    for (name, value) in headers {
+        request.headers_mut().insert(
+            axum::http::HeaderName::from_bytes(name.as_bytes()).unwrap(),
+            value.parse().unwrap(),
+        );
+    }
+    let app = create_test_router().await;
+
+    app.oneshot(request).await.unwrap()
+}
+
+/// Helper function to create a request with authentication, extra headers and a body
+#[allow(dead_code)]
+pub async fn request_with_auth_and_headers<T: serde::Serialize>(
+    method: Method,
+    uri: &str,
+    headers: &[(&str, &str)],
+    payload: &T,
+) -> Response<Body> {
+    let token = get_authentication_token().await;
+    let mut headers = headers.to_vec();
+    headers.push(("authorization", token.as_str()));
+
+    request_with_body_and_headers(method, uri, &headers, payload).await
+}
+
 /// Helper function to create a request with authentication
 #[allow(dead_code)]
 pub async fn request_with_auth(method: Method, uri: &str) -> Response<Body> {
@@ -155,6 +253,15 @@ pub async fn request_with_auth(method: Method, uri: &str) -> Response<Body> {
     app.oneshot(request.await).await.unwrap()
 }
 
+/// Helper function to create a request with a given authorization header value
+#[allow(dead_code)]
+pub async fn request_with_token(method: Method, uri: &str, token: &str) -> Response<Body> {
+    let request = get_request_with_auth(method, uri, token);
+    let app = create_test_router().await;
+
+    app.oneshot(request.await).await.unwrap()
+}
+
 /// Helper function to create a request with authentication and a body
 #[allow(dead_code)]
 pub async fn request_with_auth_and_body<T: serde::Serialize>(
@@ -170,6 +277,50 @@ pub async fn request_with_auth_and_body<T: serde::Serialize>(
     app.oneshot(request.await).await.unwrap()
 }
 
+/// Helper function to create a request with a given authorization header value and a body
+#[allow(dead_code)]
+pub async fn request_with_token_and_body<T: serde::Serialize>(
+    method: Method,
+    uri: &str,
+    token: &str,
+    payload: &T,
+) -> Response<Body> {
+    let json_payload = serde_json::to_string(payload).expect("Failed to serialize payload");
+    let request = get_request_with_auth_and_body(method, uri, token, &json_payload);
+    let app = create_test_router().await;
+
+    app.oneshot(request.await).await.unwrap()
+}
+
+/// Helper function to create a request with a form-urlencoded body and extra headers
+#[allow(dead_code)]
+pub async fn request_with_form(
+    method: Method,
+    uri: &str,
+    headers: &[(&str, &str)],
+    form: &[(&str, &str)],
+) -> Response<Body> {
+    let payload = url::form_urlencoded::Serializer::new(String::new())
+        .extend_pairs(form)
+        .finish();
+    let mut request = Request::builder()
+        .method(method)
+        .uri(uri.to_string())
+        .header(CONTENT_TYPE, "application/x-www-form-urlencoded")
+        .header(ACCEPT, "application/json")
+        .body(Body::from(payload))
+        .unwrap();
+    for (name, value) in headers {
+        request.headers_mut().insert(
+            axum::http::HeaderName::from_bytes(name.as_bytes()).unwrap(),
+            value.parse().unwrap(),
+        );
+    }
+    let app = create_test_router().await;
+
+    app.oneshot(request).await.unwrap()
+}
+
 /// Helper function to create a request with authentication and multipart data
 #[allow(dead_code)]
 pub async fn request_with_auth_and_multipart(
@@ -184,6 +335,120 @@ pub async fn request_with_auth_and_multipart(
     app.oneshot(request.await).await.unwrap()
 }
 
+/// Helper function to create a request with a given authorization header value and multipart data
+#[allow(dead_code)]
+pub async fn request_with_token_and_multipart(
+    method: Method,
+    uri: &str,
+    token: &str,
+    payload: Vec<u8>,
+) -> Response<Body> {
+    let request = get_request_with_auth_and_multipart(method, uri, token, payload);
+    let app = create_test_router().await;
+
+    app.oneshot(request.await).await.unwrap()
+}
+
+/// Helper function to create a request with authentication and a raw body of the given type
+#[allow(dead_code)]
+pub async fn request_with_auth_and_raw_body(
+    method: Method,
+    uri: &str,
+    content_type: &str,
+    payload: Vec<u8>,
+) -> Response<Body> {
+    let token = get_authentication_token().await;
+    let request = get_request_with_auth_and_raw_body(method, uri, &token, content_type, payload);
+    let app = create_test_router().await;
+
+    app.oneshot(request.await).await.unwrap()
+}
+
+/// Image uploaded as the profile picture by `create_user_with_file`, read from `tests/asset/`.
+#[allow(dead_code)]
+pub const TEST_IMAGE_FILE: &str = "cat.png";
+
+/// Helper function creates a user with a unique username and email as the test admin
+#[allow(dead_code)]
+pub async fn create_user() -> (CreateUserMultipartDto, UserDto) {
+    create_user_as(&get_authentication_token().await, None).await
+}
+
+/// Helper function creates a user with `TEST_IMAGE_FILE` as profile picture as the test admin
+#[allow(dead_code)]
+pub async fn create_user_with_file() -> (CreateUserMultipartDto, UserDto) {
+    create_user_as(&get_authentication_token().await, Some(TEST_IMAGE_FILE)).await
+}
+
+/// Helper function creates a user with a unique username and email through `POST /user`
+/// with the given authorization header value, optionally uploading a profile picture
+/// from `tests/asset/`, and returns the submitted payload with the created user
+#[allow(dead_code)]
+pub async fn create_user_as(
+    token: &str,
+    profile_picture: Option<&str>,
+) -> (CreateUserMultipartDto, UserDto) {
+    let username = format!("testuser-{}", uuid::Uuid::new_v4());
+    let payload = CreateUserMultipartDto {
+        email: format!("{username}@test.com"),
+        username,
+        modified_by: TEST_USER_ID.to_string(),
+        profile_picture: profile_picture.map(str::to_string),
+    };
+
+    let mut multipart_body = Vec::new();
+    for (name, value) in [
+        ("username", &payload.username),
+        ("email", &payload.email),
+        ("modified_by", &payload.modified_by),
+    ] {
+        write!(
+            &mut multipart_body,
+            "------XYZ\r\nContent-Disposition: form-data; name=\"{name}\"\r\n\r\n{value}\r\n"
+        )
+        .unwrap();
+    }
+    if let Some(image_file) = profile_picture {
+        let file_bytes = std::fs::read(format!("tests/asset/{image_file}"))
+            .unwrap_or_else(|_| panic!("Failed to read {image_file} from tests/asset/"));
+        write!(
+            &mut multipart_body,
+            "------XYZ\r\nContent-Disposition: form-data; name=\"profile_picture\"; filename=\"{image_file}\"\r\nContent-Type: image/png\r\n\r\n"
+        )
+        .unwrap();
+        multipart_body.extend_from_slice(&file_bytes);
+        write!(&mut multipart_body, "\r\n").unwrap();
+    }
+    write!(&mut multipart_body, "------XYZ--\r\n").unwrap();
+
+    let response = request_with_token_and_multipart(Method::POST, "/user", token, multipart_body);
+    let (parts, body) = response.await.into_parts();
+    assert_eq!(parts.status, StatusCode::OK);
+
+    let response_body: RestApiResponse<UserDto> = deserialize_json_body(body).await.unwrap();
+    (payload, response_body.0.data.unwrap())
+}
+
+/// Helper function creates an active device owned by `user_id` as the test admin
+#[allow(dead_code)]
+pub async fn create_device_for(user_id: &str) -> DeviceDto {
+    let payload = CreateDeviceDto {
+        name: format!("device-{}", uuid::Uuid::new_v4()),
+        user_id: user_id.to_string(),
+        device_os: DeviceOS::Android,
+        status: DeviceStatus::Active,
+        registered_at: Some(Utc::now()),
+        modified_by: TEST_USER_ID.to_string(),
+    };
+
+    let response = request_with_auth_and_body(Method::POST, "/device", &payload);
+    let (parts, body) = response.await.into_parts();
+    assert_eq!(parts.status, StatusCode::OK);
+
+    let response_body: RestApiResponse<DeviceDto> = deserialize_json_body(body).await.unwrap();
+    response_body.0.data.unwrap()
+}
+
 /// internal helper functions to create requests
 async fn get_request(method: Method, uri: &str) -> Request<Body> {
     Request::builder()
@@ -241,11 +506,22 @@ async fn get_request_with_auth_and_multipart(
     uri: &str,
     token: &str,
     payload: Vec<u8>,
+) -> Request<Body> {
+    let content_type = "multipart/form-data; boundary=----XYZ";
+    get_request_with_auth_and_raw_body(method, uri, token, content_type, payload).await
+}
+
+async fn get_request_with_auth_and_raw_body(
+    method: Method,
+    uri: &str,
+    token: &str,
+    content_type: &str,
+    payload: Vec<u8>,
 ) -> Request<Body> {
     Request::builder()
         .method(method)
         .uri(uri.to_string())
-        .header(CONTENT_TYPE, "multipart/form-data; boundary=----XYZ")
+        .header(CONTENT_TYPE, content_type)
         .header(AUTHORIZATION, token)
         .header(ACCEPT, "application/json")
         .body(Body::from(payload))
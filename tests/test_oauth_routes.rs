@@ -0,0 +1,469 @@
+use axum::http::{header::LOCATION, Method, StatusCode};
+use base64::{
+    engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD},
+    Engine,
+};
+use sha2::{Digest, Sha256};
+
+use clean_axum_demo::{
+    common::dto::RestApiResponse,
+    domains::oauth::dto::oauth_dto::{
+        CreateOAuthClientDto, CreatedOAuthClientDto, OAuthClientDto, OAuthErrorDto,
+        TokenResponseDto,
+    },
+};
+use test_helpers::{
+    create_user, deserialize_json_body, get_bearer_token, request_with_form, request_with_token,
+    request_with_token_and_body, TEST_CLIENT_ID, TEST_CLIENT_SECRET, TEST_NON_ADMIN_CLIENT_ID,
+    TEST_USER_ID,
+};
+
+mod test_helpers;
+
+const REDIRECT_URI: &str = "https://client.example.com/callback";
+
+/// Registers a client as the admin and returns it with its secret.
+/// Confidential clients act as `TEST_USER_ID`.
+async fn create_client(
+    grant_types: &[&str],
+    scopes: &[&str],
+    confidential: bool,
+) -> CreatedOAuthClientDto {
+    let user_id = confidential.then_some(TEST_USER_ID);
+    create_client_for(grant_types, scopes, confidential, user_id).await
+}
+
+/// Registers a client that acts as the given user for `client_credentials`.
+async fn create_client_for(
+    grant_types: &[&str],
+    scopes: &[&str],
+    confidential: bool,
+    user_id: Option<&str>,
+) -> CreatedOAuthClientDto {
+    let token = get_bearer_token(TEST_CLIENT_ID, TEST_CLIENT_SECRET).await;
+    let payload = CreateOAuthClientDto {
+        name: "integration".to_string(),
+        redirect_uris: vec![REDIRECT_URI.to_string()],
+        grant_types: grant_types.iter().map(|g| g.to_string()).collect(),
+        scopes: scopes.iter().map(|s| s.to_string()).collect(),
+        confidential,
+        user_id: user_id.map(str::to_string),
+    };
+
+    let response = request_with_token_and_body(Method::POST, "/oauth/clients", &token, &payload);
+    let (parts, body) = response.await.into_parts();
+    assert_eq!(parts.status, StatusCode::OK);
+
+    let response_body: RestApiResponse<CreatedOAuthClientDto> =
+        deserialize_json_body(body).await.unwrap();
+    response_body.0.data.unwrap()
+}
+
+fn basic_auth(client: &CreatedOAuthClientDto) -> String {
+    let credentials = format!(
+        "{}:{}",
+        client.client.client_id,
+        client.client_secret.as_deref().unwrap()
+    );
+    format!("Basic {}", STANDARD.encode(credentials))
+}
+
+async fn token_ok(headers: &[(&str, &str)], form: &[(&str, &str)]) -> TokenResponseDto {
+    let response = request_with_form(Method::POST, "/oauth/token", headers, form);
+    let (parts, body) = response.await.into_parts();
+    assert_eq!(parts.status, StatusCode::OK);
+    assert_eq!(parts.headers.get("cache-control").unwrap(), "no-store");
+
+    deserialize_json_body(body).await.unwrap()
+}
+
+async fn token_error(
+    headers: &[(&str, &str)],
+    form: &[(&str, &str)],
+    status: StatusCode,
+) -> String {
+    let response = request_with_form(Method::POST, "/oauth/token", headers, form);
+    let (parts, body) = response.await.into_parts();
+    assert_eq!(parts.status, status);
+
+    let error: OAuthErrorDto = deserialize_json_body(body).await.unwrap();
+    error.error
+}
+
+#[tokio::test]
+async fn test_client_credentials_grant() {
+    let client = create_client(&["client_credentials"], &["device:read", "user:read"], true).await;
+    let auth = basic_auth(&client);
+
+    let token = token_ok(
+        &[("authorization", &auth)],
+        &[
+            ("grant_type", "client_credentials"),
+            ("scope", "device:read"),
+        ],
+    )
+    .await;
+    assert_eq!(token.token_type, "Bearer");
+    assert_eq!(token.scope.as_deref(), Some("device:read"));
+    assert!(token.refresh_token.is_none());
+
+    // The token acts as the configured user, limited to the granted scope.
+    let bearer = format!("Bearer {}", token.access_token);
+    let response = request_with_token(Method::GET, "/device", &bearer);
+    assert_eq!(response.await.status(), StatusCode::OK);
+
+    let response = request_with_token(Method::GET, "/user", &bearer);
+    assert_eq!(response.await.status(), StatusCode::FORBIDDEN);
+
+    // Credentials may also be sent in the form, but not in both places.
+    let secret = client.client_secret.clone().unwrap();
+    token_ok(
+        &[],
+        &[
+            ("grant_type", "client_credentials"),
+            ("client_id", &client.client.client_id),
+            ("client_secret", &secret),
+        ],
+    )
+    .await;
+
+    let error = token_error(
+        &[("authorization", &auth)],
+        &[
+            ("grant_type", "client_credentials"),
+            ("client_secret", &secret),
+        ],
+        StatusCode::BAD_REQUEST,
+    )
+    .await;
+    assert_eq!(error, "invalid_request");
+}
+
+#[tokio::test]
+async fn test_client_credentials_grant_of_deleted_user() {
+    let (_, user) = create_user().await;
+    let client = create_client_for(
+        &["client_credentials"],
+        &["device:read"],
+        true,
+        Some(&user.id),
+    )
+    .await;
+    let auth = basic_auth(&client);
+    let form = [("grant_type", "client_credentials")];
+
+    token_ok(&[("authorization", &auth)], &form).await;
+
+    let token = get_bearer_token(TEST_CLIENT_ID, TEST_CLIENT_SECRET).await;
+    let uri = format!("/user/{}", user.id);
+    let response = request_with_token(Method::DELETE, &uri, &token);
+    assert_eq!(response.await.status(), StatusCode::OK);
+
+    // A soft-deleted user no longer backs the client's tokens.
+    let error = token_error(&[("authorization", &auth)], &form, StatusCode::BAD_REQUEST).await;
+    assert_eq!(error, "invalid_grant");
+}
+
+#[tokio::test]
+async fn test_token_errors() {
+    let client = create_client(&["client_credentials"], &["device:read"], true).await;
+    let auth = basic_auth(&client);
+
+    let wrong_secret = format!(
+        "Basic {}",
+        STANDARD.encode(format!("{}:wrong", client.client.client_id))
+    );
+    let response = request_with_form(
+        Method::POST,
+        "/oauth/token",
+        &[("authorization", &wrong_secret)],
+        &[("grant_type", "client_credentials")],
+    )
+    .await;
+    let (parts, body) = response.into_parts();
+    assert_eq!(parts.status, StatusCode::UNAUTHORIZED);
+    assert!(parts.headers.contains_key("www-authenticate"));
+    let error: OAuthErrorDto = deserialize_json_body(body).await.unwrap();
+    assert_eq!(error.error, "invalid_client");
+
+    let error = token_error(
+        &[("authorization", &auth)],
+        &[("grant_type", "device_code")],
+        StatusCode::BAD_REQUEST,
+    )
+    .await;
+    assert_eq!(error, "unsupported_grant_type");
+
+    let error = token_error(
+        &[("authorization", &auth)],
+        &[
+            ("grant_type", "password"),
+            ("username", TEST_NON_ADMIN_CLIENT_ID),
+            ("password", TEST_CLIENT_SECRET),
+        ],
+        StatusCode::BAD_REQUEST,
+    )
+    .await;
+    assert_eq!(error, "unauthorized_client");
+
+    let error = token_error(
+        &[("authorization", &auth)],
+        &[
+            ("grant_type", "client_credentials"),
+            ("scope", "user:delete"),
+        ],
+        StatusCode::BAD_REQUEST,
+    )
+    .await;
+    assert_eq!(error, "invalid_scope");
+}
+
+#[tokio::test]
+async fn test_password_and_refresh_token_grants() {
+    let client = create_client(
+        &["password", "refresh_token"],
+        &["device:read", "user:read"],
+        true,
+    )
+    .await;
+    let auth = basic_auth(&client);
+
+    let token = token_ok(
+        &[("authorization", &auth)],
+        &[
+            ("grant_type", "password"),
+            ("username", TEST_NON_ADMIN_CLIENT_ID),
+            ("password", TEST_CLIENT_SECRET),
+            ("scope", "device:read"),
+        ],
+    )
+    .await;
+    let refresh_token = token.refresh_token.unwrap();
+
+    let error = token_error(
+        &[("authorization", &auth)],
+        &[
+            ("grant_type", "password"),
+            ("username", TEST_NON_ADMIN_CLIENT_ID),
+            ("password", "wrong_password"),
+        ],
+        StatusCode::BAD_REQUEST,
+    )
+    .await;
+    assert_eq!(error, "invalid_grant");
+
+    // Refreshing keeps the scope of the session.
+    let refreshed = token_ok(
+        &[("authorization", &auth)],
+        &[
+            ("grant_type", "refresh_token"),
+            ("refresh_token", &refresh_token),
+        ],
+    )
+    .await;
+    let bearer = format!("Bearer {}", refreshed.access_token);
+    let response = request_with_token(Method::GET, "/device", &bearer);
+    assert_eq!(response.await.status(), StatusCode::OK);
+    let response = request_with_token(Method::GET, "/user", &bearer);
+    assert_eq!(response.await.status(), StatusCode::FORBIDDEN);
+
+    // Refresh tokens are bound to the client they were issued to.
+    let other = create_client(&["refresh_token"], &["device:read"], true).await;
+    let error = token_error(
+        &[("authorization", &basic_auth(&other))],
+        &[
+            ("grant_type", "refresh_token"),
+            ("refresh_token", refreshed.refresh_token.as_deref().unwrap()),
+        ],
+        StatusCode::BAD_REQUEST,
+    )
+    .await;
+    assert_eq!(error, "invalid_grant");
+}
+
+#[tokio::test]
+async fn test_scoped_token_of_admin_is_not_admin() {
+    let client = create_client(&["password"], &["device:read"], true).await;
+    let token = token_ok(
+        &[("authorization", &basic_auth(&client))],
+        &[
+            ("grant_type", "password"),
+            ("username", TEST_CLIENT_ID),
+            ("password", TEST_CLIENT_SECRET),
+            ("scope", "device:read"),
+        ],
+    )
+    .await;
+    let bearer = format!("Bearer {}", token.access_token);
+
+    // device02-1 is seeded for user02.
+    let response = request_with_token(
+        Method::GET,
+        "/device/00000000-0000-0000-0000-000000000005",
+        &bearer,
+    );
+    assert_eq!(response.await.status(), StatusCode::FORBIDDEN);
+
+    let response = request_with_token(Method::GET, "/device?include_deleted=true", &bearer);
+    assert_eq!(response.await.status(), StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn test_authorization_code_grant_with_pkce() {
+    let client = create_client(
+        &["authorization_code", "refresh_token"],
+        &["device:read"],
+        false,
+    )
+    .await;
+    assert!(client.client_secret.is_none());
+    let client_id = client.client.client_id.clone();
+
+    let verifier = "dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk";
+    let challenge = URL_SAFE_NO_PAD.encode(Sha256::digest(verifier.as_bytes()));
+
+    let user_token = get_bearer_token(TEST_NON_ADMIN_CLIENT_ID, TEST_CLIENT_SECRET).await;
+    let uri = format!(
+        "/oauth/authorize?response_type=code&client_id={client_id}&state=xyz\
+         &code_challenge={challenge}&code_challenge_method=S256"
+    );
+    let response = request_with_token(Method::GET, &uri, &user_token).await;
+    assert_eq!(response.status(), StatusCode::FOUND);
+
+    let location = url::Url::parse(response.headers()[LOCATION].to_str().unwrap()).unwrap();
+    assert!(location.as_str().starts_with(REDIRECT_URI));
+    let params: std::collections::HashMap<_, _> = location.query_pairs().into_owned().collect();
+    assert_eq!(params["state"], "xyz");
+    let code = params["code"].clone();
+
+    let error = token_error(
+        &[],
+        &[
+            ("grant_type", "authorization_code"),
+            ("client_id", &client_id),
+            ("code", &code),
+            ("redirect_uri", REDIRECT_URI),
+            (
+                "code_verifier",
+                "wrong-verifier-wrong-verifier-wrong-verifier",
+            ),
+        ],
+        StatusCode::BAD_REQUEST,
+    )
+    .await;
+    assert_eq!(error, "invalid_grant");
+
+    let form = [
+        ("grant_type", "authorization_code"),
+        ("client_id", client_id.as_str()),
+        ("code", code.as_str()),
+        ("redirect_uri", REDIRECT_URI),
+        ("code_verifier", verifier),
+    ];
+    let token = token_ok(&[], &form).await;
+    assert_eq!(token.scope.as_deref(), Some("device:read"));
+
+    let bearer = format!("Bearer {}", token.access_token);
+    let response = request_with_token(Method::GET, "/device", &bearer);
+    assert_eq!(response.await.status(), StatusCode::OK);
+
+    // Replaying the code fails and revokes the session started with it.
+    let error = token_error(&[], &form, StatusCode::BAD_REQUEST).await;
+    assert_eq!(error, "invalid_grant");
+
+    let error = token_error(
+        &[],
+        &[
+            ("grant_type", "refresh_token"),
+            ("client_id", &client_id),
+            ("refresh_token", token.refresh_token.as_deref().unwrap()),
+        ],
+        StatusCode::BAD_REQUEST,
+    )
+    .await;
+    assert_eq!(error, "invalid_grant");
+}
+
+#[tokio::test]
+async fn test_authorize_errors() {
+    let client = create_client(&["authorization_code"], &["device:read"], false).await;
+    let client_id = client.client.client_id.clone();
+    let user_token = get_bearer_token(TEST_NON_ADMIN_CLIENT_ID, TEST_CLIENT_SECRET).await;
+
+    // Unknown redirect URIs are never redirected to.
+    let uri = format!(
+        "/oauth/authorize?response_type=code&client_id={client_id}\
+         &redirect_uri=https%3A%2F%2Fevil.example.com%2F&code_challenge=abc&code_challenge_method=S256"
+    );
+    let response = request_with_token(Method::GET, &uri, &user_token);
+    assert_eq!(response.await.status(), StatusCode::BAD_REQUEST);
+
+    // Other errors are reported to the client.
+    let uri = format!("/oauth/authorize?response_type=code&client_id={client_id}&state=s1");
+    let response = request_with_token(Method::GET, &uri, &user_token).await;
+    assert_eq!(response.status(), StatusCode::FOUND);
+    let location = response.headers()[LOCATION].to_str().unwrap();
+    assert!(location.contains("error=invalid_request"));
+    assert!(location.contains("state=s1"));
+
+    let uri = format!(
+        "/oauth/authorize?response_type=code&client_id={client_id}&scope=user%3Adelete\
+         &code_challenge=abc&code_challenge_method=S256"
+    );
+    let response = request_with_token(Method::GET, &uri, &user_token).await;
+    let location = response.headers()[LOCATION].to_str().unwrap();
+    assert!(location.contains("error=invalid_scope"));
+}
+
+#[tokio::test]
+async fn test_manage_clients() {
+    let client = create_client(&["client_credentials"], &["device:read"], true).await;
+    let admin_token = get_bearer_token(TEST_CLIENT_ID, TEST_CLIENT_SECRET).await;
+    let user_token = get_bearer_token(TEST_NON_ADMIN_CLIENT_ID, TEST_CLIENT_SECRET).await;
+
+    let response = request_with_token(Method::GET, "/oauth/clients", &user_token);
+    assert_eq!(response.await.status(), StatusCode::FORBIDDEN);
+
+    let response = request_with_token(Method::GET, "/oauth/clients", &admin_token);
+    let (parts, body) = response.await.into_parts();
+    assert_eq!(parts.status, StatusCode::OK);
+    let response_body: RestApiResponse<Vec<OAuthClientDto>> =
+        deserialize_json_body(body).await.unwrap();
+    let listed = response_body
+        .0
+        .data
+        .unwrap()
+        .into_iter()
+        .find(|c| c.client_id == client.client.client_id)
+        .unwrap();
+    assert!(listed.confidential);
+
+    // Registrations are validated.
+    let payload = CreateOAuthClientDto {
+        name: "invalid".to_string(),
+        redirect_uris: vec!["https://client.example.com/#fragment".to_string()],
+        grant_types: vec!["authorization_code".to_string(), "implicit".to_string()],
+        scopes: vec!["device:read".to_string()],
+        confidential: false,
+        user_id: None,
+    };
+    let response =
+        request_with_token_and_body(Method::POST, "/oauth/clients", &admin_token, &payload);
+    assert_eq!(response.await.status(), StatusCode::BAD_REQUEST);
+
+    // Deleting the client invalidates its credentials.
+    let uri = format!("/oauth/clients/{}", client.client.client_id);
+    let response = request_with_token(Method::DELETE, &uri, &admin_token);
+    assert_eq!(response.await.status(), StatusCode::OK);
+
+    let response = request_with_token(Method::DELETE, &uri, &admin_token);
+    assert_eq!(response.await.status(), StatusCode::NOT_FOUND);
+
+    let error = token_error(
+        &[("authorization", &basic_auth(&client))],
+        &[("grant_type", "client_credentials")],
+        StatusCode::UNAUTHORIZED,
+    )
+    .await;
+    assert_eq!(error, "invalid_client");
+}
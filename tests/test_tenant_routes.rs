@@ -0,0 +1,244 @@
+use axum::http::{Method, StatusCode};
+use chrono::Utc;
+
+use clean_axum_demo::{
+    common::{dto::RestApiResponse, pagination::Paginated, tenant::DEFAULT_ORGANIZATION_ID},
+    domains::{
+        auth::dto::auth_dto::{AuthEventDto, AuthUserDto, CreateApiKeyDto, CreatedApiKeyDto},
+        device::{dto::device_dto::CreateDeviceDto, DeviceOS, DeviceStatus},
+        user::dto::user_dto::UserDto,
+    },
+};
+
+mod test_helpers;
+
+use test_helpers::{
+    create_user, create_user_as, deserialize_json_body, get_bearer_token, request_with_token,
+    request_with_token_and_body, setup_test_db, TEST_CLIENT_ID, TEST_CLIENT_SECRET,
+    TEST_IMAGE_FILE, TEST_USER_ID,
+};
+
+/// Second organization of the seed data, with its own admin and user.
+const ACME_ORG_ID: &str = "00000000-0000-0000-0000-000000000002";
+const ACME_ADMIN_CLIENT_ID: &str = "acmeadmin01";
+const ACME_USER_ID: &str = "00000000-0000-0000-0000-000000000023";
+const ACME_DEVICE_ID: &str = "00000000-0000-0000-0000-000000000081";
+/// Device of the default organization, owned by `TEST_USER_ID`.
+const DEFAULT_DEVICE_ID: &str = "00000000-0000-0000-0000-000000000001";
+
+async fn acme_token() -> String {
+    get_bearer_token(ACME_ADMIN_CLIENT_ID, TEST_CLIENT_SECRET).await
+}
+
+async fn default_token() -> String {
+    get_bearer_token(TEST_CLIENT_ID, TEST_CLIENT_SECRET).await
+}
+
+async fn status_of(method: Method, uri: &str, token: &str) -> StatusCode {
+    request_with_token(method, uri, token).await.status()
+}
+
+#[tokio::test]
+async fn test_cross_tenant_users_are_not_found() {
+    let acme = acme_token().await;
+    let default = default_token().await;
+
+    let uri = format!("/user/{TEST_USER_ID}");
+    assert_eq!(
+        status_of(Method::GET, &uri, &acme).await,
+        StatusCode::NOT_FOUND
+    );
+    assert_eq!(status_of(Method::GET, &uri, &default).await, StatusCode::OK);
+
+    let uri = format!("/user/{ACME_USER_ID}");
+    assert_eq!(
+        status_of(Method::GET, &uri, &default).await,
+        StatusCode::NOT_FOUND
+    );
+
+    let response = request_with_token(Method::GET, &uri, &acme);
+    let (parts, body) = response.await.into_parts();
+    assert_eq!(parts.status, StatusCode::OK);
+    let response_body: RestApiResponse<UserDto> = deserialize_json_body(body).await.unwrap();
+    assert_eq!(response_body.0.data.unwrap().org_id, ACME_ORG_ID);
+}
+
+#[tokio::test]
+async fn test_user_list_is_scoped_to_tenant() {
+    let acme = acme_token().await;
+
+    let response = request_with_token(Method::GET, "/user?size=100", &acme);
+    let (parts, body) = response.await.into_parts();
+    assert_eq!(parts.status, StatusCode::OK);
+
+    let response_body: RestApiResponse<Paginated<UserDto>> =
+        deserialize_json_body(body).await.unwrap();
+    let page = response_body.0.data.unwrap();
+    assert!(page.items.iter().any(|user| user.id == ACME_USER_ID));
+    assert!(page.items.iter().all(|user| user.org_id == ACME_ORG_ID));
+    assert_eq!(page.total as usize, page.items.len());
+}
+
+#[tokio::test]
+async fn test_cross_tenant_devices_are_not_found() {
+    let acme = acme_token().await;
+    let default = default_token().await;
+
+    let uri = format!("/device/{DEFAULT_DEVICE_ID}");
+    assert_eq!(
+        status_of(Method::GET, &uri, &acme).await,
+        StatusCode::NOT_FOUND
+    );
+
+    let uri = format!("/device/{ACME_DEVICE_ID}");
+    assert_eq!(
+        status_of(Method::GET, &uri, &default).await,
+        StatusCode::NOT_FOUND
+    );
+    assert_eq!(status_of(Method::GET, &uri, &acme).await, StatusCode::OK);
+
+    // Devices cannot be created for the users of another organization.
+    let payload = CreateDeviceDto {
+        name: format!("tenant-device-{}", uuid::Uuid::new_v4()),
+        user_id: TEST_USER_ID.to_string(),
+        device_os: DeviceOS::Android,
+        status: DeviceStatus::Active,
+        registered_at: Some(Utc::now()),
+        modified_by: TEST_USER_ID.to_string(),
+    };
+    let response = request_with_token_and_body(Method::POST, "/device", &acme, &payload);
+    assert_eq!(response.await.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_cross_tenant_files_are_not_found() {
+    let acme = acme_token().await;
+    let default = default_token().await;
+
+    let user = create_user_as(&acme, Some(TEST_IMAGE_FILE)).await.1;
+    assert_eq!(user.org_id, ACME_ORG_ID);
+    let file_id = user.file_id.unwrap();
+
+    let uri = format!("/file/{file_id}");
+    assert_eq!(status_of(Method::GET, &uri, &acme).await, StatusCode::OK);
+
+    let pool = setup_test_db().await.unwrap();
+    let file_url: String = sqlx::query_scalar("SELECT file_url FROM uploaded_files WHERE id = $1")
+        .bind(&file_id)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert!(file_url.starts_with(&format!("/assets/private/{ACME_ORG_ID}/profile_picture/")));
+
+    // The file is stored in the directory of its organization and only served to it.
+    assert_eq!(
+        status_of(Method::GET, &file_url, &acme).await,
+        StatusCode::OK
+    );
+    assert_eq!(
+        status_of(Method::GET, &file_url, &default).await,
+        StatusCode::NOT_FOUND
+    );
+
+    assert_eq!(
+        status_of(Method::GET, &uri, &default).await,
+        StatusCode::NOT_FOUND
+    );
+    assert_eq!(
+        status_of(Method::DELETE, &uri, &default).await,
+        StatusCode::NOT_FOUND
+    );
+
+    let uri = format!("/assets/private/{DEFAULT_ORGANIZATION_ID}/profile_picture/images.jpeg");
+    assert_eq!(
+        status_of(Method::GET, &uri, &acme).await,
+        StatusCode::NOT_FOUND
+    );
+    assert_eq!(status_of(Method::GET, &uri, &default).await, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_cross_tenant_api_keys_are_not_found() {
+    let acme = acme_token().await;
+    let default = default_token().await;
+
+    let payload = CreateApiKeyDto {
+        name: "tenant".to_string(),
+        scopes: vec!["user:read".to_string()],
+        expires_in: None,
+    };
+    let response = request_with_token_and_body(Method::POST, "/auth/api-keys", &default, &payload);
+    let (parts, body) = response.await.into_parts();
+    assert_eq!(parts.status, StatusCode::OK);
+    let response_body: RestApiResponse<CreatedApiKeyDto> =
+        deserialize_json_body(body).await.unwrap();
+    let created = response_body.0.data.unwrap();
+
+    let uri = format!("/auth/api-keys/{}", created.id);
+    assert_eq!(
+        status_of(Method::DELETE, &uri, &acme).await,
+        StatusCode::NOT_FOUND
+    );
+
+    // The key still works until its own organization revokes it.
+    let api_key = format!("ApiKey {}", created.api_key);
+    assert_eq!(
+        status_of(Method::GET, "/user", &api_key).await,
+        StatusCode::OK
+    );
+    assert_eq!(
+        status_of(Method::DELETE, &uri, &default).await,
+        StatusCode::OK
+    );
+}
+
+#[tokio::test]
+async fn test_auth_events_are_scoped_to_tenant() {
+    let acme = acme_token().await;
+    let default = default_token().await;
+
+    let uri = format!("/auth/events?user_id={TEST_USER_ID}");
+    assert_eq!(
+        status_of(Method::GET, &uri, &acme).await,
+        StatusCode::NOT_FOUND
+    );
+
+    let uri = format!("/auth/events?user_id={ACME_USER_ID}");
+    assert_eq!(
+        status_of(Method::GET, &uri, &default).await,
+        StatusCode::NOT_FOUND
+    );
+
+    // The unfiltered trail only holds the events of the caller's organization.
+    let response = request_with_token(Method::GET, "/auth/events?limit=1000", &acme);
+    let (parts, body) = response.await.into_parts();
+    assert_eq!(parts.status, StatusCode::OK);
+    let response_body: RestApiResponse<Vec<AuthEventDto>> =
+        deserialize_json_body(body).await.unwrap();
+    let events = response_body.0.data.unwrap();
+    assert!(events
+        .iter()
+        .any(|event| event.username.as_deref() == Some(ACME_ADMIN_CLIENT_ID)));
+    assert!(events
+        .iter()
+        .all(|event| event.username.as_deref() != Some(TEST_CLIENT_ID)));
+}
+
+#[tokio::test]
+async fn test_cross_tenant_credentials_are_not_found() {
+    let acme = acme_token().await;
+    let default = default_token().await;
+
+    let (_, user) = create_user().await;
+    let payload = AuthUserDto {
+        user_id: user.id.clone(),
+        password: "Str0ng!Passw0rd".to_string(),
+    };
+
+    let response = request_with_token_and_body(Method::POST, "/auth/credentials", &acme, &payload);
+    assert_eq!(response.await.status(), StatusCode::NOT_FOUND);
+
+    let response =
+        request_with_token_and_body(Method::POST, "/auth/credentials", &default, &payload);
+    assert_eq!(response.await.status(), StatusCode::OK);
+}
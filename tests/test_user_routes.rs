@@ -1,50 +1,38 @@
 use axum::http::{Method, StatusCode};
+use chrono::Utc;
+use serde_json::json;
 
 use clean_axum_demo::{
-    common::{dto::RestApiResponse, error::AppError},
-    domains::user::dto::user_dto::{CreateUserMultipartDto, SearchUserDto, UpdateUserDto, UserDto},
+    common::{
+        bootstrap::purge_deleted_records, config::Config, dto::RestApiResponse,
+        pagination::Paginated,
+    },
+    domains::{
+        auth::dto::auth_dto::{
+            AuthUserDto, CreateApiKeyDto, ForgotPasswordDto, MagicLinkRequestDto,
+        },
+        device::dto::device_dto::DeviceDto,
+        user::dto::user_dto::{
+            DeleteUserSummaryDto, ImportRowStatus, ImportUsersReportDto, SearchUserDto,
+            UpdateUserDto, UserDto,
+        },
+    },
 };
 
 mod test_helpers;
 
 use test_helpers::{
-    deserialize_json_body, request_with_auth, request_with_auth_and_body,
-    request_with_auth_and_multipart, TEST_USER_ID,
+    create_device_for, create_test_state, create_user, create_user_with_file,
+    deserialize_json_body, get_bearer_token, request_with_auth, request_with_auth_and_body,
+    request_with_auth_and_headers, request_with_auth_and_multipart, request_with_auth_and_raw_body,
+    request_with_body, request_with_token, request_with_token_and_body, setup_test_db,
+    TEST_CLIENT_SECRET, TEST_IMAGE_FILE, TEST_NON_ADMIN_CLIENT_ID, TEST_OTHER_USER_ID,
+    TEST_USER_ID,
 };
 
-async fn create_user() -> Result<(CreateUserMultipartDto, UserDto), AppError> {
-    let username = format!("testuser-{}", uuid::Uuid::new_v4()).to_string();
-    let email = format!("{}@test.com", username).to_string();
-
-    let payload = CreateUserMultipartDto {
-        username,
-        email,
-        modified_by: TEST_USER_ID.to_string(),
-        profile_picture: None,
-    };
-
-    let multipart_body = format!(
-        "------XYZ\r\nContent-Disposition: form-data; name=\"username\"\r\n\r\n{}\r\n------XYZ\r\nContent-Disposition: form-data; name=\"email\"\r\n\r\n{}\r\n------XYZ\r\nContent-Disposition: form-data; name=\"modified_by\"\r\n\r\n{}\r\n------XYZ--\r\n",
-        payload.username, payload.email, payload.modified_by
-    ).as_bytes().to_vec();
-
-    let response = request_with_auth_and_multipart(Method::POST, "/user", multipart_body);
-
-    let (parts, body) = response.await.into_parts();
-
-    assert_eq!(parts.status, StatusCode::OK);
-
-    let response_body: RestApiResponse<UserDto> = deserialize_json_body(body).await.unwrap();
-
-    assert_eq!(response_body.0.status, StatusCode::OK);
-    let user_dto = response_body.0.data.unwrap();
-
-    Ok((payload, user_dto))
-}
-
 #[tokio::test]
 async fn test_create_user() {
-    let created = create_user().await.expect("Failed to create user");
+    let created = create_user().await;
 
     let payload = created.0;
     let user_dto = created.1;
@@ -57,89 +45,18 @@ async fn test_create_user() {
     assert!(user_dto.file_id.is_none());
 }
 
-async fn create_user_with_file() -> Result<(CreateUserMultipartDto, UserDto, String), AppError> {
-    let username = format!("testuser-{}", uuid::Uuid::new_v4()).to_string();
-    let email = format!("{}@test.com", username).to_string();
-
-    let image_file = "cat.png";
-
-    let payload = CreateUserMultipartDto {
-        username,
-        email,
-        modified_by: TEST_USER_ID.to_string(),
-        // Indicate the file name being uploaded
-        profile_picture: Some(image_file.to_string()),
-    };
-
-    // Read the image file from the test/asset/ directory
-    let file_path = format!("tests/asset/{}", image_file);
-    let file_bytes = std::fs::read(file_path)
-        .unwrap_or_else(|_| panic!("Failed to read {} from tests/asset/", image_file));
-
-    // Build the multipart body as a byte vector (Vec<u8>)
-    let mut multipart_body = Vec::new();
-    use std::io::Write;
-    // Add the username part
-    write!(
-        &mut multipart_body,
-        "------XYZ\r\nContent-Disposition: form-data; name=\"username\"\r\n\r\n{}\r\n",
-        payload.username
-    )
-    .unwrap();
-    // Add the email part
-    write!(
-        &mut multipart_body,
-        "------XYZ\r\nContent-Disposition: form-data; name=\"email\"\r\n\r\n{}\r\n",
-        payload.email
-    )
-    .unwrap();
-    // Add the modified_by part
-    write!(
-        &mut multipart_body,
-        "------XYZ\r\nContent-Disposition: form-data; name=\"modified_by\"\r\n\r\n{}\r\n",
-        payload.modified_by
-    )
-    .unwrap();
-    // Add the file part for profile_picture
-    write!(
-        &mut multipart_body,
-        "------XYZ\r\nContent-Disposition: form-data; name=\"profile_picture\"; filename=\"{}\"\r\nContent-Type: image/png\r\n\r\n",
-        image_file
-    ).unwrap();
-    multipart_body.extend_from_slice(&file_bytes);
-    write!(&mut multipart_body, "\r\n").unwrap();
-    // Add the final boundary
-    write!(&mut multipart_body, "------XYZ--\r\n").unwrap();
-
-    let response = request_with_auth_and_multipart(Method::POST, "/user", multipart_body);
-
-    let (parts, body) = response.await.into_parts();
-
-    assert_eq!(parts.status, StatusCode::OK);
-
-    let response_body: RestApiResponse<UserDto> = deserialize_json_body(body).await.unwrap();
-
-    assert_eq!(response_body.0.status, StatusCode::OK);
-    let user_dto = response_body.0.data.unwrap();
-
-    Ok((payload, user_dto, image_file.to_string()))
-}
-
 #[tokio::test]
 async fn test_create_user_with_file() {
-    let created = create_user_with_file()
-        .await
-        .expect("Failed to create user with file");
+    let created = create_user_with_file().await;
 
     let payload = created.0;
     let user_dto = created.1;
-    let image_file = created.2;
 
     assert!(!user_dto.id.is_empty());
     assert_eq!(user_dto.username, payload.username.clone());
     assert_eq!(user_dto.email, Some(payload.email.clone()));
     assert_ne!(user_dto.modified_by, Some(payload.modified_by.clone()));
-    assert_eq!(user_dto.origin_file_name, Some(image_file.to_string()));
+    assert_eq!(user_dto.origin_file_name, Some(TEST_IMAGE_FILE.to_string()));
     assert!(!user_dto.file_id.clone().unwrap_or_default().is_empty());
 }
 
@@ -151,11 +68,12 @@ async fn test_get_users() {
 
     assert_eq!(parts.status, StatusCode::OK);
 
-    let response_body: RestApiResponse<Vec<UserDto>> = deserialize_json_body(body).await.unwrap();
+    let response_body: RestApiResponse<Paginated<UserDto>> =
+        deserialize_json_body(body).await.unwrap();
 
     assert_eq!(response_body.0.status, StatusCode::OK);
 
-    let user_dtos = response_body.0.data.unwrap();
+    let user_dtos = response_body.0.data.unwrap().items;
 
     // println!("user_dtos: {:?}", user_dtos);
     assert!(!user_dtos.is_empty());
@@ -167,8 +85,7 @@ async fn test_get_user_list() {
 
     let payload = SearchUserDto {
         username: Some(username),
-        id: None,
-        email: None,
+        ..Default::default()
     };
 
     let response = request_with_auth_and_body(Method::POST, "/user/list", &payload);
@@ -177,19 +94,134 @@ async fn test_get_user_list() {
 
     assert_eq!(parts.status, StatusCode::OK);
 
-    let response_body: RestApiResponse<Vec<UserDto>> = deserialize_json_body(body).await.unwrap();
+    let response_body: RestApiResponse<Paginated<UserDto>> =
+        deserialize_json_body(body).await.unwrap();
 
     assert_eq!(response_body.0.status, StatusCode::OK);
 
-    let user_dtos = response_body.0.data.unwrap();
+    let user_dtos = response_body.0.data.unwrap().items;
 
     // println!("user_dtos: {:?}", user_dtos);
     assert!(!user_dtos.is_empty());
 }
 
+async fn get_users_page(query: &str) -> Paginated<UserDto> {
+    let uri = format!("/user?{query}");
+    let response = request_with_auth(Method::GET, &uri);
+    let (parts, body) = response.await.into_parts();
+    assert_eq!(parts.status, StatusCode::OK);
+
+    let response_body: RestApiResponse<Paginated<UserDto>> =
+        deserialize_json_body(body).await.unwrap();
+    response_body.0.data.unwrap()
+}
+
+#[tokio::test]
+async fn test_get_users_paginated() {
+    let ids = |page: &Paginated<UserDto>| -> Vec<String> {
+        page.items.iter().map(|user| user.id.clone()).collect()
+    };
+
+    // The seeded users come first in the stable ordering, so both modes agree on them.
+    let first = get_users_page("size=5").await;
+    assert_eq!(first.items.len(), 5);
+    assert_eq!(first.size, 5);
+    assert!(first.total >= 20);
+    assert_eq!(ids(&first), ids(&get_users_page("page=1&size=5").await));
+
+    let cursor = first.next_cursor.clone().unwrap();
+    let second = get_users_page(&format!("size=5&cursor={cursor}")).await;
+    assert_eq!(ids(&second), ids(&get_users_page("page=2&size=5").await));
+    assert!(ids(&second).iter().all(|id| !ids(&first).contains(id)));
+
+    // The last page has no cursor.
+    let last = get_users_page(&format!("page={}&size=100", first.total / 100 + 1)).await;
+    assert!(last.next_cursor.is_none());
+
+    for query in ["size=0", "size=101", "page=0", "cursor=not-a-cursor"] {
+        let uri = format!("/user?{query}");
+        let response = request_with_auth(Method::GET, &uri);
+        assert_eq!(response.await.status(), StatusCode::BAD_REQUEST, "{query}");
+    }
+    let uri = format!("/user?page=2&cursor={cursor}");
+    let response = request_with_auth(Method::GET, &uri);
+    assert_eq!(response.await.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_get_users_filtered_and_sorted() {
+    let usernames = |page: &Paginated<UserDto>| -> Vec<String> {
+        page.items
+            .iter()
+            .map(|user| user.username.clone())
+            .collect()
+    };
+
+    // Text filters match case-insensitively; sort keys take a direction.
+    let page = get_users_page("username=USER0&sort=-username&size=100").await;
+    assert_eq!(page.total, 9);
+    assert_eq!(usernames(&page)[0], "user09");
+    assert!(usernames(&page).windows(2).all(|pair| pair[0] > pair[1]));
+
+    let page = get_users_page("email=User01%40Example").await;
+    assert_eq!(usernames(&page), vec!["user01".to_string()]);
+
+    // Wildcards are matched literally.
+    assert_eq!(get_users_page("username=%25").await.total, 0);
+
+    // Cursors continue in the requested order.
+    let first = get_users_page("username=user&sort=-username&size=5").await;
+    let cursor = first.next_cursor.clone().unwrap();
+    let second = get_users_page(&format!(
+        "username=user&sort=-username&size=5&cursor={cursor}"
+    ))
+    .await;
+    let last_seen = usernames(&first).pop().unwrap();
+    assert_eq!(second.items.len(), 5);
+    assert!(usernames(&second)
+        .iter()
+        .all(|username| *username < last_seen));
+
+    // Date ranges and the profile picture filter.
+    let (_, user) = create_user_with_file().await;
+    let created_at = user
+        .created_at
+        .unwrap()
+        .to_rfc3339_opts(chrono::SecondsFormat::Micros, true);
+    let page = get_users_page(&format!(
+        "created_from={created_at}&has_profile_picture=true&size=100"
+    ))
+    .await;
+    assert!(page.items.iter().any(|found| found.id == user.id));
+    assert!(page.items.iter().all(|found| found.file_id.is_some()));
+
+    let page = get_users_page(&format!(
+        "created_to={created_at}&has_profile_picture=false"
+    ))
+    .await;
+    assert!(page.items.iter().all(|found| found.file_id.is_none()));
+
+    for query in [
+        "sort=password",
+        "sort=username,-username",
+        "created_from=2030-01-01T00:00:00Z&created_to=2020-01-01T00:00:00Z",
+        "created_from=yesterday",
+        "has_profile_picture=maybe",
+    ] {
+        let uri = format!("/user?{query}");
+        let response = request_with_auth(Method::GET, &uri);
+        assert_eq!(response.await.status(), StatusCode::BAD_REQUEST, "{query}");
+    }
+
+    // A cursor only continues the ordering it was created for.
+    let uri = format!("/user?sort=username&cursor={cursor}");
+    let response = request_with_auth(Method::GET, &uri);
+    assert_eq!(response.await.status(), StatusCode::BAD_REQUEST);
+}
+
 #[tokio::test]
 async fn test_get_user_by_id() {
-    let created = create_user().await.expect("Failed to create user");
+    let created = create_user().await;
 
     let existent_user = created.1;
     let existent_id = existent_user.id;
@@ -219,7 +251,7 @@ async fn test_get_user_by_id() {
 
 #[tokio::test]
 async fn test_update_user() {
-    let created = create_user().await.expect("Failed to create user");
+    let created = create_user().await;
 
     let existent_user = created.1;
     let existent_id = existent_user.id;
@@ -251,6 +283,78 @@ async fn test_update_user() {
     assert_eq!(user_dto.email, Some(payload.email));
 }
 
+#[tokio::test]
+async fn test_duplicate_email_conflict() {
+    let (existing, _) = create_user().await;
+    let (_, other) = create_user().await;
+
+    let username = format!("testuser-{}", uuid::Uuid::new_v4());
+    let multipart_body = format!(
+        "------XYZ\r\nContent-Disposition: form-data; name=\"username\"\r\n\r\n{}\r\n------XYZ\r\nContent-Disposition: form-data; name=\"email\"\r\n\r\n{}\r\n------XYZ--\r\n",
+        username,
+        existing.email.to_uppercase()
+    ).as_bytes().to_vec();
+    let response = request_with_auth_and_multipart(Method::POST, "/user", multipart_body);
+    let (parts, body) = response.await.into_parts();
+    assert_eq!(parts.status, StatusCode::CONFLICT);
+    let response_body: RestApiResponse<()> = deserialize_json_body(body).await.unwrap();
+    assert_eq!(
+        response_body.0.message,
+        "Conflict: Email already exists".to_string()
+    );
+
+    let payload = UpdateUserDto {
+        username: other.username,
+        email: existing.email,
+        modified_by: TEST_USER_ID.to_string(),
+    };
+    let url = format!("/user/{}", other.id);
+    let response = request_with_auth_and_body(Method::PUT, url.as_str(), &payload);
+    assert_eq!(response.await.status(), StatusCode::CONFLICT);
+}
+
+#[tokio::test]
+async fn test_update_other_user_forbidden() {
+    let token = get_bearer_token(TEST_NON_ADMIN_CLIENT_ID, TEST_CLIENT_SECRET).await;
+
+    let username = format!("update-testuser-{}", uuid::Uuid::new_v4()).to_string();
+    let email = format!("{}@test.com", username).to_string();
+
+    let payload = UpdateUserDto {
+        username,
+        email,
+        modified_by: TEST_USER_ID.to_string(),
+    };
+
+    let url = format!("/user/{}", TEST_OTHER_USER_ID);
+
+    let response = request_with_token_and_body(Method::PUT, url.as_str(), &token, &payload);
+
+    let (parts, body) = response.await.into_parts();
+
+    assert_eq!(parts.status, StatusCode::FORBIDDEN);
+
+    let response_body: RestApiResponse<()> = deserialize_json_body(body).await.unwrap();
+
+    assert_eq!(response_body.0.status, StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn test_delete_user_forbidden_for_non_admin() {
+    let token = get_bearer_token(TEST_NON_ADMIN_CLIENT_ID, TEST_CLIENT_SECRET).await;
+
+    let url = format!("/user/{}", TEST_OTHER_USER_ID);
+    let response = request_with_token(Method::DELETE, url.as_str(), &token);
+
+    let (parts, body) = response.await.into_parts();
+
+    assert_eq!(parts.status, StatusCode::FORBIDDEN);
+
+    let response_body: RestApiResponse<()> = deserialize_json_body(body).await.unwrap();
+
+    assert_eq!(response_body.0.status, StatusCode::FORBIDDEN);
+}
+
 #[tokio::test]
 async fn test_delete_user_not_found() {
     let non_existent_id = uuid::Uuid::new_v4();
@@ -269,11 +373,90 @@ async fn test_delete_user_not_found() {
     // println!("response_body.0.message: {:?}", response_body.0.message);
 }
 
+/// Sends a JSON Merge Patch for a user with the given `If-Match` header.
+async fn patch_user(
+    id: &str,
+    if_match: Option<&str>,
+    patch: serde_json::Value,
+) -> (StatusCode, Option<String>, Option<UserDto>) {
+    let url = format!("/user/{id}");
+    let mut headers = vec![("content-type", "application/merge-patch+json")];
+    if let Some(if_match) = if_match {
+        headers.push(("if-match", if_match));
+    }
+
+    let response = request_with_auth_and_headers(Method::PATCH, url.as_str(), &headers, &patch);
+    let (parts, body) = response.await.into_parts();
+    let etag = parts
+        .headers
+        .get("etag")
+        .map(|value| value.to_str().unwrap().to_string());
+
+    // Failed validations return the field errors as data.
+    let response_body: RestApiResponse<serde_json::Value> =
+        deserialize_json_body(body).await.unwrap();
+    let data = response_body
+        .0
+        .data
+        .filter(|_| parts.status == StatusCode::OK)
+        .map(|data| serde_json::from_value(data).unwrap());
+    (parts.status, etag, data)
+}
+
+#[tokio::test]
+async fn test_patch_user() {
+    let (payload, user) = create_user().await;
+
+    let url = format!("/user/{}", user.id);
+    let response = request_with_auth(Method::GET, url.as_str()).await;
+    let etag = response.headers()["etag"].to_str().unwrap().to_string();
+    assert_eq!(etag, format!("\"{}\"", user.version));
+
+    // Only the members of the patch change.
+    let email = format!("patched-{}@test.com", uuid::Uuid::new_v4());
+    let (status, new_etag, patched) =
+        patch_user(&user.id, Some(&etag), json!({ "email": email })).await;
+    assert_eq!(status, StatusCode::OK);
+    let patched = patched.unwrap();
+    assert_eq!(patched.username, payload.username);
+    assert_eq!(patched.email, Some(email));
+    assert_eq!(patched.version, user.version + 1);
+    assert_eq!(new_etag, Some(format!("\"{}\"", patched.version)));
+
+    // The old ETag no longer matches, for a patch as well as for a full update.
+    let (status, _, _) = patch_user(&user.id, Some(&etag), json!({ "username": "stale" })).await;
+    assert_eq!(status, StatusCode::PRECONDITION_FAILED);
+
+    let update = UpdateUserDto {
+        username: payload.username.clone(),
+        email: payload.email.clone(),
+        modified_by: TEST_USER_ID.to_string(),
+    };
+    let headers = [("if-match", etag.as_str())];
+    let response = request_with_auth_and_headers(Method::PUT, url.as_str(), &headers, &update);
+    assert_eq!(response.await.status(), StatusCode::PRECONDITION_FAILED);
+
+    // Required members cannot be removed, and the patch is validated like an update.
+    let (status, _, _) = patch_user(&user.id, None, json!({ "username": null })).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, _, _) = patch_user(&user.id, None, json!({ "email": "not-an-email" })).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    // Unknown members are rejected rather than silently dropped.
+    let (status, _, _) = patch_user(&user.id, None, json!({ "nickname": "bob" })).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, _, _) = patch_user(&user.id, Some("*"), json!({})).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, _, _) = patch_user("unknown-user", None, json!({})).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
 #[tokio::test]
 async fn test_delete_user() {
-    let created = create_user()
-        .await
-        .expect("Failed to create user for deletion");
+    let created = create_user().await;
 
     let user = created.1;
 
@@ -285,18 +468,22 @@ async fn test_delete_user() {
 
     assert_eq!(parts.status, StatusCode::OK);
 
-    let response_body: RestApiResponse<()> = deserialize_json_body(body).await.unwrap();
+    let response_body: RestApiResponse<DeleteUserSummaryDto> =
+        deserialize_json_body(body).await.unwrap();
 
     assert_eq!(response_body.0.status, StatusCode::OK);
+    let summary = response_body.0.data.unwrap();
+    assert_eq!(summary.user_id, user.id);
+    assert!(!summary.dry_run);
+    assert!(summary.decommissioned_devices.is_empty());
+    assert!(summary.removed_files.is_empty());
     // println!("response_body.0.status: {:?}", response_body.0.status);
     // println!("response_body.0.message: {:?}", response_body.0.message);
 }
 
 #[tokio::test]
 async fn test_delete_user_file() {
-    let created = create_user_with_file()
-        .await
-        .expect("Failed to create user with file for deletion");
+    let created = create_user_with_file().await;
     let user_dto = created.1;
     let file_id = user_dto.file_id.clone().unwrap_or_default();
 
@@ -314,3 +501,687 @@ async fn test_delete_user_file() {
     // println!("response_body.0.status: {:?}", response_body.0.status);
     // println!("response_body.0.message: {:?}", response_body.0.message);
 }
+
+#[tokio::test]
+async fn test_get_file_of_other_user_forbidden() {
+    let (_, user) = create_user_with_file().await;
+    let url = format!("/file/{}", user.file_id.unwrap_or_default());
+
+    let token = get_bearer_token(TEST_NON_ADMIN_CLIENT_ID, TEST_CLIENT_SECRET).await;
+    let response = request_with_token(Method::GET, url.as_str(), &token);
+    assert_eq!(response.await.status(), StatusCode::FORBIDDEN);
+
+    let response = request_with_auth(Method::GET, url.as_str());
+    assert_eq!(response.await.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_restore_user() {
+    let (_, user) = create_user().await;
+
+    let url = format!("/user/{}", user.id);
+    let response = request_with_auth(Method::DELETE, url.as_str());
+    assert_eq!(response.await.status(), StatusCode::OK);
+
+    // Deleted users are hidden from reads and cannot be deleted again.
+    let response = request_with_auth(Method::GET, url.as_str());
+    assert_eq!(response.await.status(), StatusCode::NOT_FOUND);
+
+    let response = request_with_auth(Method::DELETE, url.as_str());
+    assert_eq!(response.await.status(), StatusCode::NOT_FOUND);
+
+    let page = get_users_page(&format!("id={}", user.id)).await;
+    assert_eq!(page.total, 0);
+
+    // Admins can still list them.
+    let page = get_users_page(&format!("id={}&include_deleted=true", user.id)).await;
+    assert_eq!(page.total, 1);
+    let deleted = &page.items[0];
+    assert!(deleted.deleted_at.is_some());
+    assert!(deleted.deleted_by.is_some());
+
+    let restore_url = format!("/user/{}/restore", user.id);
+    let response = request_with_auth(Method::POST, restore_url.as_str());
+    let (parts, body) = response.await.into_parts();
+    assert_eq!(parts.status, StatusCode::OK);
+
+    let response_body: RestApiResponse<UserDto> = deserialize_json_body(body).await.unwrap();
+    let restored = response_body.0.data.unwrap();
+    assert_eq!(restored.id, user.id);
+    assert!(restored.deleted_at.is_none());
+    assert!(restored.deleted_by.is_none());
+
+    let response = request_with_auth(Method::GET, url.as_str());
+    assert_eq!(response.await.status(), StatusCode::OK);
+
+    // Only deleted users can be restored.
+    let response = request_with_auth(Method::POST, restore_url.as_str());
+    assert_eq!(response.await.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_include_deleted_users_forbidden_for_non_admin() {
+    let token = get_bearer_token(TEST_NON_ADMIN_CLIENT_ID, TEST_CLIENT_SECRET).await;
+
+    let response = request_with_token(Method::GET, "/user?include_deleted=true", &token);
+    assert_eq!(response.await.status(), StatusCode::FORBIDDEN);
+
+    let url = format!("/user/{}/restore", TEST_OTHER_USER_ID);
+    let response = request_with_token(Method::POST, url.as_str(), &token);
+    assert_eq!(response.await.status(), StatusCode::FORBIDDEN);
+}
+
+/// Deletes a user with the given query string and returns the status and the summary.
+async fn delete_user_with(id: &str, query: &str) -> (StatusCode, Option<DeleteUserSummaryDto>) {
+    let url = format!("/user/{id}?{query}");
+    let response = request_with_auth(Method::DELETE, url.as_str());
+    let (parts, body) = response.await.into_parts();
+
+    let response_body: RestApiResponse<DeleteUserSummaryDto> =
+        deserialize_json_body(body).await.unwrap();
+    (parts.status, response_body.0.data)
+}
+
+#[tokio::test]
+async fn test_delete_user_decommissions_devices_and_deletes_files() {
+    let (_, user) = create_user_with_file().await;
+    let file_id = user.file_id.clone().unwrap();
+    let device_id = create_device_for(&user.id).await.id;
+
+    let pool = setup_test_db().await.unwrap();
+    let relative_path: String =
+        sqlx::query_scalar("SELECT file_relative_path FROM uploaded_files WHERE id = $1")
+            .bind(&file_id)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+    let config = Config::from_env().unwrap();
+    let stored_file = std::path::Path::new(&config.assets_private_path).join(relative_path);
+    assert!(stored_file.exists());
+
+    let (status, summary) = delete_user_with(&user.id, "").await;
+    assert_eq!(status, StatusCode::OK);
+    let summary = summary.unwrap();
+    assert_eq!(summary.decommissioned_devices, vec![device_id.clone()]);
+    assert!(summary.reassigned_devices.is_empty());
+    assert_eq!(summary.removed_files, vec![file_id.clone()]);
+
+    let (status, deleted_at): (String, Option<chrono::DateTime<Utc>>) =
+        sqlx::query_as("SELECT status::text, deleted_at FROM devices WHERE id = $1")
+            .bind(&device_id)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+    assert_eq!(status, "decommissioned");
+    assert!(deleted_at.is_some());
+
+    // The file is soft-deleted with the user, and the stored file is kept until it is purged.
+    let deleted_at: Option<chrono::DateTime<Utc>> =
+        sqlx::query_scalar("SELECT deleted_at FROM uploaded_files WHERE id = $1")
+            .bind(&file_id)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+    assert!(deleted_at.is_some());
+    assert!(stored_file.exists());
+}
+
+#[tokio::test]
+async fn test_restore_user_restores_files() {
+    let (_, user) = create_user_with_file().await;
+    let file_id = user.file_id.clone().unwrap();
+
+    let (status, _) = delete_user_with(&user.id, "").await;
+    assert_eq!(status, StatusCode::OK);
+
+    let url = format!("/file/{file_id}");
+    let response = request_with_auth(Method::GET, url.as_str());
+    assert_eq!(response.await.status(), StatusCode::NOT_FOUND);
+
+    let url = format!("/user/{}/restore", user.id);
+    let response = request_with_auth(Method::POST, url.as_str());
+    let (parts, body) = response.await.into_parts();
+    assert_eq!(parts.status, StatusCode::OK);
+    let response_body: RestApiResponse<UserDto> = deserialize_json_body(body).await.unwrap();
+    assert_eq!(response_body.0.data.unwrap().file_id, Some(file_id.clone()));
+
+    let url = format!("/file/{file_id}");
+    let response = request_with_auth(Method::GET, url.as_str());
+    assert_eq!(response.await.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_delete_user_reassigns_devices() {
+    let (_, user) = create_user().await;
+    let (_, new_owner) = create_user().await;
+    let device_id = create_device_for(&user.id).await.id;
+
+    // The devices cannot be handed over to the deleted user or to an unknown user.
+    let query = format!("reassign_devices_to={}", user.id);
+    let (status, _) = delete_user_with(&user.id, &query).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, _) = delete_user_with(&user.id, "reassign_devices_to=unknown-user").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let query = format!("reassign_devices_to={}", new_owner.id);
+    let (status, summary) = delete_user_with(&user.id, &query).await;
+    assert_eq!(status, StatusCode::OK);
+    let summary = summary.unwrap();
+    assert_eq!(summary.reassigned_devices, vec![device_id.clone()]);
+    assert_eq!(summary.reassigned_to, Some(new_owner.id.clone()));
+    assert!(summary.decommissioned_devices.is_empty());
+
+    let url = format!("/device/{device_id}");
+    let response = request_with_auth(Method::GET, url.as_str());
+    let (parts, body) = response.await.into_parts();
+    assert_eq!(parts.status, StatusCode::OK);
+    let response_body: RestApiResponse<DeviceDto> = deserialize_json_body(body).await.unwrap();
+    assert_eq!(response_body.0.data.unwrap().user_id, new_owner.id);
+}
+
+#[tokio::test]
+async fn test_delete_user_dry_run() {
+    let (_, user) = create_user_with_file().await;
+    let device_id = create_device_for(&user.id).await.id;
+
+    let (status, summary) = delete_user_with(&user.id, "dry_run=true").await;
+    assert_eq!(status, StatusCode::OK);
+    let summary = summary.unwrap();
+    assert!(summary.dry_run);
+    assert_eq!(summary.decommissioned_devices, vec![device_id.clone()]);
+    assert_eq!(summary.removed_files, vec![user.file_id.clone().unwrap()]);
+
+    // Nothing was changed.
+    let url = format!("/user/{}", user.id);
+    let response = request_with_auth(Method::GET, url.as_str());
+    let (parts, body) = response.await.into_parts();
+    assert_eq!(parts.status, StatusCode::OK);
+    let response_body: RestApiResponse<UserDto> = deserialize_json_body(body).await.unwrap();
+    assert_eq!(response_body.0.data.unwrap().file_id, user.file_id);
+
+    let url = format!("/device/{device_id}");
+    let response = request_with_auth(Method::GET, url.as_str());
+    assert_eq!(response.await.status(), StatusCode::OK);
+
+    let url = format!("/file/{}", user.file_id.unwrap());
+    let response = request_with_auth(Method::GET, url.as_str());
+    assert_eq!(response.await.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_purge_deleted_records() {
+    let (_, user) = create_user_with_file().await;
+    let file_id = user.file_id.clone().unwrap();
+    let device = create_device_for(&user.id).await;
+    let (_, recent_user) = create_user().await;
+
+    // The picture and the device are deleted with their user, at the same time as it.
+    for url in [
+        format!("/user/{}", user.id),
+        format!("/user/{}", recent_user.id),
+    ] {
+        let response = request_with_auth(Method::DELETE, url.as_str());
+        assert_eq!(response.await.status(), StatusCode::OK);
+    }
+
+    // Soft-deleted records are kept until the retention period has passed.
+    let pool = setup_test_db().await.unwrap();
+    let relative_path: String =
+        sqlx::query_scalar("SELECT file_relative_path FROM uploaded_files WHERE id = $1")
+            .bind(&file_id)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+    let config = Config::from_env().unwrap();
+    let stored_file = std::path::Path::new(&config.assets_private_path).join(relative_path);
+    assert!(stored_file.exists());
+
+    // Only the user is backdated: its files and devices are purged with it regardless.
+    sqlx::query("UPDATE users SET deleted_at = NOW() - INTERVAL '1 year' WHERE id = $1")
+        .bind(&user.id)
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    let state = create_test_state().await;
+    purge_deleted_records(&state).await.unwrap();
+
+    let ids = vec![user.id.clone(), recent_user.id.clone()];
+    let remaining: Vec<String> = sqlx::query_scalar("SELECT id FROM users WHERE id = ANY($1)")
+        .bind(&ids)
+        .fetch_all(&pool)
+        .await
+        .unwrap();
+    assert_eq!(remaining, vec![recent_user.id.clone()]);
+
+    let files: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM uploaded_files WHERE id = $1")
+        .bind(&file_id)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(files, 0);
+    assert!(!stored_file.exists());
+
+    let devices: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM devices WHERE id = $1")
+        .bind(&device.id)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(devices, 0);
+}
+
+#[tokio::test]
+async fn test_delete_user_revokes_api_keys_and_login_tokens() {
+    let (payload, user) = create_user().await;
+
+    let credentials = AuthUserDto {
+        user_id: user.id.clone(),
+        password: TEST_CLIENT_SECRET.to_string(),
+    };
+    let response = request_with_auth_and_body(Method::POST, "/auth/credentials", &credentials);
+    assert_eq!(response.await.status(), StatusCode::OK);
+
+    // Users created by an admin get no role, and an API key needs a granted scope.
+    let pool = setup_test_db().await.unwrap();
+    sqlx::query(
+        "INSERT INTO user_roles (user_id, role_id) SELECT $1, id FROM roles WHERE name = 'user'",
+    )
+    .bind(&user.id)
+    .execute(&pool)
+    .await
+    .unwrap();
+
+    let token = get_bearer_token(&payload.username, TEST_CLIENT_SECRET).await;
+    let api_key = CreateApiKeyDto {
+        name: "ci".to_string(),
+        scopes: vec!["user:read".to_string()],
+        expires_in: None,
+    };
+    let response = request_with_token_and_body(Method::POST, "/auth/api-keys", &token, &api_key);
+    assert_eq!(response.await.status(), StatusCode::OK);
+
+    let forgot = ForgotPasswordDto {
+        username: payload.username.clone(),
+    };
+    let response = request_with_body(Method::POST, "/auth/password/forgot", &forgot);
+    assert_eq!(response.await.status(), StatusCode::OK);
+    let magic_link = MagicLinkRequestDto {
+        username: payload.username.clone(),
+    };
+    let response = request_with_body(Method::POST, "/auth/magic-link", &magic_link);
+    assert_eq!(response.await.status(), StatusCode::OK);
+
+    let url = format!("/user/{}", user.id);
+    let response = request_with_auth(Method::DELETE, url.as_str());
+    let (parts, body) = response.await.into_parts();
+    assert_eq!(parts.status, StatusCode::OK);
+    let response_body: RestApiResponse<DeleteUserSummaryDto> =
+        deserialize_json_body(body).await.unwrap();
+    let summary = response_body.0.data.unwrap();
+    assert_eq!(summary.api_keys_revoked, 1);
+    assert_eq!(summary.login_tokens_expired, 2);
+
+    let active_keys: i64 = sqlx::query_scalar(
+        "SELECT COUNT(*) FROM api_keys WHERE user_id = $1 AND revoked_at IS NULL",
+    )
+    .bind(&user.id)
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    assert_eq!(active_keys, 0);
+
+    let open_tokens: i64 = sqlx::query_scalar(
+        "SELECT (SELECT COUNT(*) FROM password_reset_tokens WHERE user_id = $1 AND used_at IS NULL)
+              + (SELECT COUNT(*) FROM magic_links WHERE user_id = $1 AND used_at IS NULL)",
+    )
+    .bind(&user.id)
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    assert_eq!(open_tokens, 0);
+}
+
+#[tokio::test]
+async fn test_restore_user_file() {
+    let (_, user) = create_user_with_file().await;
+    let file_id = user.file_id.clone().unwrap();
+
+    let url = format!("/file/{}", file_id);
+    let response = request_with_auth(Method::DELETE, url.as_str());
+    assert_eq!(response.await.status(), StatusCode::OK);
+
+    let response = request_with_auth(Method::GET, url.as_str());
+    assert_eq!(response.await.status(), StatusCode::NOT_FOUND);
+
+    let user_url = format!("/user/{}", user.id);
+    let response = request_with_auth(Method::GET, user_url.as_str());
+    let (_, body) = response.await.into_parts();
+    let response_body: RestApiResponse<UserDto> = deserialize_json_body(body).await.unwrap();
+    assert!(response_body.0.data.unwrap().file_id.is_none());
+
+    // The stored file is kept, so admins can still fetch a deleted file.
+    let deleted_url = format!("/file/{}?include_deleted=true", file_id);
+    let response = request_with_auth(Method::GET, deleted_url.as_str());
+    assert_eq!(response.await.status(), StatusCode::OK);
+
+    let token = get_bearer_token(TEST_NON_ADMIN_CLIENT_ID, TEST_CLIENT_SECRET).await;
+    let response = request_with_token(Method::GET, deleted_url.as_str(), &token);
+    assert_eq!(response.await.status(), StatusCode::FORBIDDEN);
+
+    let restore_url = format!("/file/{}/restore", file_id);
+    let response = request_with_auth(Method::POST, restore_url.as_str());
+    assert_eq!(response.await.status(), StatusCode::OK);
+
+    let response = request_with_auth(Method::GET, url.as_str());
+    assert_eq!(response.await.status(), StatusCode::OK);
+
+    let response = request_with_auth(Method::GET, user_url.as_str());
+    let (_, body) = response.await.into_parts();
+    let response_body: RestApiResponse<UserDto> = deserialize_json_body(body).await.unwrap();
+    assert_eq!(response_body.0.data.unwrap().file_id, Some(file_id));
+}
+
+/// Builds a multipart body holding `image_file` from tests/asset/ as the profile picture.
+fn profile_picture_body(image_file: &str) -> Vec<u8> {
+    use std::io::Write;
+    let file_bytes = std::fs::read(format!("tests/asset/{}", image_file))
+        .unwrap_or_else(|_| panic!("Failed to read {} from tests/asset/", image_file));
+
+    let mut multipart_body = Vec::new();
+    write!(
+        &mut multipart_body,
+        "------XYZ\r\nContent-Disposition: form-data; name=\"profile_picture\"; filename=\"{}\"\r\nContent-Type: image/png\r\n\r\n",
+        image_file
+    ).unwrap();
+    multipart_body.extend_from_slice(&file_bytes);
+    write!(&mut multipart_body, "\r\n------XYZ--\r\n").unwrap();
+    multipart_body
+}
+
+#[tokio::test]
+async fn test_replace_profile_picture() {
+    let (_, user) = create_user_with_file().await;
+    let old_file_id = user.file_id.clone().unwrap();
+
+    let pool = setup_test_db().await.unwrap();
+    let relative_path: String =
+        sqlx::query_scalar("SELECT file_relative_path FROM uploaded_files WHERE id = $1")
+            .bind(&old_file_id)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+    let config = Config::from_env().unwrap();
+    let old_stored_file = std::path::Path::new(&config.assets_private_path).join(relative_path);
+    assert!(old_stored_file.exists());
+
+    let url = format!("/user/{}/profile-picture", user.id);
+    let response =
+        request_with_auth_and_multipart(Method::PUT, url.as_str(), profile_picture_body("cat.png"));
+    let (parts, body) = response.await.into_parts();
+    assert_eq!(parts.status, StatusCode::OK);
+
+    let response_body: RestApiResponse<UserDto> = deserialize_json_body(body).await.unwrap();
+    let replaced = response_body.0.data.unwrap();
+    assert_eq!(replaced.version, user.version + 1);
+    assert_eq!(
+        parts.headers["etag"].to_str().unwrap(),
+        format!("\"{}\"", replaced.version)
+    );
+    let new_file_id = replaced.file_id.clone().unwrap();
+    assert_ne!(new_file_id, old_file_id);
+
+    // The old picture is gone, both its record and its stored file.
+    let files: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM uploaded_files WHERE id = $1")
+        .bind(&old_file_id)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(files, 0);
+    assert!(!old_stored_file.exists());
+
+    let response = request_with_auth(Method::GET, url.as_str());
+    let (parts, body) = response.await.into_parts();
+    assert_eq!(parts.status, StatusCode::OK);
+    assert_eq!(parts.headers["content-type"], "image/png");
+    let bytes = axum::body::to_bytes(body, usize::MAX).await.unwrap();
+    assert_eq!(
+        bytes.as_ref(),
+        std::fs::read("tests/asset/cat.png").unwrap()
+    );
+
+    // A request without a picture is rejected.
+    let empty_body = b"------XYZ--\r\n".to_vec();
+    let response = request_with_auth_and_multipart(Method::PUT, url.as_str(), empty_body);
+    assert_eq!(response.await.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_get_profile_picture_of_other_user_forbidden() {
+    let (_, user) = create_user_with_file().await;
+    let url = format!("/user/{}/profile-picture", user.id);
+
+    let token = get_bearer_token(TEST_NON_ADMIN_CLIENT_ID, TEST_CLIENT_SECRET).await;
+    let response = request_with_token(Method::GET, url.as_str(), &token);
+    assert_eq!(response.await.status(), StatusCode::FORBIDDEN);
+
+    let response = request_with_auth(Method::GET, url.as_str());
+    assert_eq!(response.await.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_delete_profile_picture() {
+    let (_, user) = create_user_with_file().await;
+
+    let url = format!("/user/{}/profile-picture", user.id);
+
+    // Only admins and the user itself can delete the picture.
+    let token = get_bearer_token(TEST_NON_ADMIN_CLIENT_ID, TEST_CLIENT_SECRET).await;
+    let response = request_with_token(Method::DELETE, url.as_str(), &token);
+    assert_eq!(response.await.status(), StatusCode::FORBIDDEN);
+
+    let stale_etag = format!("\"{}\"", user.version + 1);
+    let headers = [("if-match", stale_etag.as_str())];
+    let response = request_with_auth_and_headers(Method::DELETE, url.as_str(), &headers, &());
+    assert_eq!(response.await.status(), StatusCode::PRECONDITION_FAILED);
+
+    let etag = format!("\"{}\"", user.version);
+    let headers = [("if-match", etag.as_str())];
+    let response = request_with_auth_and_headers(Method::DELETE, url.as_str(), &headers, &());
+    let (parts, body) = response.await.into_parts();
+    assert_eq!(parts.status, StatusCode::OK);
+    let response_body: RestApiResponse<UserDto> = deserialize_json_body(body).await.unwrap();
+    let updated = response_body.0.data.unwrap();
+    assert!(updated.file_id.is_none());
+    assert_eq!(updated.version, user.version + 1);
+
+    let response = request_with_auth(Method::GET, url.as_str());
+    assert_eq!(response.await.status(), StatusCode::NOT_FOUND);
+
+    let response = request_with_auth(Method::DELETE, url.as_str());
+    assert_eq!(response.await.status(), StatusCode::NOT_FOUND);
+}
+
+/// Imports users and returns the status and, for processed imports, the report.
+async fn import_users(
+    query: &str,
+    content_type: &str,
+    body: &str,
+) -> (StatusCode, Option<ImportUsersReportDto>) {
+    let url = format!("/user/import{query}");
+    let response = request_with_auth_and_raw_body(
+        Method::POST,
+        url.as_str(),
+        content_type,
+        body.as_bytes().to_vec(),
+    );
+    let (parts, body) = response.await.into_parts();
+    let response_body: RestApiResponse<serde_json::Value> =
+        deserialize_json_body(body).await.unwrap();
+    let report = response_body
+        .0
+        .data
+        .filter(|_| parts.status == StatusCode::OK)
+        .map(|data| serde_json::from_value(data).unwrap());
+    (parts.status, report)
+}
+
+/// Counts the users whose username starts with `prefix`.
+async fn count_users_with_prefix(prefix: &str) -> i64 {
+    let pool = setup_test_db().await.unwrap();
+    sqlx::query_scalar("SELECT COUNT(*) FROM users WHERE username LIKE $1 || '%'")
+        .bind(prefix)
+        .fetch_one(&pool)
+        .await
+        .unwrap()
+}
+
+fn statuses(report: &ImportUsersReportDto) -> Vec<ImportRowStatus> {
+    report.rows.iter().map(|row| row.status).collect()
+}
+
+#[tokio::test]
+async fn test_import_users_best_effort() {
+    let prefix = format!("import-{}", uuid::Uuid::new_v4());
+    let csv = format!(
+        "username,email\n{prefix}-a,{prefix}-a@test.com\n{prefix}-b,not-an-email\n\
+         {prefix}-a,{prefix}-c@test.com\n{prefix}-d\n"
+    );
+
+    let (status, report) = import_users("?mode=best_effort", "text/csv", &csv).await;
+    assert_eq!(status, StatusCode::OK);
+    let report = report.unwrap();
+    assert!(report.committed);
+    assert_eq!((report.total, report.created, report.failed), (4, 1, 3));
+    assert_eq!(
+        statuses(&report),
+        vec![
+            ImportRowStatus::Created,
+            ImportRowStatus::Invalid,
+            ImportRowStatus::Conflict,
+            ImportRowStatus::Invalid
+        ]
+    );
+    assert_eq!(report.rows[1].errors, vec!["email: Invalid email format"]);
+    assert_eq!(report.rows[2].errors, vec!["Username already exists"]);
+    assert_eq!(report.rows[3].username, None);
+
+    let url = format!("/user/{}", report.rows[0].user_id.clone().unwrap());
+    let response = request_with_auth(Method::GET, url.as_str());
+    assert_eq!(response.await.status(), StatusCode::OK);
+    assert_eq!(count_users_with_prefix(&prefix).await, 1);
+}
+
+#[tokio::test]
+async fn test_import_users_all_or_nothing() {
+    let prefix = format!("import-{}", uuid::Uuid::new_v4());
+    let valid = format!(r#"{{"username": "{prefix}-a", "email": "{prefix}-a@test.com"}}"#);
+    let invalid = format!(r#"{{"username": "{prefix}-b", "email": "not-an-email"}}"#);
+
+    // One bad row keeps every row from being created.
+    let ndjson = format!("{valid}\n\n{invalid}\n");
+    let (status, report) = import_users("", "application/x-ndjson", &ndjson).await;
+    assert_eq!(status, StatusCode::OK);
+    let report = report.unwrap();
+    assert!(!report.committed);
+    assert_eq!((report.created, report.failed), (0, 1));
+    assert_eq!(
+        statuses(&report),
+        vec![ImportRowStatus::Valid, ImportRowStatus::Invalid]
+    );
+    assert!(report.rows[0].user_id.is_none());
+    assert_eq!(count_users_with_prefix(&prefix).await, 0);
+
+    // A dry run checks the rows against the database without creating them.
+    let second = format!(r#"{{"username": "{prefix}-c", "email": "{prefix}-c@test.com"}}"#);
+    let ndjson = format!("{valid}\n{second}\n");
+    let (_, report) = import_users("?dry_run=true&format=ndjson", "text/plain", &ndjson).await;
+    let report = report.unwrap();
+    assert!(report.dry_run && !report.committed);
+    assert_eq!(
+        statuses(&report),
+        vec![ImportRowStatus::Valid, ImportRowStatus::Valid]
+    );
+    assert_eq!(count_users_with_prefix(&prefix).await, 0);
+
+    let (_, report) = import_users("", "application/x-ndjson", &ndjson).await;
+    let report = report.unwrap();
+    assert!(report.committed);
+    assert_eq!(report.created, 2);
+    assert_eq!(count_users_with_prefix(&prefix).await, 2);
+
+    // Importing the same users again conflicts with the existing ones.
+    let (_, report) = import_users("?dry_run=true", "application/x-ndjson", &ndjson).await;
+    assert_eq!(
+        statuses(&report.unwrap()),
+        vec![ImportRowStatus::Conflict, ImportRowStatus::Conflict]
+    );
+}
+
+#[tokio::test]
+async fn test_import_users_rejected() {
+    let (status, _) = import_users("", "application/json", "{}").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, _) = import_users("", "text/csv", "username,email\n").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let row = json!({ "username": "import-forbidden", "email": "import-forbidden@test.com" });
+    let token = get_bearer_token(TEST_NON_ADMIN_CLIENT_ID, TEST_CLIENT_SECRET).await;
+    let response =
+        request_with_token_and_body(Method::POST, "/user/import?format=ndjson", &token, &row);
+    assert_eq!(response.await.status(), StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn test_export_users() {
+    let prefix = format!("export-{}", uuid::Uuid::new_v4());
+    let csv = format!(
+        "username,email\n{prefix}-b,{prefix}-b@test.com\n{prefix}-a,\"{prefix}-a@test.com\"\n"
+    );
+    let (_, report) = import_users("", "text/csv", &csv).await;
+    assert_eq!(report.unwrap().created, 2);
+
+    let url = format!("/user/export?format=ndjson&username={prefix}&sort=username");
+    let response = request_with_auth(Method::GET, url.as_str());
+    let (parts, body) = response.await.into_parts();
+    assert_eq!(parts.status, StatusCode::OK);
+    assert_eq!(parts.headers["content-type"], "application/x-ndjson");
+    let bytes = axum::body::to_bytes(body, usize::MAX).await.unwrap();
+    let users: Vec<UserDto> = std::str::from_utf8(&bytes)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    let usernames: Vec<&str> = users.iter().map(|user| user.username.as_str()).collect();
+    assert_eq!(
+        usernames,
+        vec![format!("{prefix}-a"), format!("{prefix}-b")]
+    );
+
+    let url = format!("/user/export?username={prefix}&sort=-username");
+    let response = request_with_auth(Method::GET, url.as_str());
+    let (parts, body) = response.await.into_parts();
+    assert_eq!(parts.status, StatusCode::OK);
+    assert_eq!(
+        parts.headers["content-disposition"],
+        "attachment; filename=\"users.csv\""
+    );
+    let bytes = axum::body::to_bytes(body, usize::MAX).await.unwrap();
+    let export = String::from_utf8(bytes.to_vec()).unwrap();
+    let lines: Vec<&str> = export.lines().collect();
+    assert_eq!(lines.len(), 3);
+    assert!(lines[0].starts_with("id,username,email,"));
+    assert!(lines[1].contains(&format!("{prefix}-b@test.com")));
+
+    // An export can be imported again.
+    let (_, report) = import_users("?dry_run=true", "text/csv", &export).await;
+    assert_eq!(
+        statuses(&report.unwrap()),
+        vec![ImportRowStatus::Conflict, ImportRowStatus::Conflict]
+    );
+
+    let token = get_bearer_token(TEST_NON_ADMIN_CLIENT_ID, TEST_CLIENT_SECRET).await;
+    let response = request_with_token(Method::GET, "/user/export?include_deleted=true", &token);
+    assert_eq!(response.await.status(), StatusCode::FORBIDDEN);
+}